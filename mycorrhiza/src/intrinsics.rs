@@ -1,3 +1,4 @@
+use core::marker::Tuple;
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct RustcCLRInteropManagedClass<const ASSEMBLY: &'static str, const CLASS_PATH: &'static str>
@@ -66,6 +67,32 @@ impl<const ASSEMBLY: &'static str, const CLASS_PATH: &'static str>
             Arg1,
         >(self, arg1)
     }
+    /// Constructs a new object, calling the constructor taking `args`, a tuple of any number of arguments.
+    #[inline(always)]
+    pub fn ctor<Args: Tuple>(args: Args) -> Self {
+        rustc_clr_interop_managed_ctor_::<ASSEMBLY, CLASS_PATH, false, Args>(args)
+    }
+    /// Calls the static method `METHOD` with `args`, a tuple of any number of arguments.
+    #[inline(always)]
+    pub fn static_call<const METHOD: &'static str, Args: Tuple, Ret>(args: Args) -> Ret {
+        rustc_clr_interop_managed_call_::<ASSEMBLY, CLASS_PATH, false, METHOD, true, Ret, Args>(
+            args,
+        )
+    }
+    /// Calls the instance method `METHOD` with `args`, a tuple of any number of arguments, starting with the object the method is called on.
+    #[inline(always)]
+    pub fn instance_call<const METHOD: &'static str, Args: Tuple, Ret>(args: Args) -> Ret {
+        rustc_clr_interop_managed_call_::<ASSEMBLY, CLASS_PATH, false, METHOD, false, Ret, Args>(
+            args,
+        )
+    }
+    /// Calls the virtual method `METHOD` with `args`, a tuple of any number of arguments, starting with the object the method is called on.
+    #[inline(always)]
+    pub fn virt_call<const METHOD: &'static str, Args: Tuple, Ret>(args: Args) -> Ret {
+        rustc_clr_interop_managed_call_virt_::<ASSEMBLY, CLASS_PATH, false, METHOD, false, Ret, Args>(
+            args,
+        )
+    }
     #[inline(always)]
     pub fn to_mstring(self) -> crate::system::MString {
         self.instance0::<"ToString", crate::system::MString>()
@@ -76,12 +103,147 @@ impl<const ASSEMBLY: &'static str, const CLASS_PATH: &'static str>
 pub struct RustcCLRInteropManagedChar {
     utf16_char: u16,
 }
-#[derive(Clone, Copy)]
 #[repr(C)]
-struct RustcCLRInteropManagedArray<T, const DIMENSIONS: usize> {
+pub struct RustcCLRInteropManagedArray<T, const DIMENSIONS: usize> {
     object_ref: usize,
     pd: core::marker::PhantomData<T>,
 }
+// Copying an array copies the reference to it, so the elements don't need to be `Copy`.
+impl<T, const DIMENSIONS: usize> Clone for RustcCLRInteropManagedArray<T, DIMENSIONS> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T, const DIMENSIONS: usize> Copy for RustcCLRInteropManagedArray<T, DIMENSIONS> {}
+impl<T: ArrayElement> RustcCLRInteropManagedArray<T, 1> {
+    /// Creates a new managed array of `len` zero-initialized elements.
+    #[inline(always)]
+    pub fn new(len: usize) -> Self {
+        rustc_clr_interop_managed_array_new_::<T>(len)
+    }
+    /// Returns the number of elements of this array.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        rustc_clr_interop_managed_array_len_::<T>(*self)
+    }
+    /// Checks if this array has no elements.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns a copy of the element at `index`. Throws `System.IndexOutOfRangeException` if `index` is out of bounds.
+    #[inline(always)]
+    pub fn get(&self, index: usize) -> T {
+        rustc_clr_interop_managed_array_get_::<T>(*self, index)
+    }
+    /// Sets the element at `index` to `value`. Throws `System.IndexOutOfRangeException` if `index` is out of bounds.
+    #[inline(always)]
+    pub fn set(&mut self, index: usize, value: T) {
+        rustc_clr_interop_managed_array_set_::<T>(*self, index, value)
+    }
+}
+impl<T: Unmanaged> RustcCLRInteropManagedArray<T, 1> {
+    /// Pins this array, so that the GC does not move it, and its elements can be accessed as a slice, like C#'s `fixed` statement.
+    /// # Safety
+    /// Handles are `Copy`, and all copies refer to the same array, so borrowing this one does not prevent the others from accessing it.
+    /// While the returned [`PinnedArray`] is alive, the array must not be accessed(eg. using `get` or `set`) nor pinned again through any other handle.
+    pub unsafe fn pin(&mut self) -> PinnedArray<'_, T> {
+        let handle = rustc_clr_interop_managed_array_pin_::<T>(*self);
+        let len = self.len();
+        let elements = if len == 0 {
+            core::ptr::NonNull::dangling().as_ptr()
+        } else {
+            // The array was just pinned, and has at least one element.
+            unsafe { rustc_clr_interop_managed_array_addr_::<T>(*self, 0) }
+        };
+        // The array stays pinned until the `PinnedArray` is dropped, so its elements can't be moved by the GC. The caller guarantees no other handle
+        // accesses them meanwhile.
+        PinnedArray {
+            handle,
+            slice: unsafe { core::slice::from_raw_parts_mut(elements, len) },
+        }
+    }
+}
+/// A managed array pinned in memory, which can be used as a slice. Unpins the array when dropped.
+pub struct PinnedArray<'array, T> {
+    handle: isize,
+    slice: &'array mut [T],
+}
+impl<'array, T> core::ops::Deref for PinnedArray<'array, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+impl<'array, T> core::ops::DerefMut for PinnedArray<'array, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.slice
+    }
+}
+impl<'array, T> Drop for PinnedArray<'array, T> {
+    fn drop(&mut self) {
+        rustc_clr_interop_gc_handle_free_(self.handle)
+    }
+}
+/// Implemented by the types which can be stored in managed arrays.
+/// # Safety
+/// A value with all bytes set to zero must be a valid value of the type, since new arrays are zero-initialized.
+pub unsafe trait ArrayElement: Copy {}
+/// Implemented by the types which contain no references to managed objects, so arrays of them can be pinned.
+/// # Safety
+/// The type must not contain any references to managed objects.
+pub unsafe trait Unmanaged: ArrayElement {}
+macro_rules! unmanaged {
+    ($($tpe:ty),*) => {
+        $(
+            unsafe impl ArrayElement for $tpe {}
+            unsafe impl Unmanaged for $tpe {}
+        )*
+    };
+}
+unmanaged!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    bool,
+    char,
+    RustcCLRInteropManagedChar
+);
+unsafe impl<T> ArrayElement for *const T {}
+unsafe impl<T> Unmanaged for *const T {}
+unsafe impl<T> ArrayElement for *mut T {}
+unsafe impl<T> Unmanaged for *mut T {}
+unsafe impl<const ASSEMBLY: &'static str, const CLASS_PATH: &'static str> ArrayElement
+    for RustcCLRInteropManagedClass<ASSEMBLY, CLASS_PATH>
+{
+}
+unsafe impl<T, const DIMENSIONS: usize> ArrayElement
+    for RustcCLRInteropManagedArray<T, DIMENSIONS>
+{
+}
+/// Implemented by the types lowered to references to managed objects.
+/// # Safety
+/// The type must be lowered to an object reference, and not to a value type.
+pub unsafe trait ManagedObject: Copy {}
+unsafe impl<const ASSEMBLY: &'static str, const CLASS_PATH: &'static str> ManagedObject
+    for RustcCLRInteropManagedClass<ASSEMBLY, CLASS_PATH>
+{
+}
+unsafe impl<T, const DIMENSIONS: usize> ManagedObject
+    for RustcCLRInteropManagedArray<T, DIMENSIONS>
+{
+}
 //Calls
 #[allow(unused_variables)]
 #[inline(never)]
@@ -126,6 +288,22 @@ pub fn rustc_clr_interop_managed_call2_<
 ) -> Ret {
     core::intrinsics::abort();
 }
+/// Calls a managed method with `args`, a tuple of any number of arguments. If the method is not static, the first argument is the object it is called on.
+#[allow(unused_variables)]
+#[inline(never)]
+pub extern "rust-call" fn rustc_clr_interop_managed_call_<
+    const ASSEMBLY: &'static str,
+    const CLASS_PATH: &'static str,
+    const IS_VALUETYPE: bool,
+    const METHOD: &'static str,
+    const IS_STATIC: bool,
+    Ret,
+    Args: Tuple,
+>(
+    args: Args,
+) -> Ret {
+    core::intrinsics::abort();
+}
 //VCalls
 #[allow(unused_variables)]
 #[inline(never)]
@@ -170,6 +348,22 @@ pub fn rustc_clr_interop_managed_call_virt2_<
 ) -> Ret {
     core::intrinsics::abort();
 }
+/// Calls a virtual managed method with `args`, a tuple of any number of arguments, starting with the object it is called on.
+#[allow(unused_variables)]
+#[inline(never)]
+pub extern "rust-call" fn rustc_clr_interop_managed_call_virt_<
+    const ASSEMBLY: &'static str,
+    const CLASS_PATH: &'static str,
+    const IS_VALUETYPE: bool,
+    const METHOD: &'static str,
+    const IS_STATIC: bool,
+    Ret,
+    Args: Tuple,
+>(
+    args: Args,
+) -> Ret {
+    core::intrinsics::abort();
+}
 //Ctors
 #[allow(unused_variables)]
 #[inline(never)]
@@ -222,6 +416,115 @@ pub fn rustc_clr_interop_managed_ctor3_<
 ) -> RustcCLRInteropManagedClass<ASSEMBLY, CLASS_PATH> {
     core::intrinsics::abort();
 }
+/// Constructs a managed object, calling the constructor taking `args`, a tuple of any number of arguments.
+#[allow(unused_variables)]
+#[inline(never)]
+pub extern "rust-call" fn rustc_clr_interop_managed_ctor_<
+    const ASSEMBLY: &'static str,
+    const CLASS_PATH: &'static str,
+    const IS_VALUETYPE: bool,
+    Args: Tuple,
+>(
+    args: Args,
+) -> RustcCLRInteropManagedClass<ASSEMBLY, CLASS_PATH> {
+    core::intrinsics::abort();
+}
+//Arrays
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_managed_array_new_<T>(len: usize) -> RustcCLRInteropManagedArray<T, 1> {
+    core::intrinsics::abort();
+}
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_managed_array_len_<T>(array: RustcCLRInteropManagedArray<T, 1>) -> usize {
+    core::intrinsics::abort();
+}
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_managed_array_get_<T>(
+    array: RustcCLRInteropManagedArray<T, 1>,
+    index: usize,
+) -> T {
+    core::intrinsics::abort();
+}
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_managed_array_set_<T>(
+    array: RustcCLRInteropManagedArray<T, 1>,
+    index: usize,
+    value: T,
+) {
+    core::intrinsics::abort();
+}
+/// Returns the address of the element at `index`.
+/// # Safety
+/// `array` must stay pinned(see [`rustc_clr_interop_managed_array_pin_`]) for as long as the address is used, since the GC may move it otherwise.
+#[allow(unused_variables)]
+#[inline(never)]
+pub unsafe fn rustc_clr_interop_managed_array_addr_<T>(
+    array: RustcCLRInteropManagedArray<T, 1>,
+    index: usize,
+) -> *mut T {
+    core::intrinsics::abort();
+}
+/// Pins `array` using a pinned `GCHandle`, returned as a `native int`.
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_managed_array_pin_<T>(array: RustcCLRInteropManagedArray<T, 1>) -> isize {
+    core::intrinsics::abort();
+}
+//GC handles
+/// Allocates a `GCHandle` keeping `object` alive, returned as a `native int`.
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_gc_handle_alloc_<T: ManagedObject>(object: T) -> isize {
+    core::intrinsics::abort();
+}
+/// Returns the object the `GCHandle` `handle` refers to.
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_gc_handle_target_<T: ManagedObject>(handle: isize) -> T {
+    core::intrinsics::abort();
+}
+/// Frees the `GCHandle` `handle`, allocated by [`rustc_clr_interop_gc_handle_alloc_`] or [`rustc_clr_interop_managed_array_pin_`].
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_gc_handle_free_(handle: isize) {
+    core::intrinsics::abort();
+}
+//Panics
+#[allow(unused_variables)]
+#[inline(never)]
+pub fn rustc_clr_interop_throw_panic(
+    message: *const u8,
+    message_len: usize,
+    file: *const u8,
+    file_len: usize,
+    line: u32,
+    column: u32,
+) -> ! {
+    core::intrinsics::abort();
+}
+/// Throws a managed `RustPanicException` with the message and location of `info`.
+pub fn throw_panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    let message = info
+        .message()
+        .and_then(|message| message.as_str())
+        .unwrap_or("Rust code panicked");
+    let (file, line, column) = info
+        .location()
+        .map(|location| (location.file(), location.line(), location.column()))
+        .unwrap_or(("", 0, 0));
+    rustc_clr_interop_throw_panic(
+        message.as_ptr(),
+        message.len(),
+        file.as_ptr(),
+        file.len(),
+        line,
+        column,
+    )
+}
 impl From<u16> for RustcCLRInteropManagedChar {
     fn from(utf16_char: u16) -> RustcCLRInteropManagedChar {
         unsafe {
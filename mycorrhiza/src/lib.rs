@@ -4,7 +4,13 @@
 //! implement an equivalent APIs in standard Rust.
 #![no_std]
 #![allow(internal_features, incomplete_features)]
-#![feature(core_intrinsics, adt_const_params)]
+#![feature(
+    core_intrinsics,
+    adt_const_params,
+    panic_info_message,
+    unboxed_closures,
+    tuple_trait
+)]
 
 /// Very low-level interop stuff. Don't use unless you need to.
 pub mod intrinsics;
@@ -14,13 +20,15 @@ pub mod std;
 pub mod system;
 /// C# `char` type
 pub type DotNetChar = crate::intrinsics::RustcCLRInteropManagedChar;
+/// C# one-dimensional array, eg. `int[]`
+pub type ManagedArray<T> = crate::intrinsics::RustcCLRInteropManagedArray<T, 1>;
 
 #[macro_export]
 macro_rules! panic_handler {
     () => {
         #[panic_handler]
-        fn panic(_panic: &core::panic::PanicInfo<'_>) -> ! {
-            core::intrinsics::abort();
+        fn panic(panic: &core::panic::PanicInfo<'_>) -> ! {
+            $crate::intrinsics::throw_panic(panic)
         }
     };
 }
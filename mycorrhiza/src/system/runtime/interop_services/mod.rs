@@ -1 +1,34 @@
-
+use crate::intrinsics::{
+    rustc_clr_interop_gc_handle_alloc_, rustc_clr_interop_gc_handle_free_,
+    rustc_clr_interop_gc_handle_target_, ManagedObject,
+};
+use core::marker::PhantomData;
+/// A handle to a managed object, backed by `System.Runtime.InteropServices.GCHandle`. The object is kept alive until the handle is dropped.
+/// Unlike a reference to the object, the handle can be stored in unmanaged memory, eg. in a `Vec` or a `Box`.
+pub struct GcHandle<T: ManagedObject> {
+    handle: isize,
+    pd: PhantomData<T>,
+}
+impl<T: ManagedObject> GcHandle<T> {
+    /// Allocates a handle to `object`.
+    pub fn new(object: T) -> Self {
+        Self {
+            handle: rustc_clr_interop_gc_handle_alloc_(object),
+            pd: PhantomData,
+        }
+    }
+    /// Returns the object this handle refers to.
+    pub fn target(&self) -> T {
+        rustc_clr_interop_gc_handle_target_(self.handle)
+    }
+}
+impl<T: ManagedObject> Clone for GcHandle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.target())
+    }
+}
+impl<T: ManagedObject> Drop for GcHandle<T> {
+    fn drop(&mut self) {
+        rustc_clr_interop_gc_handle_free_(self.handle)
+    }
+}
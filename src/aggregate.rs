@@ -137,7 +137,55 @@ pub fn handle_aggregate<'tyctx>(
                 ops
             }
         }
-        _ => todo!("Unsuported aggregate kind {aggregate_kind:?}"),
+        AggregateKind::Closure(..) | AggregateKind::Coroutine(..) => {
+            let closure_ty = crate::utilis::monomorphize(
+                &method_instance,
+                target_location.ty(method, tyctx).ty,
+                tyctx,
+            );
+            let closure_getter = super::place::place_adress(
+                target_location,
+                tyctx,
+                method,
+                method_instance,
+                tycache,
+            );
+            let mut ops: Vec<CILOp> = Vec::with_capacity(values.len() * 2);
+            for (idx, value) in values {
+                ops.extend(closure_getter.iter().cloned());
+                ops.extend(value);
+                let field_desc = crate::utilis::field_descrptor(
+                    closure_ty,
+                    idx,
+                    tyctx,
+                    method_instance,
+                    tycache,
+                );
+                ops.push(CILOp::STField(field_desc.into()));
+            }
+            // A new coroutine starts in the `Unresumed` state, which has the index 0.
+            if let TyKind::Coroutine(..) = closure_ty.kind() {
+                let closure_type = tycache
+                    .type_from_cache(closure_ty, tyctx, Some(method_instance))
+                    .as_dotnet()
+                    .expect("Coroutine is not a dotnet type!");
+                ops.extend(closure_getter);
+                ops.push(CILOp::LdcI32(0));
+                ops.push(CILOp::STField(FieldDescriptor::boxed(
+                    closure_type,
+                    Type::U32,
+                    "_tag".into(),
+                )));
+            }
+            ops.extend(super::place::place_get(
+                target_location,
+                tyctx,
+                method,
+                method_instance,
+                tycache,
+            ));
+            ops
+        }
     }
 }
 /// Builds an Algebraic Data Type (struct,enum,union) at location `target_location`, with fields set using ops in `fields`.
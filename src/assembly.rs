@@ -2,22 +2,32 @@ use crate::{
     access_modifier::AccessModifer,
     cil::{CILOp, CallSite},
     codegen_error::CodegenError,
-    codegen_error::MethodCodegenError,
+    codegen_error::{CodegenLint, MethodCodegenError},
+    config::CodegenConfig,
     function_sig::FnSig,
     method::Method,
+    r#type::DotnetTypeRef,
     r#type::TyCache,
     r#type::Type,
     r#type::TypeDef,
+    report::{CodegenReport, Construct, Failure},
     IString,
 };
 use rustc_middle::mir::{
-    interpret::{AllocId, GlobalAlloc},
+    interpret::{AllocId, Allocation, GlobalAlloc},
     mono::MonoItem,
     Local, LocalDecl, Statement, Terminator,
 };
-use rustc_middle::ty::{Instance, ParamEnv, TyCtxt, TyKind};
+use rustc_middle::ty::{Instance, InstanceDef, ParamEnv, TyCtxt, TyKind};
+use rustc_span::{
+    def_id::{DefId, CRATE_DEF_ID},
+    Span, DUMMY_SP,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
 #[derive(Clone, Copy, Serialize, Deserialize, Debug)]
 /// Data representing a reference to an external assembly.
 pub struct AssemblyExternRef {
@@ -37,8 +47,30 @@ pub struct Assembly {
     entrypoint: Option<CallSite>,
     extern_refs: HashMap<IString, AssemblyExternRef>,
     static_fields: HashMap<IString, Type>,
+    /// Ops initializing the static fields holding allocations, in the order they have to run. See [`Assembly::add_static_ctor`].
+    static_initializers: Vec<(IString, Vec<CILOp>)>,
+    report: CodegenReport,
 }
 impl Assembly {
+    /// Returns the report of all the MIR constructs which could not be compiled.
+    pub fn report(&self) -> &CodegenReport {
+        &self.report
+    }
+    /// Adds `failure` to the report of constructs which could not be compiled.
+    pub fn add_failure(&mut self, failure: Failure) {
+        self.report.add(failure);
+    }
+    /// Records `failure` of the item `def_id` in the report, and reports it to the user as a rustc diagnostic at `span`.
+    fn codegen_failure(&mut self, tcx: TyCtxt, def_id: DefId, span: Span, failure: Failure) {
+        CodegenLint::from_construct(failure.construct()).emit(
+            tcx,
+            def_id,
+            span,
+            &failure.describe(),
+            failure.message(),
+        );
+        self.report.add(failure);
+    }
     /// Returns iterator over all global fields
     pub fn globals(&self) -> impl Iterator<Item = (&IString, &Type)> {
         self.static_fields.iter()
@@ -55,6 +87,8 @@ impl Assembly {
             entrypoint: None,
             extern_refs: HashMap::new(),
             static_fields: HashMap::new(),
+            static_initializers: Vec::new(),
+            report: CodegenReport::empty(),
         };
         let dotnet_ver = AssemblyExternRef {
             version: (6, 12, 0, 0),
@@ -65,22 +99,56 @@ impl Assembly {
             .insert("System.Runtime.InteropServices".into(), dotnet_ver);
         res
     }
-    /// Joins 2 assemblies together.
+    /// Joins 2 assemblies together. Type definitions are deduplicated by name, since each codegen unit emits its own copy of the types it uses.
     pub fn join(self, other: Self) -> Self {
-        let types = self.types.union(&other.types).cloned().collect();
+        let mut types = self.types;
+        let mut type_names: HashSet<IString> = types.iter().map(|tpe| tpe.name().into()).collect();
+        for tpe in other.types {
+            if type_names.insert(tpe.name().into()) {
+                types.insert(tpe);
+            } else {
+                let existing = types.iter().find(|old| old.name() == tpe.name()).unwrap();
+                assert!(
+                    existing.fields() == tpe.fields()
+                        && existing.explicit_offsets() == tpe.explicit_offsets()
+                        && existing.gargc() == tpe.gargc()
+                        && existing.extends() == tpe.extends()
+                        && existing.is_export_class() == tpe.is_export_class(),
+                    "Joined assemblies define type {name} with different layouts!",
+                    name = tpe.name()
+                );
+                add_missing_methods(&mut types, tpe);
+            }
+        }
         let mut functions = self.functions;
         functions.extend(other.functions);
+        // Allocations with the same name have the same contents, so each is initialized once. The targets of pointers within an allocation
+        // are initialized before it in both assemblies, so they still are after joining them.
+        let mut static_initializers = self.static_initializers;
+        let initialized: HashSet<IString> = static_initializers
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        static_initializers.extend(
+            other
+                .static_initializers
+                .into_iter()
+                .filter(|(name, _)| !initialized.contains(name)),
+        );
         let entrypoint = self.entrypoint.or(other.entrypoint);
         let mut extern_refs = self.extern_refs;
         let mut static_fields = self.static_fields;
         static_fields.extend(other.static_fields);
         extern_refs.extend(other.extern_refs);
+        let report = self.report.join(other.report);
         Self {
             types,
             functions,
             entrypoint,
             extern_refs,
             static_fields,
+            static_initializers,
+            report,
         }
     }
     /// Gets the typdefef at path `path`.
@@ -112,41 +180,76 @@ impl Assembly {
         }
         None
     }
-    /// Turns a terminator into ops, if ABORT_ON_ERROR set to false, will handle and recover from errors.
+    /// Turns a terminator into ops, if `abort_on_error` is not set, will handle and recover from errors.
+    /// Recovered errors are recorded in the report, as failures of the method `name`.
+    #[allow(clippy::too_many_arguments)]
     pub fn terminator_to_ops<'tcx>(
+        &mut self,
         term: &Terminator<'tcx>,
         mir: &'tcx rustc_middle::mir::Body<'tcx>,
         tcx: TyCtxt<'tcx>,
         instance: Instance<'tcx>,
         type_cache: &mut TyCache,
+        config: &CodegenConfig,
+        name: &str,
     ) -> Vec<CILOp> {
-        let mut terminator = if crate::ABORT_ON_ERROR {
-            crate::terminator::handle_terminator(term, mir, tcx, mir, instance, type_cache)
+        let handled = if config.abort_on_error {
+            Ok(
+                crate::terminator::handle_terminator(term, mir, tcx, mir, instance, type_cache)
+                    .unwrap_or_else(|err| panic!("Could not compile terminator {term:?}: {err:?}")),
+            )
         } else {
             match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
                 crate::terminator::handle_terminator(term, mir, tcx, mir, instance, type_cache)
             })) {
-                Ok(ok) => ok,
+                Ok(result) => result.map_err(|err| format!("{err:?}")),
                 Err(payload) => {
                     type_cache.recover_from_panic();
-                    let msg = if let Some(msg) = payload.downcast_ref::<&str>() {
-                        rustc_middle::ty::print::with_no_trimmed_paths! {
-                        format!("Tried to execute terminator {term:?} whose compialtion message {msg:?}!")}
-                    } else {
-                        eprintln!("handle_terminator panicked with a non-string message!");
-                        rustc_middle::ty::print::with_no_trimmed_paths! {
-                        format!("Tried to execute terminator {term:?} whose compialtion failed with a no-string message!")
-                        }
-                    };
-                    CILOp::throw_msg(&msg).into()
+                    Err(crate::codegen_error::panic_message(&*payload)
+                        .unwrap_or("handle_terminator panicked with a non-string message!")
+                        .to_owned())
                 }
             }
         };
+        let mut terminator = match handled {
+            Ok(ops) => ops,
+            Err(error) => {
+                let msg = rustc_middle::ty::print::with_no_trimmed_paths! {
+                format!("Tried to execute terminator {term:?} whose compialtion message {error:?}!")};
+                self.codegen_failure(
+                    tcx,
+                    instance.def_id(),
+                    term.source_info.span,
+                    Failure::new(
+                        tcx,
+                        name,
+                        Construct::Terminator,
+                        crate::report::terminator_kind(&term.kind),
+                        &error,
+                        term.source_info.span,
+                    ),
+                );
+                CILOp::throw_msg(&msg).into()
+            }
+        };
         let argc = mir.arg_count as u32;
-        let locc = mir.local_decls.len() as u32 - mir.arg_count as u32;
+        let locc = mir.local_decls.len() as u32 - mir.arg_count as u32
+            + u32::from(mir.spread_arg.is_some());
         if !crate::utilis::verify_locals_within_range(&terminator, argc, locc) {
             let msg = rustc_middle::ty::print::with_no_trimmed_paths! {format!("{term:?} failed verification, because it refered to local varibles/arguments that do not exist. ops:{terminator:?} argc:{argc} locc:{locc}")};
-            eprintln!("WARING: teminator {msg}");
+            self.codegen_failure(
+                tcx,
+                instance.def_id(),
+                term.source_info.span,
+                Failure::new(
+                    tcx,
+                    name,
+                    Construct::Terminator,
+                    crate::report::terminator_kind(&term.kind),
+                    &msg,
+                    term.source_info.span,
+                ),
+            );
             terminator.clear();
             rustc_middle::ty::print::with_no_trimmed_paths! {terminator.extend(CILOp::throw_msg(&format!(
                 "Tried to execute miscompiled terminator {term:?}, which {msg}"
@@ -154,25 +257,28 @@ impl Assembly {
         }
         terminator
     }
-    /// Turns a statement into ops, if ABORT_ON_ERROR set to false, will handle and recover from errors.
+    /// Turns a statement into ops, if `abort_on_error` is not set, will handle and recover from errors.
     pub fn statement_to_ops<'tcx>(
         statement: &Statement<'tcx>,
         tcx: TyCtxt<'tcx>,
         mir: &rustc_middle::mir::Body<'tcx>,
         instance: Instance<'tcx>,
         type_cache: &mut TyCache,
+        config: &CodegenConfig,
     ) -> Result<Vec<CILOp>, CodegenError> {
-        if crate::ABORT_ON_ERROR {
+        if config.abort_on_error {
             Ok(crate::statement::handle_statement(
-                statement, tcx, mir, instance, type_cache,
+                statement, tcx, mir, instance, type_cache, config,
             ))
         } else {
             match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                crate::statement::handle_statement(statement, tcx, mir, instance, type_cache)
+                crate::statement::handle_statement(
+                    statement, tcx, mir, instance, type_cache, config,
+                )
             })) {
                 Ok(success) => Ok(success),
                 Err(payload) => {
-                    if let Some(msg) = payload.downcast_ref::<&str>() {
+                    if let Some(msg) = crate::codegen_error::panic_message(&*payload) {
                         Err(crate::codegen_error::CodegenError::from_panic_message(msg))
                     } else {
                         Err(crate::codegen_error::CodegenError::from_panic_message(
@@ -190,21 +296,30 @@ impl Assembly {
         tcx: TyCtxt<'tcx>,
         name: &str,
         cache: &mut TyCache,
+        config: &CodegenConfig,
     ) -> Result<(), MethodCodegenError> {
         match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            self.add_fn(instance, tcx, name, cache)
+            self.add_fn(instance, tcx, name, cache, config)
         })) {
             Ok(success) => success,
             Err(payload) => {
                 cache.recover_from_panic();
-                if let Some(msg) = payload.downcast_ref::<&str>() {
-                    eprintln!("could not compile method {name}. fn_add panicked with unhandled message: {msg:?}");
-                    //self.add_method(Method::missing_because(format!("could not compile method {name}. fn_add panicked with unhandled message: {msg:?}")));
-                    Ok(())
-                } else {
-                    eprintln!("could not compile method {name}. fn_add panicked with no message.");
-                    Ok(())
-                }
+                let msg = crate::codegen_error::panic_message(&*payload)
+                    .unwrap_or("fn_add panicked with no message.");
+                self.codegen_failure(
+                    tcx,
+                    instance.def_id(),
+                    tcx.def_span(instance.def_id()),
+                    Failure::new(
+                        tcx,
+                        name,
+                        Construct::Method,
+                        "Method",
+                        msg,
+                        tcx.def_span(instance.def_id()),
+                    ),
+                );
+                Ok(())
             }
         }
     }
@@ -216,99 +331,220 @@ impl Assembly {
         tcx: TyCtxt<'tcx>,
         name: &str,
         cache: &mut TyCache,
+        config: &CodegenConfig,
     ) -> Result<(), MethodCodegenError> {
         if crate::utilis::is_function_magic(name) {
             return Ok(());
         }
-        if let TyKind::FnDef(_, _) = instance.ty(tcx, ParamEnv::reveal_all()).kind() {
+        if let TyKind::FnDef(_, _) | TyKind::Closure(..) | TyKind::Coroutine(..) =
+            instance.ty(tcx, ParamEnv::reveal_all()).kind()
+        {
             //ALL OK.
         } else {
             eprintln!("fn item {instance:?} is not a function definition type. Skippping.");
             return Ok(());
         }
 
-        // Get the MIR if it exisits. Othervise, return early.
-        if !tcx.is_mir_available(instance.def_id()) {
-            println!("function {instance:?} has no MIR. Skippping.");
-            return Ok(());
+        // Get the MIR if it exisits. Othervise, return early. Shims (drop glue, closure shims, etc.) have their MIR built on demand.
+        if let InstanceDef::Item(def_id) = instance.def {
+            if !tcx.is_mir_available(def_id) {
+                self.codegen_failure(
+                    tcx,
+                    def_id,
+                    tcx.def_span(def_id),
+                    Failure::new(
+                        tcx,
+                        name,
+                        Construct::Method,
+                        "Method",
+                        "Function has no MIR.",
+                        tcx.def_span(def_id),
+                    ),
+                );
+                return Ok(());
+            }
         }
 
-        let mir = tcx.optimized_mir(instance.def_id());
-        // Check if function is public or not.
-        // FIXME: figure out the source of the bug causing visibility to not be read propely.
-        // let access_modifier = AccessModifer::from_visibility(tcx.visibility(instance.def_id()));
-        let access_modifier = AccessModifer::Public;
+        let mir = tcx.instance_mir(instance.def);
+        // Rust functions are only visible to other .NET code through the public wrappers of exported functions.
+        let access_modifier = AccessModifer::Private;
         // Handle the function signature
         let sig = match FnSig::sig_from_instance_(instance, tcx, cache) {
             Ok(sig) => sig,
             Err(err) => {
-                eprintln!("Could not get the signature of function {name} because {err:?}");
+                self.codegen_failure(
+                    tcx,
+                    instance.def_id(),
+                    tcx.def_span(instance.def_id()),
+                    Failure::new(
+                        tcx,
+                        name,
+                        Construct::Method,
+                        "Method",
+                        &format!("Could not get the signature: {err:?}"),
+                        tcx.def_span(instance.def_id()),
+                    ),
+                );
                 return Ok(());
             }
         };
 
         // Get locals
         //eprintln!("method")
-        let locals = locals_from_mir(&mir.local_decls, tcx, mir.arg_count, &instance, cache);
+        let mut locals = locals_from_mir(&mir.local_decls, tcx, mir.arg_count, &instance, cache);
+        // The tuple of arguments spread by the "rust-call" ABI is a local variable, placed after all others.
+        if let Some(spread_arg) = mir.spread_arg {
+            let spread_ty =
+                crate::utilis::monomorphize(&instance, mir.local_decls[spread_arg].ty, tcx);
+            locals.push((None, cache.type_from_cache(spread_ty, tcx, Some(instance))));
+        }
         // Create method prototype
         let mut method = Method::new(access_modifier, true, sig, name, locals);
+        method.set_generic_count(crate::generics::generic_count(instance));
         let mut ops = Vec::new();
-        if crate::TRACE_CALLS {
+        if config.trace_calls {
             ops.extend(CILOp::debug_msg(&format!("Called {name}.")));
         }
-        let mut last_bb_id = 0;
-
+        ops.extend(crate::closure::retuple_spread_arg(
+            mir, tcx, instance, cache,
+        ));
         let blocks = &(*mir.basic_blocks);
         let does_return_void: bool = *method.sig().output() == Type::Void;
+        // Statement and terminator ops of each block. They are kept apart, since cleanup blocks need to be copied into handlers, and terminators with cleanup need to be wrapped in try blocks.
+        let mut block_ops = Vec::with_capacity(blocks.len());
         for block_data in blocks {
-            ops.push(CILOp::Label(last_bb_id));
-            last_bb_id += 1;
+            let mut statements_ops = Vec::new();
             for statement in &block_data.statements {
-                if crate::INSERT_MIR_DEBUG_COMMENTS {
-                    rustc_middle::ty::print::with_no_trimmed_paths! {ops.push(CILOp::Comment(format!("{statement:?}").into()))};
+                if config.insert_mir_debug_comments {
+                    rustc_middle::ty::print::with_no_trimmed_paths! {statements_ops.push(CILOp::Comment(format!("{statement:?}").into()))};
                 }
                 let statement_ops = match Self::statement_to_ops(
-                    statement, tcx, mir, instance, cache,
+                    statement, tcx, mir, instance, cache, config,
                 ) {
                     Ok(ops) => ops,
                     Err(err) => {
                         cache.recover_from_panic();
-                        rustc_middle::ty::print::with_no_trimmed_paths! {eprintln!(
-                            "Method \"{name}\" failed to compile statement {statement:?} with message {err:?}"
-                        )};
+                        self.codegen_failure(
+                            tcx,
+                            instance.def_id(),
+                            statement.source_info.span,
+                            Failure::new(
+                                tcx,
+                                name,
+                                Construct::Statement,
+                                &crate::report::statement_kind(&statement.kind),
+                                &format!("{err:?}"),
+                                statement.source_info.span,
+                            ),
+                        );
                         rustc_middle::ty::print::with_no_trimmed_paths! {CILOp::throw_msg(&format!("Tired to run a statement {statement:?} which failed to compile with error message {err:?}.")).into()}
                     }
                 };
-                crate::utilis::check_debugable(&statement_ops, statement, does_return_void);
-                ops.extend(statement_ops);
-                if crate::INSERT_MIR_DEBUG_COMMENTS {
-                    ops.push(CILOp::Comment("STATEMENT END.".into()));
+                if !crate::utilis::check_debugable(&statement_ops, statement, does_return_void) {
+                    CodegenLint::emit_miscompilation(
+                        tcx,
+                        instance.def_id(),
+                        statement.source_info.span,
+                        &format!("probable miscompilation of a statement in method `{name}`"),
+                        "the compiled statement did not pass the stack check",
+                        config,
+                    );
+                }
+                statements_ops.extend(statement_ops);
+                if config.insert_mir_debug_comments {
+                    statements_ops.push(CILOp::Comment("STATEMENT END.".into()));
                 }
             }
-            match &block_data.terminator {
+            let term_ops = match &block_data.terminator {
                 Some(term) => {
-                    let term_ops = Self::terminator_to_ops(term, mir, tcx, instance, cache);
-                    if term_ops != &[CILOp::Ret] {
-                        crate::utilis::check_debugable(&term_ops, term, does_return_void);
+                    let term_ops =
+                        self.terminator_to_ops(term, mir, tcx, instance, cache, config, name);
+                    if term_ops != &[CILOp::Ret]
+                        && !crate::utilis::check_debugable(&term_ops, term, does_return_void)
+                    {
+                        CodegenLint::emit_miscompilation(
+                            tcx,
+                            instance.def_id(),
+                            term.source_info.span,
+                            &format!("probable miscompilation of a terminator in method `{name}`"),
+                            "the compiled terminator did not pass the stack check",
+                            config,
+                        );
                     }
-
-                    ops.extend(term_ops)
+                    term_ops
                 }
-                None => (),
+                None => Vec::new(),
+            };
+            block_ops.push((statements_ops, term_ops));
+        }
+        // Each handler gets its own copy of the cleanup blocks, with its own labels.
+        let mut handler_copies = 0;
+        for (block, block_data) in blocks.iter_enumerated() {
+            // Cleanup blocks are only reachable by unwinding, so they are only emitted inside handlers.
+            if block_data.is_cleanup {
+                continue;
+            }
+            let (statements_ops, term_ops) = &block_ops[block.as_usize()];
+            ops.push(CILOp::Label(block.as_u32()));
+            ops.extend(statements_ops.iter().cloned());
+            match block_data
+                .terminator
+                .as_ref()
+                .and_then(crate::unwind::cleanup_target)
+            {
+                Some(cleanup) => {
+                    handler_copies += 1;
+                    let handler =
+                        crate::unwind::cleanup_handler(mir, &block_ops, cleanup, handler_copies);
+                    ops.extend(crate::unwind::wrap_in_try(
+                        term_ops,
+                        handler,
+                        handler_copies,
+                        blocks.len() as u32,
+                    ));
+                }
+                None => ops.extend(term_ops.iter().cloned()),
             }
         }
         ops.iter_mut().for_each(|op| match op {
             CILOp::LoadGlobalAllocPtr { alloc_id } => {
-                *op = CILOp::LDStaticField(self.add_allocation(*alloc_id, tcx).into());
+                *op = CILOp::LDStaticField(self.add_allocation(*alloc_id, tcx, cache).into());
             }
             _ => (),
         });
 
         method.set_ops(ops);
         // Do some basic checks on the method as a whole.
-        crate::utilis::check_debugable(method.get_ops(), &method, does_return_void);
+        if !crate::utilis::check_debugable(method.get_ops(), &method, does_return_void) {
+            CodegenLint::emit_miscompilation(
+                tcx,
+                instance.def_id(),
+                tcx.def_span(instance.def_id()),
+                &format!("probable miscompilation of method `{name}`"),
+                "the compiled method did not pass the stack check",
+                config,
+            );
+        }
+        if config.verify_cil {
+            if let Err(err) = crate::verifier::verify_method(&method) {
+                CodegenLint::emit_miscompilation(
+                    tcx,
+                    instance.def_id(),
+                    tcx.def_span(instance.def_id()),
+                    &format!("probable miscompilation of method `{name}`"),
+                    &format!("the compiled method did not pass CIL verification: {err}"),
+                    config,
+                );
+            }
+        }
         self.types.extend(cache.defs().cloned());
         println!("Compiled method {name}");
+        if let Some(thunk) = crate::closure::fn_ptr_thunk(&method, mir, tcx, instance) {
+            self.add_method(thunk);
+        }
+        if let Some(export) = crate::export::Export::of_instance(instance, tcx) {
+            self.add_export(&export, &method.call_site());
+        }
         self.add_method(method);
         Ok(())
         //todo!("Can't add function")
@@ -317,87 +553,154 @@ impl Assembly {
     pub fn add_static(&mut self, tpe: Type, name: &str) {
         self.static_fields.insert(name.into(), tpe);
     }
-    fn add_allocation(
+    /// Returns the static field holding the allocation `alloc_id`. If the field is not in the assembly yet, adds it, together with its initializer.
+    fn add_allocation<'tcx>(
         &mut self,
         alloc_id: u64,
-        tcx: TyCtxt<'_>,
+        tcx: TyCtxt<'tcx>,
+        cache: &mut TyCache,
     ) -> crate::cil::StaticFieldDescriptor {
-        let const_allocation =
-            match tcx.global_alloc(AllocId(alloc_id.try_into().expect("0 alloc id?"))) {
-                GlobalAlloc::Memory(alloc) => alloc,
-                GlobalAlloc::Static(def_id) => {
-                    let alloc = tcx.eval_static_initializer(def_id).unwrap();
-                    //tcx.reserve_and_set_memory_alloc(alloc)
-                    alloc
-                }
-                GlobalAlloc::Function(_) | GlobalAlloc::VTable(..) => {
-                    unreachable!()
-                }
-            };
-        let const_allocation = const_allocation.inner();
-        let bytes: &[u8] = const_allocation
-            .inspect_with_uninit_and_ptr_outside_interpreter(0..const_allocation.len());
-        //let byte_hash = calculate_hash(&bytes);
-
-        let alloc_fld: IString = format!("alloc_{alloc_id:x}").into();
+        let alloc_id = AllocId(alloc_id.try_into().expect("0 alloc id?"));
+        let alloc_fld = allocation_name(alloc_id, tcx);
         let field_desc = crate::cil::StaticFieldDescriptor::new(
             None,
             Type::Ptr(Type::U8.into()),
             alloc_fld.clone(),
         );
         if self.static_fields.get(&alloc_fld).is_none() {
-            let method = self
-                .functions
-                .entry(CallSite::new(
-                    None,
-                    ".cctor".into(),
-                    FnSig::new(&[], &Type::Void),
-                    true,
-                ))
-                .or_insert_with(|| {
-                    Method::new(
-                        AccessModifer::Public,
-                        true,
-                        FnSig::new(&[], &Type::Void),
-                        ".cctor",
-                        vec![
-                            (None, Type::Ptr(Type::U8.into())),
-                            (None, Type::Ptr(Type::U8.into())),
-                        ],
-                    )
-                });
-            let ops: &mut Vec<CILOp> = method.ops_mut();
-            if !ops.is_empty() && ops[ops.len() - 1] == CILOp::Ret {
-                ops.pop();
-            }
+            let const_allocation = match tcx.global_alloc(alloc_id) {
+                GlobalAlloc::Memory(alloc) => alloc,
+                GlobalAlloc::Static(def_id) => tcx.eval_static_initializer(def_id).unwrap(),
+                GlobalAlloc::Function(_) | GlobalAlloc::VTable(..) => {
+                    unreachable!()
+                }
+            };
+            let const_allocation = const_allocation.inner();
+            let bytes: &[u8] = const_allocation
+                .inspect_with_uninit_and_ptr_outside_interpreter(0..const_allocation.len());
+            // The field is added before handling pointers within this allocation, since they may point back to it.
+            self.static_fields
+                .insert(alloc_fld.clone(), Type::Ptr(Type::U8.into()));
+            let relocations = self.allocation_relocations(const_allocation, tcx, cache);
+            self.init_allocation(&field_desc, bytes, relocations, CallSite::malloc(tcx));
+        }
+        field_desc
+    }
+    /// Adds the initializer of the static field `field`: it allocates a buffer using `malloc`, copies `bytes` into it, and runs `relocations`(see [`Self::allocation_relocations`]).
+    fn init_allocation(
+        &mut self,
+        field: &crate::cil::StaticFieldDescriptor,
+        bytes: &[u8],
+        relocations: Vec<CILOp>,
+        malloc: CallSite,
+    ) {
+        let mut ops = vec![
+            CILOp::LdcI64(bytes.len() as u64 as i64),
+            CILOp::ConvISize(false),
+            CILOp::Call(malloc.into()),
+            CILOp::Dup,
+            CILOp::STLoc(0),
+            CILOp::STLoc(1),
+        ];
+        for byte in bytes {
             ops.extend([
-                CILOp::LdcI64(const_allocation.len() as u64 as i64),
-                CILOp::ConvISize(false),
-                CILOp::Call(CallSite::malloc(tcx).into()),
-                CILOp::Dup,
+                CILOp::LDLoc(0),
+                CILOp::LdcI32(*byte as i32),
+                CILOp::STIndI8,
+                CILOp::LDLoc(0),
+                CILOp::LdcI32(1),
+                CILOp::Add,
                 CILOp::STLoc(0),
-                CILOp::STLoc(1),
             ]);
-            for byte in bytes {
-                ops.extend([
-                    CILOp::LDLoc(0),
-                    CILOp::LdcI32(*byte as i32),
-                    CILOp::STIndI8,
-                    CILOp::LDLoc(0),
-                    CILOp::LdcI32(1),
-                    CILOp::Add,
-                    CILOp::STLoc(0),
-                ]);
-            }
+        }
+        ops.extend(relocations);
+        ops.extend([CILOp::LDLoc(1), CILOp::STStaticField(field.clone().into())]);
+        self.add_static_initializer(field.name(), ops);
+    }
+    /// Adds `ops`, initializing the static field `name`, to the static constructor. They run after the initializers added before them,
+    /// and may use the 2 locals of the static constructor.
+    pub(crate) fn add_static_initializer(&mut self, name: &str, ops: Vec<CILOp>) {
+        self.static_initializers.push((name.into(), ops));
+    }
+    /// Returns the static fields initialized by the static constructor, and the ops initializing them.
+    pub(crate) fn static_initializers(&self) -> impl Iterator<Item = (&IString, &[CILOp])> {
+        self.static_initializers
+            .iter()
+            .map(|(name, ops)| (name, ops.as_slice()))
+    }
+    /// Adds the static constructor, running the initializers of the static fields left in the assembly. Called by the linker once the
+    /// dead code is removed, so only the fields which are used get initialized.
+    pub fn add_static_ctor(&mut self) {
+        if self.static_initializers.is_empty() {
+            return;
+        }
+        let mut ops: Vec<CILOp> = std::mem::take(&mut self.static_initializers)
+            .into_iter()
+            .flat_map(|(_, ops)| ops)
+            .collect();
+        ops.push(CILOp::Ret);
+        let mut cctor = Method::new(
+            AccessModifer::Public,
+            true,
+            FnSig::new(&[], &Type::Void),
+            ".cctor",
+            vec![
+                (None, Type::Ptr(Type::U8.into())),
+                (None, Type::Ptr(Type::U8.into())),
+            ],
+        );
+        cctor.set_ops(ops);
+        self.add_method(cctor);
+    }
+    /// Returns the ops setting the pointers within `allocation`, which is being initialized in the static constructor.
+    /// Pointers to functions are set using `ldftn`, and vtables are allocations of such pointers.
+    fn allocation_relocations<'tcx>(
+        &mut self,
+        allocation: &Allocation,
+        tcx: TyCtxt<'tcx>,
+        cache: &mut TyCache,
+    ) -> Vec<CILOp> {
+        let ptr_size = tcx.data_layout.pointer_size.bytes_usize();
+        let mut ops = Vec::new();
+        for &(offset, target) in allocation.provenance().ptrs().iter() {
+            let start = offset.bytes_usize();
+            // The bytes of a pointer contain its offset within the allocation it points to.
+            let mut addend = [0; 8];
+            addend[..ptr_size].copy_from_slice(
+                allocation.inspect_with_uninit_and_ptr_outside_interpreter(start..start + ptr_size),
+            );
+            let addend = u64::from_le_bytes(addend);
+            // Local 1 contains the address of the allocation.
             ops.extend([
                 CILOp::LDLoc(1),
-                CILOp::STStaticField(field_desc.clone().into()),
-                CILOp::Ret,
+                CILOp::LdcI64(start as i64),
+                CILOp::ConvISize(false),
+                CILOp::Add,
+            ]);
+            match tcx.global_alloc(target) {
+                GlobalAlloc::Function(instance) => ops.push(CILOp::LDFtn(
+                    CallSite::from_instance(instance, tcx, cache).into(),
+                )),
+                GlobalAlloc::VTable(ty, trait_ref) => {
+                    let vtable = tcx.vtable_allocation((ty, trait_ref));
+                    let vtable =
+                        self.add_allocation(crate::utilis::alloc_id_to_u64(vtable), tcx, cache);
+                    ops.push(CILOp::LDStaticField(vtable.into()));
+                }
+                GlobalAlloc::Memory(_) | GlobalAlloc::Static(_) => {
+                    let target =
+                        self.add_allocation(crate::utilis::alloc_id_to_u64(target), tcx, cache);
+                    ops.push(CILOp::LDStaticField(target.into()));
+                }
+            }
+            ops.extend([
+                CILOp::LdcI64(addend as i64),
+                CILOp::ConvISize(false),
+                CILOp::Add,
+                CILOp::STIndISize,
             ]);
-            self.static_fields
-                .insert(alloc_fld, Type::Ptr(Type::U8.into()));
         }
-        field_desc
+        ops
     }
     /// Adds 100 first array types
     pub fn add_array_types(&mut self) {
@@ -417,31 +720,158 @@ impl Assembly {
         method.ensure_valid();
         self.functions.insert(method.call_site(), method);
     }
-    /// Returns the list of all calls within the method. Calls may repeat.
+    /// Returns the list of all calls within the methods of the assembly, including the methods of its types(eg. export wrappers) and the initializers
+    /// of its static fields. Calls may repeat.
     pub fn call_sites(&self) -> impl Iterator<Item = &CallSite> {
-        self.methods().flat_map(|method| method.calls())
+        let mut type_methods = Vec::new();
+        let mut type_defs: Vec<&TypeDef> = self.types().collect();
+        while let Some(type_def) = type_defs.pop() {
+            type_methods.extend(type_def.methods());
+            type_defs.extend(type_def.inner_types());
+        }
+        self.methods()
+            .chain(type_methods)
+            .flat_map(|method| method.calls())
+            .chain(
+                self.static_initializers
+                    .iter()
+                    .flat_map(|(_, ops)| ops.iter().filter_map(CILOp::call)),
+            )
     }
     /// Returns an interator over all methods within the assembly.
     pub fn methods(&self) -> impl Iterator<Item = &Method> {
         self.functions.values()
     }
+    /// Returns the method behind `site`, if it is defined within the assembly.
+    pub fn get_method(&self, site: &CallSite) -> Option<&Method> {
+        self.functions.get(site)
+    }
+    /// Inlines calls to trivial methods across the whole assembly, and then removes the inlined methods which are no longer referenced.
+    /// Public methods are kept if `keep_public` is set. Used by the linker, where calls between crates can be inlined too. Returns the number of inlined calls.
+    pub fn inline_calls(&mut self, keep_public: bool) -> usize {
+        let sites: Vec<_> = self.functions.keys().cloned().collect();
+        let mut inlined = HashSet::new();
+        let mut count = 0;
+        for site in sites {
+            let mut method = self.functions[&site].clone();
+            let inlined_calls = crate::opt::inline_calls(&mut method, self, &mut inlined);
+            if inlined_calls > 0 {
+                count += inlined_calls;
+                self.functions.insert(site, method);
+            }
+        }
+        let referenced: HashSet<CallSite> = self.call_sites().cloned().collect();
+        let entrypoint = self.entrypoint.clone();
+        self.functions.retain(|site, method| {
+            !inlined.contains(site)
+                || referenced.contains(site)
+                || method.is_entrypoint()
+                || entrypoint.as_ref() == Some(site)
+                || (keep_public && method.access() == AccessModifer::Public)
+        });
+        count
+    }
+    /// Keeps only the methods whose call sites satisfy `keep`. Returns the number of removed methods.
+    pub(crate) fn retain_methods(&mut self, keep: impl Fn(&CallSite) -> bool) -> usize {
+        let count = self.functions.len();
+        self.functions.retain(|site, _| keep(site));
+        count - self.functions.len()
+    }
+    /// Keeps only the type definitions which satisfy `keep`. Returns the number of removed types.
+    pub(crate) fn retain_types(&mut self, keep: impl Fn(&TypeDef) -> bool) -> usize {
+        let count = self.types.len();
+        self.types.retain(|type_def| keep(type_def));
+        count - self.types.len()
+    }
+    /// Keeps only the static fields(and their initializers) whose names satisfy `keep`. Returns the number of removed static fields.
+    pub(crate) fn retain_statics(&mut self, keep: impl Fn(&str) -> bool) -> usize {
+        let count = self.static_fields.len();
+        self.static_fields.retain(|name, _| keep(name));
+        self.static_initializers.retain(|(name, _)| keep(name));
+        count - self.static_fields.len()
+    }
+    /// Replaces the types used anywhere within the assembly, as specified by `map`. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        self.types = std::mem::take(&mut self.types)
+            .into_iter()
+            .map(|mut type_def| {
+                type_def.map_types(map);
+                type_def
+            })
+            .collect();
+        // Methods are keyed by their call sites, which contain their signatures.
+        self.functions = std::mem::take(&mut self.functions)
+            .into_values()
+            .map(|mut method| {
+                method.map_types(map);
+                (method.call_site(), method)
+            })
+            .collect();
+        if let Some(entrypoint) = &mut self.entrypoint {
+            entrypoint.map_types(map);
+        }
+        for tpe in self.static_fields.values_mut() {
+            tpe.map_types(map);
+        }
+        for op in self
+            .static_initializers
+            .iter_mut()
+            .flat_map(|(_, ops)| ops.iter_mut())
+        {
+            op.map_types(map);
+        }
+    }
     /// Returns an iterator over all types witin the assembly.
     pub fn types(&self) -> impl Iterator<Item = &TypeDef> {
         self.types.iter()
     }
-    /// Optimizes all the methods witin the assembly.
-    pub fn opt(&mut self) {
+    /// Optimizes all the methods witin the assembly, and returns the statistics of the optimization passes.
+    /// Methods which do not pass CIL verification after being optimized are reported as miscompilations of the crate.
+    pub fn opt(&mut self, tcx: TyCtxt, config: &CodegenConfig) -> crate::opt::OptStats {
+        let mut stats = crate::opt::OptStats::default();
         let functions: HashMap<_, _> = self
             .functions
             .iter()
             .map(|method| {
                 let (site, method) = method;
                 let mut method = method.clone();
-                crate::opt::opt_method(&mut method, self);
+                crate::opt::opt_method(&mut method, self, config, &mut stats);
+                if config.verify_cil {
+                    if let Err(err) = crate::verifier::verify_method(&method) {
+                        CodegenLint::emit_miscompilation(
+                            tcx,
+                            CRATE_DEF_ID.to_def_id(),
+                            DUMMY_SP,
+                            &format!(
+                                "probable miscompilation of method `{name}`",
+                                name = method.name()
+                            ),
+                            &format!("the optimized method did not pass CIL verification: {err}"),
+                            config,
+                        );
+                    }
+                }
                 (site.clone(), method)
             })
             .collect();
         self.functions = functions;
+        stats
+    }
+    /// Adds the public wrapper of the exported function `target` to the class it is exported in, defining the class if needed.
+    pub fn add_export(&mut self, export: &crate::export::Export, target: &CallSite) {
+        let mut class = TypeDef::new(
+            AccessModifer::Public,
+            export.class().into(),
+            vec![],
+            vec![],
+            vec![],
+            None,
+            0,
+            Some(DotnetTypeRef::object_type()),
+        );
+        class.set_export_class();
+        class.add_method(export.wrapper(target));
+        add_missing_methods(&mut self.types, class);
     }
     /// Adds a definition of a type to the assembly.
     pub fn add_typedef(&mut self, type_def: TypeDef) {
@@ -453,6 +883,7 @@ impl Assembly {
         item: MonoItem<'tcx>,
         tcx: TyCtxt<'tcx>,
         cache: &mut TyCache,
+        config: &CodegenConfig,
     ) -> Result<(), CodegenError> {
         if !item.is_instantiable(tcx) {
             let name = item.symbol_name(tcx);
@@ -465,9 +896,24 @@ impl Assembly {
         match item {
             MonoItem::Fn(instance) => {
                 //let instance = crate::utilis::monomorphize(&instance,tcx);
-                let symbol_name = crate::utilis::function_name(item.symbol_name(tcx));
+                let (instance, symbol_name) =
+                    match crate::generics::preserved_instance(instance, tcx, cache) {
+                        // All instances of a function emitted as a CIL generic method share a single definition.
+                        Some(generic) => {
+                            let symbol_name =
+                                crate::utilis::function_name(tcx.symbol_name(generic));
+                            if self.contains_fn_named(&symbol_name) {
+                                return Ok(());
+                            }
+                            (generic, symbol_name)
+                        }
+                        None => (
+                            instance,
+                            crate::utilis::function_name(item.symbol_name(tcx)),
+                        ),
+                    };
 
-                self.checked_add_fn(instance, tcx, &symbol_name, cache)
+                self.checked_add_fn(instance, tcx, &symbol_name, cache, config)
                     .expect("Could not add function!");
 
                 Ok(())
@@ -477,14 +923,18 @@ impl Assembly {
                 Ok(())
             }
             MonoItem::Static(stotic) => {
-                let alloc = tcx.eval_static_initializer(stotic).unwrap();
-                let alloc_id = tcx.reserve_and_set_memory_alloc(alloc);
-                self.add_allocation(crate::utilis::alloc_id_to_u64(alloc_id), tcx);
+                // The static gets the same field as the references to it.
+                let alloc_id = tcx.reserve_and_set_static_alloc(stotic);
+                self.add_allocation(crate::utilis::alloc_id_to_u64(alloc_id), tcx, cache);
                 //eprintln!("Unsuported item - Static:{stotic:?}");
                 Ok(())
             }
         }
     }
+    /// Returns the call site of the entrypoint of the assembly, if it has one.
+    pub fn entrypoint(&self) -> Option<&CallSite> {
+        self.entrypoint.as_ref()
+    }
     /// Sets the entrypoint of the assembly to the method behind `CallSite`.
     pub fn set_entrypoint(&mut self, entrypoint: CallSite) {
         assert!(self.entrypoint.is_none(), "ERROR: Multiple entrypoints");
@@ -493,6 +943,83 @@ impl Assembly {
         self.entrypoint = Some(entrypoint);
     }
 }
+/// Returns the name of the static field holding the allocation `alloc_id`.
+/// Allocation ids are only valid within one session, so the name is derived from the allocation itself: the symbol of a static, or the contents of other allocations.
+/// This way, fragments reused by incremental compilation and other crates use the same name for the same allocation, and never for a different one.
+fn allocation_name(alloc_id: AllocId, tcx: TyCtxt) -> IString {
+    match tcx.global_alloc(alloc_id) {
+        GlobalAlloc::Static(def_id) => {
+            let symbol = tcx.symbol_name(Instance::mono(tcx, def_id));
+            static_allocation_name(symbol.name)
+        }
+        GlobalAlloc::Memory(alloc) => {
+            let alloc = alloc.inner();
+            let bytes = alloc.inspect_with_uninit_and_ptr_outside_interpreter(0..alloc.len());
+            let relocations: Vec<(u64, IString)> = alloc
+                .provenance()
+                .ptrs()
+                .iter()
+                .map(|&(offset, target)| {
+                    let target = match tcx.global_alloc(target) {
+                        GlobalAlloc::Function(instance) => {
+                            crate::utilis::function_name(tcx.symbol_name(instance))
+                        }
+                        GlobalAlloc::VTable(ty, trait_ref) => {
+                            allocation_name(tcx.vtable_allocation((ty, trait_ref)), tcx)
+                        }
+                        GlobalAlloc::Memory(_) | GlobalAlloc::Static(_) => {
+                            allocation_name(target, tcx)
+                        }
+                    };
+                    (offset.bytes(), target)
+                })
+                .collect();
+            memory_allocation_name(bytes, &relocations)
+        }
+        GlobalAlloc::Function(_) | GlobalAlloc::VTable(..) => unreachable!(),
+    }
+}
+/// Returns the name of the static field holding the static with the symbol `symbol`.
+fn static_allocation_name(symbol: &str) -> IString {
+    let mut hasher = DefaultHasher::new();
+    "static".hash(&mut hasher);
+    symbol.hash(&mut hasher);
+    format!("alloc_{:x}", hasher.finish()).into()
+}
+/// Returns the name of the static field holding an allocation containing `bytes`, with pointers to `relocations`(offsets of the pointers and names of their targets).
+fn memory_allocation_name(bytes: &[u8], relocations: &[(u64, IString)]) -> IString {
+    let mut hasher = DefaultHasher::new();
+    "memory".hash(&mut hasher);
+    bytes.hash(&mut hasher);
+    relocations.hash(&mut hasher);
+    format!("alloc_{:x}", hasher.finish()).into()
+}
+/// Adds the methods of `type_def` missing from the type with the same name in `types`, or `type_def` itself if there is no such type.
+/// Exported functions of different codegen units and crates may be placed in the same class.
+fn add_missing_methods(types: &mut HashSet<TypeDef>, type_def: TypeDef) {
+    let Some(existing) = types.iter().find(|tpe| tpe.name() == type_def.name()) else {
+        types.insert(type_def);
+        return;
+    };
+    let missing: Vec<_> = type_def
+        .methods()
+        .filter(|method| {
+            !existing
+                .methods()
+                .any(|old| old.name() == method.name() && old.sig() == method.sig())
+        })
+        .cloned()
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+    let mut merged = existing.clone();
+    types.remove(&merged);
+    for method in missing {
+        merged.add_method(method);
+    }
+    types.insert(merged);
+}
 /// Returns the list of all local variables within MIR of a function, and converts them to the internal type represenation `Type`
 fn locals_from_mir<'tyctx>(
     locals: &rustc_index::IndexVec<Local, LocalDecl<'tyctx>>,
@@ -518,3 +1045,226 @@ fn locals_from_mir<'tyctx>(
     }
     local_types
 }
+#[test]
+fn join_dedups_types() {
+    let shared = |method: &str| {
+        let mut shared = TypeDef::nameonly("Shared");
+        shared.add_field("field".into(), Type::I32);
+        let mut method = Method::new(
+            AccessModifer::Public,
+            true,
+            FnSig::new(&[], &Type::Void),
+            method,
+            vec![],
+        );
+        method.set_ops(vec![CILOp::Ret]);
+        shared.add_method(method);
+        shared
+    };
+    let mut a = Assembly::empty();
+    a.add_typedef(shared("First"));
+    let mut b = Assembly::empty();
+    b.add_typedef(shared("Second"));
+    b.add_typedef(TypeDef::nameonly("Other"));
+    let joined = a.join(b);
+    assert_eq!(joined.types().count(), 2);
+    let shared = joined.get_typedef_by_path("Shared").unwrap();
+    assert_eq!(shared.fields(), [("field".into(), Type::I32)]);
+    // The methods of both definitions are kept.
+    let mut names: Vec<_> = shared.methods().map(|method| method.name()).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["First", "Second"]);
+}
+#[test]
+#[should_panic(expected = "different layouts")]
+fn join_rejects_different_layouts() {
+    let mut a = Assembly::empty();
+    a.add_typedef(TypeDef::nameonly("Shared"));
+    let mut b = Assembly::empty();
+    let mut shared = TypeDef::nameonly("Shared");
+    shared.add_field("field".into(), Type::I32);
+    b.add_typedef(shared);
+    let _ = a.join(b);
+}
+#[test]
+fn join_merges_static_initializers() {
+    // Initializer storing a fresh allocation, like the ones built by `add_allocation`.
+    let storing = |field: &str| {
+        let field =
+            crate::cil::StaticFieldDescriptor::new(None, Type::Ptr(Type::U8.into()), field.into());
+        vec![
+            CILOp::LdcI64(0),
+            CILOp::ConvISize(false),
+            CILOp::Dup,
+            CILOp::STLoc(0),
+            CILOp::STLoc(1),
+            CILOp::LDLoc(1),
+            CILOp::STStaticField(field.into()),
+        ]
+    };
+    let mut a = Assembly::empty();
+    a.add_static_initializer("alloc_a", storing("alloc_a"));
+    a.add_static_initializer("alloc_shared", storing("alloc_shared"));
+    let mut b = Assembly::empty();
+    b.add_static_initializer("alloc_shared", storing("alloc_shared"));
+    b.add_static_initializer("alloc_b", storing("alloc_b"));
+    let mut joined = a.join(b);
+    // The static constructor is only added by the linker.
+    assert!(joined.methods().all(|method| method.name() != ".cctor"));
+    joined.add_static_ctor();
+    let cctor = joined
+        .methods()
+        .find(|method| method.name() == ".cctor")
+        .unwrap();
+    assert_eq!(cctor.locals().len(), 2);
+    let ops = cctor.get_ops();
+    let stored: Vec<_> = ops
+        .iter()
+        .filter_map(|op| match op {
+            CILOp::STStaticField(field) => Some(field.name()),
+            _ => None,
+        })
+        .collect();
+    // Allocations initialized by both assemblies are initialized once.
+    assert_eq!(stored, ["alloc_a", "alloc_shared", "alloc_b"]);
+    assert_eq!(ops.iter().filter(|op| **op == CILOp::Ret).count(), 1);
+    assert_eq!(ops.last(), Some(&CILOp::Ret));
+}
+#[test]
+fn join_keeps_allocations_with_the_same_id_apart() {
+    // Two codegen units, each holding a different allocation under the same allocation id.
+    let malloc = CallSite::new(
+        None,
+        "malloc".into(),
+        FnSig::new(&[Type::USize], &Type::Ptr(Type::Void.into())),
+        true,
+    );
+    let fragment = |bytes: &[u8]| {
+        let mut asm = Assembly::empty();
+        let name = memory_allocation_name(bytes, &[]);
+        let field =
+            crate::cil::StaticFieldDescriptor::new(None, Type::Ptr(Type::U8.into()), name.clone());
+        asm.add_static(Type::Ptr(Type::U8.into()), &name);
+        asm.init_allocation(&field, bytes, vec![], malloc.clone());
+        (asm, field)
+    };
+    let (a, field_a) = fragment(&[1, 2, 3, 4]);
+    let (b, field_b) = fragment(&[5, 6, 7, 8]);
+    assert_ne!(field_a.name(), field_b.name());
+    let mut joined = a.join(b);
+    assert_eq!(joined.globals().count(), 2);
+    // Each field is initialized once, by its own initializer.
+    joined.add_static_ctor();
+    let cctor = joined
+        .methods()
+        .find(|method| method.name() == ".cctor")
+        .unwrap();
+    let stored: Vec<_> = cctor
+        .get_ops()
+        .iter()
+        .filter_map(|op| match op {
+            CILOp::STStaticField(field) => Some(field.name()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stored, [field_a.name(), field_b.name()]);
+    // A pointer to a different target changes the name too.
+    assert_ne!(
+        memory_allocation_name(&[0; 8], &[(0, "alloc_a".into())]),
+        memory_allocation_name(&[0; 8], &[(0, "alloc_b".into())])
+    );
+}
+#[test]
+fn link_time_inlining() {
+    let sig = FnSig::new(&[Type::I32, Type::I32], &Type::I32);
+    let mut sub = Method::new(AccessModifer::Private, true, sig.clone(), "sub", vec![]);
+    sub.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::Sub,
+        CILOp::Ret,
+    ]);
+    let mut main = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[], &Type::I32),
+        "main",
+        vec![],
+    );
+    main.set_ops(vec![
+        CILOp::LdcI32(5),
+        CILOp::LdcI32(3),
+        CILOp::Call(CallSite::boxed(None, "sub".into(), sig, true)),
+        CILOp::Ret,
+    ]);
+    let mut asm = Assembly::empty();
+    asm.add_method(sub);
+    asm.add_method(main);
+    assert_eq!(asm.inline_calls(false), 1);
+    // `sub` is no longer called, so it is removed.
+    assert_eq!(asm.methods().count(), 1);
+    let main = asm.methods().next().unwrap();
+    // The arguments are stored into locals in reverse order.
+    assert_eq!(
+        main.get_ops()[2..6],
+        [
+            CILOp::STLoc(2),
+            CILOp::STLoc(1),
+            CILOp::LDLoc(1),
+            CILOp::LDLoc(2)
+        ]
+    );
+}
+#[test]
+fn inlining_keeps_exported_functions() {
+    let sig = FnSig::new(&[Type::I32, Type::I32], &Type::I32);
+    let sub_site = CallSite::new(None, "sub".into(), sig.clone(), true);
+    let mut sub = Method::new(AccessModifer::Private, true, sig, "sub", vec![]);
+    sub.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::Sub,
+        CILOp::Ret,
+    ]);
+    let mut main = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[], &Type::I32),
+        "main",
+        vec![],
+    );
+    main.set_ops(vec![
+        CILOp::LdcI32(5),
+        CILOp::LdcI32(3),
+        CILOp::Call(sub_site.clone().into()),
+        CILOp::Ret,
+    ]);
+    let mut asm = Assembly::empty();
+    asm.add_method(sub);
+    asm.add_method(main);
+    // Exported functions are private, and only called by the wrappers within their export class.
+    asm.add_export(&crate::export::Export::new("MyLib.Api", "Sub"), &sub_site);
+    assert_eq!(asm.inline_calls(false), 1);
+    assert!(asm.get_method(&sub_site).is_some());
+}
+#[test]
+fn join_merges_export_classes() {
+    let sig = FnSig::new(&[Type::I32], &Type::I32);
+    let mut a = Assembly::empty();
+    a.add_export(
+        &crate::export::Export::new("MyLib.Api", "Compute"),
+        &CallSite::new(None, "compute".into(), sig.clone(), true),
+    );
+    let mut b = Assembly::empty();
+    b.add_export(
+        &crate::export::Export::new("MyLib.Api", "Negate"),
+        &CallSite::new(None, "negate".into(), sig, true),
+    );
+    let joined = a.join(b);
+    let api = joined.get_typedef_by_path("MyLib.Api").unwrap();
+    assert!(api.is_export_class());
+    assert_eq!(api.access_modifier(), AccessModifer::Public);
+    let mut names: Vec<_> = api.methods().map(|method| method.name()).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["Compute", "Negate"]);
+}
@@ -0,0 +1,378 @@
+use crate::{
+    assembly::Assembly,
+    function_sig::FnSig,
+    method::Method,
+    r#type::{DotnetTypeRef, Type, TypeDef},
+    IString,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+/// Comment and usings at the start of the generated source.
+const HEADER: &str = "// Declarations of the functions exported by a Rust library, and of the types they use. Generated by rustc_codegen_clr.
+// Method bodies are stubs: this file documents the library, and can be compiled (with `AllowUnsafeBlocks`) into its reference assembly.
+using System.Runtime.InteropServices;
+";
+/// C# keywords, which have to be prefixed with `@` to be used as identifiers.
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "as",
+    "base",
+    "bool",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "checked",
+    "class",
+    "const",
+    "continue",
+    "decimal",
+    "default",
+    "delegate",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "event",
+    "explicit",
+    "extern",
+    "false",
+    "finally",
+    "fixed",
+    "float",
+    "for",
+    "foreach",
+    "goto",
+    "if",
+    "implicit",
+    "in",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "lock",
+    "long",
+    "namespace",
+    "new",
+    "null",
+    "object",
+    "operator",
+    "out",
+    "override",
+    "params",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "ref",
+    "return",
+    "sbyte",
+    "sealed",
+    "short",
+    "sizeof",
+    "stackalloc",
+    "static",
+    "string",
+    "struct",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "uint",
+    "ulong",
+    "unchecked",
+    "unsafe",
+    "ushort",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+/// Returns the C# source declaring the classes holding the exported functions of `asm`, and all the types used by those functions.
+/// Structs with explicit field offsets(unions and enums) are declared with `[StructLayout(LayoutKind.Explicit)]`.
+pub fn csharp_bindings(asm: &Assembly) -> String {
+    let mut classes: Vec<_> = asm.types().filter(|tpe| tpe.is_export_class()).collect();
+    classes.sort_by_key(|class| class.name());
+    let mut used = BTreeSet::new();
+    let mut source = String::from(HEADER);
+    for class in classes {
+        let (namespace, name) = split_name(class.name());
+        let indent = if namespace.is_some() { "    " } else { "" };
+        let mut methods: Vec<_> = class.methods().collect();
+        methods.sort_by_key(|method| method.name());
+        let mut declaration = format!(
+            "{indent}public static unsafe class {name}\n{indent}{{\n",
+            name = identifier(name)
+        );
+        for method in methods {
+            declaration.push_str(&method_stub(method, &format!("{indent}    "), &mut used));
+        }
+        writeln!(declaration, "{indent}}}").unwrap();
+        source.push_str(&in_namespace(namespace, &declaration));
+    }
+    // Types used by the exported functions, and by the fields of other used types.
+    let mut declared = BTreeMap::new();
+    while let Some(name) = used
+        .iter()
+        .find(|name| !declared.contains_key(*name))
+        .cloned()
+    {
+        let Some(type_def) = asm.get_typedef_by_path(&name) else {
+            let note = format!(
+                "// The type {name} is not defined by the library, so it can't be declared.\n"
+            );
+            declared.insert(name, note);
+            continue;
+        };
+        let (namespace, _) = split_name(&name);
+        let indent = if namespace.is_some() { "    " } else { "" };
+        let declaration = struct_declaration(type_def, indent, &mut used);
+        declared.insert(name, in_namespace(namespace, &declaration));
+    }
+    for declaration in declared.values() {
+        source.push_str(declaration);
+    }
+    source
+}
+/// Declares a stub of the exported method `method`, documenting the Rust function it calls.
+fn method_stub(method: &Method, indent: &str, used: &mut BTreeSet<IString>) -> String {
+    let mut stub = String::new();
+    if let Some(target) = method.get_ops().iter().find_map(|op| op.call()) {
+        writeln!(
+            stub,
+            "{indent}/// <summary>Calls the Rust function <c>{target}</c>.</summary>",
+            target = target.name()
+        )
+        .unwrap();
+    }
+    let inputs: Vec<_> = method
+        .sig()
+        .inputs()
+        .iter()
+        .enumerate()
+        .map(|(idx, tpe)| format!("{tpe} arg{idx}", tpe = csharp_type(tpe, used)))
+        .collect();
+    writeln!(
+        stub,
+        "{indent}public static {output} {name}({inputs}) => throw null;",
+        output = output_type(method.sig().output(), used),
+        name = identifier(method.name()),
+        inputs = inputs.join(", ")
+    )
+    .unwrap();
+    stub
+}
+/// Declares a struct mirroring `type_def`, and its inner types.
+fn struct_declaration(type_def: &TypeDef, indent: &str, used: &mut BTreeSet<IString>) -> String {
+    let (_, name) = split_name(type_def.name());
+    let generics: Vec<_> = (0..type_def.gargc()).map(|idx| format!("G{idx}")).collect();
+    let layout = if type_def.explicit_offsets().is_some() {
+        "Explicit"
+    } else {
+        "Sequential"
+    };
+    let mut declaration = format!("{indent}[StructLayout(LayoutKind.{layout})]\n");
+    if generics.is_empty() {
+        writeln!(
+            declaration,
+            "{indent}public unsafe struct {}",
+            identifier(name)
+        )
+        .unwrap();
+    } else {
+        // Generic arguments may be used behind pointers, so they have to be unmanaged.
+        let constraints: String = generics
+            .iter()
+            .map(|generic| format!(" where {generic} : unmanaged"))
+            .collect();
+        writeln!(
+            declaration,
+            "{indent}public unsafe struct {name}<{generics}>{constraints}",
+            name = identifier(name),
+            generics = generics.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(declaration, "{indent}{{").unwrap();
+    for inner in type_def.inner_types() {
+        declaration.push_str(&struct_declaration(inner, &format!("{indent}    "), used));
+    }
+    for (idx, (field_name, field_type)) in type_def.fields().iter().enumerate() {
+        let offset = type_def
+            .explicit_offsets()
+            .map(|offsets| format!("[FieldOffset({})] ", offsets[idx]))
+            .unwrap_or_default();
+        writeln!(
+            declaration,
+            "{indent}    {offset}public {tpe} {name};",
+            tpe = csharp_type(field_type, used),
+            name = identifier(field_name)
+        )
+        .unwrap();
+    }
+    writeln!(declaration, "{indent}}}").unwrap();
+    declaration
+}
+/// Returns the C# name of the return type `tpe`.
+fn output_type(tpe: &Type, used: &mut BTreeSet<IString>) -> String {
+    match tpe {
+        Type::Void => "void".into(),
+        _ => csharp_type(tpe, used),
+    }
+}
+/// Returns the C# name of `tpe`, and adds the types defined within the assembly it refers to to `used`.
+fn csharp_type(tpe: &Type, used: &mut BTreeSet<IString>) -> String {
+    match tpe {
+        Type::Bool => "bool".into(),
+        Type::DotnetChar => "char".into(),
+        Type::F32 => "float".into(),
+        Type::F64 => "double".into(),
+        Type::U8 => "byte".into(),
+        Type::U16 => "ushort".into(),
+        Type::U32 => "uint".into(),
+        Type::U64 => "ulong".into(),
+        Type::USize => "nuint".into(),
+        Type::I8 => "sbyte".into(),
+        Type::I16 => "short".into(),
+        Type::I32 => "int".into(),
+        Type::I64 => "long".into(),
+        Type::ISize => "nint".into(),
+        Type::I128 => dotnet_type(&DotnetTypeRef::int_128(), used),
+        Type::U128 => dotnet_type(&DotnetTypeRef::uint_128(), used),
+        Type::Ptr(inner) => format!("{}*", output_type(inner, used)),
+        Type::DotnetType(dref) => dotnet_type(dref, used),
+        Type::DotnetArray(array) => format!(
+            "{element}[{dimensions}]",
+            element = csharp_type(&array.element, used),
+            dimensions = ",".repeat(array.dimensions.saturating_sub(1) as usize)
+        ),
+        Type::FnPtr(sig) => fn_ptr_type(sig, used),
+        Type::GenericArg(idx) => format!("G{idx}"),
+        Type::MethodGenericArg(idx) => format!("M{idx}"),
+        // Those types are emitted as references to types defined within the assembly.
+        Type::Void => dotnet_type(&DotnetTypeRef::new(None, "RustVoid"), used),
+        Type::Unresolved => dotnet_type(&DotnetTypeRef::new(None, "Unresolved"), used),
+        Type::Foreign => dotnet_type(&DotnetTypeRef::new(None, "Foreign"), used),
+        Type::FnDef(name) => dotnet_type(&DotnetTypeRef::new(None, &format!("fn_{name}")), used),
+    }
+}
+/// Returns the C# name of the type `dref` refers to.
+fn dotnet_type(dref: &DotnetTypeRef, used: &mut BTreeSet<IString>) -> String {
+    if dref.asm().is_none() {
+        let top_level = dref.name_path().split('/').next().unwrap_or_default();
+        used.insert(top_level.into());
+    } else if !dref.is_valuetype() {
+        match dref.name_path() {
+            "System.String" => return "string".into(),
+            "System.Object" => return "object".into(),
+            _ => (),
+        }
+    }
+    let path: Vec<_> = dref.name_path().split(['/', '.']).map(identifier).collect();
+    let mut name = format!("global::{}", path.join("."));
+    if !dref.generics().is_empty() {
+        let generics: Vec<_> = dref
+            .generics()
+            .iter()
+            .map(|generic| csharp_type(generic, used))
+            .collect();
+        write!(name, "<{}>", generics.join(", ")).unwrap();
+    }
+    name
+}
+/// Returns the C# function pointer type with the signature `sig`.
+fn fn_ptr_type(sig: &FnSig, used: &mut BTreeSet<IString>) -> String {
+    let mut types: Vec<_> = sig
+        .inputs()
+        .iter()
+        .map(|tpe| csharp_type(tpe, used))
+        .collect();
+    types.push(output_type(sig.output(), used));
+    format!("delegate*<{}>", types.join(", "))
+}
+/// Splits the full name of a type into its namespace and its name.
+fn split_name(full_name: &str) -> (Option<&str>, &str) {
+    match full_name.rsplit_once('.') {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, full_name),
+    }
+}
+/// Places `declaration` within `namespace`, if it has one.
+fn in_namespace(namespace: Option<&str>, declaration: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("namespace {namespace}\n{{\n{declaration}}}\n"),
+        None => declaration.into(),
+    }
+}
+/// Escapes `name` if it is a C# keyword.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("@{name}")
+    } else {
+        name.into()
+    }
+}
+#[test]
+fn bindings() {
+    use crate::{
+        access_modifier::AccessModifer,
+        cil::{CILOp, CallSite},
+    };
+    let mut asm = Assembly::empty();
+    let vec3 = TypeDef::new(
+        AccessModifer::Public,
+        "Vec3".into(),
+        vec![],
+        vec![
+            ("x".into(), Type::F32),
+            ("y".into(), Type::F32),
+            ("z".into(), Type::F32),
+        ],
+        vec![],
+        Some(vec![0, 4, 8]),
+        0,
+        None,
+    );
+    asm.add_typedef(vec3);
+    asm.add_typedef(TypeDef::nameonly("Unused"));
+    // Classes which are not export classes are not declared, even if they extend `System.Object`.
+    asm.add_typedef(TypeDef::new(
+        AccessModifer::Public,
+        "Helper".into(),
+        vec![],
+        vec![],
+        vec![],
+        None,
+        0,
+        Some(DotnetTypeRef::object_type()),
+    ));
+    let vec3_ptr = Type::Ptr(Type::DotnetType(DotnetTypeRef::new(None, "Vec3").into()).into());
+    let sig = FnSig::new(&[vec3_ptr, Type::I32], &Type::F64);
+    asm.add_export(
+        &crate::export::Export::new("MyLib.Api", "Length"),
+        &CallSite::new(None, "_ZN5mylib6length17h0E".into(), sig, true),
+    );
+    let mut private = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[], &Type::Void),
+        "private",
+        vec![],
+    );
+    private.set_ops(vec![CILOp::Ret]);
+    asm.add_method(private);
+    let source = csharp_bindings(&asm);
+    assert!(source.contains(
+        "namespace MyLib\n{\n    public static unsafe class Api\n    {\n        /// <summary>Calls the Rust function <c>_ZN5mylib6length17h0E</c>.</summary>\n        public static double Length(global::Vec3* arg0, int arg1) => throw null;\n    }\n}\n"
+    ));
+    assert!(source.contains(
+        "[StructLayout(LayoutKind.Explicit)]\npublic unsafe struct Vec3\n{\n    [FieldOffset(0)] public float x;\n    [FieldOffset(4)] public float y;\n    [FieldOffset(8)] public float z;\n}\n"
+    ));
+    assert!(!source.contains("Unused"));
+    assert!(!source.contains("Helper"));
+    assert!(!source.contains("private"));
+}
@@ -2,6 +2,9 @@ use super::AssemblyExporter;
 use crate::{
     access_modifier::AccessModifer,
     assembly_exporter::AssemblyExportError,
+    cil::CallSite,
+    config::CodegenConfig,
+    function_sig::FnSig,
     method::Method,
     r#type::TypeDef,
     r#type::{DotnetTypeRef, Type},
@@ -11,6 +14,7 @@ use std::{borrow::Cow, io::Write, ops::Deref};
 /// A struct used to export an asssembly using the ILASM tool as a .NET assembly creator.
 pub struct ILASMExporter {
     encoded_asm: Vec<u8>,
+    config: CodegenConfig,
 }
 impl std::io::Write for ILASMExporter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
@@ -26,10 +30,13 @@ impl AssemblyExporter for ILASMExporter {
         writeln!(self, ".field static {tpe} {name}", tpe = type_cil(tpe))
             .expect("Could not write global!")
     }
-    fn init(asm_name: &str) -> Self {
+    fn init(asm_name: &str, config: &CodegenConfig) -> Self {
         let mut encoded_asm = Vec::with_capacity(0x1_00);
         write!(encoded_asm, ".assembly {asm_name}{{}}").expect("Write error!");
-        Self { encoded_asm }
+        Self {
+            encoded_asm,
+            config: *config,
+        }
     }
     fn add_extern_ref(
         &mut self,
@@ -44,11 +51,11 @@ impl AssemblyExporter for ILASMExporter {
         .expect("Write error!");
     }
     fn add_type(&mut self, tpe: &TypeDef) {
-        type_def_cli(&mut self.encoded_asm, tpe).expect("Error");
+        type_def_cli(&mut self.encoded_asm, tpe, &self.config).expect("Error");
         //let _ = self.types.push(tpe.clone());
     }
     fn add_method(&mut self, method: &Method) {
-        method_cil(&mut self.encoded_asm, method).expect("Error");
+        method_cil(&mut self.encoded_asm, method, &self.config).expect("Error");
     }
     fn finalize(
         self,
@@ -101,7 +108,11 @@ impl AssemblyExporter for ILASMExporter {
     }
 }
 
-fn type_def_cli(w: &mut impl Write, tpe: &TypeDef) -> Result<(), super::AssemblyExportError> {
+fn type_def_cli(
+    w: &mut impl Write,
+    tpe: &TypeDef,
+    config: &CodegenConfig,
+) -> Result<(), super::AssemblyExportError> {
     let name = tpe.name();
     let mut generics = String::new();
     if tpe.gargc() != 0 {
@@ -116,10 +127,10 @@ fn type_def_cli(w: &mut impl Write, tpe: &TypeDef) -> Result<(), super::Assembly
     if tpe.gargc() != 0 {
         generics.push('>');
     }
-    let extended = if let Some(_extended) = tpe.extends() {
-        todo!("Can't handle inheretence yet!");
+    let extended = if let Some(extended) = tpe.extends() {
+        dotnet_type_ref_cli(extended).into()
     } else {
-        "[System.Runtime]System.ValueType"
+        Cow::Borrowed("[System.Runtime]System.ValueType")
     };
     let access = if let AccessModifer::Public = tpe.access_modifier() {
         "public"
@@ -133,7 +144,7 @@ fn type_def_cli(w: &mut impl Write, tpe: &TypeDef) -> Result<(), super::Assembly
     }
 
     for inner_type in tpe.inner_types() {
-        type_def_cli(w, inner_type)?;
+        type_def_cli(w, inner_type, config)?;
     }
     let _field_string = String::new();
     if let Some(offsets) = tpe.explicit_offsets() {
@@ -154,7 +165,7 @@ fn type_def_cli(w: &mut impl Write, tpe: &TypeDef) -> Result<(), super::Assembly
         }
     }
     for (_, method) in tpe.methods().enumerate() {
-        method_cil(w, method)?;
+        method_cil(w, method, config)?;
     }
     writeln!(w, "}}")?;
     Ok(())
@@ -168,7 +179,7 @@ fn absolute_path(path: &std::path::Path) -> std::io::Result<std::path::PathBuf>
         Ok(abs_path)
     }
 }
-fn method_cil(w: &mut impl Write, method: &Method) -> std::io::Result<()> {
+fn method_cil(w: &mut impl Write, method: &Method, config: &CodegenConfig) -> std::io::Result<()> {
     let access = if let AccessModifer::Private = method.access() {
         "private"
     } else {
@@ -179,18 +190,32 @@ fn method_cil(w: &mut impl Write, method: &Method) -> std::io::Result<()> {
     } else {
         "instance"
     };
+    let special_name = if method.name() == ".ctor" || method.name() == ".cctor" {
+        "specialname rtspecialname "
+    } else {
+        ""
+    };
     let output = output_type_cil(method.sig().output());
     let name = method.name();
+    let generics = (0..method.generic_count())
+        .map(|idx| format!("M{idx}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    let generics = if generics.is_empty() {
+        generics
+    } else {
+        format!("<{generics}>")
+    };
     write!(
         w,
-        ".method {access} hidebysig {static_inst} {output} {name}"
+        ".method {access} hidebysig {special_name}{static_inst} {output} {name}{generics}"
     )?;
     args_cli(w, method.explicit_inputs())?;
     writeln!(w, "{{")?;
     if method.is_entrypoint() {
         writeln!(w, ".entrypoint")?;
     }
-    if crate::ALWAYS_INIT_LOCALS {
+    if config.always_init_locals {
         writeln!(w, "\t.locals init(")?;
     } else {
         writeln!(w, "\t.locals (")?;
@@ -230,72 +255,33 @@ fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         CILOp::BLe(id) => format!("ble bb_{id}").into(),
         CILOp::BZero(id) => format!("brzero bb_{id}").into(),
         CILOp::BTrue(id) => format!("brtrue bb_{id}").into(),
+        CILOp::BeginTry => ".try{".into(),
+        CILOp::EndTry | CILOp::EndHandler => "}".into(),
+        CILOp::BeginCatch(tpe) => format!("catch {tpe}{{", tpe = dotnet_type_ref_cli(tpe)).into(),
+        CILOp::BeginFinally => "finally{".into(),
+        CILOp::Leave(id) => format!("leave bb_{id}").into(),
+        CILOp::EndFinally => "endfinally".into(),
         CILOp::Call(call_site) => {
             if call_site.is_nop() {
                 "".into()
             } else {
-                //assert!(sig.inputs.is_empty());
-                let mut inputs_iter = call_site.explicit_inputs().iter();
-                let mut input_string = String::new();
-                if let Some(firts_arg) = inputs_iter.next() {
-                    input_string.push_str(&call_arg_type_cil(firts_arg));
-                }
-                for arg in inputs_iter {
-                    input_string.push(',');
-                    input_string.push_str(&call_arg_type_cil(arg));
-                }
-                let prefix = if call_site.is_static() {
-                    ""
-                } else {
-                    "instance"
-                };
-                let owner_name = match &call_site.class() {
-                    Some(owner) => {
-                        format!("{}::", prefixed_type_cil(&owner.deref().clone().into()))
-                    }
-                    None => String::new(),
-                };
-                format!(
-                    "call {prefix} {output} {owner_name} {function_name}({input_string})",
-                    function_name = call_site.name(),
-                    output = call_output_type_cil(call_site.signature().output())
-                )
-                .into()
+                format!("call {}", call_site_cil(call_site)).into()
             }
         }
         CILOp::CallVirt(call_site) => {
             if call_site.is_nop() {
                 "".into()
             } else {
-                //assert!(sig.inputs.is_empty());
-                let mut inputs_iter = call_site.explicit_inputs().iter();
-                let mut input_string = String::new();
-                if let Some(firts_arg) = inputs_iter.next() {
-                    input_string.push_str(&call_arg_type_cil(firts_arg));
-                }
-                for arg in inputs_iter {
-                    input_string.push(',');
-                    input_string.push_str(&call_arg_type_cil(arg));
-                }
-                let prefix = if call_site.is_static() {
-                    ""
-                } else {
-                    "instance"
-                };
-                let owner_name = match &call_site.class() {
-                    Some(owner) => {
-                        format!("{}::", prefixed_type_cil(&owner.deref().clone().into()))
-                    }
-                    None => String::new(),
-                };
-                format!(
-                    "callvirt {prefix} {output} {owner_name} {function_name}({input_string})",
-                    function_name = call_site.name(),
-                    output = call_output_type_cil(call_site.signature().output())
-                )
-                .into()
+                format!("callvirt {}", call_site_cil(call_site)).into()
             }
         }
+        CILOp::LDFtn(call_site) => format!("ldftn {}", call_site_cil(call_site)).into(),
+        CILOp::CallI(sig) => format!(
+            "calli {output}{inputs}",
+            output = call_output_type_cil(sig.output()),
+            inputs = call_inputs_cil(sig.inputs())
+        )
+        .into(),
         //Arthmetics
         CILOp::Add => "add".into(),
         CILOp::AddOvf => "add.ovf".into(),
@@ -306,7 +292,9 @@ fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         CILOp::Mul => "mul".into(),
         CILOp::MulOvf => "mul.ovf".into(),
         CILOp::Div => "div".into(),
+        CILOp::DivUn => "div.un".into(),
         CILOp::Rem => "rem".into(),
+        CILOp::RemUn => "rem.un".into(),
         CILOp::Neg => "neg".into(),
         //Bitwise
         CILOp::And => "and".into(),
@@ -316,10 +304,13 @@ fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         //Bitshifts
         CILOp::Shl => "shl".into(),
         CILOp::Shr => "shr".into(),
+        CILOp::ShrUn => "shr.un".into(),
         //Comparisons
         CILOp::Gt => "cgt".into(),
+        CILOp::GtUn => "cgt.un".into(),
         CILOp::Eq => "ceq".into(),
         CILOp::Lt => "clt".into(),
+        CILOp::LtUn => "clt.un".into(),
         //Arguments
         CILOp::LDArg(argnum) => {
             if *argnum < 4 {
@@ -501,6 +492,7 @@ fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
                 "conv.r8".into()
             }
         }
+        CILOp::ConvFUn => "conv.r.un".into(),
         // Pointer stuff
         CILOp::LDIndI8 => "ldind.i1".into(),
         CILOp::LDIndI16 => "ldind.i2".into(),
@@ -520,6 +512,7 @@ fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         CILOp::LocAlloc => "localloc".into(),
         //OOP
         CILOp::SizeOf(tpe) => format!("sizeof {tpe}", tpe = prefixed_type_cil(tpe)).into(),
+        CILOp::CastClass(tpe) => format!("castclass {tpe}", tpe = prefixed_type_cil(tpe)).into(),
         CILOp::Throw => "throw".into(),
         CILOp::Rethrow => "rethrow".into(),
         CILOp::LdStr(str) => format!("ldstr {str:?}").replace('\'',"\\\'").into(),
@@ -555,6 +548,12 @@ fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         )
         .into(),
         CILOp::CpBlk=>"cpblk".into(),
+        //Arrays
+        CILOp::NewArr(tpe) => format!("newarr {tpe}", tpe = prefixed_type_cil(tpe)).into(),
+        CILOp::LDElem(tpe) => format!("ldelem {tpe}", tpe = prefixed_type_cil(tpe)).into(),
+        CILOp::STElem(tpe) => format!("stelem {tpe}", tpe = prefixed_type_cil(tpe)).into(),
+        CILOp::LDElemA(tpe) => format!("ldelema {tpe}", tpe = prefixed_type_cil(tpe)).into(),
+        CILOp::LDLen => "ldlen".into(),
         CILOp::NewObj(call_site) => {
             if call_site.is_nop() {
                 "".into()
@@ -606,6 +605,39 @@ fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         }
     }
 }
+/// Returns the part of a `call`, `callvirt` or `ldftn` op describing the targeted method.
+fn call_site_cil(call_site: &CallSite) -> String {
+    let prefix = if call_site.is_static() {
+        ""
+    } else {
+        "instance"
+    };
+    let owner_name = match &call_site.class() {
+        Some(owner) => {
+            format!("{}::", prefixed_type_cil(&owner.deref().clone().into()))
+        }
+        None => String::new(),
+    };
+    format!(
+        "{prefix} {output} {owner_name} {function_name}{generics}{inputs}",
+        function_name = call_site.name(),
+        generics = generics_str(call_site.generics()),
+        output = call_output_type_cil(call_site.signature().output()),
+        inputs = call_inputs_cil(call_site.explicit_inputs())
+    )
+}
+fn call_inputs_cil(inputs: &[Type]) -> String {
+    let inputs: Vec<_> = inputs.iter().map(call_arg_type_cil).collect();
+    format!("({})", inputs.join(","))
+}
+fn fn_ptr_cil(sig: &FnSig) -> Cow<'static, str> {
+    format!(
+        "method {output} *{inputs}",
+        output = call_output_type_cil(sig.output()),
+        inputs = call_inputs_cil(sig.inputs())
+    )
+    .into()
+}
 fn output_type_cil(tpe: &Type) -> Cow<'static, str> {
     match tpe {
         Type::Void => "void".into(),
@@ -673,8 +705,8 @@ fn type_cil(tpe: &Type) -> Cow<'static, str> {
         Type::F64 => "float64".into(),
         Type::I64 => "int64".into(),
         Type::U64 => "uint64".into(),
-        Type::I128 => "[System.Rutnime]System.Int128".into(),
-        Type::U128 => "[System.Rutnime]System.UInt128".into(),
+        Type::I128 => dotnet_type_ref_cli(&DotnetTypeRef::int_128()).into(),
+        Type::U128 => dotnet_type_ref_cli(&DotnetTypeRef::uint_128()).into(),
         Type::ISize => "native int".into(),
         Type::USize => "native uint".into(),
         Type::Ptr(inner) => format!("{inner}*", inner = type_cil(inner)).into(),
@@ -684,6 +716,7 @@ fn type_cil(tpe: &Type) -> Cow<'static, str> {
         Type::Bool => "bool".into(),
         Type::DotnetChar => "char".into(),
         Type::GenericArg(idx) => format!("!G{idx}").into(),
+        Type::MethodGenericArg(idx) => format!("!!{idx}").into(),
         Type::Foreign => "valuetype Foreign".into(),
         Type::DotnetArray(array) => {
             let arr = if array.dimensions > 0 {
@@ -691,15 +724,17 @@ fn type_cil(tpe: &Type) -> Cow<'static, str> {
             } else {
                 "".into()
             };
-            format!("{tpe}[{arr}]", tpe = type_cil(&array.element)).into()
+            format!("{tpe}[{arr}]", tpe = prefixed_type_cil(&array.element)).into()
         } //_ => todo!("Unsuported type {tpe:?}"),
         Type::FnDef(_site) => "FnDef".into(),
+        Type::FnPtr(sig) => fn_ptr_cil(sig),
     }
 }
 fn field_type_cil(tpe: &Type) -> Cow<'static, str> {
     match tpe {
         Type::Ptr(inner) => format!("{inner}*", inner = type_cil(inner)).into(),
         Type::GenericArg(id) => format!("!{id}").into(),
+        Type::MethodGenericArg(id) => format!("!!{id}").into(),
         Type::DotnetType(dotnet_type) => dotnet_type_ref_cli_generics_unescaped(dotnet_type).into(),
         _ => prefixed_type_cil(tpe),
     }
@@ -708,9 +743,11 @@ fn prefixed_field_type_cil(tpe: &Type) -> Cow<'static, str> {
     match tpe {
         Type::Ptr(inner) => format!("{inner}*", inner = prefixed_field_type_cil(inner)).into(),
         Type::GenericArg(id) => format!("!{id}").into(),
+        Type::MethodGenericArg(id) => format!("!!{id}").into(),
         Type::DotnetType(dotnet_type) => dotnet_type_ref_cli_generics_unescaped(dotnet_type).into(),
         Type::Void => "valuetype RustVoid".into(),
         Type::FnDef(name) => format!("valuetype fn_{name}").into(),
+        Type::FnPtr(sig) => fn_ptr_cil(sig),
         Type::I8 => "int8".into(),
         Type::U8 => "uint8".into(),
         Type::I16 => "int16".into(),
@@ -721,8 +758,8 @@ fn prefixed_field_type_cil(tpe: &Type) -> Cow<'static, str> {
         Type::F64 => "float64".into(),
         Type::I64 => "int64".into(),
         Type::U64 => "uint64".into(),
-        Type::I128 => "valuetype [System.Runtime]System.Int128".into(),
-        Type::U128 => "valuetype [System.Runtime]System.UInt128".into(),
+        Type::I128 => format!("valuetype {}", dotnet_type_ref_cli(&DotnetTypeRef::int_128())).into(),
+        Type::U128 => format!("valuetype {}", dotnet_type_ref_cli(&DotnetTypeRef::uint_128())).into(),
         Type::ISize => "native int".into(),
         Type::USize => "native uint".into(),
         //Special type
@@ -736,7 +773,7 @@ fn prefixed_field_type_cil(tpe: &Type) -> Cow<'static, str> {
             } else {
                 "".into()
             };
-            format!("{tpe}[{arr}]", tpe = type_cil(&array.element)).into()
+            format!("{tpe}[{arr}]", tpe = prefixed_type_cil(&array.element)).into()
         } //_ => todo!("Unsuported type {tpe:?}"),
           //_ => prefixed_field_type_cil(tpe),
     }
@@ -745,6 +782,7 @@ fn prefixed_type_cil(tpe: &Type) -> Cow<'static, str> {
     match tpe {
         Type::Void => "valuetype RustVoid".into(),
         Type::FnDef(name) => format!("valuetype fn_{name}").into(),
+        Type::FnPtr(sig) => fn_ptr_cil(sig),
         Type::I8 => "int8".into(),
         Type::U8 => "uint8".into(),
         Type::I16 => "int16".into(),
@@ -755,8 +793,8 @@ fn prefixed_type_cil(tpe: &Type) -> Cow<'static, str> {
         Type::F64 => "float64".into(),
         Type::I64 => "int64".into(),
         Type::U64 => "uint64".into(),
-        Type::I128 => "valuetype [System.Runtime]System.Int128".into(),
-        Type::U128 => "valuetype [System.Runtime]System.UInt128".into(),
+        Type::I128 => format!("valuetype {}", dotnet_type_ref_cli(&DotnetTypeRef::int_128())).into(),
+        Type::U128 => format!("valuetype {}", dotnet_type_ref_cli(&DotnetTypeRef::uint_128())).into(),
         Type::ISize => "native int".into(),
         Type::USize => "native uint".into(),
         Type::Ptr(inner) => format!("{inner}*", inner = prefixed_type_cil(inner)).into(),
@@ -770,13 +808,14 @@ fn prefixed_type_cil(tpe: &Type) -> Cow<'static, str> {
         Type::Bool => "bool".into(),
         Type::DotnetChar => "char".into(),
         Type::GenericArg(idx) => format!("!G{idx}").into(),
+        Type::MethodGenericArg(idx) => format!("!!{idx}").into(),
         Type::DotnetArray(array) => {
             let arr = if array.dimensions > 0 {
                 (0..(array.dimensions - 1)).map(|_| ",").collect::<String>()
             } else {
                 "".into()
             };
-            format!("{tpe}[{arr}]", tpe = type_cil(&array.element)).into()
+            format!("{tpe}[{arr}]", tpe = prefixed_type_cil(&array.element)).into()
         } //_ => todo!("Unsuported type {tpe:?}"),
     }
 }
@@ -813,13 +852,13 @@ fn generics_str(generics: &[Type]) -> Cow<'static, str> {
         if let Some(first_generic) = generic_iter.next() {
             garg_string.push_str(&format!(
                 "{type_cil}",
-                type_cil = prefixed_field_type_cil(first_generic)
+                type_cil = prefixed_field_type_cil(first_generic.as_generic_arg())
             ));
         }
         for arg in generic_iter {
             garg_string.push_str(&format!(
                 ",{type_cil}",
-                type_cil = prefixed_field_type_cil(arg)
+                type_cil = prefixed_field_type_cil(arg.as_generic_arg())
             ));
         }
         format!("<{garg_string}>").into()
@@ -834,13 +873,13 @@ fn generics_ident_str(generics: &[Type]) -> Cow<'static, str> {
         if let Some(first_generic) = generic_iter.next() {
             garg_string.push_str(&format!(
                 "{type_cil}",
-                type_cil = prefixed_field_type_cil(first_generic)
+                type_cil = prefixed_field_type_cil(first_generic.as_generic_arg())
             ));
         }
         for arg in generic_iter {
             garg_string.push_str(&format!(
                 ",{type_cil}",
-                type_cil = prefixed_field_type_cil(arg)
+                type_cil = prefixed_field_type_cil(arg.as_generic_arg())
             ));
         }
         format!("<{garg_string}>").into()
@@ -851,6 +890,9 @@ fn generic_prefix() {
     let generic = Type::GenericArg(0);
     assert_eq!("!G0", &prefixed_type_cil(&generic));
     assert_eq!("!0", &prefixed_field_type_cil(&generic));
+    let method_generic = Type::MethodGenericArg(1);
+    assert_eq!("!!1", &prefixed_type_cil(&method_generic));
+    assert_eq!("!!1", &prefixed_field_type_cil(&method_generic));
 }
 #[test]
 fn tuple_type() {
@@ -870,3 +912,14 @@ fn tuple_type() {
         &prefixed_field_type_cil(&generic)
     );
 }
+#[test]
+fn fn_ptr_type() {
+    let fn_ptr =
+        Type::FnPtr(FnSig::new(&[Type::I32, Type::Ptr(Type::U8.into())], &Type::Void).into());
+    assert_eq!("method void *(int32,uint8*)", &prefixed_type_cil(&fn_ptr));
+    let tuple: Type = crate::r#type::tuple_type(&[fn_ptr]).into();
+    assert_eq!(
+        "valuetype [System.Runtime]System.ValueTuple`1<native uint>",
+        &prefixed_type_cil(&tuple)
+    );
+}
@@ -7,16 +7,21 @@ enum AccessModifer {
 }
 use crate::{
     assembly::Assembly,
+    config::CodegenConfig,
     method::Method,
     r#type::{Type, TypeDef},
     IString,
 };
+/// Generator of C# source declaring the functions exported by a library, and the types they use.
+pub mod csharp_bindings;
 /// ILASM-based assembly exporter.
 pub mod ilasm_exporter;
-/// This trait represents an interface implemented by all .NET assembly exporters. (Currently only ilasm)
+/// Exporter writing the PE file and its metadata directly, without ILASM.
+pub mod pe_exporter;
+/// This trait represents an interface implemented by all .NET assembly exporters. (ILASM and the native PE exporter)
 pub trait AssemblyExporter: Sized {
-    /// Initializes an assembly exporter.
-    fn init(asm_info: &AssemblyInfo) -> Self;
+    /// Initializes an assembly exporter, configured by `config`.
+    fn init(asm_info: &AssemblyInfo, config: &CodegenConfig) -> Self;
     /// Adds type definition `tpe` to the assembly.
     fn add_type(&mut self, tpe: &TypeDef);
     /// Adds method to assembly.
@@ -33,8 +38,9 @@ pub trait AssemblyExporter: Sized {
         asm: &Assembly,
         final_path: &Path,
         is_dll: bool,
+        config: &CodegenConfig,
     ) -> Result<(), AssemblyExportError> {
-        let mut asm_exporter = Self::init("asm");
+        let mut asm_exporter = Self::init("asm", config);
         for (asm_name, asm_ref) in asm.extern_refs() {
             asm_exporter.add_extern_ref(asm_name, asm_ref);
         }
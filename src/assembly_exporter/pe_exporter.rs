@@ -0,0 +1,1837 @@
+use super::{AssemblyExportError, AssemblyExporter};
+use crate::{
+    access_modifier::AccessModifer,
+    assembly::AssemblyExternRef,
+    cil::{CILOp, CallSite, FieldDescriptor, StaticFieldDescriptor},
+    config::CodegenConfig,
+    function_sig::FnSig,
+    method::Method,
+    r#type::{DotnetTypeRef, Type, TypeDef},
+    IString,
+};
+use std::collections::HashMap;
+/// Major, minor, build and revision number of an assembly.
+type AssemblyVersion = (u16, u16, u16, u16);
+#[must_use]
+/// A struct used to export an asssembly by writing the PE file and its ECMA-335 metadata directly, without calling any external tools.
+pub struct PEExporter {
+    asm_name: IString,
+    types: Vec<TypeDef>,
+    methods: Vec<Method>,
+    globals: Vec<(IString, Type)>,
+    extern_refs: Vec<(IString, AssemblyVersion)>,
+    config: CodegenConfig,
+}
+impl AssemblyExporter for PEExporter {
+    fn init(asm_name: &str, config: &CodegenConfig) -> Self {
+        Self {
+            asm_name: asm_name.into(),
+            types: Vec::new(),
+            methods: Vec::new(),
+            globals: Vec::new(),
+            extern_refs: Vec::new(),
+            config: *config,
+        }
+    }
+    fn add_type(&mut self, tpe: &TypeDef) {
+        self.types.push(tpe.clone());
+    }
+    fn add_method(&mut self, method: &Method) {
+        self.methods.push(method.clone());
+    }
+    fn add_extern_ref(&mut self, asm_name: &str, info: &AssemblyExternRef) {
+        self.extern_refs.push((asm_name.into(), info.version()));
+    }
+    fn add_global(&mut self, tpe: &Type, name: &str) {
+        self.globals.push((name.into(), tpe.clone()));
+    }
+    fn finalize(
+        self,
+        final_path: &std::path::Path,
+        is_dll: bool,
+    ) -> Result<(), AssemblyExportError> {
+        let module_name = final_path
+            .file_name()
+            .expect("Target file has no name!")
+            .to_string_lossy()
+            .to_string();
+        let mut builder =
+            MetadataBuilder::new(&self.asm_name, &module_name, &self.extern_refs, self.config);
+        builder.add_definitions(self.globals, self.methods, self.types);
+        let image = builder.build_image(is_dll)?;
+        std::fs::write(final_path, image)?;
+        Ok(())
+    }
+}
+// Metadata table ids, as defined in ECMA-335 II.22
+const TABLE_MODULE: usize = 0x00;
+const TABLE_TYPE_REF: usize = 0x01;
+const TABLE_TYPE_DEF: usize = 0x02;
+const TABLE_FIELD: usize = 0x04;
+const TABLE_METHOD_DEF: usize = 0x06;
+const TABLE_PARAM: usize = 0x08;
+const TABLE_MEMBER_REF: usize = 0x0A;
+const TABLE_FIELD_LAYOUT: usize = 0x10;
+const TABLE_STAND_ALONE_SIG: usize = 0x11;
+const TABLE_TYPE_SPEC: usize = 0x1B;
+const TABLE_ASSEMBLY: usize = 0x20;
+const TABLE_ASSEMBLY_REF: usize = 0x23;
+const TABLE_NESTED_CLASS: usize = 0x29;
+const TABLE_GENERIC_PARAM: usize = 0x2A;
+const TABLE_METHOD_SPEC: usize = 0x2B;
+/// Bitmask of tables which must be sorted, matching the one emmited by ILASM and Roslyn.
+const SORTED_TABLES: u64 = 0x0000_1600_3301_FA00;
+/// RVA of the only section(.text) of the image.
+const TEXT_RVA: u32 = 0x2000;
+/// File offset of the .text section.
+const TEXT_FILE_OFFSET: u32 = 0x200;
+const FILE_ALIGNMENT: u32 = 0x200;
+const SECTION_ALIGNMENT: u32 = 0x2000;
+/// Size of the CLI header, which is placed at the very begining of the .text section.
+const CLI_HEADER_SIZE: u32 = 72;
+/// A reference to a row in a metadata table. Row indices start at 1.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum TableRef {
+    Module(u32),
+    TypeRef(u32),
+    TypeDef(u32),
+    TypeSpec(u32),
+    MethodDef(u32),
+    MemberRef(u32),
+    AssemblyRef(u32),
+}
+impl TableRef {
+    fn table(&self) -> usize {
+        match self {
+            Self::Module(_) => TABLE_MODULE,
+            Self::TypeRef(_) => TABLE_TYPE_REF,
+            Self::TypeDef(_) => TABLE_TYPE_DEF,
+            Self::TypeSpec(_) => TABLE_TYPE_SPEC,
+            Self::MethodDef(_) => TABLE_METHOD_DEF,
+            Self::MemberRef(_) => TABLE_MEMBER_REF,
+            Self::AssemblyRef(_) => TABLE_ASSEMBLY_REF,
+        }
+    }
+    fn row(&self) -> u32 {
+        match self {
+            Self::Module(row)
+            | Self::TypeRef(row)
+            | Self::TypeDef(row)
+            | Self::TypeSpec(row)
+            | Self::MethodDef(row)
+            | Self::MemberRef(row)
+            | Self::AssemblyRef(row) => *row,
+        }
+    }
+    /// Metadata token of the refered row.
+    fn token(&self) -> u32 {
+        ((self.table() as u32) << 24) | self.row()
+    }
+}
+/// Kinds of coded indices used by the tables emmited by this exporter(ECMA-335 II.24.2.6).
+#[derive(Clone, Copy, Debug)]
+enum CodedIndex {
+    TypeDefOrRef,
+    ResolutionScope,
+    MemberRefParent,
+    TypeOrMethodDef,
+    MethodDefOrRef,
+}
+impl CodedIndex {
+    /// Tables which can be refered to by this coded index, in tag order. `None` marks tables this exporter never uses.
+    fn tables(&self) -> &'static [Option<usize>] {
+        match self {
+            Self::TypeDefOrRef => &[
+                Some(TABLE_TYPE_DEF),
+                Some(TABLE_TYPE_REF),
+                Some(TABLE_TYPE_SPEC),
+            ],
+            // ModuleRef is never emmited.
+            Self::ResolutionScope => &[
+                Some(TABLE_MODULE),
+                None,
+                Some(TABLE_ASSEMBLY_REF),
+                Some(TABLE_TYPE_REF),
+            ],
+            Self::MemberRefParent => &[
+                Some(TABLE_TYPE_DEF),
+                Some(TABLE_TYPE_REF),
+                None,
+                Some(TABLE_METHOD_DEF),
+                Some(TABLE_TYPE_SPEC),
+            ],
+            Self::TypeOrMethodDef => &[Some(TABLE_TYPE_DEF), Some(TABLE_METHOD_DEF)],
+            Self::MethodDefOrRef => &[Some(TABLE_METHOD_DEF), Some(TABLE_MEMBER_REF)],
+        }
+    }
+    fn tag_bits(&self) -> u32 {
+        match self {
+            Self::TypeDefOrRef | Self::ResolutionScope => 2,
+            Self::MemberRefParent => 3,
+            Self::TypeOrMethodDef | Self::MethodDefOrRef => 1,
+        }
+    }
+    fn encode(&self, target: TableRef) -> u32 {
+        let tag = self
+            .tables()
+            .iter()
+            .position(|table| *table == Some(target.table()))
+            .unwrap_or_else(|| panic!("{target:?} can't be encoded as {self:?}"));
+        (target.row() << self.tag_bits()) | tag as u32
+    }
+}
+/// Appends `value` encoded as a compressed unsigned intieger(ECMA-335 II.23.2).
+fn compress_u32(value: u32, out: &mut Vec<u8>) {
+    if value <= 0x7F {
+        out.push(value as u8);
+    } else if value <= 0x3FFF {
+        out.extend(((value as u16) | 0x8000).to_be_bytes());
+    } else {
+        assert!(
+            value <= 0x1FFF_FFFF,
+            "Value {value} is too big to be compressed!"
+        );
+        out.extend((value | 0xC000_0000).to_be_bytes());
+    }
+}
+/// The `#Strings` heap: deduplicated, null-terminated UTF-8 strings.
+struct StringHeap {
+    data: Vec<u8>,
+    cache: HashMap<IString, u32>,
+}
+impl StringHeap {
+    fn new() -> Self {
+        Self {
+            data: vec![0],
+            cache: HashMap::new(),
+        }
+    }
+    fn add(&mut self, string: &str) -> u32 {
+        if string.is_empty() {
+            return 0;
+        }
+        if let Some(idx) = self.cache.get(string) {
+            return *idx;
+        }
+        let idx = self.data.len() as u32;
+        self.data.extend(string.as_bytes());
+        self.data.push(0);
+        self.cache.insert(string.into(), idx);
+        idx
+    }
+}
+/// The `#US` heap: length-prefixed UTF-16 string literals used by `ldstr`.
+struct UserStringHeap {
+    data: Vec<u8>,
+    cache: HashMap<IString, u32>,
+}
+impl UserStringHeap {
+    fn new() -> Self {
+        Self {
+            data: vec![0],
+            cache: HashMap::new(),
+        }
+    }
+    fn add(&mut self, string: &str) -> u32 {
+        if let Some(idx) = self.cache.get(string) {
+            return *idx;
+        }
+        let idx = self.data.len() as u32;
+        let utf16: Vec<u16> = string.encode_utf16().collect();
+        compress_u32(utf16.len() as u32 * 2 + 1, &mut self.data);
+        // The final byte is set if any char needs special handling when compared(II.24.2.4).
+        let mut has_special = false;
+        for unit in &utf16 {
+            let [low, high] = unit.to_le_bytes();
+            if high != 0 || matches!(low, 0x01..=0x08 | 0x0E..=0x1F | 0x27 | 0x2D | 0x7F) {
+                has_special = true;
+            }
+            self.data.extend([low, high]);
+        }
+        self.data.push(u8::from(has_special));
+        self.cache.insert(string.into(), idx);
+        idx
+    }
+}
+/// The `#Blob` heap: deduplicated, length-prefixed binary blobs(mostly signatures).
+struct BlobHeap {
+    data: Vec<u8>,
+    cache: HashMap<Vec<u8>, u32>,
+}
+impl BlobHeap {
+    fn new() -> Self {
+        Self {
+            data: vec![0],
+            cache: HashMap::new(),
+        }
+    }
+    fn add(&mut self, blob: &[u8]) -> u32 {
+        if blob.is_empty() {
+            return 0;
+        }
+        if let Some(idx) = self.cache.get(blob) {
+            return *idx;
+        }
+        let idx = self.data.len() as u32;
+        compress_u32(blob.len() as u32, &mut self.data);
+        self.data.extend(blob);
+        self.cache.insert(blob.to_vec(), idx);
+        idx
+    }
+}
+struct TypeRefRow {
+    scope: TableRef,
+    name: u32,
+    namespace: u32,
+}
+struct TypeDefRow {
+    flags: u32,
+    name: u32,
+    namespace: u32,
+    extends: Option<TableRef>,
+    field_list: u32,
+    method_list: u32,
+}
+struct FieldRow {
+    flags: u16,
+    name: u32,
+    signature: u32,
+}
+struct MethodDefRow {
+    rva: u32,
+    flags: u16,
+    name: u32,
+    signature: u32,
+}
+struct MemberRefRow {
+    parent: TableRef,
+    name: u32,
+    signature: u32,
+}
+struct AssemblyRefRow {
+    version: AssemblyVersion,
+    name: u32,
+}
+struct GenericParamRow {
+    number: u16,
+    owner: TableRef,
+    name: u32,
+}
+struct MethodSpecRow {
+    method: TableRef,
+    instantiation: u32,
+}
+/// A single exception handling clause of a method body(II.25.4.6).
+struct EHClause {
+    flags: u32,
+    try_offset: u32,
+    try_length: u32,
+    handler_offset: u32,
+    handler_length: u32,
+    class_token: u32,
+}
+/// Builds the metadata of an assembly, assigning tokens to all definitions and references.
+struct MetadataBuilder {
+    asm_name: u32,
+    module_name: u32,
+    strings: StringHeap,
+    user_strings: UserStringHeap,
+    blobs: BlobHeap,
+    type_refs: Vec<TypeRefRow>,
+    type_defs: Vec<TypeDefRow>,
+    fields: Vec<FieldRow>,
+    method_defs: Vec<MethodDefRow>,
+    member_refs: Vec<MemberRefRow>,
+    field_layouts: Vec<(u32, u32)>,
+    stand_alone_sigs: Vec<u32>,
+    type_specs: Vec<u32>,
+    assembly_refs: Vec<AssemblyRefRow>,
+    nested_classes: Vec<(u32, u32)>,
+    generic_params: Vec<GenericParamRow>,
+    method_specs: Vec<MethodSpecRow>,
+    /// Methods in `MethodDef` row order, waiting for their bodies to be encoded.
+    pending_methods: Vec<Method>,
+    config: CodegenConfig,
+    /// Maps a type path to its `TypeDef` row.
+    type_def_rows: HashMap<IString, u32>,
+    type_ref_cache: HashMap<(Option<IString>, IString), u32>,
+    type_spec_cache: HashMap<Vec<u8>, u32>,
+    member_ref_cache: HashMap<(TableRef, u32, u32), u32>,
+    assembly_ref_rows: HashMap<IString, u32>,
+    method_def_rows: HashMap<(Option<IString>, IString, crate::function_sig::FnSig), u32>,
+    field_rows: HashMap<(Option<IString>, IString), u32>,
+    entrypoint: Option<u32>,
+    /// Version of `System.Runtime`, used for referenced framework assemblies with no explicit extern reference(eg. `System.Console`).
+    framework_version: AssemblyVersion,
+}
+impl MetadataBuilder {
+    fn new(
+        asm_name: &str,
+        module_name: &str,
+        extern_refs: &[(IString, AssemblyVersion)],
+        config: CodegenConfig,
+    ) -> Self {
+        let mut strings = StringHeap::new();
+        let asm_name = strings.add(asm_name);
+        let module_name = strings.add(module_name);
+        let mut res = Self {
+            asm_name,
+            module_name,
+            strings,
+            user_strings: UserStringHeap::new(),
+            blobs: BlobHeap::new(),
+            type_refs: Vec::new(),
+            type_defs: Vec::new(),
+            fields: Vec::new(),
+            method_defs: Vec::new(),
+            member_refs: Vec::new(),
+            field_layouts: Vec::new(),
+            stand_alone_sigs: Vec::new(),
+            type_specs: Vec::new(),
+            assembly_refs: Vec::new(),
+            nested_classes: Vec::new(),
+            generic_params: Vec::new(),
+            method_specs: Vec::new(),
+            pending_methods: Vec::new(),
+            config,
+            type_def_rows: HashMap::new(),
+            type_ref_cache: HashMap::new(),
+            type_spec_cache: HashMap::new(),
+            member_ref_cache: HashMap::new(),
+            assembly_ref_rows: HashMap::new(),
+            method_def_rows: HashMap::new(),
+            field_rows: HashMap::new(),
+            entrypoint: None,
+            framework_version: extern_refs
+                .iter()
+                .find(|(name, _)| name.as_ref() == "System.Runtime")
+                .map_or((0, 0, 0, 0), |(_, version)| *version),
+        };
+        for (name, version) in extern_refs {
+            res.assembly_ref(name, *version);
+        }
+        res
+    }
+    /// Returns the `AssemblyRef` row of assembly `name`, adding it if needed.
+    fn assembly_ref(&mut self, name: &str, version: AssemblyVersion) -> u32 {
+        if let Some(row) = self.assembly_ref_rows.get(name) {
+            return *row;
+        }
+        let name_idx = self.strings.add(name);
+        self.assembly_refs.push(AssemblyRefRow {
+            version,
+            name: name_idx,
+        });
+        let row = self.assembly_refs.len() as u32;
+        self.assembly_ref_rows.insert(name.into(), row);
+        row
+    }
+    /// Assigns rows to all type, field and method definitions. Global fields and methods are placed in the `<Module>` type.
+    fn add_definitions(
+        &mut self,
+        globals: Vec<(IString, Type)>,
+        methods: Vec<Method>,
+        types: Vec<TypeDef>,
+    ) {
+        // Types need rows before any signature gets encoded, since signatures may refer to them.
+        let mut row = 2;
+        for tpe in &types {
+            Self::reserve_type_rows(&mut self.type_def_rows, tpe, None, &mut row);
+        }
+        let module_name = self.strings.add("<Module>");
+        self.type_defs.push(TypeDefRow {
+            flags: 0,
+            name: module_name,
+            namespace: 0,
+            extends: None,
+            field_list: 1,
+            method_list: 1,
+        });
+        for (name, tpe) in globals {
+            let signature = self.field_sig(&tpe);
+            let name_idx = self.strings.add(&name);
+            self.fields.push(FieldRow {
+                // public static
+                flags: 0x0016,
+                name: name_idx,
+                signature,
+            });
+            self.field_rows
+                .insert((None, name), self.fields.len() as u32);
+        }
+        for method in methods {
+            self.add_method_def(None, method);
+        }
+        for tpe in types {
+            self.add_type_def(tpe, None);
+        }
+    }
+    fn reserve_type_rows(
+        rows: &mut HashMap<IString, u32>,
+        tpe: &TypeDef,
+        enclosing: Option<&str>,
+        row: &mut u32,
+    ) {
+        let path: IString = match enclosing {
+            Some(enclosing) => format!("{enclosing}/{name}", name = tpe.name()).into(),
+            None => tpe.name().into(),
+        };
+        rows.insert(path.clone(), *row);
+        *row += 1;
+        for inner in tpe.inner_types() {
+            Self::reserve_type_rows(rows, inner, Some(&path), row);
+        }
+    }
+    fn add_type_def(&mut self, tpe: TypeDef, enclosing: Option<(&str, u32)>) {
+        let path: IString = match enclosing {
+            Some((enclosing, _)) => format!("{enclosing}/{name}", name = tpe.name()).into(),
+            None => tpe.name().into(),
+        };
+        let row = self.type_defs.len() as u32 + 1;
+        assert_eq!(
+            Some(&row),
+            self.type_def_rows.get(&path),
+            "TypeDef row of {path} changed between reservation and definition!"
+        );
+        let is_public = matches!(tpe.access_modifier(), AccessModifer::Public);
+        let mut flags = match (enclosing.is_some(), is_public) {
+            (false, false) => 0x0,
+            (false, true) => 0x1,
+            (true, true) => 0x2,
+            (true, false) => 0x3,
+        };
+        if tpe.explicit_offsets().is_some() {
+            // explicit sealed beforefieldinit
+            flags |= 0x0010 | 0x0100 | 0x0010_0000;
+        }
+        let (namespace, name) = match (enclosing, tpe.name().rsplit_once('.')) {
+            (None, Some((namespace, name))) => (namespace, name),
+            _ => ("", tpe.name()),
+        };
+        let name = self.strings.add(name);
+        let namespace = self.strings.add(namespace);
+        let extends = match tpe.extends() {
+            Some(extended) => self.type_def_or_ref(extended),
+            None => self.type_def_or_ref(&DotnetTypeRef::new(
+                Some("System.Runtime"),
+                "System.ValueType",
+            )),
+        };
+        self.type_defs.push(TypeDefRow {
+            flags,
+            name,
+            namespace,
+            extends: Some(extends),
+            field_list: self.fields.len() as u32 + 1,
+            method_list: self.method_defs.len() as u32 + 1,
+        });
+        if let Some((_, enclosing_row)) = enclosing {
+            self.nested_classes.push((row, enclosing_row));
+        }
+        for number in 0..tpe.gargc() {
+            let name = self.strings.add(&format!("G{number}"));
+            self.generic_params.push(GenericParamRow {
+                number: number as u16,
+                owner: TableRef::TypeDef(row),
+                name,
+            });
+        }
+        let offsets = tpe.explicit_offsets().cloned();
+        for (idx, (field_name, field_type)) in tpe.fields().iter().enumerate() {
+            let signature = self.field_sig(field_type);
+            let name = self.strings.add(field_name);
+            self.fields.push(FieldRow {
+                // public
+                flags: 0x0006,
+                name,
+                signature,
+            });
+            let field_row = self.fields.len() as u32;
+            if let Some(offsets) = &offsets {
+                self.field_layouts.push((offsets[idx], field_row));
+            }
+            self.field_rows
+                .insert((Some(path.clone()), field_name.clone()), field_row);
+        }
+        for method in tpe.methods() {
+            self.add_method_def(Some(path.clone()), method.clone());
+        }
+        for inner in tpe.inner_types() {
+            self.add_type_def(inner.clone(), Some((&path, row)));
+        }
+    }
+    fn add_method_def(&mut self, owner: Option<IString>, method: Method) {
+        let mut flags: u16 = match method.access() {
+            AccessModifer::Public => 0x0006,
+            AccessModifer::Private => 0x0001,
+        };
+        // hidebysig
+        flags |= 0x0080;
+        if method.is_static() {
+            flags |= 0x0010;
+        }
+        if method.name() == ".ctor" || method.name() == ".cctor" {
+            // specialname rtspecialname
+            flags |= 0x0800 | 0x1000;
+        }
+        let signature = self.method_sig(
+            !method.is_static(),
+            method.generic_count(),
+            method.explicit_inputs(),
+            method.sig().output(),
+        );
+        let name = self.strings.add(method.name());
+        self.method_defs.push(MethodDefRow {
+            rva: 0,
+            flags,
+            name,
+            signature,
+        });
+        let row = self.method_defs.len() as u32;
+        for number in 0..method.generic_count() {
+            let name = self.strings.add(&format!("M{number}"));
+            self.generic_params.push(GenericParamRow {
+                number: number as u16,
+                owner: TableRef::MethodDef(row),
+                name,
+            });
+        }
+        if method.is_entrypoint() {
+            self.entrypoint = Some(TableRef::MethodDef(row).token());
+        }
+        self.method_def_rows
+            .insert((owner, method.name().into(), method.sig().clone()), row);
+        self.pending_methods.push(method);
+    }
+    /// Returns a `TypeDef` or `TypeRef` refering to `tpe`, ignoring its generic arguments.
+    fn type_def_or_ref(&mut self, tpe: &DotnetTypeRef) -> TableRef {
+        match tpe.asm() {
+            None => match self.type_def_rows.get(tpe.name_path()) {
+                Some(row) => TableRef::TypeDef(*row),
+                // Not defined in this assembly, so let the runtime resolve it within this module.
+                None => TableRef::TypeRef(self.type_ref(None, tpe.name_path())),
+            },
+            Some(asm) => TableRef::TypeRef(self.type_ref(Some(asm), tpe.name_path())),
+        }
+    }
+    fn type_ref(&mut self, asm: Option<&str>, path: &str) -> u32 {
+        let key = (asm.map(IString::from), IString::from(path));
+        if let Some(row) = self.type_ref_cache.get(&key) {
+            return *row;
+        }
+        let (scope, namespace, name) = match path.rsplit_once('/') {
+            Some((enclosing, name)) => (TableRef::TypeRef(self.type_ref(asm, enclosing)), "", name),
+            None => {
+                let scope = match asm {
+                    Some(asm) => {
+                        TableRef::AssemblyRef(self.assembly_ref(asm, self.framework_version))
+                    }
+                    None => TableRef::Module(1),
+                };
+                match path.rsplit_once('.') {
+                    Some((namespace, name)) => (scope, namespace, name),
+                    None => (scope, "", path),
+                }
+            }
+        };
+        let name = self.strings.add(name);
+        let namespace = self.strings.add(namespace);
+        self.type_refs.push(TypeRefRow {
+            scope,
+            name,
+            namespace,
+        });
+        let row = self.type_refs.len() as u32;
+        self.type_ref_cache.insert(key, row);
+        row
+    }
+    /// Returns a `TypeSpec` with signature `sig`.
+    fn type_spec(&mut self, sig: Vec<u8>) -> TableRef {
+        if let Some(row) = self.type_spec_cache.get(&sig) {
+            return TableRef::TypeSpec(*row);
+        }
+        let blob = self.blobs.add(&sig);
+        self.type_specs.push(blob);
+        let row = self.type_specs.len() as u32;
+        self.type_spec_cache.insert(sig, row);
+        TableRef::TypeSpec(row)
+    }
+    /// Returns the token used by ops like `ldobj` or `sizeof` to refer to `tpe`.
+    fn type_token(&mut self, tpe: &Type) -> u32 {
+        match tpe {
+            Type::DotnetType(dotnet)
+                if dotnet.generics().is_empty() && !is_system_string(dotnet) =>
+            {
+                self.type_def_or_ref(dotnet).token()
+            }
+            _ => {
+                let mut sig = Vec::new();
+                self.encode_type(tpe, false, &mut sig);
+                self.type_spec(sig).token()
+            }
+        }
+    }
+    /// Returns the parent of members of `owner`.
+    fn member_parent(&mut self, owner: &DotnetTypeRef) -> TableRef {
+        if owner.generics().is_empty() {
+            self.type_def_or_ref(owner)
+        } else {
+            let mut sig = Vec::new();
+            self.encode_type(&owner.clone().into(), false, &mut sig);
+            self.type_spec(sig)
+        }
+    }
+    fn member_ref(&mut self, parent: TableRef, name: &str, signature: u32) -> u32 {
+        let name = self.strings.add(name);
+        let key = (parent, name, signature);
+        if let Some(row) = self.member_ref_cache.get(&key) {
+            return *row;
+        }
+        self.member_refs.push(MemberRefRow {
+            parent,
+            name,
+            signature,
+        });
+        let row = self.member_refs.len() as u32;
+        self.member_ref_cache.insert(key, row);
+        row
+    }
+    /// Returns the token of method refered to by `site`. Instantiations of generic methods get a `MethodSpec` row.
+    fn method_token(&mut self, site: &CallSite) -> Result<u32, AssemblyExportError> {
+        let method = self.method_def_or_ref(site)?;
+        if site.generics().is_empty() {
+            return Ok(method.token());
+        }
+        // GENERICINST
+        let mut instantiation = vec![0x0A];
+        compress_u32(site.generics().len() as u32, &mut instantiation);
+        for generic in site.generics() {
+            self.encode_type(generic.as_generic_arg(), false, &mut instantiation);
+        }
+        let instantiation = self.blobs.add(&instantiation);
+        self.method_specs.push(MethodSpecRow {
+            method,
+            instantiation,
+        });
+        Ok(((TABLE_METHOD_SPEC as u32) << 24) | self.method_specs.len() as u32)
+    }
+    /// Returns the `MethodDef` or `MemberRef` row of the (uninstantiated) method refered to by `site`.
+    fn method_def_or_ref(&mut self, site: &CallSite) -> Result<TableRef, AssemblyExportError> {
+        let owner = match site.class() {
+            None => None,
+            Some(class) if class.asm().is_none() && class.generics().is_empty() => {
+                Some(IString::from(class.name_path()))
+            }
+            Some(class) => {
+                let parent = self.member_parent(class);
+                let signature = self.method_sig(
+                    !site.is_static(),
+                    site.generics().len() as u32,
+                    site.explicit_inputs(),
+                    site.signature().output(),
+                );
+                let row = self.member_ref(parent, site.name(), signature);
+                return Ok(TableRef::MemberRef(row));
+            }
+        };
+        let key = (owner, site.name().into(), site.signature().clone());
+        match self.method_def_rows.get(&key) {
+            Some(row) => Ok(TableRef::MethodDef(*row)),
+            None => Err(AssemblyExportError::ExporterError(
+                format!("Could not find method {site:?}").into(),
+            )),
+        }
+    }
+    /// Returns the token of the instance field described by `desc`.
+    fn field_token(&mut self, desc: &FieldDescriptor) -> u32 {
+        let owner = desc.owner();
+        if owner.asm().is_none() && owner.generics().is_empty() {
+            let key = (
+                Some(IString::from(owner.name_path())),
+                IString::from(desc.name()),
+            );
+            if let Some(row) = self.field_rows.get(&key) {
+                return ((TABLE_FIELD as u32) << 24) | *row;
+            }
+        }
+        let parent = self.member_parent(owner);
+        let signature = self.field_sig(desc.tpe());
+        let row = self.member_ref(parent, desc.name(), signature);
+        ((TABLE_MEMBER_REF as u32) << 24) | row
+    }
+    /// Returns the token of the static field described by `desc`.
+    fn static_field_token(
+        &mut self,
+        desc: &StaticFieldDescriptor,
+    ) -> Result<u32, AssemblyExportError> {
+        match desc.owner() {
+            None => match self.field_rows.get(&(None, IString::from(desc.name()))) {
+                Some(row) => Ok(((TABLE_FIELD as u32) << 24) | *row),
+                None => Err(AssemblyExportError::ExporterError(
+                    format!("Could not find static {desc:?}").into(),
+                )),
+            },
+            Some(owner) => Ok(self.field_token(&FieldDescriptor::new(
+                owner.clone(),
+                desc.tpe().clone(),
+                desc.name().into(),
+            ))),
+        }
+    }
+    fn field_sig(&mut self, tpe: &Type) -> u32 {
+        // FIELD
+        let mut sig = vec![0x06];
+        self.encode_type(tpe, false, &mut sig);
+        self.blobs.add(&sig)
+    }
+    fn method_sig(
+        &mut self,
+        has_this: bool,
+        generic_count: u32,
+        inputs: &[Type],
+        output: &Type,
+    ) -> u32 {
+        let mut sig = Vec::new();
+        self.encode_method_sig(has_this, inputs, output, &mut sig);
+        if generic_count != 0 {
+            // GENERIC, followed by the number of generic arguments.
+            sig[0] |= 0x10;
+            let mut count = Vec::new();
+            compress_u32(generic_count, &mut count);
+            sig.splice(1..1, count);
+        }
+        self.blobs.add(&sig)
+    }
+    /// Encodes a method signature(II.23.2.1), also used by function pointers and `calli`.
+    fn encode_method_sig(
+        &mut self,
+        has_this: bool,
+        inputs: &[Type],
+        output: &Type,
+        sig: &mut Vec<u8>,
+    ) {
+        // HASTHIS or DEFAULT
+        sig.push(if has_this { 0x20 } else { 0x00 });
+        compress_u32(inputs.len() as u32, sig);
+        self.encode_type(output, true, sig);
+        for input in inputs {
+            self.encode_type(input, false, sig);
+        }
+    }
+    /// Returns the `StandAloneSig` token describing the call site of a `calli` calling a function with signature `fn_sig`.
+    fn calli_sig_token(&mut self, fn_sig: &FnSig) -> u32 {
+        let mut sig = Vec::new();
+        self.encode_method_sig(false, fn_sig.inputs(), fn_sig.output(), &mut sig);
+        let blob = self.blobs.add(&sig);
+        self.stand_alone_sigs.push(blob);
+        ((TABLE_STAND_ALONE_SIG as u32) << 24) | self.stand_alone_sigs.len() as u32
+    }
+    /// Returns the `StandAloneSig` token describing the locals of `method`, or 0 if it has none.
+    fn locals_token(&mut self, method: &Method) -> u32 {
+        if method.locals().is_empty() {
+            return 0;
+        }
+        // LOCAL_SIG
+        let mut sig = vec![0x07];
+        compress_u32(method.locals().len() as u32, &mut sig);
+        for (_, local) in method.locals() {
+            self.encode_type(local, false, &mut sig);
+        }
+        let blob = self.blobs.add(&sig);
+        self.stand_alone_sigs.push(blob);
+        ((TABLE_STAND_ALONE_SIG as u32) << 24) | self.stand_alone_sigs.len() as u32
+    }
+    /// Encodes `tpe` as a type signature(II.23.2.12). `is_ret` mirrors the special handling ILASM exporter gives to return types.
+    fn encode_type(&mut self, tpe: &Type, is_ret: bool, sig: &mut Vec<u8>) {
+        match tpe {
+            Type::Void if is_ret => sig.push(0x01),
+            Type::Void => self.encode_named_valuetype("RustVoid", sig),
+            Type::Bool => sig.push(0x02),
+            Type::DotnetChar => sig.push(0x03),
+            Type::I8 => sig.push(0x04),
+            Type::U8 => sig.push(0x05),
+            Type::I16 => sig.push(0x06),
+            Type::U16 => sig.push(0x07),
+            Type::I32 => sig.push(0x08),
+            Type::U32 => sig.push(0x09),
+            Type::I64 => sig.push(0x0A),
+            Type::U64 => sig.push(0x0B),
+            Type::F32 => sig.push(0x0C),
+            Type::F64 => sig.push(0x0D),
+            Type::ISize => sig.push(0x18),
+            Type::USize => sig.push(0x19),
+            Type::I128 => self.encode_type(&DotnetTypeRef::int_128().into(), is_ret, sig),
+            Type::U128 => self.encode_type(&DotnetTypeRef::uint_128().into(), is_ret, sig),
+            Type::Ptr(inner) => {
+                sig.push(0x0F);
+                self.encode_type(inner, is_ret, sig);
+            }
+            Type::GenericArg(idx) => {
+                // VAR
+                sig.push(0x13);
+                compress_u32(*idx, sig);
+            }
+            Type::MethodGenericArg(idx) => {
+                // MVAR
+                sig.push(0x1E);
+                compress_u32(*idx, sig);
+            }
+            Type::Unresolved => self.encode_named_valuetype("Unresolved", sig),
+            Type::Foreign => self.encode_named_valuetype("Foreign", sig),
+            Type::FnDef(name) => self.encode_named_valuetype(&format!("fn_{name}"), sig),
+            Type::FnPtr(fn_sig) => {
+                // FNPTR
+                sig.push(0x1B);
+                self.encode_method_sig(false, fn_sig.inputs(), fn_sig.output(), sig);
+            }
+            Type::DotnetArray(array) => {
+                if array.dimensions <= 1 {
+                    // SZARRAY
+                    sig.push(0x1D);
+                    self.encode_type(&array.element, false, sig);
+                } else {
+                    // ARRAY with no sizes and no lower bounds
+                    sig.push(0x14);
+                    self.encode_type(&array.element, false, sig);
+                    compress_u32(array.dimensions as u32, sig);
+                    sig.extend([0, 0]);
+                }
+            }
+            Type::DotnetType(dotnet) => {
+                if is_system_string(dotnet) {
+                    sig.push(0x0E);
+                    return;
+                }
+                let kind = if dotnet.is_valuetype() { 0x11 } else { 0x12 };
+                let generics = dotnet.generics();
+                if !generics.is_empty() {
+                    // GENERICINST
+                    sig.push(0x15);
+                }
+                sig.push(kind);
+                let def_or_ref = self.type_def_or_ref(dotnet);
+                compress_u32(CodedIndex::TypeDefOrRef.encode(def_or_ref), sig);
+                if !generics.is_empty() {
+                    compress_u32(generics.len() as u32, sig);
+                    for generic in generics {
+                        self.encode_type(generic.as_generic_arg(), false, sig);
+                    }
+                }
+            }
+        }
+    }
+    fn encode_named_valuetype(&mut self, name: &str, sig: &mut Vec<u8>) {
+        sig.push(0x11);
+        let def_or_ref = self.type_def_or_ref(&DotnetTypeRef::new(None, name));
+        compress_u32(CodedIndex::TypeDefOrRef.encode(def_or_ref), sig);
+    }
+    /// Encodes the bodies of all methods, filling in their RVAs. `base_rva` is the RVA of the first body.
+    fn encode_bodies(&mut self, base_rva: u32) -> Result<Vec<u8>, AssemblyExportError> {
+        let mut bodies = Vec::new();
+        let methods = std::mem::take(&mut self.pending_methods);
+        for (idx, method) in methods.iter().enumerate() {
+            // Fat headers must be 4 byte aligned.
+            while bodies.len() % 4 != 0 {
+                bodies.push(0);
+            }
+            self.method_defs[idx].rva = base_rva + bodies.len() as u32;
+            let (code, clauses) = self.encode_ops(method.get_ops())?;
+            let max_stack = max_stack(method.get_ops());
+            let locals = self.locals_token(method);
+            if code.len() < 64 && max_stack <= 8 && locals == 0 && clauses.is_empty() {
+                // Tiny header: format in the low 2 bits, code size in the upper 6.
+                bodies.push(((code.len() as u8) << 2) | 0x2);
+            } else {
+                // Fat format, header size of 3 dwords.
+                let mut flags: u16 = 0x3003;
+                if self.config.always_init_locals {
+                    flags |= 0x10;
+                }
+                if !clauses.is_empty() {
+                    flags |= 0x08;
+                }
+                bodies.extend(flags.to_le_bytes());
+                bodies.extend(max_stack.to_le_bytes());
+                bodies.extend((code.len() as u32).to_le_bytes());
+                bodies.extend(locals.to_le_bytes());
+            }
+            bodies.extend(code);
+            if !clauses.is_empty() {
+                // Fat exception handling section, which must be 4 byte aligned(II.25.4.5).
+                while bodies.len() % 4 != 0 {
+                    bodies.push(0);
+                }
+                let data_size = 4 + 24 * clauses.len() as u32;
+                bodies.push(0x41);
+                bodies.extend(&data_size.to_le_bytes()[..3]);
+                for clause in clauses {
+                    bodies.extend(clause.flags.to_le_bytes());
+                    bodies.extend(clause.try_offset.to_le_bytes());
+                    bodies.extend(clause.try_length.to_le_bytes());
+                    bodies.extend(clause.handler_offset.to_le_bytes());
+                    bodies.extend(clause.handler_length.to_le_bytes());
+                    bodies.extend(clause.class_token.to_le_bytes());
+                }
+            }
+        }
+        Ok(bodies)
+    }
+    /// Encodes `ops` into CIL bytecode and exception handling clauses. All branches use their long forms, so the offsets are known after a single pass.
+    fn encode_ops(
+        &mut self,
+        ops: &[CILOp],
+    ) -> Result<(Vec<u8>, Vec<EHClause>), AssemblyExportError> {
+        let mut code = Vec::new();
+        let mut labels = HashMap::new();
+        // (position of the offset, target label)
+        let mut fixups = Vec::new();
+        // Starts of the currently open protected regions.
+        let mut tries = Vec::new();
+        // Offset and length of the last closed protected region.
+        let mut last_try = None;
+        // Currently open handlers, with their clauses missing the handler length.
+        let mut handlers: Vec<EHClause> = Vec::new();
+        let mut clauses = Vec::new();
+        for op in ops {
+            let branch = |code: &mut Vec<u8>, fixups: &mut Vec<(usize, u32)>, opcode, target| {
+                code.push(opcode);
+                fixups.push((code.len(), target));
+                code.extend(0_i32.to_le_bytes());
+            };
+            match op {
+                CILOp::Label(id) => {
+                    labels.insert(*id, code.len());
+                }
+                CILOp::Comment(_) => (),
+                CILOp::Nop => code.push(0x00),
+                //Control flow
+                CILOp::GoTo(target) => branch(&mut code, &mut fixups, 0x38, *target),
+                CILOp::BZero(target) => branch(&mut code, &mut fixups, 0x39, *target),
+                CILOp::BTrue(target) => branch(&mut code, &mut fixups, 0x3A, *target),
+                CILOp::BEq(target) => branch(&mut code, &mut fixups, 0x3B, *target),
+                CILOp::BGe(target) => branch(&mut code, &mut fixups, 0x3C, *target),
+                CILOp::BLe(target) => branch(&mut code, &mut fixups, 0x3E, *target),
+                CILOp::BLt(target) => branch(&mut code, &mut fixups, 0x3F, *target),
+                CILOp::BNe(target) => branch(&mut code, &mut fixups, 0x40, *target),
+                CILOp::Leave(target) => branch(&mut code, &mut fixups, 0xDD, *target),
+                CILOp::EndFinally => code.push(0xDC),
+                //Exception handling regions
+                CILOp::BeginTry => tries.push(code.len() as u32),
+                CILOp::EndTry => {
+                    let start = tries.pop().ok_or_else(|| {
+                        AssemblyExportError::ExporterError("Unmatched end of a try block".into())
+                    })?;
+                    last_try = Some((start, code.len() as u32 - start));
+                }
+                CILOp::BeginCatch(_) | CILOp::BeginFinally => {
+                    let (try_offset, try_length) = last_try.take().ok_or_else(|| {
+                        AssemblyExportError::ExporterError(
+                            "Handler not preceded by a try block".into(),
+                        )
+                    })?;
+                    let (flags, class_token) = match op {
+                        CILOp::BeginCatch(tpe) => (0, self.type_token(&tpe.as_ref().clone().into())),
+                        _ => (2, 0),
+                    };
+                    handlers.push(EHClause {
+                        flags,
+                        try_offset,
+                        try_length,
+                        handler_offset: code.len() as u32,
+                        handler_length: 0,
+                        class_token,
+                    });
+                }
+                CILOp::EndHandler => {
+                    let mut clause = handlers.pop().ok_or_else(|| {
+                        AssemblyExportError::ExporterError("Unmatched end of a handler".into())
+                    })?;
+                    clause.handler_length = code.len() as u32 - clause.handler_offset;
+                    // Inner handlers always end first, so clauses end up ordered from the innermost one(II.19).
+                    clauses.push(clause);
+                }
+                CILOp::Ret => code.push(0x2A),
+                CILOp::Throw => code.push(0x7A),
+                CILOp::Rethrow => code.extend([0xFE, 0x1A]),
+                CILOp::Call(site) | CILOp::CallVirt(site) | CILOp::NewObj(site) => {
+                    if site.is_nop() {
+                        continue;
+                    }
+                    let opcode = match op {
+                        CILOp::Call(_) => 0x28,
+                        CILOp::CallVirt(_) => 0x6F,
+                        _ => 0x73,
+                    };
+                    code.push(opcode);
+                    code.extend(self.method_token(site)?.to_le_bytes());
+                }
+                CILOp::LDFtn(site) => {
+                    code.extend([0xFE, 0x06]);
+                    code.extend(self.method_token(site)?.to_le_bytes());
+                }
+                CILOp::CallI(sig) => {
+                    code.push(0x29);
+                    code.extend(self.calli_sig_token(sig).to_le_bytes());
+                }
+                //Arguments
+                CILOp::LDArg(arg) => short_or_long(&mut code, *arg, Some(0x02), 0x0E, 0x09),
+                CILOp::LDArgA(arg) => short_or_long(&mut code, *arg, None, 0x0F, 0x0A),
+                CILOp::STArg(arg) => short_or_long(&mut code, *arg, None, 0x10, 0x0B),
+                //Locals
+                CILOp::LDLoc(loc) => short_or_long(&mut code, *loc, Some(0x06), 0x11, 0x0C),
+                CILOp::LDLocA(loc) => short_or_long(&mut code, *loc, None, 0x12, 0x0D),
+                CILOp::STLoc(loc) => short_or_long(&mut code, *loc, Some(0x0A), 0x13, 0x0E),
+                //Constants
+                CILOp::LdcI32(value) => ldc_i4(&mut code, *value),
+                CILOp::LdcI64(value) => {
+                    // Mirrors ILASM exporter, which uses `ldc.i4` for small values.
+                    if let Ok(value) = i32::try_from(*value) {
+                        ldc_i4(&mut code, value);
+                    } else {
+                        code.push(0x21);
+                        code.extend(value.to_le_bytes());
+                    }
+                }
+                CILOp::LdcF32(value) => {
+                    code.push(0x22);
+                    code.extend(value.to_le_bytes());
+                }
+                CILOp::LdcF64(value) => {
+                    code.push(0x23);
+                    code.extend(value.to_le_bytes());
+                }
+                CILOp::LdStr(string) => {
+                    code.push(0x72);
+                    code.extend((0x7000_0000 | self.user_strings.add(string)).to_le_bytes());
+                }
+                CILOp::LdNull => code.push(0x14),
+                //Convertions
+                CILOp::ConvI8(checked) => code.push(if *checked { 0xB3 } else { 0x67 }),
+                CILOp::ConvI16(checked) => code.push(if *checked { 0xB5 } else { 0x68 }),
+                CILOp::ConvI32(checked) => code.push(if *checked { 0xB7 } else { 0x69 }),
+                CILOp::ConvI64(checked) => code.push(if *checked { 0xB9 } else { 0x6A }),
+                CILOp::ConvISize(checked) => code.push(if *checked { 0xD4 } else { 0xD3 }),
+                CILOp::ConvU8(checked) => code.push(if *checked { 0xB4 } else { 0xD2 }),
+                CILOp::ConvU16(checked) => code.push(if *checked { 0xB6 } else { 0xD1 }),
+                CILOp::ConvU32(checked) => code.push(if *checked { 0xB8 } else { 0x6D }),
+                CILOp::ConvU64(checked) => code.push(if *checked { 0xBA } else { 0x6E }),
+                CILOp::ConvUSize(checked) => code.push(if *checked { 0xD5 } else { 0xE0 }),
+                // There are no checked float convertions in CIL.
+                CILOp::ConvF32(_) => code.push(0x6B),
+                CILOp::ConvF64(_) => code.push(0x6C),
+                CILOp::ConvFUn => code.push(0x76),
+                // Pointer stuff
+                CILOp::LDIndI8 => code.push(0x46),
+                CILOp::LDIndI16 => code.push(0x48),
+                CILOp::LDIndI32 => code.push(0x4A),
+                CILOp::LDIndI64 => code.push(0x4C),
+                CILOp::LDIndISize => code.push(0x4D),
+                CILOp::LDIndF32 => code.push(0x4E),
+                CILOp::LDIndF64 => code.push(0x4F),
+                CILOp::LDIndRef => code.push(0x50),
+                CILOp::STIndI8 => code.push(0x52),
+                CILOp::STIndI16 => code.push(0x53),
+                CILOp::STIndI32 => code.push(0x54),
+                CILOp::STIndI64 => code.push(0x55),
+                CILOp::STIndF32 => code.push(0x56),
+                CILOp::STIndF64 => code.push(0x57),
+                CILOp::STIndISize => code.push(0xDF),
+                CILOp::LocAlloc => code.extend([0xFE, 0x0F]),
+                CILOp::CpBlk => code.extend([0xFE, 0x17]),
+                //Arthmetics
+                CILOp::Add => code.push(0x58),
+                CILOp::AddOvf => code.push(0xD6),
+                CILOp::AddOvfUn => code.push(0xD7),
+                CILOp::Sub => code.push(0x59),
+                CILOp::SubOvf => code.push(0xDA),
+                CILOp::SubOvfUn => code.push(0xDB),
+                CILOp::Mul => code.push(0x5A),
+                CILOp::MulOvf => code.push(0xD8),
+                CILOp::Div => code.push(0x5B),
+                CILOp::DivUn => code.push(0x5C),
+                CILOp::Rem => code.push(0x5D),
+                CILOp::RemUn => code.push(0x5E),
+                CILOp::Neg => code.push(0x65),
+                //Bitwise
+                CILOp::And => code.push(0x5F),
+                CILOp::Or => code.push(0x60),
+                CILOp::XOr => code.push(0x61),
+                CILOp::Not => code.push(0x66),
+                //Bitshifts
+                CILOp::Shl => code.push(0x62),
+                CILOp::Shr => code.push(0x63),
+                CILOp::ShrUn => code.push(0x64),
+                //Comparisons
+                CILOp::Eq => code.extend([0xFE, 0x01]),
+                CILOp::Gt => code.extend([0xFE, 0x02]),
+                CILOp::GtUn => code.extend([0xFE, 0x03]),
+                CILOp::Lt => code.extend([0xFE, 0x04]),
+                CILOp::LtUn => code.extend([0xFE, 0x05]),
+                //Special
+                CILOp::Pop => code.push(0x26),
+                CILOp::Dup => code.push(0x25),
+                //OOP
+                CILOp::LdObj(tpe) => {
+                    code.push(0x71);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::STObj(tpe) => {
+                    code.push(0x81);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::SizeOf(tpe) => {
+                    code.extend([0xFE, 0x1C]);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::CastClass(tpe) => {
+                    code.push(0x74);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::LDField(desc) => {
+                    code.push(0x7B);
+                    code.extend(self.field_token(desc).to_le_bytes());
+                }
+                CILOp::LDFieldAdress(desc) => {
+                    code.push(0x7C);
+                    code.extend(self.field_token(desc).to_le_bytes());
+                }
+                CILOp::STField(desc) => {
+                    code.push(0x7D);
+                    code.extend(self.field_token(desc).to_le_bytes());
+                }
+                CILOp::LDStaticField(desc) => {
+                    code.push(0x7E);
+                    code.extend(self.static_field_token(desc)?.to_le_bytes());
+                }
+                CILOp::STStaticField(desc) => {
+                    code.push(0x80);
+                    code.extend(self.static_field_token(desc)?.to_le_bytes());
+                }
+                //Arrays
+                CILOp::NewArr(tpe) => {
+                    code.push(0x8D);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::LDElem(tpe) => {
+                    code.push(0xA3);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::STElem(tpe) => {
+                    code.push(0xA4);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::LDElemA(tpe) => {
+                    code.push(0x8F);
+                    code.extend(self.type_token(tpe).to_le_bytes());
+                }
+                CILOp::LDLen => code.push(0x8E),
+                CILOp::NewTMPLocal(_)
+                | CILOp::FreeTMPLocal
+                | CILOp::LoadAddresOfTMPLocal
+                | CILOp::SetTMPLocal
+                | CILOp::LoadTMPLocal
+                | CILOp::LoadUnderTMPLocal(_)
+                | CILOp::LoadAdressUnderTMPLocal(_)
+                | CILOp::LoadGlobalStaticPtr { .. } => panic!(
+                    "CRITICAL INTERNAL ERROR: OP '{op:?}' is syntetic(internal only) and should have been substituted before being emmited!"
+                ),
+                CILOp::LoadGlobalAllocPtr { alloc_id } => panic!(
+                    "CRITICAL INTERNAL ERROR:Allocation {alloc_id} was not resolved to a static."
+                ),
+            }
+        }
+        for (pos, target) in fixups {
+            let target_pos = *labels.get(&target).ok_or_else(|| {
+                AssemblyExportError::ExporterError(format!("Missing label bb_{target}").into())
+            })?;
+            // Branch offsets are relative to the start of the next instruction.
+            let offset = target_pos as i32 - (pos + 4) as i32;
+            code[pos..(pos + 4)].copy_from_slice(&offset.to_le_bytes());
+        }
+        Ok((code, clauses))
+    }
+    /// Builds the metadata root and all of its streams(II.24.2.1).
+    fn build_metadata(&self) -> Vec<u8> {
+        let tables = self.build_table_stream();
+        // Deterministic MVID, derived from the contents of the assembly.
+        let mvid = {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            tables.hash(&mut hasher);
+            let low = hasher.finish();
+            self.blobs.data.hash(&mut hasher);
+            let high = hasher.finish();
+            let mut mvid = [0; 16];
+            mvid[..8].copy_from_slice(&low.to_le_bytes());
+            mvid[8..].copy_from_slice(&high.to_le_bytes());
+            mvid
+        };
+        let streams: [(&str, &[u8]); 5] = [
+            ("#~", &tables),
+            ("#Strings", &self.strings.data),
+            ("#US", &self.user_strings.data),
+            ("#GUID", &mvid),
+            ("#Blob", &self.blobs.data),
+        ];
+        let version = b"v4.0.30319\0\0";
+        let mut header = Vec::new();
+        header.extend(0x424A_5342_u32.to_le_bytes());
+        header.extend(1_u16.to_le_bytes());
+        header.extend(1_u16.to_le_bytes());
+        header.extend(0_u32.to_le_bytes());
+        header.extend((version.len() as u32).to_le_bytes());
+        header.extend(version);
+        header.extend(0_u16.to_le_bytes());
+        header.extend((streams.len() as u16).to_le_bytes());
+        let headers_size: usize = streams
+            .iter()
+            .map(|(name, _)| 8 + align(name.len() as u32 + 1, 4) as usize)
+            .sum();
+        let mut offset = (header.len() + headers_size) as u32;
+        let mut data = Vec::new();
+        for (name, stream) in streams {
+            let size = align(stream.len() as u32, 4);
+            header.extend(offset.to_le_bytes());
+            header.extend(size.to_le_bytes());
+            header.extend(name.as_bytes());
+            header.push(0);
+            while header.len() % 4 != 0 {
+                header.push(0);
+            }
+            data.extend(stream);
+            data.resize(data.len() + (size as usize - stream.len()), 0);
+            offset += size;
+        }
+        header.extend(data);
+        header
+    }
+    /// Builds the `#~` stream(II.24.2.6).
+    fn build_table_stream(&self) -> Vec<u8> {
+        let mut rows = [0_u32; 64];
+        rows[TABLE_MODULE] = 1;
+        rows[TABLE_TYPE_REF] = self.type_refs.len() as u32;
+        rows[TABLE_TYPE_DEF] = self.type_defs.len() as u32;
+        rows[TABLE_FIELD] = self.fields.len() as u32;
+        rows[TABLE_METHOD_DEF] = self.method_defs.len() as u32;
+        rows[TABLE_MEMBER_REF] = self.member_refs.len() as u32;
+        rows[TABLE_FIELD_LAYOUT] = self.field_layouts.len() as u32;
+        rows[TABLE_STAND_ALONE_SIG] = self.stand_alone_sigs.len() as u32;
+        rows[TABLE_TYPE_SPEC] = self.type_specs.len() as u32;
+        rows[TABLE_ASSEMBLY] = 1;
+        rows[TABLE_ASSEMBLY_REF] = self.assembly_refs.len() as u32;
+        rows[TABLE_NESTED_CLASS] = self.nested_classes.len() as u32;
+        rows[TABLE_GENERIC_PARAM] = self.generic_params.len() as u32;
+        rows[TABLE_METHOD_SPEC] = self.method_specs.len() as u32;
+        let w = TableWriter {
+            rows,
+            wide_strings: self.strings.data.len() > 0xFFFF,
+            wide_blobs: self.blobs.data.len() > 0xFFFF,
+        };
+        let mut out = Vec::new();
+        out.extend(0_u32.to_le_bytes());
+        // Version 2.0
+        out.extend([2, 0]);
+        let heap_sizes = u8::from(w.wide_strings) | (u8::from(w.wide_blobs) << 2);
+        out.extend([heap_sizes, 1]);
+        let valid = rows
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .fold(0_u64, |valid, (table, _)| valid | (1 << table));
+        out.extend(valid.to_le_bytes());
+        out.extend(SORTED_TABLES.to_le_bytes());
+        for count in rows.iter().filter(|count| **count > 0) {
+            out.extend(count.to_le_bytes());
+        }
+        // Module
+        out.extend(0_u16.to_le_bytes());
+        w.string(&mut out, self.module_name);
+        // Mvid, EncId, EncBaseId. The GUID heap index is 4 bytes wide only for huge heaps.
+        out.extend([1, 0, 0, 0, 0, 0]);
+        for row in &self.type_refs {
+            w.coded(&mut out, CodedIndex::ResolutionScope, Some(row.scope));
+            w.string(&mut out, row.name);
+            w.string(&mut out, row.namespace);
+        }
+        for row in &self.type_defs {
+            out.extend(row.flags.to_le_bytes());
+            w.string(&mut out, row.name);
+            w.string(&mut out, row.namespace);
+            w.coded(&mut out, CodedIndex::TypeDefOrRef, row.extends);
+            w.index(&mut out, TABLE_FIELD, row.field_list);
+            w.index(&mut out, TABLE_METHOD_DEF, row.method_list);
+        }
+        for row in &self.fields {
+            out.extend(row.flags.to_le_bytes());
+            w.string(&mut out, row.name);
+            w.blob(&mut out, row.signature);
+        }
+        for row in &self.method_defs {
+            out.extend(row.rva.to_le_bytes());
+            // ImplFlags: IL, managed
+            out.extend(0_u16.to_le_bytes());
+            out.extend(row.flags.to_le_bytes());
+            w.string(&mut out, row.name);
+            w.blob(&mut out, row.signature);
+            // No `Param` rows are emmited.
+            w.index(&mut out, TABLE_PARAM, 1);
+        }
+        for row in &self.member_refs {
+            w.coded(&mut out, CodedIndex::MemberRefParent, Some(row.parent));
+            w.string(&mut out, row.name);
+            w.blob(&mut out, row.signature);
+        }
+        for (offset, field) in &self.field_layouts {
+            out.extend(offset.to_le_bytes());
+            w.index(&mut out, TABLE_FIELD, *field);
+        }
+        for signature in &self.stand_alone_sigs {
+            w.blob(&mut out, *signature);
+        }
+        for signature in &self.type_specs {
+            w.blob(&mut out, *signature);
+        }
+        // Assembly: SHA1 hash algorithm, version 0.0.0.0, no flags, no public key, no culture.
+        out.extend(0x8004_u32.to_le_bytes());
+        out.extend([0; 8]);
+        out.extend(0_u32.to_le_bytes());
+        w.blob(&mut out, 0);
+        w.string(&mut out, self.asm_name);
+        w.string(&mut out, 0);
+        for row in &self.assembly_refs {
+            let (v1, v2, v3, v4) = row.version;
+            for part in [v1, v2, v3, v4] {
+                out.extend(part.to_le_bytes());
+            }
+            out.extend(0_u32.to_le_bytes());
+            w.blob(&mut out, 0);
+            w.string(&mut out, row.name);
+            w.string(&mut out, 0);
+            w.blob(&mut out, 0);
+        }
+        for (nested, enclosing) in &self.nested_classes {
+            w.index(&mut out, TABLE_TYPE_DEF, *nested);
+            w.index(&mut out, TABLE_TYPE_DEF, *enclosing);
+        }
+        // The `GenericParam` table is sorted by owner, and the params of methods are added after the params of types.
+        let mut generic_params: Vec<_> = self.generic_params.iter().collect();
+        generic_params
+            .sort_by_key(|row| (CodedIndex::TypeOrMethodDef.encode(row.owner), row.number));
+        for row in generic_params {
+            out.extend(row.number.to_le_bytes());
+            out.extend(0_u16.to_le_bytes());
+            w.coded(&mut out, CodedIndex::TypeOrMethodDef, Some(row.owner));
+            w.string(&mut out, row.name);
+        }
+        for row in &self.method_specs {
+            w.coded(&mut out, CodedIndex::MethodDefOrRef, Some(row.method));
+            w.blob(&mut out, row.instantiation);
+        }
+        while out.len() % 4 != 0 {
+            out.push(0);
+        }
+        out
+    }
+    /// Lays out the whole PE image.
+    fn build_image(mut self, is_dll: bool) -> Result<Vec<u8>, AssemblyExportError> {
+        let bodies = self.encode_bodies(TEXT_RVA + CLI_HEADER_SIZE)?;
+        let metadata_rva = align(TEXT_RVA + CLI_HEADER_SIZE + bodies.len() as u32, 4);
+        let metadata = self.build_metadata();
+        let mut text = Vec::new();
+        // CLI header(II.25.3.3)
+        text.extend(CLI_HEADER_SIZE.to_le_bytes());
+        text.extend(2_u16.to_le_bytes());
+        text.extend(5_u16.to_le_bytes());
+        text.extend(metadata_rva.to_le_bytes());
+        text.extend((metadata.len() as u32).to_le_bytes());
+        // COMIMAGE_FLAGS_ILONLY
+        text.extend(1_u32.to_le_bytes());
+        text.extend(self.entrypoint.unwrap_or(0).to_le_bytes());
+        // Resources, StrongNameSignature, CodeManagerTable, VTableFixups, ExportAddressTableJumps, ManagedNativeHeader
+        text.extend([0; 48]);
+        text.extend(bodies);
+        while text.len() % 4 != 0 {
+            text.push(0);
+        }
+        text.extend(metadata);
+        Ok(pe_image(&text, is_dll))
+    }
+}
+/// Writes rows of metadata tables, using index sizes appropriate for the row counts and heap sizes.
+struct TableWriter {
+    rows: [u32; 64],
+    wide_strings: bool,
+    wide_blobs: bool,
+}
+impl TableWriter {
+    fn write(out: &mut Vec<u8>, value: u32, wide: bool) {
+        if wide {
+            out.extend(value.to_le_bytes());
+        } else {
+            out.extend((value as u16).to_le_bytes());
+        }
+    }
+    fn string(&self, out: &mut Vec<u8>, idx: u32) {
+        Self::write(out, idx, self.wide_strings);
+    }
+    fn blob(&self, out: &mut Vec<u8>, idx: u32) {
+        Self::write(out, idx, self.wide_blobs);
+    }
+    fn index(&self, out: &mut Vec<u8>, table: usize, row: u32) {
+        Self::write(out, row, self.rows[table] > 0xFFFF);
+    }
+    fn coded(&self, out: &mut Vec<u8>, kind: CodedIndex, target: Option<TableRef>) {
+        let max_rows = kind
+            .tables()
+            .iter()
+            .flatten()
+            .map(|table| self.rows[*table])
+            .max()
+            .unwrap_or(0);
+        let wide = max_rows >= (1 << (16 - kind.tag_bits()));
+        Self::write(out, target.map_or(0, |target| kind.encode(target)), wide);
+    }
+}
+fn is_system_string(tpe: &DotnetTypeRef) -> bool {
+    Some("System.Runtime") == tpe.asm() && "System.String" == tpe.name_path() && !tpe.is_valuetype()
+}
+fn align(value: u32, alignment: u32) -> u32 {
+    (value + alignment - 1) / alignment * alignment
+}
+/// Emmits the shortest form of a local or argument op. `macro_base` is the opcode of the `.0` form, if the op has one.
+fn short_or_long(code: &mut Vec<u8>, idx: u32, macro_base: Option<u8>, short: u8, long: u8) {
+    match (macro_base, u8::try_from(idx)) {
+        (Some(base), _) if idx < 4 => code.push(base + idx as u8),
+        (_, Ok(idx)) => code.extend([short, idx]),
+        (_, Err(_)) => {
+            code.extend([0xFE, long]);
+            code.extend((idx as u16).to_le_bytes());
+        }
+    }
+}
+fn ldc_i4(code: &mut Vec<u8>, value: i32) {
+    if (-1..=8).contains(&value) {
+        code.push((0x16 + value) as u8);
+    } else if let Ok(value) = i8::try_from(value) {
+        code.extend([0x1F, value as u8]);
+    } else {
+        code.push(0x20);
+        code.extend(value.to_le_bytes());
+    }
+}
+/// Calculates the maximum depth of the evaluation stack, following branches to labels.
+fn max_stack(ops: &[CILOp]) -> u16 {
+    let mut label_depths: HashMap<u32, isize> = HashMap::new();
+    let mut depth: Option<isize> = Some(0);
+    let mut max = 0;
+    for op in ops {
+        // Handlers are entered with an empty stack(or with just the exception, for catch handlers).
+        if let CILOp::BeginCatch(_) | CILOp::BeginFinally = op {
+            depth = Some(0);
+        }
+        if let CILOp::Label(id) = op {
+            depth = match (depth, label_depths.get(id)) {
+                (Some(depth), _) => Some(depth),
+                (None, Some(label_depth)) => Some(*label_depth),
+                // Only reachable by a backwards jump, which must arrive with an empty stack(III.1.7.5).
+                (None, None) => Some(0),
+            };
+        }
+        let Some(current) = depth else {
+            continue;
+        };
+        let current = (current + op.stack_diff()).max(0);
+        max = max.max(current);
+        depth = Some(current);
+        match op {
+            CILOp::GoTo(target) => {
+                label_depths.entry(*target).or_insert(current);
+                depth = None;
+            }
+            // `leave` empties the stack.
+            CILOp::Leave(target) => {
+                label_depths.entry(*target).or_insert(0);
+                depth = None;
+            }
+            CILOp::BEq(target)
+            | CILOp::BNe(target)
+            | CILOp::BLt(target)
+            | CILOp::BGe(target)
+            | CILOp::BLe(target)
+            | CILOp::BZero(target)
+            | CILOp::BTrue(target) => {
+                label_depths.entry(*target).or_insert(current);
+            }
+            CILOp::Ret | CILOp::Throw | CILOp::Rethrow | CILOp::EndFinally => depth = None,
+            _ => (),
+        }
+    }
+    max.min(u16::MAX as isize) as u16
+}
+/// Wraps the contents of the .text section in a PE32 image(II.25).
+fn pe_image(text: &[u8], is_dll: bool) -> Vec<u8> {
+    let text_raw_size = align(text.len() as u32, FILE_ALIGNMENT);
+    let image_size = align(TEXT_RVA + text.len() as u32, SECTION_ALIGNMENT);
+    let mut image = Vec::with_capacity((TEXT_FILE_OFFSET + text_raw_size) as usize);
+    // DOS header, with `e_lfanew` pointing right after it.
+    let mut dos = [0_u8; 0x80];
+    dos[0..2].copy_from_slice(b"MZ");
+    dos[0x3C..0x40].copy_from_slice(&0x80_u32.to_le_bytes());
+    image.extend(dos);
+    image.extend(b"PE\0\0");
+    // COFF header: i386, 1 section, no symbols.
+    image.extend(0x014C_u16.to_le_bytes());
+    image.extend(1_u16.to_le_bytes());
+    image.extend(0_u32.to_le_bytes());
+    image.extend(0_u32.to_le_bytes());
+    image.extend(0_u32.to_le_bytes());
+    image.extend(0xE0_u16.to_le_bytes());
+    // EXECUTABLE_IMAGE | 32BIT_MACHINE | (DLL)
+    let characteristics: u16 = 0x0102 | if is_dll { 0x2000 } else { 0 };
+    image.extend(characteristics.to_le_bytes());
+    // PE32 optional header
+    image.extend(0x010B_u16.to_le_bytes());
+    image.extend([8, 0]);
+    image.extend(text_raw_size.to_le_bytes());
+    image.extend(0_u32.to_le_bytes());
+    image.extend(0_u32.to_le_bytes());
+    // No native entry stub: the runtime uses the CLI header entrypoint.
+    image.extend(0_u32.to_le_bytes());
+    image.extend(TEXT_RVA.to_le_bytes());
+    image.extend(image_size.to_le_bytes());
+    image.extend(0x0040_0000_u32.to_le_bytes());
+    image.extend(SECTION_ALIGNMENT.to_le_bytes());
+    image.extend(FILE_ALIGNMENT.to_le_bytes());
+    // OS, image and subsystem versions
+    image.extend([4, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0]);
+    image.extend(0_u32.to_le_bytes());
+    image.extend(image_size.to_le_bytes());
+    image.extend(TEXT_FILE_OFFSET.to_le_bytes());
+    image.extend(0_u32.to_le_bytes());
+    // Console subsystem
+    image.extend(3_u16.to_le_bytes());
+    // DYNAMIC_BASE | NX_COMPAT | NO_SEH | TERMINAL_SERVER_AWARE
+    image.extend(0x8540_u16.to_le_bytes());
+    for size in [0x10_0000_u32, 0x1000, 0x10_0000, 0x1000] {
+        image.extend(size.to_le_bytes());
+    }
+    image.extend(0_u32.to_le_bytes());
+    image.extend(16_u32.to_le_bytes());
+    for directory in 0..16 {
+        // Only the CLI header directory is used.
+        if directory == 14 {
+            image.extend(TEXT_RVA.to_le_bytes());
+            image.extend(CLI_HEADER_SIZE.to_le_bytes());
+        } else {
+            image.extend([0; 8]);
+        }
+    }
+    // .text section header
+    image.extend(b".text\0\0\0");
+    image.extend((text.len() as u32).to_le_bytes());
+    image.extend(TEXT_RVA.to_le_bytes());
+    image.extend(text_raw_size.to_le_bytes());
+    image.extend(TEXT_FILE_OFFSET.to_le_bytes());
+    image.extend([0; 12]);
+    // CODE | EXECUTE | READ
+    image.extend(0x6000_0020_u32.to_le_bytes());
+    image.resize(TEXT_FILE_OFFSET as usize, 0);
+    image.extend(text);
+    image.resize((TEXT_FILE_OFFSET + text_raw_size) as usize, 0);
+    image
+}
+#[test]
+fn compressed_ints() {
+    // Examples from ECMA-335 II.23.2
+    for (value, encoded) in [
+        (0x03, &[0x03][..]),
+        (0x7F, &[0x7F]),
+        (0x80, &[0x80, 0x80]),
+        (0x2E57, &[0xAE, 0x57]),
+        (0x3FFF, &[0xBF, 0xFF]),
+        (0x4000, &[0xC0, 0x00, 0x40, 0x00]),
+        (0x1FFF_FFFF, &[0xDF, 0xFF, 0xFF, 0xFF]),
+    ] {
+        let mut out = Vec::new();
+        compress_u32(value, &mut out);
+        assert_eq!(encoded, &out[..], "compressing {value:#x}");
+    }
+}
+#[cfg(test)]
+/// Minimal reader of the images built by [`MetadataBuilder`], used to check that the tables and heaps can be parsed back.
+struct ImageReader<'image> {
+    image: &'image [u8],
+    /// File offsets and sizes of the metadata streams, by name.
+    streams: HashMap<String, (usize, usize)>,
+    /// File offset of the first row of each present table.
+    tables: HashMap<usize, usize>,
+    rows: [u32; 64],
+}
+#[cfg(test)]
+impl<'image> ImageReader<'image> {
+    fn u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes(self.image[offset..offset + 2].try_into().unwrap())
+    }
+    fn u32(&self, offset: usize) -> u32 {
+        u32::from_le_bytes(self.image[offset..offset + 4].try_into().unwrap())
+    }
+    fn file_offset(rva: u32) -> usize {
+        (rva - TEXT_RVA + TEXT_FILE_OFFSET) as usize
+    }
+    /// Size of a row of `table`, for images small enough to only use 2 byte heap and table indices.
+    fn row_size(table: usize) -> usize {
+        match table {
+            TABLE_MODULE => 10,
+            TABLE_TYPE_REF | TABLE_FIELD | TABLE_MEMBER_REF | TABLE_FIELD_LAYOUT => 6,
+            TABLE_TYPE_DEF | TABLE_METHOD_DEF => 14,
+            TABLE_STAND_ALONE_SIG | TABLE_TYPE_SPEC => 2,
+            TABLE_ASSEMBLY => 22,
+            TABLE_ASSEMBLY_REF => 20,
+            TABLE_NESTED_CLASS | TABLE_METHOD_SPEC => 4,
+            TABLE_GENERIC_PARAM => 8,
+            _ => panic!("Unexpected table {table:#x}"),
+        }
+    }
+    fn new(image: &'image [u8]) -> Self {
+        let mut reader = Self {
+            image,
+            streams: HashMap::new(),
+            tables: HashMap::new(),
+            rows: [0; 64],
+        };
+        assert_eq!(&image[..2], b"MZ");
+        let pe = reader.u32(0x3C) as usize;
+        assert_eq!(&image[pe..pe + 4], b"PE\0\0");
+        // The optional header follows the 20 byte COFF header, and its data directories start at its 96th byte.
+        let cli_header = Self::file_offset(reader.u32(pe + 24 + 96 + 14 * 8));
+        let metadata = Self::file_offset(reader.u32(cli_header + 8));
+        assert_eq!(&image[metadata..metadata + 4], b"BSJB");
+        let version_len = reader.u32(metadata + 12) as usize;
+        let stream_count = reader.u16(metadata + 16 + version_len + 2);
+        let mut header = metadata + 16 + version_len + 4;
+        for _ in 0..stream_count {
+            let offset = metadata + reader.u32(header) as usize;
+            let size = reader.u32(header + 4) as usize;
+            let name_len = image[header + 8..].iter().position(|b| *b == 0).unwrap();
+            let name =
+                String::from_utf8(image[header + 8..header + 8 + name_len].to_vec()).unwrap();
+            reader.streams.insert(name, (offset, size));
+            header += 8 + align(name_len as u32 + 1, 4) as usize;
+        }
+        let (tables, _) = reader.streams["#~"];
+        assert_eq!(image[tables + 6], 0, "heap indices must be 2 bytes wide");
+        let valid = u64::from_le_bytes(image[tables + 8..tables + 16].try_into().unwrap());
+        let mut offset = tables + 24;
+        for table in (0..64).filter(|table| valid & (1 << table) != 0) {
+            reader.rows[table] = reader.u32(offset);
+            offset += 4;
+        }
+        for table in (0..64).filter(|table| valid & (1 << table) != 0) {
+            reader.tables.insert(table, offset);
+            offset += Self::row_size(table) * reader.rows[table] as usize;
+        }
+        reader
+    }
+    /// File offset of row `row`(starting at 1) of `table`.
+    fn row(&self, table: usize, row: u32) -> usize {
+        assert!(
+            (1..=self.rows[table]).contains(&row),
+            "row {row} of table {table:#x} out of range"
+        );
+        self.tables[&table] + Self::row_size(table) * (row - 1) as usize
+    }
+    /// Reads the string at the 2 byte `#Strings` index at `offset`.
+    fn string(&self, offset: usize) -> &str {
+        let (heap, _) = self.streams["#Strings"];
+        let start = heap + self.u16(offset) as usize;
+        let len = self.image[start..].iter().position(|b| *b == 0).unwrap();
+        std::str::from_utf8(&self.image[start..start + len]).unwrap()
+    }
+}
+#[test]
+fn image_metadata() {
+    let console =
+        DotnetTypeRef::new(Some("System.Console"), "System.Console").with_valuetype(false);
+    let mut main = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::Void),
+        "main",
+        vec![],
+    );
+    main.set_ops(vec![
+        CILOp::LdcI32(5),
+        CILOp::Call(CallSite::boxed(
+            Some(console),
+            "WriteLine".into(),
+            FnSig::new(&[Type::I32], &Type::Void),
+            true,
+        )),
+        CILOp::Ret,
+    ]);
+    let point = TypeDef::new(
+        AccessModifer::Public,
+        "Geometry.Point".into(),
+        vec![],
+        vec![("x".into(), Type::F32), ("y".into(), Type::F32)],
+        vec![],
+        Some(vec![0, 4]),
+        0,
+        None,
+    );
+    let mut builder = MetadataBuilder::new(
+        "test",
+        "test.dll",
+        &[("System.Runtime".into(), (6, 12, 0, 0))],
+        CodegenConfig::default(),
+    );
+    builder.add_definitions(vec![], vec![main], vec![point]);
+    let image = builder.build_image(true).unwrap();
+    let reader = ImageReader::new(&image);
+    for stream in ["#~", "#Strings", "#US", "#GUID", "#Blob"] {
+        assert!(
+            reader.streams.contains_key(stream),
+            "missing stream {stream}"
+        );
+    }
+    let module = reader.row(TABLE_MODULE, 1);
+    assert_eq!(reader.string(module + 2), "test.dll");
+    let assembly = reader.row(TABLE_ASSEMBLY, 1);
+    assert_eq!(reader.string(assembly + 18), "test");
+    // `<Module>`, followed by the defined type.
+    assert_eq!(reader.rows[TABLE_TYPE_DEF], 2);
+    let module_type = reader.row(TABLE_TYPE_DEF, 1);
+    assert_eq!(reader.string(module_type + 4), "<Module>");
+    let point = reader.row(TABLE_TYPE_DEF, 2);
+    assert_eq!(reader.string(point + 4), "Point");
+    assert_eq!(reader.string(point + 6), "Geometry");
+    assert_eq!(reader.rows[TABLE_FIELD], 2);
+    assert_eq!(reader.string(reader.row(TABLE_FIELD, 2) + 2), "y");
+    assert_eq!(reader.rows[TABLE_FIELD_LAYOUT], 2);
+    assert_eq!(reader.u32(reader.row(TABLE_FIELD_LAYOUT, 2)), 4);
+    // Both the explicitly referenced assembly and `System.Console` use the version of the framework.
+    let mut refs: Vec<_> = (1..=reader.rows[TABLE_ASSEMBLY_REF])
+        .map(|row| {
+            let row = reader.row(TABLE_ASSEMBLY_REF, row);
+            let version: Vec<_> = (0..4).map(|part| reader.u16(row + part * 2)).collect();
+            (reader.string(row + 14).to_owned(), version)
+        })
+        .collect();
+    refs.sort();
+    assert_eq!(
+        refs,
+        [
+            ("System.Console".to_owned(), vec![6, 12, 0, 0]),
+            ("System.Runtime".to_owned(), vec![6, 12, 0, 0])
+        ]
+    );
+    let main = reader.row(TABLE_METHOD_DEF, 1);
+    assert_eq!(reader.string(main + 8), "main");
+    // `ldc.i4.5`, `call <token>`, `ret`, with a tiny header.
+    let body = ImageReader::file_offset(reader.u32(main));
+    assert_eq!(image[body], (7 << 2) | 0x2);
+    assert_eq!(image[body + 1], 0x1B);
+    assert_eq!(image[body + 2], 0x28);
+    assert_eq!(reader.u32(body + 3) >> 24, TABLE_MEMBER_REF as u32);
+    assert_eq!(image[body + 7], 0x2A);
+}
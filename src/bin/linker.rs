@@ -96,9 +96,10 @@ fn autopatch(asm: &mut Assembly) {
             patched.insert(call.clone(), patch_missing_method(call));
         }
     }
-    patched
-        .values()
-        .for_each(|method| asm.add_method(method.clone()));
+    patched.values().for_each(|method| {
+        asm.add_failure(report::Failure::missing_method(method.name()));
+        asm.add_method(method.clone());
+    });
 }
 fn add_mandatory_statics(asm: &mut Assembly) {
     asm.add_static(Type::U8, "__rust_alloc_error_handler_should_panic");
@@ -108,6 +109,9 @@ fn add_mandatory_statics(asm: &mut Assembly) {
 fn main() {
     use std::io::Read;
     let args: Vec<String> = env::args().collect();
+    // The linker does not receive the backend options, so it can only be configured using environment variables.
+    let config = config::CodegenConfig::from_env()
+        .unwrap_or_else(|err| panic!("Invalid codegen options: {err}"));
     let args = &args[1..];
     let to_link: Vec<_> = args.iter().filter(|arg| arg.contains(".bc")).collect();
     let ar_to_link: Vec<_> = args.iter().filter(|arg| arg.contains(".rlib")).collect();
@@ -135,19 +139,73 @@ fn main() {
     }
     //final_assembly.add_array_types();
     //
-    if !rustc_codegen_clr::ABORT_ON_ERROR {
+    if config.software_128_bit_ints {
+        int128::use_software_int128(&mut final_assembly);
+    }
+    if !config.abort_on_error {
         autopatch(&mut final_assembly);
     }
 
     use rustc_codegen_clr::assembly_exporter::AssemblyExporter;
     let path = output;
     let is_lib = output.contains(".dll") || output.contains(".so") || output.contains(".o");
+    // Calls to methods of other crates(eg. small `core` helpers) can only be inlined once all the crates are joined.
+    if config.optimize_cil && config.opt_passes.contains(opt::Pass::InlineCalls) {
+        let inlined = final_assembly.inline_calls(is_lib);
+        if config.print_opt_stats {
+            eprintln!("Inlined {inlined} calls at link time.");
+        }
+    }
+    if config.remove_dead_code {
+        let removed = dead_code::eliminate_dead_code(&mut final_assembly, is_lib);
+        if config.print_opt_stats {
+            eprintln!(
+                "Removed {methods} methods, {types} types and {statics} statics which were never used.",
+                methods = removed.methods,
+                types = removed.types,
+                statics = removed.statics
+            );
+        }
+    }
+    // The static constructor is added last, so that it only initializes the statics which were not removed.
+    final_assembly.add_static_ctor();
     add_mandatory_statics(&mut final_assembly);
-    rustc_codegen_clr::assembly_exporter::ilasm_exporter::ILASMExporter::export_assembly(
-        &final_assembly,
-        path.as_ref(),
-        is_lib,
-    )
-    .expect("Assembly export faliure!");
+    if is_lib && config.csharp_bindings {
+        let bindings_path = std::path::Path::new(output).with_extension("cs");
+        std::fs::write(
+            &bindings_path,
+            assembly_exporter::csharp_bindings::csharp_bindings(&final_assembly),
+        )
+        .expect("Could not save the C# bindings!");
+    }
+    // Lists the failures of all the linked crates, and the methods replaced by `autopatch`.
+    final_assembly
+        .report()
+        .save(output, format!("{output}.{REPORT_EXTENSION}").as_ref());
+    // Saves the final assembly, so it can be run by the interpreter.
+    if config.save_linked_assembly {
+        std::fs::write(
+            format!("{output}.{LINKED_ASSEMBLY_EXTENSION}"),
+            postcard::to_stdvec(&final_assembly).expect("Could not serialize the linked assembly!"),
+        )
+        .expect("Could not save the linked assembly!");
+    }
+    if config.native_pe_exporter {
+        rustc_codegen_clr::assembly_exporter::pe_exporter::PEExporter::export_assembly(
+            &final_assembly,
+            path.as_ref(),
+            is_lib,
+            &config,
+        )
+        .expect("Assembly export faliure!");
+    } else {
+        rustc_codegen_clr::assembly_exporter::ilasm_exporter::ILASMExporter::export_assembly(
+            &final_assembly,
+            path.as_ref(),
+            is_lib,
+            &config,
+        )
+        .expect("Assembly export faliure!");
+    }
     //todo!()
 }
@@ -1,9 +1,9 @@
 use rustc_middle::mir::{BinOp, Operand};
 use rustc_middle::ty::{Instance, IntTy, Ty, TyCtxt, TyKind, UintTy};
 
-use crate::cil::{CILOp, CallSite};
-use crate::function_sig::FnSig;
-use crate::r#type::{DotnetTypeRef, TyCache};
+use crate::cil::CILOp;
+use crate::int128::int128_type;
+use crate::r#type::{TyCache, Type};
 /// Preforms an unchecked binary operation.
 pub(crate) fn binop_unchecked<'tyctx>(
     binop: BinOp,
@@ -17,24 +17,18 @@ pub(crate) fn binop_unchecked<'tyctx>(
     let ops_a = crate::operand::handle_operand(operand_a, tyctx, method, method_instance, tycache);
     let ops_b = crate::operand::handle_operand(operand_b, tyctx, method, method_instance, tycache);
     let ty_a = operand_a.ty(&method.local_decls, tyctx);
+    let ty_a = crate::utilis::monomorphize(&method_instance, ty_a, tyctx);
     let ty_b = operand_b.ty(&method.local_decls, tyctx);
+    let ty_b = crate::utilis::monomorphize(&method_instance, ty_b, tyctx);
     match binop {
-        BinOp::Add | BinOp::AddUnchecked => [
-            ops_a,
-            ops_b,
-            add_unchecked(ty_a, ty_b, tyctx, &method_instance, tycache),
-        ]
-        .into_iter()
-        .flatten()
-        .collect(),
-        BinOp::Sub | BinOp::SubUnchecked => [
-            ops_a,
-            ops_b,
-            sub_unchecked(ty_a, ty_b, tyctx, &method_instance, tycache),
-        ]
-        .into_iter()
-        .flatten()
-        .collect(),
+        BinOp::Add | BinOp::AddUnchecked => [ops_a, ops_b, add_unchecked(ty_a, ty_b)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        BinOp::Sub | BinOp::SubUnchecked => [ops_a, ops_b, sub_unchecked(ty_a, ty_b)]
+            .into_iter()
+            .flatten()
+            .collect(),
         BinOp::Ne => [ops_a, ops_b, ne_unchecked(ty_a, ty_b)]
             .into_iter()
             .flatten()
@@ -122,44 +116,18 @@ pub(crate) fn binop_unchecked<'tyctx>(
     }
 }
 /// Preforms unchecked addition
-fn add_unchecked<'tyctx>(
-    ty_a: Ty<'tyctx>,
-    ty_b: Ty<'tyctx>,
-    tyctx: TyCtxt<'tyctx>,
-    method_instance: &Instance<'tyctx>,
-    tycache: &mut TyCache,
-) -> Vec<CILOp> {
+fn add_unchecked<'tyctx>(ty_a: Ty<'tyctx>, ty_b: Ty<'tyctx>) -> Vec<CILOp> {
     match ty_a.kind() {
         TyKind::Int(int_ty) => {
             if let IntTy::I128 = int_ty {
-                let ty_a = tycache.type_from_cache(ty_a, tyctx, Some(*method_instance));
-                let ty_b = tycache.type_from_cache(ty_b, tyctx, Some(*method_instance));
-                vec![CILOp::Call(
-                    CallSite::new(
-                        Some(DotnetTypeRef::int_128()),
-                        "op_Addition".into(),
-                        FnSig::new(&[ty_a.clone(), ty_b], &ty_a),
-                        true,
-                    )
-                    .into(),
-                )]
+                vec![crate::int128::binop(&Type::I128, "op_Addition")]
             } else {
                 vec![CILOp::Add]
             }
         }
         TyKind::Uint(uint_ty) => {
             if let UintTy::U128 = uint_ty {
-                let ty_a = tycache.type_from_cache(ty_a, tyctx, Some(*method_instance));
-                let ty_b = tycache.type_from_cache(ty_b, tyctx, Some(*method_instance));
-                vec![CILOp::Call(
-                    CallSite::new(
-                        Some(DotnetTypeRef::uint_128()),
-                        "op_Addition".into(),
-                        FnSig::new(&[ty_a.clone(), ty_b], &ty_a),
-                        true,
-                    )
-                    .into(),
-                )]
+                vec![crate::int128::binop(&Type::U128, "op_Addition")]
             } else {
                 match uint_ty {
                     UintTy::U8 => vec![CILOp::Add, CILOp::ConvU8(false)],
@@ -175,44 +143,18 @@ fn add_unchecked<'tyctx>(
     }
 }
 /// Preforms unchecked subtraction
-fn sub_unchecked<'tyctx>(
-    ty_a: Ty<'tyctx>,
-    ty_b: Ty<'tyctx>,
-    tyctx: TyCtxt<'tyctx>,
-    method_instance: &Instance<'tyctx>,
-    tycache: &mut TyCache,
-) -> Vec<CILOp> {
+fn sub_unchecked<'tyctx>(ty_a: Ty<'tyctx>, ty_b: Ty<'tyctx>) -> Vec<CILOp> {
     match ty_a.kind() {
         TyKind::Int(int_ty) => {
             if let IntTy::I128 = int_ty {
-                let ty_a = tycache.type_from_cache(ty_a, tyctx, Some(*method_instance));
-                let ty_b = tycache.type_from_cache(ty_b, tyctx, Some(*method_instance));
-                vec![CILOp::Call(
-                    CallSite::new(
-                        Some(DotnetTypeRef::int_128()),
-                        "op_Subtraction".into(),
-                        FnSig::new(&[ty_a.clone(), ty_b], &ty_a),
-                        true,
-                    )
-                    .into(),
-                )]
+                vec![crate::int128::binop(&Type::I128, "op_Subtraction")]
             } else {
                 vec![CILOp::Sub]
             }
         }
         TyKind::Uint(uint_ty) => {
             if let UintTy::U128 = uint_ty {
-                let ty_a = tycache.type_from_cache(ty_a, tyctx, Some(*method_instance));
-                let ty_b = tycache.type_from_cache(ty_b, tyctx, Some(*method_instance));
-                vec![CILOp::Call(
-                    CallSite::new(
-                        Some(DotnetTypeRef::uint_128()),
-                        "op_Subtraction".into(),
-                        FnSig::new(&[ty_a.clone(), ty_b], &ty_a),
-                        true,
-                    )
-                    .into(),
-                )]
+                vec![crate::int128::binop(&Type::U128, "op_Subtraction")]
             } else {
                 vec![CILOp::Sub]
             }
@@ -221,39 +163,90 @@ fn sub_unchecked<'tyctx>(
         _ => todo!("can't add numbers of types {ty_a} and {ty_b}"),
     }
 }
-fn ne_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Eq, CILOp::LdcI32(0), CILOp::Eq]
+fn ne_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::cmp(&tpe, "op_Inequality")],
+        None => vec![CILOp::Eq, CILOp::LdcI32(0), CILOp::Eq],
+    }
 }
-fn eq_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Eq]
+fn eq_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::cmp(&tpe, "op_Equality")],
+        None => vec![CILOp::Eq],
+    }
 }
-fn lt_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Lt]
+fn lt_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::cmp(&tpe, "op_LessThan")],
+        None => vec![CILOp::Lt],
+    }
 }
-fn gt_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Gt]
+fn gt_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::cmp(&tpe, "op_GreaterThan")],
+        None => vec![CILOp::Gt],
+    }
 }
-fn bit_and_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::And]
+fn bit_and_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::binop(&tpe, "op_BitwiseAnd")],
+        None => vec![CILOp::And],
+    }
 }
-fn bit_or_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Or]
+fn bit_or_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::binop(&tpe, "op_BitwiseOr")],
+        None => vec![CILOp::Or],
+    }
 }
-fn bit_xor_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::XOr]
+fn bit_xor_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::binop(&tpe, "op_ExclusiveOr")],
+        None => vec![CILOp::XOr],
+    }
 }
-fn rem_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Rem]
+fn rem_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::binop(&tpe, "op_Modulus")],
+        None => vec![CILOp::Rem],
+    }
 }
-fn shr_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Shr]
+/// Converts the shift amount of type `ty_b` on top of the stack into the `i32` taken by the shift operators of 128 bit intigers.
+fn shift_amount<'tyctx>(ty_b: Ty<'tyctx>) -> CILOp {
+    match int128_type(ty_b) {
+        Some(tpe) => crate::int128::operator(&tpe, "op_Explicit", &[tpe.clone()], &Type::I32),
+        None => CILOp::ConvI32(false),
+    }
 }
-fn shl_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Shl]
+fn shr_unchecked<'tyctx>(ty_a: Ty<'tyctx>, ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match (int128_type(ty_a), int128_type(ty_b)) {
+        (Some(tpe), _) => vec![
+            shift_amount(ty_b),
+            crate::int128::shift(&tpe, "op_RightShift"),
+        ],
+        (None, Some(_)) => vec![shift_amount(ty_b), CILOp::Shr],
+        (None, None) => vec![CILOp::Shr],
+    }
 }
-fn mul_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Mul]
+fn shl_unchecked<'tyctx>(ty_a: Ty<'tyctx>, ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match (int128_type(ty_a), int128_type(ty_b)) {
+        (Some(tpe), _) => vec![
+            shift_amount(ty_b),
+            crate::int128::shift(&tpe, "op_LeftShift"),
+        ],
+        (None, Some(_)) => vec![shift_amount(ty_b), CILOp::Shl],
+        (None, None) => vec![CILOp::Shl],
+    }
+}
+fn mul_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::binop(&tpe, "op_Multiply")],
+        None => vec![CILOp::Mul],
+    }
 }
-fn div_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Div]
+fn div_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    match int128_type(ty_a) {
+        Some(tpe) => vec![crate::int128::binop(&tpe, "op_Division")],
+        None => vec![CILOp::Div],
+    }
 }
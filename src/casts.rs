@@ -1,89 +1,41 @@
-use crate::{
-    cil::{CILOp, CallSite},
-    function_sig::FnSig,
-    r#type::{DotnetTypeRef, Type},
-};
+use crate::{cil::CILOp, r#type::Type};
 /// Casts from intiger type `src` to target `target`
 pub fn int_to_int(src: Type, target: Type) -> Vec<CILOp> {
     match (&src, &target) {
-        (_, Type::I128) => {
-            vec![CILOp::Call(
-                CallSite::new(
-                    Some(DotnetTypeRef::int_128()),
-                    "op_Implicit".into(),
-                    FnSig::new(&[src], &target),
-                    true,
-                )
-                .into(),
-            )]
-        }
-        (_, Type::U128) => {
-            vec![CILOp::Call(
-                CallSite::new(
-                    Some(DotnetTypeRef::uint_128()),
-                    "op_Implicit".into(),
-                    FnSig::new(&[src], &target),
-                    true,
-                )
-                .into(),
-            )]
-        }
-        (Type::I128, _) => {
-            vec![CILOp::Call(
-                CallSite::new(
-                    Some(DotnetTypeRef::int_128()),
-                    "op_Explicit".into(),
-                    FnSig::new(&[src], &target),
-                    true,
-                )
-                .into(),
-            )]
-        }
-        (Type::U128, _) => {
-            vec![CILOp::Call(
-                CallSite::new(
-                    Some(DotnetTypeRef::uint_128()),
-                    "op_Explicit".into(),
-                    FnSig::new(&[src], &target),
-                    true,
-                )
-                .into(),
-            )]
-        }
-        //todo!("Casting to 128 bit intiegers is not supported!"),
+        (Type::I128 | Type::U128, _) | (_, Type::I128 | Type::U128) => int128_cast(src, target),
         _ => to_int(target),
     }
 }
 /// Returns CIL ops required to convert type src to target
 pub fn float_to_int(src: Type, target: Type) -> Vec<CILOp> {
     match target {
-        Type::I128 => {
-            vec![CILOp::Call(
-                CallSite::new(
-                    Some(DotnetTypeRef::int_128()),
-                    "op_Explicit".into(),
-                    FnSig::new(&[src], &target),
-                    true,
-                )
-                .into(),
-            )]
-        }
-        Type::U128 => {
-            vec![CILOp::Call(
-                CallSite::new(
-                    Some(DotnetTypeRef::uint_128()),
-                    "op_Explicit".into(),
-                    FnSig::new(&[src], &target),
-                    true,
-                )
-                .into(),
-            )]
-        } //todo!("Casting to 128 bit intiegers is not supported!"),
+        Type::I128 | Type::U128 => int128_cast(src, target),
         _ => to_int(target),
     }
-
-    //call uint64 [System.Runtime]System.Int128::op_Explicit(valuetype [System.Runtime]System.Int128)
-    //
+}
+/// Returns CIL ops converting between a 128 bit intiger and another numeric type `target`, using the conversion operators of `System.Int128` and `System.UInt128`.
+fn int128_cast(src: Type, target: Type) -> Vec<CILOp> {
+    // `bool`s are just small intigers.
+    let src = if src == Type::Bool { Type::U8 } else { src };
+    if src == target {
+        return vec![];
+    }
+    // Conversions from a 128 bit intiger are defined by the type converted from.
+    if matches!(src, Type::I128 | Type::U128) {
+        return vec![crate::int128::operator(
+            &src,
+            "op_Explicit",
+            &[src.clone()],
+            &target,
+        )];
+    }
+    // Unsigned intigers convert implicitly to both 128 bit types, signed ones only to `System.Int128`.
+    let name = match (&src, &target) {
+        (Type::U8 | Type::U16 | Type::U32 | Type::U64 | Type::USize, _)
+        | (Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::ISize, Type::I128) => "op_Implicit",
+        _ => "op_Explicit",
+    };
+    vec![crate::int128::operator(&target, name, &[src], &target)]
 }
 /// Returns CIL ops required to convert to intiger of type `target`
 pub fn to_int(target: Type) -> Vec<CILOp> {
@@ -104,15 +56,17 @@ pub fn to_int(target: Type) -> Vec<CILOp> {
 /// Returns CIL ops required to casts from intiger type `src` to `target`
 pub fn int_to_float(src: Type, target: Type) -> Vec<CILOp> {
     if matches!(src, Type::I128 | Type::U128) {
-        todo!("Casting from 128 bit intiegers is not supported!")
+        return int128_cast(src, target);
     }
-    if matches!(target, Type::I128 | Type::U128) {
-        todo!("Casting to 128 bit intiegers is not supported!")
-    } else {
-        match target {
-            Type::F32 => vec![CILOp::ConvF32(false)],
-            Type::F64 => vec![CILOp::ConvF64(false)],
-            _ => todo!("Can't cast to {target:?} yet!"),
-        }
+    let is_unsigned = matches!(
+        src,
+        Type::U8 | Type::U16 | Type::U32 | Type::U64 | Type::USize
+    );
+    match (target, is_unsigned) {
+        (Type::F32, false) => vec![CILOp::ConvF32(false)],
+        (Type::F64, false) => vec![CILOp::ConvF64(false)],
+        (Type::F32, true) => vec![CILOp::ConvFUn, CILOp::ConvF32(false)],
+        (Type::F64, true) => vec![CILOp::ConvFUn, CILOp::ConvF64(false)],
+        (target, _) => todo!("Can't cast to {target:?} yet!"),
     }
 }
@@ -102,7 +102,7 @@ fn mul(tpe: Type) -> Vec<CILOp> {
             CILOp::LdcI64(u64::MAX as i64),
             CILOp::Call(CallSite::boxed(
                 Some(DotnetTypeRef::uint_128()),
-                "op_Multiplication".into(),
+                "op_Multiply".into(),
                 crate::function_sig::FnSig::new(&[Type::U128, Type::U128], &Type::U128),
                 true,
             )),
@@ -126,81 +126,12 @@ fn mul(tpe: Type) -> Vec<CILOp> {
             CILOp::LdcI64(i64::MIN),
             CILOp::Call(CallSite::boxed(
                 Some(DotnetTypeRef::int_128()),
-                "op_Multiplication".into(),
+                "op_Multiply".into(),
                 crate::function_sig::FnSig::new(&[Type::I128, Type::I128], &Type::I128),
                 true,
             )),
         ),
-        Type::I128 => {
-            eprintln!("WARING: checked 128 bit arthmetics ARE NOT SUPPOPRTED YET. Using unchecked variants, bugs may occur.");
-            let tuple = crate::r#type::simple_tuple(&[tpe.clone(), Type::Bool]);
-            let tuple_ty: Type = tuple.clone().into();
-            vec![
-                CILOp::Call(
-                    CallSite::new(
-                        Some(DotnetTypeRef::int_128()),
-                        "op_Multiplication".into(),
-                        crate::function_sig::FnSig::new(&[Type::I128, Type::I128], &Type::I128),
-                        true,
-                    )
-                    .into(),
-                ),
-                CILOp::NewTMPLocal(tpe.clone().into()),
-                CILOp::SetTMPLocal,
-                CILOp::NewTMPLocal(tuple_ty.into()),
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LoadUnderTMPLocal(1),
-                CILOp::STField(FieldDescriptor::boxed(
-                    tuple.clone(),
-                    Type::GenericArg(0),
-                    "Item1".into(),
-                )),
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LdcI32(0),
-                CILOp::STField(FieldDescriptor::boxed(
-                    tuple.clone(),
-                    Type::GenericArg(1),
-                    "Item2".into(),
-                )),
-                CILOp::LoadTMPLocal,
-                CILOp::FreeTMPLocal,
-            ]
-        }
-        Type::U128 => {
-            eprintln!("WARING: checked 128 bit arthmetics ARE NOT SUPPOPRTED YET. Using unchecked variants, bugs may occur.");
-            let tuple = crate::r#type::simple_tuple(&[tpe.clone(), Type::Bool]);
-            let tuple_ty: Type = tuple.clone().into();
-            vec![
-                CILOp::Call(
-                    CallSite::new(
-                        Some(DotnetTypeRef::uint_128()),
-                        "op_Multiplication".into(),
-                        crate::function_sig::FnSig::new(&[Type::U128, Type::U128], &Type::U128),
-                        true,
-                    )
-                    .into(),
-                ),
-                CILOp::NewTMPLocal(tpe.clone().into()),
-                CILOp::SetTMPLocal,
-                CILOp::NewTMPLocal(tuple_ty.into()),
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LoadUnderTMPLocal(1),
-                CILOp::STField(FieldDescriptor::boxed(
-                    tuple.clone(),
-                    Type::GenericArg(0),
-                    "Item1".into(),
-                )),
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LdcI32(0),
-                CILOp::STField(FieldDescriptor::boxed(
-                    tuple.clone(),
-                    Type::GenericArg(1),
-                    "Item2".into(),
-                )),
-                CILOp::LoadTMPLocal,
-                CILOp::FreeTMPLocal,
-            ]
-        }
+        Type::I128 | Type::U128 => crate::int128::checked(tpe, BinOp::Mul),
         Type::USize => promoted_ubinop(
             Type::USize,
             Type::U128,
@@ -224,7 +155,7 @@ fn mul(tpe: Type) -> Vec<CILOp> {
             )),
             CILOp::Call(CallSite::boxed(
                 Some(DotnetTypeRef::uint_128()),
-                "op_Multiplication".into(),
+                "op_Multiply".into(),
                 crate::function_sig::FnSig::new(&[Type::U128, Type::U128], &Type::U128),
                 true,
             )),
@@ -259,7 +190,7 @@ fn mul(tpe: Type) -> Vec<CILOp> {
             )),
             CILOp::Call(CallSite::boxed(
                 Some(DotnetTypeRef::int_128()),
-                "op_Multiplication".into(),
+                "op_Multiply".into(),
                 crate::function_sig::FnSig::new(&[Type::I128, Type::I128], &Type::I128),
                 true,
             )),
@@ -325,25 +256,7 @@ fn add(tpe: Type) -> Vec<CILOp> {
             )),
         ),
         Type::U64 => checked_uadd_type(Type::U64, CILOp::Nop, CILOp::Add),
-        Type::U128 => checked_uadd_type(
-            Type::U64,
-            CILOp::Nop,
-            CILOp::Call(
-                CallSite::new(
-                    Some(DotnetTypeRef::uint_128()),
-                    "op_Addition".into(),
-                    crate::function_sig::FnSig::new(
-                        &[
-                            DotnetTypeRef::uint_128().into(),
-                            DotnetTypeRef::uint_128().into(),
-                        ],
-                        &DotnetTypeRef::uint_128().into(),
-                    ),
-                    true,
-                )
-                .into(),
-            ),
-        ),
+        Type::I128 | Type::U128 => crate::int128::checked(tpe, BinOp::Add),
         Type::USize => promoted_ubinop(
             Type::USize,
             Type::U128,
@@ -674,6 +587,7 @@ fn sub(tpe: Type) -> Vec<CILOp> {
                 true,
             )),
         ),
+        Type::I128 | Type::U128 => crate::int128::checked(tpe, BinOp::Sub),
         _ => todo!("Can't preform checked sub on type {tpe:?} yet!"),
     }
 }
@@ -727,6 +641,7 @@ pub fn promoted_ubinop(
 ) -> Vec<CILOp> {
     let tuple = crate::r#type::simple_tuple(&[tpe.clone(), Type::Bool]);
     let tuple_ty: Type = tuple.clone().into();
+    let (gt, _) = promoted_cmp(&promoted_type);
     vec![
         // Promote arguments
         CILOp::NewTMPLocal(tpe.clone().into()),
@@ -743,7 +658,7 @@ pub fn promoted_ubinop(
         CILOp::LoadTMPLocal,
         omask.clone(),
         promote.clone(),
-        CILOp::Gt,
+        gt,
         // Save the bollean indicating overflow
         CILOp::NewTMPLocal(Type::Bool.into()),
         CILOp::SetTMPLocal,
@@ -786,6 +701,7 @@ pub fn promoted_sbinop(
 ) -> Vec<CILOp> {
     let tuple = crate::r#type::simple_tuple(&[tpe.clone(), Type::Bool]);
     let tuple_ty: Type = tuple.clone().into();
+    let (gt, lt) = promoted_cmp(&promoted_type);
     vec![
         // Promote arguments
         CILOp::NewTMPLocal(tpe.clone().into()),
@@ -802,12 +718,12 @@ pub fn promoted_sbinop(
         CILOp::LoadTMPLocal,
         omask.clone(),
         promote.clone(),
-        CILOp::Gt,
+        gt,
         CILOp::LoadTMPLocal,
         // Compare the result to the undeflow mask
         umask.clone(),
         promote.clone(),
-        CILOp::Lt,
+        lt,
         CILOp::Or,
         // Save the bollean indicating overflow
         CILOp::NewTMPLocal(Type::Bool.into()),
@@ -840,3 +756,13 @@ pub fn promoted_sbinop(
         CILOp::FreeTMPLocal,
     ]
 }
+/// Returns the ops comparing 2 values of the promoted type(greater than, less than).
+fn promoted_cmp(promoted_type: &Type) -> (CILOp, CILOp) {
+    match promoted_type {
+        Type::I128 | Type::U128 => (
+            crate::int128::cmp(promoted_type, "op_GreaterThan"),
+            crate::int128::cmp(promoted_type, "op_LessThan"),
+        ),
+        _ => (CILOp::Gt, CILOp::Lt),
+    }
+}
@@ -2,10 +2,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     function_sig::FnSig,
-    r#type::{DotnetTypeRef, Type},
+    r#type::{DotnetTypeRef, TyCache, Type},
     IString,
 };
-use rustc_middle::ty::TyCtxt;
+use rustc_middle::ty::{Instance, TyCtxt};
 /// Represenation of a target of a call.
 #[derive(Clone, PartialEq, Serialize, Deserialize, Eq, Hash, Debug)]
 pub struct CallSite {
@@ -13,6 +13,7 @@ pub struct CallSite {
     name: IString,
     signature: FnSig,
     is_static: bool,
+    generics: Vec<Type>,
 }
 impl CallSite {
     /// Constructs a new call site targeting method `name`, with signature `signature` and bleonging to class `class`. If `class` is [`None`], then the `<Module>` class
@@ -28,6 +29,7 @@ impl CallSite {
             name,
             signature,
             is_static,
+            generics: Vec::new(),
         }
     }
     /// The same as [`Self::new`], but boxes the result.
@@ -39,6 +41,20 @@ impl CallSite {
     ) -> Box<Self> {
         Box::new(Self::new(class, name, signature, is_static))
     }
+    /// Returns the call site refering to the function behind `instance`.
+    pub fn from_instance<'tcx>(
+        instance: Instance<'tcx>,
+        tyctx: TyCtxt<'tcx>,
+        tycache: &mut TyCache,
+    ) -> Self {
+        if let Some(generic) = crate::generics::preserved_instance(instance, tyctx, tycache) {
+            return crate::generics::generic_call_site(instance, generic, tyctx, tycache);
+        }
+        let signature = FnSig::sig_from_instance_(instance, tyctx, tycache)
+            .expect("Could not resolve function sig");
+        let function_name = crate::utilis::function_name(tyctx.symbol_name(instance));
+        Self::new(None, function_name, signature, true)
+    }
     /// Returns the signature of the function this call site targets.
     pub fn signature(&self) -> &FnSig {
         &self.signature
@@ -52,6 +68,24 @@ impl CallSite {
             true,
         )
     }
+    /// Returns the generic arguments the targeted method is instantiated with.
+    pub fn generics(&self) -> &[Type] {
+        &self.generics
+    }
+    /// Sets the generic arguments the targeted method is instantiated with.
+    pub fn set_generics(&mut self, generics: impl Into<Vec<Type>>) {
+        self.generics = generics.into();
+    }
+    /// Replaces the class, signature and generic arguments of the targeted method, as specified by `map`. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        if let Some(class) = &mut self.class {
+            class.map_types(map);
+        }
+        self.signature.map_types(map);
+        for generic in &mut self.generics {
+            generic.map_types(map);
+        }
+    }
     /// Returns the class the targeted method belongs to.
     pub fn class(&self) -> Option<&DotnetTypeRef> {
         self.class.as_ref()
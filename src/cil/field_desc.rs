@@ -33,4 +33,9 @@ impl FieldDescriptor {
     pub fn boxed(owner: DotnetTypeRef, tpe: Type, name: IString) -> Box<Self> {
         Box::new(Self { owner, tpe, name })
     }
+    /// Replaces the owner and type of the field, as specified by `map`. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        self.owner.map_types(map);
+        self.tpe.map_types(map);
+    }
 }
@@ -56,12 +56,30 @@ pub enum CILOp {
     Call(Box<CallSite>),
     /// Call the virtual method behind `call_site`.`
     CallVirt(Box<CallSite>),
+    /// Call the function pointer on top of the stack, which points to a function with signature `FnSig`.
+    CallI(Box<FnSig>),
+    /// Load a pointer to the method behind `call_site` on top of the stack.
+    LDFtn(Box<CallSite>),
     /// Throw the top value on the stack as an exception
     Throw,
     /// Rethrow the current exception
     Rethrow,
     /// Return the top value on the stack from this function
     Ret,
+    /// Begins a protected (`.try`) region. The evaluation stack must be empty.
+    BeginTry,
+    /// Ends the protected region started by the last [`CILOp::BeginTry`].
+    EndTry,
+    /// Begins a handler catching exceptions of type `DotnetTypeRef`, which pushes the caught exception on top of the stack.
+    BeginCatch(Box<DotnetTypeRef>),
+    /// Begins a `finally` handler, which runs both on normal and exceptional exit from the protected region.
+    BeginFinally,
+    /// Ends the handler started by the last [`CILOp::BeginCatch`] or [`CILOp::BeginFinally`].
+    EndHandler,
+    /// Leaves a protected region or a catch handler, emptying the stack and jumping to a label with the specified id.
+    Leave(u32),
+    /// Ends a `finally` handler, returning control to the runtime.
+    EndFinally,
 
     // Load/Store/AdressOf locals
     /// Load the local number `n` on top of the stack
@@ -155,6 +173,8 @@ pub enum CILOp {
     ConvF32(bool),
     /// Convert the value on top of the stack to an f64. Preform checked convertion if true.
     ConvF64(bool),
+    /// Convert the unsigned intiger on top of the stack to a float.
+    ConvFUn,
     // Pointer
     /// Load a value of type i8 at adress represented by the pointer at the top of the stack.
     LDIndI8,
@@ -200,10 +220,16 @@ pub enum CILOp {
     And,
     /// Divides the value on top of the stack, by the value under it.
     Div,
+    /// Variant of `Div` treating its operands as unsigned.
+    DivUn,
     /// Divides the value on top of the stack, by the value under it, and pushes the reminder on the top of the stack.
     Rem,
+    /// Variant of `Rem` treating its operands as unsigned.
+    RemUn,
     /// Shifts the value on top of the stack to right by the value under it.
     Shr,
+    /// Variant of `Shr` shifting in zeroes instead of the sign bit.
+    ShrUn,
     /// Shifts the value on top of the stack to left by the value under it.
     Shl,
     /// Subtracts from the value on top of the stack, the value under it.
@@ -229,8 +255,12 @@ pub enum CILOp {
     Eq,
     /// Checks if the upper value on the stack is less than the lower one, pushes 0 if not, and 1 if it is.
     Lt,
+    /// Variant of `Lt` treating its operands as unsigned.
+    LtUn,
     /// Checks if the upper value on the stack is greater than the lower one, pushes 0 if not, and 1 if it is.
     Gt,
+    /// Variant of `Gt` treating its operands as unsigned.
+    GtUn,
     //Special
     /// Discards the top value on the stack.
     Pop,
@@ -243,6 +273,8 @@ pub enum CILOp {
     //OOP
     /// Allocates a new object using the constructor in `call_site`
     NewObj(Box<CallSite>),
+    /// Casts the object reference on top of the stack to `type`. Throws `System.InvalidCastException` if the object is not an instance of `type`.
+    CastClass(Box<crate::r#type::Type>),
     /// Loads the value field described by `field_describtor`
     LDField(Box<FieldDescriptor>),
     /// Loads the adress of the field described by `field_describtor`
@@ -261,6 +293,17 @@ pub enum CILOp {
     STStaticField(Box<StaticFieldDescriptor>),
     /// Copies to *dst* from *src* *count* bytes.  
     CpBlk,
+    //Arrays
+    /// Creates a new managed array with elements of `type`, with length equal to the value on top of the stack.
+    NewArr(Box<crate::r#type::Type>),
+    /// Loads the element of `type` at the index on top of the stack, from the managed array under it.
+    LDElem(Box<crate::r#type::Type>),
+    /// Sets the element of `type` at an index of a managed array to the value on top of the stack. The index is under the value, and the array is under the index.
+    STElem(Box<crate::r#type::Type>),
+    /// Loads the adress of the element of `type` at the index on top of the stack, from the managed array under it.
+    LDElemA(Box<crate::r#type::Type>),
+    /// Loads the length of the managed array on top of the stack.
+    LDLen,
 }
 impl CILOp {
     /// If this op is a branch operation, and its target is `original`, replaces the target with `replacement`
@@ -306,18 +349,66 @@ impl CILOp {
                     *target = replacement
                 }
             }
+            CILOp::Leave(target) => {
+                if orignal == *target {
+                    *target = replacement
+                }
+            }
             _ => (),
         }
     }
-    /// If the cil op is a call, virtual call or new object cosntructor, returns the [`CallSite`] representing the called function.
+    /// If this op is a branch operation, returns its target.
+    pub fn branch_target(&self) -> Option<u32> {
+        match self {
+            CILOp::GoTo(target)
+            | CILOp::BEq(target)
+            | CILOp::BNe(target)
+            | CILOp::BLt(target)
+            | CILOp::BGe(target)
+            | CILOp::BLe(target)
+            | CILOp::BZero(target)
+            | CILOp::BTrue(target)
+            | CILOp::Leave(target) => Some(*target),
+            _ => None,
+        }
+    }
+    /// If the cil op is a call, virtual call, new object cosntructor or loads a function pointer, returns the [`CallSite`] representing the called function.
     pub fn call(&self) -> Option<&CallSite> {
         match self {
             Self::Call(site) => Some(site),
             Self::CallVirt(site) => Some(site),
             Self::NewObj(site) => Some(site),
+            Self::LDFtn(site) => Some(site),
             _ => None,
         }
     }
+    /// Replaces the types this op refers to, as specified by `map`. See [`crate::r#type::Type::map_types`].
+    pub fn map_types(
+        &mut self,
+        map: &impl Fn(&crate::r#type::Type) -> Option<crate::r#type::Type>,
+    ) {
+        match self {
+            Self::Call(site) | Self::CallVirt(site) | Self::NewObj(site) | Self::LDFtn(site) => {
+                site.map_types(map)
+            }
+            Self::CallI(sig) => sig.map_types(map),
+            Self::LDField(field) | Self::LDFieldAdress(field) | Self::STField(field) => {
+                field.map_types(map)
+            }
+            Self::LDStaticField(field) | Self::STStaticField(field) => field.map_types(map),
+            Self::NewTMPLocal(tpe)
+            | Self::LdObj(tpe)
+            | Self::STObj(tpe)
+            | Self::SizeOf(tpe)
+            | Self::CastClass(tpe)
+            | Self::NewArr(tpe)
+            | Self::LDElem(tpe)
+            | Self::STElem(tpe)
+            | Self::LDElemA(tpe) => tpe.map_types(map),
+            Self::BeginCatch(class) => class.map_types(map),
+            _ => (),
+        }
+    }
     /// Returns the ops necesary to construct and throw a new `System.Exception` with message `msg`.
     pub fn throw_msg(msg: &str) -> [CILOp; 3] {
         let mut class = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
@@ -411,6 +502,13 @@ impl CILOp {
             CILOp::Nop => 0,
             CILOp::Comment(_) => 0,
             CILOp::Label(_) | CILOp::GoTo(_) => 0,
+            CILOp::BeginTry
+            | CILOp::EndTry
+            | CILOp::BeginFinally
+            | CILOp::EndHandler
+            | CILOp::Leave(_)
+            | CILOp::EndFinally => 0,
+            CILOp::BeginCatch(_) => 1,
             CILOp::BZero(_) | CILOp::BTrue(_) => -1,
             CILOp::BEq(_) | CILOp::BNe(_) | CILOp::BLt(_) | CILOp::BGe(_) | CILOp::BLe(_) => -2,
             CILOp::LDArg(_) | CILOp::LDArgA(_) | CILOp::LDLoc(_) | CILOp::LDLocA(_) => 1,
@@ -432,7 +530,8 @@ impl CILOp {
             | CILOp::ConvU64(_)
             | CILOp::ConvUSize(_)
             | CILOp::ConvF32(_)
-            | CILOp::ConvF64(_) => 0,
+            | CILOp::ConvF64(_)
+            | CILOp::ConvFUn => 0,
             CILOp::LDIndI8
             | CILOp::LDIndI16
             | CILOp::LDIndI32
@@ -452,8 +551,11 @@ impl CILOp {
             CILOp::Dup => 1,
             CILOp::LDField(_) | CILOp::LDFieldAdress(_) => 0,
             CILOp::LocAlloc => 0,
+            CILOp::NewArr(_) | CILOp::LDLen => 0,
+            CILOp::LDElem(_) | CILOp::LDElemA(_) => -1,
+            CILOp::STElem(_) => -3,
             CILOp::NewObj(site) => 1 - (site.explicit_inputs().len() as isize),
-            CILOp::LdObj(_) => 0,
+            CILOp::LdObj(_) | CILOp::CastClass(_) => 0,
             CILOp::LDStaticField(_) => 1,
             CILOp::STStaticField(_) => -1,
             CILOp::STObj(_) => -2,
@@ -463,8 +565,11 @@ impl CILOp {
             | CILOp::AddOvfUn
             | CILOp::And
             | CILOp::Div
+            | CILOp::DivUn
             | CILOp::Rem
+            | CILOp::RemUn
             | CILOp::Shr
+            | CILOp::ShrUn
             | CILOp::Shl
             | CILOp::Sub
             | CILOp::SubOvf
@@ -475,7 +580,9 @@ impl CILOp {
             | CILOp::XOr
             | CILOp::Eq
             | CILOp::Lt
-            | CILOp::Gt => -1,
+            | CILOp::LtUn
+            | CILOp::Gt
+            | CILOp::GtUn => -1,
             CILOp::Not | CILOp::Neg => 0,
             CILOp::STLoc(_) | CILOp::STArg(_) => -1,
             CILOp::Call(site) | CILOp::CallVirt(site) => {
@@ -485,8 +592,17 @@ impl CILOp {
                     1 - (site.signature().inputs().len() as isize)
                 }
             }
+            // The function pointer is popped too.
+            CILOp::CallI(sig) => {
+                if *sig.output() == crate::r#type::Type::Void {
+                    -(sig.inputs().len() as isize) - 1
+                } else {
+                    -(sig.inputs().len() as isize)
+                }
+            }
+            CILOp::LDFtn(_) => 1,
             CILOp::Throw => -1,
-            CILOp::Rethrow => -1,
+            CILOp::Rethrow => 0,
             CILOp::Ret => -1,
             CILOp::CpBlk => -3,
             // Syntetic instructions
@@ -33,4 +33,11 @@ impl StaticFieldDescriptor {
     pub fn boxed(owner: Option<DotnetTypeRef>, tpe: Type, name: IString) -> Box<Self> {
         Box::new(Self { owner, tpe, name })
     }
+    /// Replaces the owner and type of the static field, as specified by `map`. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        if let Some(owner) = &mut self.owner {
+            owner.map_types(map);
+        }
+        self.tpe.map_types(map);
+    }
 }
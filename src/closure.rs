@@ -0,0 +1,162 @@
+use crate::{
+    access_modifier::AccessModifer,
+    cil::{CILOp, CallSite},
+    function_sig::FnSig,
+    method::Method,
+    operand::handle_operand,
+    r#type::TyCache,
+    utilis::monomorphize,
+};
+use rustc_middle::{
+    mir::{Body, Local, Operand},
+    ty::{ClosureKind, Instance, TyCtxt, TyKind},
+};
+/// Suffix of the name of a wrapper allowing a closure capturing no variables to be called through a function pointer.
+const FN_PTR_THUNK_SUFFIX: &str = "_as_fn_ptr";
+/// Returns the ops pushing `args` of a call. If `rust_call` is set, the last argument is a tuple, whose elements are passed as separate arguments.
+pub fn call_args<'ctx>(
+    args: &[Operand<'ctx>],
+    rust_call: bool,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut TyCache,
+) -> Vec<CILOp> {
+    let (tupled, args) = match args.split_last() {
+        Some((tupled, args)) if rust_call => (Some(tupled), args),
+        _ => (None, args),
+    };
+    let mut ops = Vec::new();
+    for arg in args {
+        ops.extend(handle_operand(
+            arg,
+            tyctx,
+            body,
+            method_instance,
+            type_cache,
+        ));
+    }
+    let Some(tupled) = tupled else {
+        return ops;
+    };
+    let tuple_ty = monomorphize(&method_instance, tupled.ty(body, tyctx), tyctx);
+    let TyKind::Tuple(elements) = tuple_ty.kind() else {
+        panic!("The last argument of a \"rust-call\" function must be a tuple, but it is {tuple_ty:?}!");
+    };
+    if elements.is_empty() {
+        return ops;
+    }
+    let tuple_type = type_cache.type_from_cache(tuple_ty, tyctx, Some(method_instance));
+    ops.extend(handle_operand(
+        tupled,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    ));
+    ops.extend([CILOp::NewTMPLocal(tuple_type.into()), CILOp::SetTMPLocal]);
+    for idx in 0..elements.len() {
+        let field_desc = crate::utilis::field_descrptor(
+            tuple_ty,
+            idx as u32,
+            tyctx,
+            method_instance,
+            type_cache,
+        );
+        ops.extend([
+            CILOp::LoadAddresOfTMPLocal,
+            CILOp::LDField(field_desc.into()),
+        ]);
+    }
+    ops.push(CILOp::FreeTMPLocal);
+    ops
+}
+/// Returns the ops packing the arguments spread by the "rust-call" ABI back into the tuple local the body of `method_instance` expects.
+pub fn retuple_spread_arg<'ctx>(
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut TyCache,
+) -> Vec<CILOp> {
+    let Some(spread_arg) = body.spread_arg else {
+        return vec![];
+    };
+    let tuple_ty = monomorphize(&method_instance, body.local_decls[spread_arg].ty, tyctx);
+    let TyKind::Tuple(elements) = tuple_ty.kind() else {
+        panic!("The spread argument of a \"rust-call\" function must be a tuple, but it is {tuple_ty:?}!");
+    };
+    // The elements of the tuple are passed in place of the tuple itself.
+    let first_arg = spread_arg.as_u32() - 1;
+    let mut ops = Vec::with_capacity(elements.len() * 3);
+    for idx in 0..elements.len() {
+        let field_desc = crate::utilis::field_descrptor(
+            tuple_ty,
+            idx as u32,
+            tyctx,
+            method_instance,
+            type_cache,
+        );
+        ops.extend([
+            CILOp::LDLocA(crate::place::spread_local(body)),
+            CILOp::LDArg(first_arg + idx as u32),
+            CILOp::STField(field_desc.into()),
+        ]);
+    }
+    ops
+}
+/// Returns the call site of the wrapper used when a closure capturing no variables is turned into a function pointer.
+pub fn fn_ptr_thunk_site<'ctx>(
+    instance: Instance<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    type_cache: &mut TyCache,
+) -> CallSite {
+    let shim = CallSite::from_instance(instance, tyctx, type_cache);
+    // Function pointers don't take the (empty) closure environment.
+    let sig = FnSig::new(&shim.inputs()[1..], shim.signature().output());
+    CallSite::new(
+        None,
+        format!("{name}{FN_PTR_THUNK_SUFFIX}", name = shim.name()).into(),
+        sig,
+        true,
+    )
+}
+/// If `method` is the `FnOnce` shim of a closure capturing no variables, returns the wrapper allowing it to be called through a function pointer.
+/// The wrapper passes a fresh, empty environment to the shim.
+pub fn fn_ptr_thunk<'ctx>(
+    method: &Method,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    instance: Instance<'ctx>,
+) -> Option<Method> {
+    if body.arg_count == 0 {
+        return None;
+    }
+    let env_ty = monomorphize(&instance, body.local_decls[Local::from_u32(1)].ty, tyctx);
+    let TyKind::Closure(def_id, args) = env_ty.kind() else {
+        return None;
+    };
+    if !args.as_closure().upvar_tys().is_empty() {
+        return None;
+    }
+    let reified = Instance::resolve_closure(tyctx, *def_id, args, ClosureKind::FnOnce)?;
+    if reified.polymorphize(tyctx) != instance {
+        return None;
+    }
+    let sig = method.sig();
+    let mut ops = vec![
+        CILOp::NewTMPLocal(sig.inputs()[0].clone().into()),
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+    ];
+    ops.extend((0..(sig.inputs().len() - 1) as u32).map(CILOp::LDArg));
+    ops.extend([CILOp::Call(method.call_site().into()), CILOp::Ret]);
+    let mut thunk = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&sig.inputs()[1..], sig.output()),
+        &format!("{name}{FN_PTR_THUNK_SUFFIX}", name = method.name()),
+        vec![],
+    );
+    thunk.set_ops(ops);
+    Some(thunk)
+}
@@ -1,3 +1,7 @@
+use crate::{config::CodegenConfig, report::Construct};
+use rustc_middle::ty::TyCtxt;
+use rustc_session::lint::Level;
+use rustc_span::{def_id::DefId, symbol::sym, Span};
 use std::fmt::Debug;
 
 #[derive(Debug)]
@@ -7,27 +11,201 @@ pub enum CodegenError {
     Error(crate::IString),
     Method(MethodCodegenError),
 }
-impl From<CodegenError> for rustc_errors::ErrorGuaranteed {
-    fn from(val: CodegenError) -> Self {
-        val.report_error();
-        error_guaranteed()
-    }
-}
 impl From<MethodCodegenError> for CodegenError {
     fn from(value: MethodCodegenError) -> Self {
         Self::Method(value)
     }
 }
 impl CodegenError {
-    fn report_error(&self) {
-        eprintln!("Codegen faliled with error:{self:?}")
+    /// Reports this error to the user as a rustc error, pointing at `span`.
+    pub fn emit(&self, tcx: TyCtxt, span: Span) -> rustc_errors::ErrorGuaranteed {
+        tcx.sess.dcx().span_err(
+            span,
+            format!(
+                "[{code}] codegen failed with error: {self:?}",
+                code = CodegenLint::UnsupportedMethod.code()
+            ),
+        )
     }
     pub fn from_panic_message(msg: &str) -> Self {
         Self::Error(msg.into())
     }
 }
-fn error_guaranteed() -> rustc_errors::ErrorGuaranteed {
-    unsafe { std::mem::transmute(()) }
+/// Lint-like categories of codegen diagnostics. Their level can be changed with the `allow`, `warn`, `deny` and `forbid` attributes
+/// on the affected item or any of its parents, using the `clr` tool prefix(eg. `#[allow(clr::miscompilation)]`, which requires `#![register_tool(clr)]`),
+/// or on the command line(eg. `-A clr::miscompilation`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodegenLint {
+    /// A statement could not be compiled, and was replaced with a throw.
+    UnsupportedStatement,
+    /// A terminator could not be compiled, and was replaced with a throw.
+    UnsupportedTerminator,
+    /// A whole method could not be compiled.
+    UnsupportedMethod,
+    /// Compiled ops did not pass the stack check, so they are probably miscompiled.
+    Miscompilation,
+}
+/// Level of a [`CodegenLint`], from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+impl CodegenLint {
+    /// Returns the lint which reports failures of `construct`.
+    pub fn from_construct(construct: Construct) -> Self {
+        match construct {
+            Construct::Statement => Self::UnsupportedStatement,
+            Construct::Terminator => Self::UnsupportedTerminator,
+            Construct::Method | Construct::MissingMethod => Self::UnsupportedMethod,
+        }
+    }
+    /// Name of the lint, used in attributes after the `clr::` prefix.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnsupportedStatement => "unsupported_statement",
+            Self::UnsupportedTerminator => "unsupported_terminator",
+            Self::UnsupportedMethod => "unsupported_method",
+            Self::Miscompilation => "miscompilation",
+        }
+    }
+    /// Code identifying the diagnostics of this lint.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::UnsupportedStatement => "CLR0001",
+            Self::UnsupportedTerminator => "CLR0002",
+            Self::UnsupportedMethod => "CLR0003",
+            Self::Miscompilation => "CLR0004",
+        }
+    }
+    /// Returns the level of this lint within `def_id`, lowered to the cap set by `--cap-lints`(which cargo passes when building dependencies).
+    /// The level, and what sets it, are returned.
+    fn level(self, tcx: TyCtxt, def_id: DefId) -> (LintLevel, Option<String>) {
+        cap_level(self.uncapped_level(tcx, def_id), tcx.sess.opts.lint_cap)
+    }
+    /// Returns the level of this lint within `def_id`: set by the innermost lint attribute refering to it, then by the last command line flag
+    /// refering to it, or warn by default. The level, and what sets it, are returned.
+    fn uncapped_level(self, tcx: TyCtxt, def_id: DefId) -> (LintLevel, Option<String>) {
+        let mut current = Some(def_id);
+        while let Some(def_id) = current {
+            for attr in tcx.get_attrs_unchecked(def_id) {
+                let level = match attr.name_or_empty() {
+                    name if name == sym::allow => LintLevel::Allow,
+                    name if name == sym::warn => LintLevel::Warn,
+                    name if name == sym::deny || name == sym::forbid => LintLevel::Deny,
+                    _ => continue,
+                };
+                let refers_to_self = attr.meta_item_list().is_some_and(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| item.meta_item())
+                        .any(|meta| {
+                            let segments = &meta.path.segments;
+                            segments.len() == 2
+                                && segments[0].ident.name.as_str() == "clr"
+                                && segments[1].ident.name.as_str() == self.name()
+                        })
+                });
+                if refers_to_self {
+                    return (
+                        level,
+                        Some(format!(
+                            "`#[{attr}(clr::{name})]`",
+                            attr = attr.name_or_empty(),
+                            name = self.name()
+                        )),
+                    );
+                }
+            }
+            current = tcx.opt_parent(def_id);
+        }
+        let lint_name = format!("clr::{name}", name = self.name());
+        let flag = tcx
+            .sess
+            .opts
+            .lint_opts
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == lint_name);
+        match flag {
+            Some((_, level)) => {
+                let (level, flag) = match level {
+                    Level::Allow | Level::Expect(..) => (LintLevel::Allow, "-A"),
+                    Level::Deny => (LintLevel::Deny, "-D"),
+                    Level::Forbid => (LintLevel::Deny, "-F"),
+                    _ => (LintLevel::Warn, "-W"),
+                };
+                (level, Some(format!("`{flag} {lint_name}`")))
+            }
+            None => (LintLevel::Warn, None),
+        }
+    }
+    /// Reports `msg` about the code at `span`, within the item `def_id`, as a rustc warning or error, depending on the level of this lint.
+    /// `note` explains the cause of the diagnostic.
+    pub fn emit(self, tcx: TyCtxt, def_id: DefId, span: Span, msg: &str, note: &str) {
+        self.emit_at(self.level(tcx, def_id), tcx, span, msg, note);
+    }
+    /// Reports a probable miscompilation like [`CodegenLint::emit`]. If the `allow-miscompilations` option is turned off, it is always an error.
+    pub fn emit_miscompilation(
+        tcx: TyCtxt,
+        def_id: DefId,
+        span: Span,
+        msg: &str,
+        note: &str,
+        config: &CodegenConfig,
+    ) {
+        let level = if config.allow_miscompilations {
+            Self::Miscompilation.level(tcx, def_id)
+        } else {
+            (
+                LintLevel::Deny,
+                Some("the `allow-miscompilations=no` codegen option".into()),
+            )
+        };
+        Self::Miscompilation.emit_at(level, tcx, span, msg, note);
+    }
+    /// Reports `msg` at `level`, which is set by `source`.
+    fn emit_at(
+        self,
+        (level, source): (LintLevel, Option<String>),
+        tcx: TyCtxt,
+        span: Span,
+        msg: &str,
+        note: &str,
+    ) {
+        let dcx = tcx.sess.dcx();
+        let mut diag = match level {
+            LintLevel::Allow => return,
+            LintLevel::Warn => dcx.struct_span_warn(span, format!("[{}] {msg}", self.code())),
+            LintLevel::Deny => dcx.struct_span_err(span, format!("[{}] {msg}", self.code())),
+        };
+        diag.note(note.to_owned());
+        match source {
+            Some(source) => diag.note(format!("the level of this diagnostic is set by {source}")),
+            None => diag.note(format!(
+                "`#[warn(clr::{name})]` on by default",
+                name = self.name()
+            )),
+        };
+        diag.emit();
+    }
+}
+/// Lowers `level` to `cap`, the level set by `--cap-lints`.
+fn cap_level(
+    (level, source): (LintLevel, Option<String>),
+    cap: Option<Level>,
+) -> (LintLevel, Option<String>) {
+    let (cap, flag) = match cap {
+        Some(Level::Allow | Level::Expect(..)) => (LintLevel::Allow, "`--cap-lints allow`"),
+        Some(Level::Warn | Level::ForceWarn(..)) => (LintLevel::Warn, "`--cap-lints warn`"),
+        _ => return (level, source),
+    };
+    if level > cap {
+        (cap, Some(flag.into()))
+    } else {
+        (level, source)
+    }
 }
 pub struct MethodCodegenError {
     file: String,
@@ -66,3 +244,26 @@ impl Debug for MethodCodegenError {
         )
     }
 }
+/// Returns the message of a caught panic, if it has one. `todo!`s and `panic!`s with formatted messages carry a `String`, while ones with literal messages carry a `&str`.
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+#[test]
+fn cap_lints() {
+    let deny = (LintLevel::Deny, Some("`-D clr::miscompilation`".to_owned()));
+    assert_eq!(cap_level(deny.clone(), None), deny);
+    assert_eq!(cap_level(deny.clone(), Some(Level::Deny)), deny);
+    assert_eq!(
+        cap_level(deny.clone(), Some(Level::Warn)).0,
+        LintLevel::Warn
+    );
+    assert_eq!(cap_level(deny, Some(Level::Allow)).0, LintLevel::Allow);
+    // Lints are never raised to the cap.
+    assert_eq!(
+        cap_level((LintLevel::Warn, None), Some(Level::Deny)),
+        (LintLevel::Warn, None)
+    );
+}
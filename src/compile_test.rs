@@ -16,11 +16,14 @@ fn peverify(file_path: &str, test_dir: &str) {
         );
     }
 }
+/// Runs the .NET build of a test program with mono and dotnet, or with the CIL interpreter if neither is installed, and checks it succeeds.
+/// Returns the output of the last run, so it can be compared with the native build.
 #[cfg(test)]
-fn test_dotnet_executable(file_path: &str, test_dir: &str) {
+fn test_dotnet_executable(file_path: &str, test_dir: &str) -> TestOutput {
     use std::io::Write;
 
     let exec_path = &format!("{file_path}.exe");
+    let mut output = None;
     if *IS_MONO_PRESENT {
         // Execute the test assembly
         let out = std::process::Command::new("mono")
@@ -28,11 +31,12 @@ fn test_dotnet_executable(file_path: &str, test_dir: &str) {
             .args([exec_path])
             .output()
             .expect("failed to run test assebmly!");
-        let stderr = String::from_utf8(out.stderr).expect("Stdout is not UTF8 String!");
+        let stderr = String::from_utf8(out.stderr.clone()).expect("Stdout is not UTF8 String!");
         assert!(
             stderr.is_empty(),
             "Test program failed with message {stderr:}"
         );
+        output = Some(TestOutput::from_process(out));
     }
     //println!("exec_path:{exec_path:?}");
     if *IS_DOTNET_PRESENT {
@@ -48,15 +52,130 @@ fn test_dotnet_executable(file_path: &str, test_dir: &str) {
             .output()
             .expect("failed to run test assebmly!");
 
-        let stderr = String::from_utf8(out.stderr).expect("Stdout is not UTF8 String!");
+        let stderr = String::from_utf8(out.stderr.clone()).expect("Stdout is not UTF8 String!");
         assert!(
             stderr.is_empty(),
             "Test program failed with message {stderr:}"
         );
+        output = Some(TestOutput::from_process(out));
     }
     if !(*IS_DOTNET_PRESENT || *IS_MONO_PRESENT) {
-        panic!("You must have either mono or dotnet runtime installed to run tests.");
+        // Without a .NET runtime, the linked assembly is run by the interpreter instead.
+        let file_name = std::path::Path::new(file_path)
+            .file_name()
+            .expect("Test executable path has no file name!");
+        let execution = interpret_executable(
+            &std::path::Path::new(test_dir)
+                .join(file_name)
+                .with_extension("exe"),
+        );
+        assert!(
+            execution.stderr().is_empty(),
+            "Test program failed with message {stderr}",
+            stderr = execution.stderr()
+        );
+        assert_eq!(
+            execution.exit_code(),
+            0,
+            "Test program exited with a non-zero exit code"
+        );
+        output = Some(TestOutput {
+            stdout: execution.stdout().to_owned(),
+            exit_code: execution.exit_code(),
+        });
+    }
+    output.expect("The test program was not run!")
+}
+/// Runs the assembly linked into the executable `exe_path` using the CIL interpreter.
+#[cfg(test)]
+fn interpret_executable(exe_path: &std::path::Path) -> crate::interpreter::Execution {
+    let asm_path = format!(
+        "{exe_path}.{ext}",
+        exe_path = exe_path.display(),
+        ext = crate::LINKED_ASSEMBLY_EXTENSION
+    );
+    let asm = crate::load_assembly(asm_path.as_ref());
+    crate::interpreter::run(&asm)
+        .unwrap_or_else(|err| panic!("Could not interpret the test program: {err}"))
+}
+/// Observable behaviour of a test program, compared between its native and .NET builds.
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct TestOutput {
+    stdout: String,
+    /// Programs killed by a signal get the exit code `128 + signal`, like in a shell. This makes a native `abort` match an unhandled .NET exception.
+    exit_code: i32,
+}
+#[cfg(test)]
+impl TestOutput {
+    fn from_process(out: std::process::Output) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&out.status);
+        #[cfg(not(unix))]
+        let signal: Option<i32> = None;
+        let exit_code = match (out.status.code(), signal) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => panic!("Test program has no exit code!"),
+        };
+        Self {
+            stdout: String::from_utf8_lossy(&out.stdout).into_owned(),
+            exit_code,
+        }
+    }
+}
+/// Returns the environment variables turning on the codegen `options`. They are seen both by the codegen and by the linker it runs.
+#[cfg(test)]
+fn option_vars(options: &[&str]) -> Vec<(String, &'static str)> {
+    options
+        .iter()
+        .map(|option| {
+            let name = option.to_uppercase().replace('-', "_");
+            (format!("CODEGEN_CLR_{name}"), "yes")
+        })
+        .collect()
+}
+/// Compiles the test program `test_name` using the native LLVM backend, and checks it behaves the same way as its .NET build, which produced `dotnet`.
+#[cfg(test)]
+fn compare_with_native(
+    source: &str,
+    test_name: &str,
+    test_dir: &str,
+    optimize: bool,
+    dotnet: &TestOutput,
+) {
+    let native_path = format!("./{test_name}_native");
+    let mut command = std::process::Command::new("rustc");
+    command.current_dir(test_dir);
+    if optimize {
+        command.arg("-O");
     }
+    // Test programs are `no_std` and use the C standard library directly, so they must be linked against it explicitly.
+    command.args([
+        "-C",
+        "panic=abort",
+        "-C",
+        "link-arg=-lc",
+        source,
+        "-o",
+        &native_path,
+    ]);
+    let out = command.output().expect("failed to execute process");
+    if !out.status.success() {
+        let stderr =
+            String::from_utf8(out.stderr).expect("rustc error contained non-UTF8 characters.");
+        panic!("Native build of {test_name} failed. stderr:\n{stderr}");
+    }
+    let native = TestOutput::from_process(
+        std::process::Command::new(&native_path)
+            .current_dir(test_dir)
+            .output()
+            .expect("failed to run the native test program!"),
+    );
+    assert_eq!(
+        *dotnet, native,
+        "The .NET build of {test_name} behaves differently than the native one."
+    );
 }
 macro_rules! test_lib {
     ($test_name:ident) => {
@@ -194,7 +313,18 @@ macro_rules! test_lib {
 }
 macro_rules! run_test {
     ($prefix:ident,$test_name:ident) => {
-        mod $test_name {
+        run_test! {@impl $prefix,$test_name,$test_name,false,[]}
+    };
+    // Also compares the output and exit code of the test program with its native build.
+    ($prefix:ident,$test_name:ident,compare_native) => {
+        run_test! {@impl $prefix,$test_name,$test_name,true,[]}
+    };
+    // Compiles the test program `$test_name` as `$variant`, with the codegen options `$option` turned on.
+    ($prefix:ident,$test_name:ident as $variant:ident,options = [$($option:literal),+]) => {
+        run_test! {@impl $prefix,$test_name,$variant,false,[$($option),+]}
+    };
+    (@impl $prefix:ident,$test_name:ident,$variant:ident,$compare_native:literal,[$($option:literal),*]) => {
+        mod $variant {
             #[cfg(test)]
             use ntest::timeout;
             #[cfg(test)]
@@ -213,6 +343,8 @@ macro_rules! run_test {
                 // Compiles the test project
                 let out = std::process::Command::new("rustc")
                     //.env("RUST_TARGET_PATH","../../")
+                    // The CIL verifier runs on every test, so that any method it rejects fails the test.
+                    .envs(super::option_vars(&["verify-cil", "save-linked-assembly", $($option),*]))
                     .current_dir(test_dir)
                     .args([
                         "-O",
@@ -222,7 +354,7 @@ macro_rules! run_test {
                         &format!("linker={}", super::RUSTC_CODEGEN_CLR_LINKER.display()),
                         concat!("./", stringify!($test_name), ".rs"),
                         "-o",
-                        concat!("./", stringify!($test_name), ".exe"),
+                        concat!("./", stringify!($variant), ".exe"),
                         //"--target",
                         //"clr64-unknown-clr"
                     ])
@@ -236,10 +368,19 @@ macro_rules! run_test {
                         .expect("rustc error contained non-UTF8 characters.");
                     panic!("stdout:\n{stdout}\nstderr:\n{stderr}");
                 }
-                let exec_path = concat!("../", stringify!($test_name));
+                let exec_path = concat!("../", stringify!($variant));
                 drop(lock);
                 super::peverify(exec_path, test_dir);
-                super::test_dotnet_executable(exec_path, test_dir);
+                let dotnet = super::test_dotnet_executable(exec_path, test_dir);
+                if $compare_native {
+                    super::compare_with_native(
+                        concat!("./", stringify!($test_name), ".rs"),
+                        stringify!($variant),
+                        test_dir,
+                        true,
+                        &dotnet,
+                    );
+                }
             }
             #[test]
             #[timeout(30_000)]
@@ -252,11 +393,13 @@ macro_rules! run_test {
                 super::RUSTC_BUILD_STATUS
                     .as_ref()
                     .expect("Could not build rustc!");
-                let test_name = concat!("debug_", stringify!($test_name));
+                let test_name = concat!("debug_", stringify!($variant));
                 let output_path = format!("./{test_name}.exe");
                 // Compiles the test project
                 let out = std::process::Command::new("rustc")
                     //.env("RUST_TARGET_PATH","../../")
+                    // The CIL verifier runs on every test, so that any method it rejects fails the test.
+                    .envs(super::option_vars(&["verify-cil", "save-linked-assembly", $($option),*]))
                     .current_dir(test_dir)
                     .args([
                         "-Z",
@@ -282,11 +425,23 @@ macro_rules! run_test {
                 let exec_path = format!("../{test_name}");
                 drop(lock);
                 super::peverify(&exec_path, test_dir);
-                super::test_dotnet_executable(&exec_path, test_dir);
+                let dotnet = super::test_dotnet_executable(&exec_path, test_dir);
+                if $compare_native {
+                    super::compare_with_native(
+                        concat!("./", stringify!($test_name), ".rs"),
+                        test_name,
+                        test_dir,
+                        false,
+                        &dotnet,
+                    );
+                }
             }
         }
     };
 }
+// Cargo tests only check that a crate and its dependencies build: unlike `run_test!`, they have no `compare_native` mode.
+// Every cargo test project is built on `mycorrhiza`, whose .NET interop(eg. `Console` or `StringBuilder`) has no native implementation,
+// so `cargo run` can't build them for the host. Behaviour is compared with native builds by the `run_test!` programs instead.
 macro_rules! cargo_test {
     ($test_name:ident) => {
         mod $test_name {
@@ -576,14 +731,19 @@ test_lib! {types}
 test_lib! {recursive}
 test_lib! {tuple}
 
-run_test! {arthm,add}
+run_test! {arthm,add,compare_native}
 run_test! {types,tuple_structs}
 run_test! {arthm,mul}
 run_test! {arthm,sub}
+run_test! {arthm,i128,compare_native}
+run_test! {arthm,i128 as software_i128,options = ["software-128-bit-ints"]}
 run_test! {types,enums}
-run_test! {types,nbody}
+run_test! {types,nbody,compare_native}
 run_test! {types,structs}
 run_test! {types,interop}
+run_test! {types,interop_variadic}
+run_test! {types,interop_array}
+run_test! {types,interop_gc_handle}
 run_test! {types,vec}
 run_test! {types,string_slice}
 run_test! {types,ref_deref}
@@ -591,9 +751,16 @@ run_test! {types,slice_ptr_cast}
 
 run_test! {types,slice}
 run_test! {types,statics}
+run_test! {types,fn_ptr}
+run_test! {types,dyn_trait}
+run_test! {types,closures}
+run_test! {types,generics}
+run_test! {types,generics as preserved_generics,options = ["preserve-generics"]}
 run_test! {std,main}
-run_test! {control_flow,cf_for}
+run_test! {control_flow,cf_for,compare_native}
 run_test! {control_flow,drop}
+run_test! {control_flow,catch_unwind}
+run_test! {control_flow,catch_unwind_std}
 cargo_test! {hello_world}
 cargo_test! {std_hello_world}
 cargo_test_ignored! {build_core}
@@ -0,0 +1,206 @@
+/// Prefix of the environment variables setting codegen options, eg. `CODEGEN_CLR_TRACE_CALLS=yes` sets the `trace-calls` option.
+const ENV_PREFIX: &str = "CODEGEN_CLR_";
+/// Options changing the behaviour of the codegen. They are read from the environment, and then from the backend options(`-C llvm-args`),
+/// which take precedence. Each option is set using `-Z`-like syntax, eg. `-C llvm-args="trace-calls always-init-locals=no"`.
+/// Options set using environment variables are not tracked by incremental compilation, so saved codegen units are only reused if they were compiled with the same options(see [`CodegenConfig::codegen_fingerprint`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CodegenConfig {
+    /// Tells the codegen to insert comments containing the MIR statemtens after each one of them. Turns off CIL optimizations.
+    pub insert_mir_debug_comments: bool,
+    /// Tells the codegen to optmize the emiited CIL.
+    pub optimize_cil: bool,
+    /// Optimization passes which are enabled. Each one can be toggled using the `opt-<pass name>` option, eg. `opt-alias-locals=no`.
+    pub opt_passes: crate::opt::PassSet,
+    /// Prints the ops of each method before the optimizations, and after each optimization pass which changed them.
+    pub dump_opt_passes: bool,
+    /// Prints statistics of the optimization passes of each codegen unit. Also turned on by `-Z time-passes`.
+    pub print_opt_stats: bool,
+    /// Tells the linker to remove the methods, types and static fields which can't be reached from the entrypoint, or from public items of libraries.
+    pub remove_dead_code: bool,
+    /// Tells the linker to write a C# source file declaring the functions exported by a library, and the types they use, next to it.
+    pub csharp_bindings: bool,
+    /// Changes `.locals` into `.locals init`. Causes the runtime to always initialize local variables.
+    /// Try turining on in cause of issues. If it fixes them, then their root cause is UB(eg. use of uninitailized memory).
+    pub always_init_locals: bool,
+    /// Should the codegen stop working when ecountering an error, or try to press on, replacing unusuported code with exceptions throws?
+    pub abort_on_error: bool,
+    /// Should the codegen continue working after it encoutnered a miscompilation?
+    pub allow_miscompilations: bool,
+    /// Preapends each function call with a debug message
+    pub trace_calls: bool,
+    /// Preapends each statement with a debug message
+    pub trace_statements: bool,
+    /// Runs the CIL verifier on each method after it is compiled, and after it is optimized.
+    pub verify_cil: bool,
+    /// Makes the linker write the final assembly directly, instead of emmiting IL and calling `ilasm`.
+    pub native_pe_exporter: bool,
+    /// Makes the linker implement 128 bit integers in software, instead of using `System.Int128` and `System.UInt128`. Needed for runtimes lacking those types(eg. mono).
+    /// Only used by the linker, so it has to be set using an environment variable. See [`crate::int128::use_software_int128`].
+    pub software_128_bit_ints: bool,
+    /// Makes the codegen emit generic functions and structs whose layout does not depend on their generic parameters only once, as CIL generics
+    /// instantiated by the runtime, instead of emitting each of their monomorphized instances.
+    pub preserve_generics: bool,
+    /// Tells the linker to save the final assembly next to its output, so it can be run by the [`crate::interpreter`]. Used by the tests.
+    pub save_linked_assembly: bool,
+}
+impl Default for CodegenConfig {
+    fn default() -> Self {
+        Self {
+            insert_mir_debug_comments: false,
+            optimize_cil: false,
+            opt_passes: crate::opt::PassSet::default(),
+            dump_opt_passes: false,
+            print_opt_stats: false,
+            remove_dead_code: true,
+            csharp_bindings: false,
+            always_init_locals: false,
+            abort_on_error: false,
+            allow_miscompilations: true,
+            trace_calls: false,
+            trace_statements: false,
+            verify_cil: false,
+            native_pe_exporter: false,
+            software_128_bit_ints: false,
+            preserve_generics: false,
+            save_linked_assembly: false,
+        }
+    }
+}
+impl CodegenConfig {
+    /// Reads the config from the environment variables only. Used by the linker, which does not receive the backend options.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+        config.apply_env(std::env::vars())?;
+        Ok(config)
+    }
+    /// Reads the config from the environment variables, and then the backend options `options`.
+    pub fn from_options(options: &[String]) -> Result<Self, String> {
+        let mut config = Self::from_env()?;
+        for option in options {
+            config.set_option(option)?;
+        }
+        Ok(config)
+    }
+    /// Returns a fingerprint of the options changing the compiled codegen units. A codegen unit saved by incremental compilation is only reused if it was compiled with the same fingerprint.
+    pub fn codegen_fingerprint(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        // Options used only by the linker, or changing only what is printed, are left out.
+        let defaults = Self::default();
+        let codegen_options = Self {
+            dump_opt_passes: defaults.dump_opt_passes,
+            print_opt_stats: defaults.print_opt_stats,
+            remove_dead_code: defaults.remove_dead_code,
+            csharp_bindings: defaults.csharp_bindings,
+            native_pe_exporter: defaults.native_pe_exporter,
+            software_128_bit_ints: defaults.software_128_bit_ints,
+            save_linked_assembly: defaults.save_linked_assembly,
+            ..*self
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        codegen_options.hash(&mut hasher);
+        hasher.finish()
+    }
+    /// Sets an option in the form `name`, `name=yes` or `name=no`.
+    pub fn set_option(&mut self, option: &str) -> Result<(), String> {
+        let (name, value) = option.split_once('=').unwrap_or((option, "yes"));
+        let value = parse_bool(value)
+            .ok_or_else(|| format!("Codegen option {name:?} expects a boolean, got {value:?}."))?;
+        let name = name.trim_start_matches('-');
+        if let Some(pass) = name
+            .strip_prefix("opt-")
+            .and_then(crate::opt::Pass::from_name)
+        {
+            self.opt_passes.set(pass, value);
+            return Ok(());
+        }
+        *self
+            .option_mut(name)
+            .ok_or_else(|| format!("Unknown codegen option {name:?}."))? = value;
+        Ok(())
+    }
+    /// Sets the options specified by the environment variables `vars` starting with [`ENV_PREFIX`].
+    fn apply_env(&mut self, vars: impl Iterator<Item = (String, String)>) -> Result<(), String> {
+        for (key, value) in vars {
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let name = name.to_lowercase().replace('_', "-");
+            self.set_option(&format!("{name}={value}"))?;
+        }
+        Ok(())
+    }
+    /// Returns the option named `name`.
+    fn option_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "insert-mir-debug-comments" => Some(&mut self.insert_mir_debug_comments),
+            "optimize-cil" => Some(&mut self.optimize_cil),
+            "dump-opt-passes" => Some(&mut self.dump_opt_passes),
+            "print-opt-stats" => Some(&mut self.print_opt_stats),
+            "remove-dead-code" => Some(&mut self.remove_dead_code),
+            "csharp-bindings" => Some(&mut self.csharp_bindings),
+            "always-init-locals" => Some(&mut self.always_init_locals),
+            "abort-on-error" => Some(&mut self.abort_on_error),
+            "allow-miscompilations" => Some(&mut self.allow_miscompilations),
+            "trace-calls" => Some(&mut self.trace_calls),
+            "trace-statements" => Some(&mut self.trace_statements),
+            "verify-cil" => Some(&mut self.verify_cil),
+            "native-pe-exporter" => Some(&mut self.native_pe_exporter),
+            "software-128-bit-ints" => Some(&mut self.software_128_bit_ints),
+            "preserve-generics" => Some(&mut self.preserve_generics),
+            "save-linked-assembly" => Some(&mut self.save_linked_assembly),
+            _ => None,
+        }
+    }
+}
+/// Parses the value of a boolean option.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "y" | "yes" | "on" | "true" | "1" => Some(true),
+        "n" | "no" | "off" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+#[test]
+fn parse_options() {
+    let mut config = CodegenConfig::default();
+    config.set_option("trace-calls").unwrap();
+    config.set_option("allow-miscompilations=no").unwrap();
+    assert!(config.trace_calls);
+    assert!(!config.allow_miscompilations);
+    assert!(config.set_option("trace-everything").is_err());
+    assert!(config.set_option("trace-calls=maybe").is_err());
+    config.set_option("opt-alias-locals=no").unwrap();
+    config.set_option("opt-inline-calls").unwrap();
+    assert!(!config.opt_passes.contains(crate::opt::Pass::AliasLocals));
+    assert!(config.opt_passes.contains(crate::opt::Pass::InlineCalls));
+    config.set_option("software-128-bit-ints").unwrap();
+    assert!(config.software_128_bit_ints);
+    assert!(!config.preserve_generics);
+}
+#[test]
+fn parse_env() {
+    let mut config = CodegenConfig::default();
+    config
+        .apply_env(
+            [
+                ("CODEGEN_CLR_TRACE_STATEMENTS".into(), "1".into()),
+                ("PATH".into(), "/bin".into()),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+    assert!(config.trace_statements);
+    assert!(!config.trace_calls);
+}
+#[test]
+fn codegen_fingerprint() {
+    let config = CodegenConfig::default();
+    let mut generics = config;
+    generics.set_option("preserve-generics").unwrap();
+    assert_ne!(config.codegen_fingerprint(), generics.codegen_fingerprint());
+    // The linker options don't change the compiled codegen units.
+    let mut linker = config;
+    linker.set_option("software-128-bit-ints").unwrap();
+    linker.set_option("save-linked-assembly").unwrap();
+    assert_eq!(config.codegen_fingerprint(), linker.codegen_fingerprint());
+}
@@ -10,6 +10,7 @@ use rustc_middle::mir::{
 use rustc_middle::ty::{
     AdtDef, AdtKind, FloatTy, Instance, IntTy, List, ParamEnv, Ty, TyCtxt, TyKind, UintTy,
 };
+use rustc_span::{Span, Symbol};
 pub fn handle_constant<'ctx>(
     constant_op: &ConstOperand<'ctx>,
     tyctx: TyCtxt<'ctx>,
@@ -31,6 +32,34 @@ pub fn handle_constant<'ctx>(
         tycache,
     )
 }
+/// Returns the file, line and column `span` should be reported at, in the same way as `core::panic::Location`.
+pub fn span_location(span: Span, tyctx: TyCtxt) -> (Symbol, u32, u32) {
+    let topmost = span.ctxt().outer_expn().expansion_cause().unwrap_or(span);
+    let caller = tyctx.sess.source_map().lookup_char_pos(topmost.lo());
+    (
+        Symbol::intern(&caller.file.name.prefer_remapped().to_string_lossy()),
+        caller.line as u32,
+        caller.col_display as u32 + 1,
+    )
+}
+/// Returns the ops loading a pointer to a static `core::panic::Location` describing `span`.
+pub fn load_caller_location<'ctx>(
+    span: Span,
+    tyctx: TyCtxt<'ctx>,
+    method: &rustc_middle::mir::Body<'ctx>,
+    method_instance: Instance<'ctx>,
+    tycache: &mut TyCache,
+) -> Vec<CILOp> {
+    let location = tyctx.const_caller_location(span_location(span, tyctx));
+    load_const_value(
+        location,
+        tyctx.caller_location_ty(),
+        tyctx,
+        method,
+        method_instance,
+        tycache,
+    )
+}
 /// Returns the ops neceasry to create constant ADT of type represented by `adt_def` and `subst` with byte values matching the ones in the slice bytes
 fn create_const_adt_from_bytes<'ctx>(
     ty: Ty<'ctx>,
@@ -159,7 +188,10 @@ fn create_const_from_slice<'ctx>(
                 )),
                 CILOp::ConvUSize(false),
             ],
-            _ => todo!("Can't yet load const value of type {int:?} with bytes:{bytes:?}"),
+            IntTy::I128 => crate::int128::load_const(
+                &Type::I128,
+                u128::from_le_bytes(bytes[..std::mem::size_of::<i128>()].try_into().unwrap()),
+            ),
         },
         TyKind::Uint(int) => match int {
             UintTy::U8 => vec![
@@ -188,7 +220,10 @@ fn create_const_from_slice<'ctx>(
                 )),
                 CILOp::ConvUSize(false),
             ],
-            _ => todo!("Can't yet load const value of type {int:?} with bytes:{bytes:?}"),
+            UintTy::U128 => crate::int128::load_const(
+                &Type::U128,
+                u128::from_le_bytes(bytes[..std::mem::size_of::<u128>()].try_into().unwrap()),
+            ),
         },
         TyKind::RawPtr(type_and_mut) => match type_and_mut.ty.kind() {
             TyKind::Slice(_) => {
@@ -452,7 +487,23 @@ fn load_const_scalar<'ctx>(
                         CILOp::Add,
                     ];
                 }
-                _ => todo!("Unhandled global alloc {global_alloc:?}"),
+                GlobalAlloc::Function(instance) => {
+                    assert_eq!(offset.bytes(), 0, "Offset pointer to a function!");
+                    return vec![CILOp::LDFtn(
+                        CallSite::from_instance(instance, tyctx, tycache).into(),
+                    )];
+                }
+                GlobalAlloc::VTable(ty, trait_ref) => {
+                    let alloc_id = tyctx.vtable_allocation((ty, trait_ref));
+                    return vec![
+                        CILOp::LoadGlobalAllocPtr {
+                            alloc_id: crate::utilis::alloc_id_to_u64(alloc_id),
+                        },
+                        CILOp::LdcI64(offset.bytes() as i64),
+                        CILOp::ConvISize(false),
+                        CILOp::Add,
+                    ];
+                }
             }
             //panic!("alloc_id:{alloc_id:?}")
         }
@@ -528,24 +579,7 @@ pub fn load_const_int(value: u128, int_type: &IntTy) -> Vec<CILOp> {
             let value = i64::from_ne_bytes((value as u64).to_ne_bytes());
             vec![CILOp::LdcI64(value), CILOp::ConvISize(false)]
         }
-        IntTy::I128 => {
-            let low = (value & u128::from(u64::MAX)) as u64;
-            let high = (value << 64) as u64;
-            let low = i64::from_ne_bytes(low.to_ne_bytes());
-            let high = i64::from_ne_bytes(high.to_ne_bytes());
-            let i128_class = DotnetTypeRef::new(Some("System.Runtime"), "System.Int128");
-            let ctor_sig = crate::function_sig::FnSig::new(&[Type::U64, Type::U64], &Type::I128);
-            vec![
-                CILOp::LdcI64(high),
-                CILOp::LdcI64(low),
-                CILOp::Call(CallSite::boxed(
-                    Some(i128_class),
-                    ".ctor".into(),
-                    ctor_sig,
-                    true,
-                )),
-            ]
-        }
+        IntTy::I128 => crate::int128::load_const(&Type::I128, value),
     }
 }
 pub fn load_const_uint(value: u128, int_type: &UintTy) -> Vec<CILOp> {
@@ -570,23 +604,6 @@ pub fn load_const_uint(value: u128, int_type: &UintTy) -> Vec<CILOp> {
             let value = i64::from_ne_bytes((value as u64).to_ne_bytes());
             vec![CILOp::LdcI64(value), CILOp::ConvUSize(false)]
         }
-        UintTy::U128 => {
-            let low = (value & u128::from(u64::MAX)) as u64;
-            let high = (value << 64) as u64;
-            let low = i64::from_ne_bytes(low.to_ne_bytes());
-            let high = i64::from_ne_bytes(high.to_ne_bytes());
-            let i128_class = DotnetTypeRef::new(Some("System.Runtime"), "System.UInt128");
-            let ctor_sig = crate::function_sig::FnSig::new(&[Type::U64, Type::U64], &Type::U128);
-            vec![
-                CILOp::LdcI64(high),
-                CILOp::LdcI64(low),
-                CILOp::Call(CallSite::boxed(
-                    Some(i128_class),
-                    ".ctor".into(),
-                    ctor_sig,
-                    true,
-                )),
-            ]
-        }
+        UintTy::U128 => crate::int128::load_const(&Type::U128, value),
     }
 }
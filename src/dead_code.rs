@@ -0,0 +1,308 @@
+use crate::{
+    access_modifier::AccessModifer,
+    assembly::Assembly,
+    cil::{CILOp, CallSite},
+    function_sig::FnSig,
+    method::Method,
+    r#type::{DotnetTypeRef, Type, TypeDef},
+    IString,
+};
+use std::collections::{HashMap, HashSet};
+/// Number of items removed from an assembly by [`eliminate_dead_code`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RemovedItems {
+    /// Number of removed methods.
+    pub methods: usize,
+    /// Number of removed type definitions.
+    pub types: usize,
+    /// Number of removed static fields.
+    pub statics: usize,
+}
+/// Methods, types defined within the assembly, and static fields which can be reached from the roots of an assembly.
+#[derive(Default)]
+struct Reachable {
+    /// Call sites of the reachable methods.
+    methods: HashSet<CallSite>,
+    /// Names of the top-level types. Inner types are reachable with the types containing them.
+    types: HashSet<IString>,
+    /// Names of the reachable static fields.
+    statics: HashSet<IString>,
+    /// Reached methods whose ops were not visited yet.
+    to_visit: Vec<CallSite>,
+    /// Reached static fields whose initializers were not visited yet.
+    statics_to_visit: Vec<IString>,
+}
+impl Reachable {
+    /// Marks the method behind `site` as reachable, and queues it for visiting.
+    fn add_method(&mut self, site: &CallSite) {
+        if self.methods.insert(site.clone()) {
+            self.to_visit.push(site.clone());
+        }
+    }
+    /// Marks the static field `name` as reachable, and queues its initializer for visiting.
+    fn add_static(&mut self, name: &str) {
+        if self.statics.insert(name.into()) {
+            self.statics_to_visit.push(name.into());
+        }
+    }
+    /// Marks all the types defined within the assembly which `tpe` refers to as reachable.
+    fn add_type(&mut self, asm: &Assembly, tpe: &Type) {
+        match tpe {
+            Type::DotnetType(dref) => self.add_dotnet_type(asm, dref),
+            Type::DotnetArray(array) => self.add_type(asm, &array.element),
+            Type::Ptr(inner) => self.add_type(asm, inner),
+            Type::FnPtr(sig) => self.add_sig(asm, sig),
+            // Those types are emitted as references to types defined within the assembly.
+            Type::FnDef(name) => self.add_type_named(asm, &format!("fn_{name}")),
+            Type::Void => self.add_type_named(asm, "RustVoid"),
+            Type::Unresolved => self.add_type_named(asm, "Unresolved"),
+            Type::Foreign => self.add_type_named(asm, "Foreign"),
+            Type::I128 => self.add_dotnet_type(asm, &DotnetTypeRef::int_128()),
+            Type::U128 => self.add_dotnet_type(asm, &DotnetTypeRef::uint_128()),
+            _ => (),
+        }
+    }
+    /// Marks the type `dref` refers to, and its generic arguments, as reachable.
+    fn add_dotnet_type(&mut self, asm: &Assembly, dref: &DotnetTypeRef) {
+        if dref.asm().is_none() {
+            let top_level = dref.name_path().split('/').next().unwrap_or_default();
+            self.add_type_named(asm, top_level);
+        }
+        for generic in dref.generics() {
+            self.add_type(asm, generic);
+        }
+    }
+    /// Marks the top-level type called `name`, and everything its definition refers to, as reachable.
+    fn add_type_named(&mut self, asm: &Assembly, name: &str) {
+        if self.types.contains(name) {
+            return;
+        }
+        self.types.insert(name.into());
+        if let Some(type_def) = asm.get_typedef_by_path(name) {
+            self.add_type_def(asm, type_def);
+        }
+    }
+    /// Marks everything the definition of a reachable type refers to, including its methods and inner types, as reachable.
+    fn add_type_def(&mut self, asm: &Assembly, type_def: &TypeDef) {
+        for (_, tpe) in type_def.fields() {
+            self.add_type(asm, tpe);
+        }
+        if let Some(extends) = type_def.extends() {
+            self.add_dotnet_type(asm, extends);
+        }
+        for method in type_def.methods() {
+            self.add_method_body(asm, method);
+        }
+        for inner in type_def.inner_types() {
+            self.add_type_def(asm, inner);
+        }
+    }
+    /// Marks the types used by the signature `sig` as reachable.
+    fn add_sig(&mut self, asm: &Assembly, sig: &FnSig) {
+        for tpe in sig.inputs() {
+            self.add_type(asm, tpe);
+        }
+        self.add_type(asm, sig.output());
+    }
+    /// Marks the method behind `site`, or the class it belongs to, as reachable.
+    fn add_call_site(&mut self, asm: &Assembly, site: &CallSite) {
+        match site.class() {
+            Some(class) => self.add_dotnet_type(asm, class),
+            None => self.add_method(site),
+        }
+        self.add_sig(asm, site.signature());
+        for generic in site.generics() {
+            self.add_type(asm, generic);
+        }
+    }
+    /// Marks everything the signature, locals and ops of `method` refer to as reachable.
+    fn add_method_body(&mut self, asm: &Assembly, method: &Method) {
+        self.add_sig(asm, method.sig());
+        for (_, tpe) in method.locals() {
+            self.add_type(asm, tpe);
+        }
+        self.add_ops(asm, method.get_ops());
+    }
+    /// Marks the methods, types and static fields `ops` refer to as reachable.
+    fn add_ops(&mut self, asm: &Assembly, ops: &[CILOp]) {
+        for op in ops {
+            match op {
+                CILOp::Call(site)
+                | CILOp::CallVirt(site)
+                | CILOp::NewObj(site)
+                | CILOp::LDFtn(site) => self.add_call_site(asm, site),
+                CILOp::CallI(sig) => self.add_sig(asm, sig),
+                CILOp::LDField(field) | CILOp::LDFieldAdress(field) | CILOp::STField(field) => {
+                    self.add_dotnet_type(asm, field.owner());
+                    self.add_type(asm, field.tpe());
+                }
+                CILOp::LDStaticField(field) | CILOp::STStaticField(field) => {
+                    match field.owner() {
+                        Some(owner) => self.add_dotnet_type(asm, owner),
+                        None => self.add_static(field.name()),
+                    }
+                    self.add_type(asm, field.tpe());
+                }
+                CILOp::NewTMPLocal(tpe)
+                | CILOp::LdObj(tpe)
+                | CILOp::STObj(tpe)
+                | CILOp::SizeOf(tpe)
+                | CILOp::CastClass(tpe)
+                | CILOp::NewArr(tpe)
+                | CILOp::LDElem(tpe)
+                | CILOp::STElem(tpe)
+                | CILOp::LDElemA(tpe) => self.add_type(asm, tpe),
+                CILOp::BeginCatch(class) => self.add_dotnet_type(asm, class),
+                _ => (),
+            }
+        }
+    }
+}
+/// Removes the methods, types and static fields which can't be reached from the entrypoint of `asm`, following calls, function pointers,
+/// and references to fields and types. The initializer of a static field is only reached with the field, so it must run before the static constructor is added(see [`Assembly::add_static_ctor`]).
+/// If `keep_public` is set, public methods and types are kept too, since they may be used by other assemblies.
+pub fn eliminate_dead_code(asm: &mut Assembly, keep_public: bool) -> RemovedItems {
+    let mut reachable = Reachable::default();
+    for method in asm.methods() {
+        let is_root = method.is_entrypoint()
+            || asm.entrypoint() == Some(&method.call_site())
+            || (keep_public && method.access() == AccessModifer::Public);
+        if is_root {
+            reachable.add_method(&method.call_site());
+        }
+    }
+    if keep_public {
+        for type_def in asm.types() {
+            if type_def.access_modifier() == AccessModifer::Public {
+                reachable.add_type_named(asm, type_def.name());
+            }
+        }
+    }
+    let initializers: HashMap<&str, &[CILOp]> = asm
+        .static_initializers()
+        .map(|(name, ops)| (&**name, ops))
+        .collect();
+    loop {
+        if let Some(site) = reachable.to_visit.pop() {
+            // Methods which are not defined within the assembly are handled by the linker, before the dead code is eliminated.
+            if let Some(method) = asm.get_method(&site) {
+                reachable.add_method_body(asm, method);
+            }
+        } else if let Some(name) = reachable.statics_to_visit.pop() {
+            if let Some(ops) = initializers.get(&*name) {
+                reachable.add_ops(asm, ops);
+            }
+        } else {
+            break;
+        }
+    }
+    for (_, tpe) in asm
+        .globals()
+        .filter(|(name, _)| reachable.statics.contains(*name))
+    {
+        reachable.add_type(asm, tpe);
+    }
+    RemovedItems {
+        methods: asm.retain_methods(|site| reachable.methods.contains(site)),
+        types: asm.retain_types(|type_def| reachable.types.contains(type_def.name())),
+        statics: asm.retain_statics(|name| reachable.statics.contains(name)),
+    }
+}
+#[test]
+fn unreachable_items() {
+    let mut asm = Assembly::empty();
+    let sig = FnSig::new(&[], &Type::Void);
+    let mut main = Method::new(AccessModifer::Public, true, sig.clone(), "main", vec![]);
+    main.set_ops(vec![
+        CILOp::LDStaticField(crate::cil::StaticFieldDescriptor::boxed(
+            None,
+            Type::I32,
+            "used_static".into(),
+        )),
+        CILOp::Pop,
+        CILOp::LDStaticField(crate::cil::StaticFieldDescriptor::boxed(
+            None,
+            Type::Ptr(Type::U8.into()),
+            "used_alloc".into(),
+        )),
+        CILOp::Pop,
+        CILOp::Call(CallSite::boxed(None, "used".into(), sig.clone(), true)),
+        CILOp::Ret,
+    ]);
+    let used_type = Type::DotnetType(DotnetTypeRef::new(None, "UsedType").into());
+    let mut used = Method::new(
+        AccessModifer::Private,
+        true,
+        sig.clone(),
+        "used",
+        vec![(None, used_type)],
+    );
+    used.set_ops(vec![CILOp::Ret]);
+    let mut unused = Method::new(AccessModifer::Public, true, sig.clone(), "unused", vec![]);
+    unused.set_ops(vec![
+        CILOp::Call(CallSite::boxed(None, "used".into(), sig.clone(), true)),
+        CILOp::Ret,
+    ]);
+    // Methods only referenced by the initializers of static allocations, like vtable entries.
+    let alloc_method = |name: &str| {
+        let mut method = Method::new(AccessModifer::Private, true, sig.clone(), name, vec![]);
+        method.set_ops(vec![CILOp::Ret]);
+        method
+    };
+    let initializer = |alloc: &str, method: &str| {
+        vec![
+            CILOp::LDFtn(CallSite::boxed(None, method.into(), sig.clone(), true)),
+            CILOp::STStaticField(crate::cil::StaticFieldDescriptor::boxed(
+                None,
+                Type::Ptr(Type::U8.into()),
+                alloc.into(),
+            )),
+        ]
+    };
+    asm.add_method(main);
+    asm.add_method(used);
+    asm.add_method(unused);
+    asm.add_method(alloc_method("in_used_alloc"));
+    asm.add_method(alloc_method("in_unused_alloc"));
+    asm.add_static(Type::Ptr(Type::U8.into()), "used_alloc");
+    asm.add_static(Type::Ptr(Type::U8.into()), "unused_alloc");
+    asm.add_static_initializer("used_alloc", initializer("used_alloc", "in_used_alloc"));
+    asm.add_static_initializer(
+        "unused_alloc",
+        initializer("unused_alloc", "in_unused_alloc"),
+    );
+    asm.set_entrypoint(CallSite::new(None, "main".into(), sig, true));
+    asm.add_typedef(TypeDef::nameonly("UsedType"));
+    asm.add_typedef(TypeDef::nameonly("UnusedType"));
+    asm.add_static(Type::I32, "used_static");
+    asm.add_static(Type::I32, "unused_static");
+    let removed = eliminate_dead_code(&mut asm, false);
+    assert_eq!(
+        removed,
+        RemovedItems {
+            methods: 2,
+            types: 1,
+            statics: 2
+        }
+    );
+    assert!(!asm.contains_fn_named("unused"));
+    assert!(asm.contains_fn_named("entrypoint"));
+    assert!(asm.get_typedef_by_path("UsedType").is_some());
+    assert!(asm.contains_fn_named("in_used_alloc"));
+    assert!(!asm.contains_fn_named("in_unused_alloc"));
+    // Only the static which is used is initialized by the static constructor.
+    asm.add_static_ctor();
+    let cctor = asm
+        .methods()
+        .find(|method| method.name() == ".cctor")
+        .unwrap();
+    let stored: Vec<_> = cctor
+        .get_ops()
+        .iter()
+        .filter_map(|op| match op {
+            CILOp::STStaticField(field) => Some(field.name()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stored, ["used_alloc"]);
+}
@@ -0,0 +1,115 @@
+use crate::{
+    access_modifier::AccessModifer,
+    cil::{CILOp, CallSite},
+    method::Method,
+    IString,
+};
+use rustc_hir::def::DefKind;
+use rustc_middle::{
+    middle::codegen_fn_attrs::CodegenFnAttrFlags,
+    ty::{Instance, InstanceDef, TyCtxt},
+};
+use rustc_span::Symbol;
+/// Path of the tool attribute setting the class and name of an exported function, eg. `#[clr::dotnet_export(class = "MyLib.Api", name = "Compute")]`.
+/// Requires `#![register_tool(clr)]`.
+const EXPORT_ATTR: [&str; 2] = ["clr", "dotnet_export"];
+/// A Rust function exported as a public static method of a public class, so that it can be called from C# and other .NET languages.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Export {
+    /// Full name of the class, including its namespace, eg. `MyLib.Api`.
+    class: IString,
+    /// Name of the method.
+    name: IString,
+}
+impl Export {
+    /// Creates an export of a function as the method `name` of the class `class`.
+    pub fn new(class: &str, name: &str) -> Self {
+        Self {
+            class: class.into(),
+            name: name.into(),
+        }
+    }
+    /// Returns the export of the function behind `instance`, if it is `#[no_mangle]`, or has the `#[clr::dotnet_export]` attribute.
+    /// The class defaults to the name of the crate, and the name of the method to the name of the function.
+    pub fn of_instance<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
+        let InstanceDef::Item(def_id) = instance.def else {
+            return None;
+        };
+        if !matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn) {
+            return None;
+        }
+        let export_attr = EXPORT_ATTR.map(Symbol::intern);
+        let attr = tcx
+            .get_attrs_unchecked(def_id)
+            .iter()
+            .find(|attr| attr.path_matches(&export_attr));
+        // `compiler_builtins` defines a lot of `#[no_mangle]` functions, which are not meant to be used by .NET code.
+        let no_mangle = tcx
+            .codegen_fn_attrs(def_id)
+            .flags
+            .contains(CodegenFnAttrFlags::NO_MANGLE)
+            && !tcx.is_compiler_builtins(def_id.krate);
+        if attr.is_none() && !no_mangle {
+            return None;
+        }
+        let mut class = None;
+        let mut name = None;
+        for item in attr
+            .and_then(|attr| attr.meta_item_list())
+            .into_iter()
+            .flatten()
+        {
+            let value = item.meta_item().and_then(|meta| meta.value_str());
+            match (item.name_or_empty().as_str(), value) {
+                ("class", Some(value)) => class = Some(value),
+                ("name", Some(value)) => name = Some(value),
+                _ => {
+                    tcx.sess.dcx().span_err(
+                        item.span(),
+                        "expected `class = \"...\"` or `name = \"...\"` in `#[clr::dotnet_export]`",
+                    );
+                }
+            }
+        }
+        let class = class.unwrap_or_else(|| tcx.crate_name(def_id.krate));
+        let name = name.unwrap_or_else(|| tcx.item_name(def_id));
+        Some(Self::new(class.as_str(), name.as_str()))
+    }
+    /// Full name of the class the function is exported in.
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+    /// Name of the exported method.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Creates the public method forwarding its arguments to `target`, the function behind this export.
+    pub fn wrapper(&self, target: &CallSite) -> Method {
+        let sig = target.signature().clone();
+        let mut ops: Vec<_> = (0..sig.inputs().len() as u32).map(CILOp::LDArg).collect();
+        ops.push(CILOp::Call(target.clone().into()));
+        ops.push(CILOp::Ret);
+        let mut method = Method::new(AccessModifer::Public, true, sig, &self.name, vec![]);
+        method.set_ops(ops);
+        method
+    }
+}
+#[test]
+fn export_wrapper() {
+    use crate::{function_sig::FnSig, r#type::Type};
+    let sig = FnSig::new(&[Type::I32, Type::F64], &Type::F64);
+    let target = CallSite::new(None, "_ZN5mylib7compute17h0E".into(), sig.clone(), true);
+    let wrapper = Export::new("MyLib.Api", "Compute").wrapper(&target);
+    assert_eq!(wrapper.name(), "Compute");
+    assert_eq!(wrapper.access(), AccessModifer::Public);
+    assert_eq!(wrapper.sig(), &sig);
+    assert_eq!(
+        wrapper.get_ops(),
+        &[
+            CILOp::LDArg(0),
+            CILOp::LDArg(1),
+            CILOp::Call(target.into()),
+            CILOp::Ret
+        ]
+    );
+}
@@ -2,8 +2,10 @@ use crate::{
     codegen_error::CodegenError,
     r#type::{TyCache, Type},
 };
-use rustc_middle::ty::{Instance, List, ParamEnv, ParamEnvAnd, TyCtxt};
-use rustc_target::abi::call::Conv;
+use rustc_middle::ty::{
+    Instance, List, ParamEnv, ParamEnvAnd, PolyFnSig, Ty, TyCtxt, TypeVisitableExt,
+};
+use rustc_target::abi::call::{Conv, FnAbi};
 use serde::{Deserialize, Serialize};
 /// Function signature.
 #[derive(Clone, PartialEq, Serialize, Deserialize, Eq, Hash, Debug)]
@@ -18,21 +20,23 @@ impl FnSig {
         tcx: TyCtxt<'tcx>,
         tycache: &mut TyCache,
     ) -> Result<Self, CodegenError> {
+        // The layout of generic parameters is unknown, so the signature of a generic function is its declared one.
+        if function.args.has_param() {
+            return Ok(Self::sig_from_generic_instance(function, tcx, tycache));
+        }
         let fn_abi = tcx.fn_abi_of_instance(ParamEnvAnd {
             param_env: ParamEnv::reveal_all(),
             value: (function, List::empty()),
         });
         let fn_abi = match fn_abi {
             Ok(abi) => abi,
-            Err(_error) => todo!(),
+            Err(error) => {
+                return Err(CodegenError::Error(
+                    format!("Could not get the ABI of {function:?}: {error:?}").into(),
+                ))
+            }
         };
-        let conv = fn_abi.conv;
-        match conv {
-            Conv::Rust => (),
-            Conv::C => (),
-            _ => panic!("ERROR:calling using convention {conv:?} is not supported!"),
-        }
-        assert!(!fn_abi.c_variadic);
+        check_abi(fn_abi, &function)?;
         let ret = tycache.type_from_cache(fn_abi.ret.layout.ty, tcx, Some(function));
         let mut args = Vec::with_capacity(fn_abi.args.len());
         for arg in fn_abi.args.iter() {
@@ -43,6 +47,57 @@ impl FnSig {
             output: ret,
         })
     }
+    /// Returns the signature of the CIL generic method `function` is emitted as. See [`crate::generics`].
+    fn sig_from_generic_instance<'tcx>(
+        function: Instance<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        tycache: &mut TyCache,
+    ) -> Self {
+        let sig = tcx
+            .fn_sig(function.def_id())
+            .instantiate(tcx, function.args);
+        let sig = tcx.instantiate_bound_regions_with_erased(sig);
+        let inputs = sig
+            .inputs()
+            .iter()
+            .map(|input| tycache.type_from_cache(*input, tcx, Some(function)))
+            .collect();
+        let output = tycache.type_from_cache(sig.output(), tcx, Some(function));
+        Self { inputs, output }
+    }
+    /// Returns the signature of functions pointed to by function pointers of type `sig`.
+    pub fn sig_from_fn_ptr<'tcx>(
+        sig: PolyFnSig<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        tycache: &mut TyCache,
+    ) -> Result<Self, CodegenError> {
+        let fn_abi = tcx.fn_abi_of_fn_ptr(ParamEnvAnd {
+            param_env: ParamEnv::reveal_all(),
+            value: (sig, List::empty()),
+        });
+        let fn_abi = match fn_abi {
+            Ok(abi) => abi,
+            Err(error) => {
+                return Err(CodegenError::Error(
+                    format!(
+                        "Could not get the ABI of function pointers of type {sig:?}: {error:?}"
+                    )
+                    .into(),
+                ))
+            }
+        };
+        check_abi(fn_abi, &sig)?;
+        let ret = tycache.type_from_cache(fn_abi.ret.layout.ty, tcx, None);
+        let args = fn_abi
+            .args
+            .iter()
+            .map(|arg| tycache.type_from_cache(arg.layout.ty, tcx, None))
+            .collect();
+        Ok(Self {
+            inputs: args,
+            output: ret,
+        })
+    }
     /// Returns the list of function inputs.
     pub fn inputs(&self) -> &[Type] {
         &self.inputs
@@ -51,6 +106,13 @@ impl FnSig {
     pub fn output(&self) -> &Type {
         &self.output
     }
+    /// Replaces the types of the inputs and output, as specified by `map`. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        for input in &mut self.inputs {
+            input.map_types(map);
+        }
+        self.output.map_types(map);
+    }
     /// Creates a new function signature. For non-static functions, this must include the hidden first `this` argument!
     pub fn new(inputs: &[Type], output: &Type) -> Self {
         Self {
@@ -59,3 +121,20 @@ impl FnSig {
         }
     }
 }
+/// Checks if functions with the ABI `fn_abi` can be called. `callee` describes the function(or function pointer type) in the error message.
+fn check_abi(fn_abi: &FnAbi<Ty>, callee: &impl std::fmt::Debug) -> Result<(), CodegenError> {
+    match fn_abi.conv {
+        Conv::Rust | Conv::C => (),
+        conv => {
+            return Err(CodegenError::Error(
+                format!("Calling {callee:?} using convention {conv:?} is not supported!").into(),
+            ))
+        }
+    }
+    if fn_abi.c_variadic {
+        return Err(CodegenError::Error(
+            format!("Calling the variadic {callee:?} is not supported!").into(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,323 @@
+use crate::{
+    cil::CallSite,
+    function_sig::FnSig,
+    r#type::{TyCache, Type},
+};
+use rustc_hir::def::DefKind;
+use rustc_middle::{
+    mir::{
+        visit::Visitor, BinOp, CastKind, ConstOperand, Location, NonDivergingIntrinsic, Rvalue,
+        Statement, StatementKind, Terminator, TerminatorKind,
+    },
+    ty::{
+        AdtDef, GenericArgs, GenericArgsRef, Instance, InstanceDef, ParamEnv, Ty, TyCtxt, TyKind,
+        TypeVisitableExt,
+    },
+};
+use rustc_span::def_id::DefId;
+use rustc_target::spec::abi::Abi;
+/// If the `preserve-generics` option is set, and `instance` is an instantiation of a function which can be emitted as a CIL generic method,
+/// returns the generic instance(instantiated with its own generic parameters) of that function.
+pub fn preserved_instance<'tcx>(
+    instance: Instance<'tcx>,
+    tyctx: TyCtxt<'tcx>,
+    tycache: &mut TyCache,
+) -> Option<Instance<'tcx>> {
+    if !tycache.config().preserve_generics {
+        return None;
+    }
+    let InstanceDef::Item(def_id) = instance.def else {
+        return None;
+    };
+    if !is_preservable(def_id, tyctx, tycache, &mut Vec::new()) {
+        return None;
+    }
+    Some(Instance::new(
+        def_id,
+        GenericArgs::identity_for_item(tyctx, def_id),
+    ))
+}
+/// Returns the number of generic arguments of the CIL method `instance` is compiled into.
+pub fn generic_count(instance: Instance) -> u32 {
+    if instance.args.has_param() {
+        instance.args.len() as u32
+    } else {
+        0
+    }
+}
+/// Returns the call site refering to the CIL generic method `generic`, instantiated with the generic arguments of `instance`.
+pub fn generic_call_site<'tcx>(
+    instance: Instance<'tcx>,
+    generic: Instance<'tcx>,
+    tyctx: TyCtxt<'tcx>,
+    tycache: &mut TyCache,
+) -> CallSite {
+    let mut site = CallSite::new(
+        None,
+        crate::utilis::function_name(tyctx.symbol_name(generic)),
+        FnSig::sig_from_instance_(generic, tyctx, tycache)
+            .expect("Could not resolve the signature of a generic function"),
+        true,
+    );
+    let generics: Vec<_> = instance
+        .args
+        .types()
+        .map(|ty| tycache.type_from_cache(ty, tyctx, None))
+        .collect();
+    site.set_generics(generics);
+    site
+}
+/// Checks if the function `def_id` can be emitted as a single CIL generic method. This is the case if the layout of none of its locals depends on its
+/// generic parameters, and it never does anything requiring such a layout (eg. getting the size of a generic parameter, or dropping it).
+/// `visiting` contains the functions whose check is in progress, which are assumed to be preservable, so that recursion terminates.
+/// Results are cached in `tycache`, except for functions found preservable while relying on such an assumption.
+fn is_preservable(
+    def_id: DefId,
+    tyctx: TyCtxt,
+    tycache: &mut TyCache,
+    visiting: &mut Vec<DefId>,
+) -> bool {
+    if visiting.contains(&def_id) {
+        return true;
+    }
+    if let Some(preservable) = tycache.preservable(def_id) {
+        return preservable;
+    }
+    let preservable = check_preservable(def_id, tyctx, tycache, visiting);
+    // Assuming other functions are preservable can only make a function be found preservable, so negative results are always valid.
+    if !preservable || visiting.is_empty() {
+        tycache.set_preservable(def_id, preservable);
+    }
+    preservable
+}
+/// Performs the check of [`is_preservable`], without using the cache.
+fn check_preservable(
+    def_id: DefId,
+    tyctx: TyCtxt,
+    tycache: &mut TyCache,
+    visiting: &mut Vec<DefId>,
+) -> bool {
+    if !matches!(tyctx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn)
+        || !tyctx.is_mir_available(def_id)
+    {
+        return false;
+    }
+    // Only type parameters are supported, since they map directly to CIL generic parameters.
+    let param_env = tyctx.param_env(def_id);
+    let identity = GenericArgs::identity_for_item(tyctx, def_id);
+    if identity.is_empty()
+        || !identity.iter().all(|arg| {
+            arg.as_type()
+                .is_some_and(|ty| ty.is_sized(tyctx, param_env))
+        })
+    {
+        return false;
+    }
+    let sig = tyctx.fn_sig(def_id).skip_binder();
+    if sig.abi() != Abi::Rust || sig.c_variadic() {
+        return false;
+    }
+    if InstanceDef::Item(def_id).requires_caller_location(tyctx) {
+        return false;
+    }
+    let body = tyctx.instance_mir(InstanceDef::Item(def_id));
+    if body.spread_arg.is_some()
+        || !body
+            .local_decls
+            .iter()
+            .all(|local| is_ty_preservable(local.ty, tyctx))
+    {
+        return false;
+    }
+    visiting.push(def_id);
+    let mut checker = PreservableChecker {
+        tyctx,
+        body,
+        param_env,
+        tycache,
+        visiting,
+        preservable: true,
+    };
+    checker.visit_body(body);
+    let preservable = checker.preservable;
+    visiting.pop();
+    preservable
+}
+/// Checks if a local of type `ty` can be a part of a CIL generic method: `ty` must either not depend on generic parameters, or its layout must
+/// be known without knowing them.
+fn is_ty_preservable<'tcx>(ty: Ty<'tcx>, tyctx: TyCtxt<'tcx>) -> bool {
+    if !ty.has_param() {
+        return true;
+    }
+    match ty.kind() {
+        TyKind::Param(_) => true,
+        TyKind::Ref(_, inner, _) => matches!(inner.kind(), TyKind::Param(_)),
+        TyKind::RawPtr(type_and_mut) => matches!(type_and_mut.ty.kind(), TyKind::Param(_)),
+        TyKind::Adt(adt, subst) => {
+            is_adt_shared(*adt, tyctx)
+                && subst
+                    .types()
+                    .all(|arg| matches!(arg.kind(), TyKind::Param(_)) || !arg.has_param())
+        }
+        _ => false,
+    }
+}
+/// Walks the body of a function, checking if any of its statements or terminators depend on the layout of a generic parameter.
+struct PreservableChecker<'tcx, 'visiting> {
+    tyctx: TyCtxt<'tcx>,
+    body: &'tcx rustc_middle::mir::Body<'tcx>,
+    param_env: ParamEnv<'tcx>,
+    tycache: &'visiting mut TyCache,
+    visiting: &'visiting mut Vec<DefId>,
+    preservable: bool,
+}
+impl<'tcx, 'visiting> Visitor<'tcx> for PreservableChecker<'tcx, 'visiting> {
+    fn visit_statement(&mut self, statement: &Statement<'tcx>, location: Location) {
+        if let StatementKind::Intrinsic(intrinsic) = &statement.kind {
+            if let NonDivergingIntrinsic::CopyNonOverlapping(copy) = intrinsic.as_ref() {
+                // The size of the copied memory depends on the size of the pointee.
+                if copy.src.ty(self.body, self.tyctx).has_param() {
+                    self.preservable = false;
+                }
+            }
+        }
+        self.super_statement(statement, location);
+    }
+    fn visit_rvalue(&mut self, rvalue: &Rvalue<'tcx>, location: Location) {
+        let depends_on_layout = match rvalue {
+            Rvalue::NullaryOp(_, ty) => ty.has_param(),
+            Rvalue::Cast(kind, operand, target) => {
+                !matches!(kind, CastKind::PtrToPtr)
+                    && (target.has_param() || operand.ty(self.body, self.tyctx).has_param())
+            }
+            Rvalue::BinaryOp(BinOp::Offset, operands)
+            | Rvalue::CheckedBinaryOp(BinOp::Offset, operands) => {
+                operands.0.ty(self.body, self.tyctx).has_param()
+            }
+            _ => false,
+        };
+        if depends_on_layout {
+            self.preservable = false;
+        }
+        self.super_rvalue(rvalue, location);
+    }
+    fn visit_constant(&mut self, constant: &ConstOperand<'tcx>, location: Location) {
+        let ty = constant.const_.ty();
+        // Functions are zero-sized constants, whose calls are checked separately.
+        if ty.has_param() && !matches!(ty.kind(), TyKind::FnDef(..)) {
+            self.preservable = false;
+        }
+        self.super_constant(constant, location);
+    }
+    fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
+        match &terminator.kind {
+            TerminatorKind::Drop { place, .. } => {
+                // Drop glue is generated per type, so it can't be called for a generic parameter.
+                if place.ty(self.body, self.tyctx).ty.has_param() {
+                    self.preservable = false;
+                }
+            }
+            TerminatorKind::Call { func, .. } => {
+                let fn_ty = func.ty(self.body, self.tyctx);
+                if let TyKind::FnDef(def_id, args) = fn_ty.kind() {
+                    if args.has_param() && !self.is_call_preservable(*def_id, args) {
+                        self.preservable = false;
+                    }
+                }
+            }
+            _ => (),
+        }
+        self.super_terminator(terminator, location);
+    }
+}
+impl<'tcx, 'visiting> PreservableChecker<'tcx, 'visiting> {
+    /// Checks if a call to `def_id` with generic arguments `args` can be made from a CIL generic method. The callee must itself be preservable.
+    fn is_call_preservable(&mut self, def_id: DefId, args: GenericArgsRef<'tcx>) -> bool {
+        match Instance::resolve(self.tyctx, self.param_env, def_id, args) {
+            Ok(Some(Instance {
+                def: InstanceDef::Item(callee),
+                ..
+            })) => is_preservable(callee, self.tyctx, self.tycache, self.visiting),
+            // Trait methods of generic parameters, intrinsics and shims depend on the exact type.
+            _ => false,
+        }
+    }
+}
+/// Checks if `adt` can be emitted as a single CIL generic class, instantiated by the runtime, when the `preserve-generics` option is set.
+/// This is the case for structs with only type parameters, whose layout does not depend on them.
+pub fn is_adt_shared(adt: AdtDef, tyctx: TyCtxt) -> bool {
+    if !adt.is_struct() {
+        return false;
+    }
+    let identity = GenericArgs::identity_for_item(tyctx, adt.did());
+    if identity.is_empty() || !identity.iter().all(|arg| arg.as_type().is_some()) {
+        return false;
+    }
+    let param_env = tyctx.param_env(adt.did());
+    adt.all_fields().all(|field| {
+        let field_ty = tyctx.type_of(field.did).instantiate_identity();
+        if !field_ty.has_param() {
+            return true;
+        }
+        match field_ty.kind() {
+            // A pointer to a sized type is always thin.
+            TyKind::Ref(_, inner, _) => {
+                matches!(inner.kind(), TyKind::Param(_)) && inner.is_sized(tyctx, param_env)
+            }
+            TyKind::RawPtr(type_and_mut) => {
+                matches!(type_and_mut.ty.kind(), TyKind::Param(_))
+                    && type_and_mut.ty.is_sized(tyctx, param_env)
+            }
+            TyKind::Adt(inner, subst) => {
+                *inner != adt
+                    && is_adt_shared(*inner, tyctx)
+                    && subst
+                        .types()
+                        .all(|arg| matches!(arg.kind(), TyKind::Param(_)) || !arg.has_param())
+            }
+            _ => false,
+        }
+    })
+}
+/// Checks if the `preserve-generics` option is set, and the instantiation of the shared ADT `adt` with `subst` can be refered to: all of its
+/// generic arguments must be representable as .NET types.
+pub fn is_instance_shared<'tcx>(
+    adt: AdtDef<'tcx>,
+    subst: GenericArgsRef<'tcx>,
+    tyctx: TyCtxt<'tcx>,
+    tycache: &TyCache,
+) -> bool {
+    tycache.config().preserve_generics
+        && is_adt_shared(adt, tyctx)
+        && subst
+            .types()
+            .all(|ty| ty.has_param() || ty.is_sized(tyctx, ParamEnv::reveal_all()))
+}
+/// Turns the generic arguments of a method into the generic arguments of a class. Used for the fields of shared ADTs, whose types are
+/// resolved in the same way as types used within methods.
+pub fn class_generics(tpe: Type) -> Type {
+    match tpe {
+        Type::MethodGenericArg(idx) => Type::GenericArg(idx),
+        Type::Ptr(inner) => Type::Ptr(class_generics(*inner).into()),
+        Type::DotnetType(mut dotnet) => {
+            let generics: Vec<_> = dotnet
+                .generics()
+                .iter()
+                .cloned()
+                .map(class_generics)
+                .collect();
+            dotnet.set_generics(generics);
+            Type::DotnetType(dotnet)
+        }
+        _ => tpe,
+    }
+}
+#[test]
+fn class_generics_nested() {
+    let mut dotnet = crate::r#type::DotnetTypeRef::new(None, "Wrapper");
+    dotnet.set_generics([Type::Ptr(Type::MethodGenericArg(1).into())]);
+    let mut expected = crate::r#type::DotnetTypeRef::new(None, "Wrapper");
+    expected.set_generics([Type::Ptr(Type::GenericArg(1).into())]);
+    assert_eq!(class_generics(dotnet.into()), expected.into());
+    assert_eq!(class_generics(Type::I32), Type::I32);
+}
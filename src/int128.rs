@@ -0,0 +1,1072 @@
+use crate::{
+    access_modifier::AccessModifer,
+    assembly::Assembly,
+    cil::{CILOp, CallSite, FieldDescriptor},
+    function_sig::FnSig,
+    method::{LocalDef, Method},
+    r#type::{DotnetTypeRef, Type, TypeDef},
+};
+use rustc_middle::mir::BinOp;
+use rustc_middle::ty::{IntTy, Ty, TyKind, UintTy};
+/// Name of the type used in place of `System.Int128` if the `software-128-bit-ints` option is set.
+pub const SOFTWARE_I128_NAME: &str = "RustI128";
+/// Name of the type used in place of `System.UInt128` if the `software-128-bit-ints` option is set.
+pub const SOFTWARE_U128_NAME: &str = "RustU128";
+/// Returns the type implementing the 128 bit intiger type `tpe` in software.
+fn software_class(tpe: &Type) -> DotnetTypeRef {
+    match tpe {
+        Type::I128 => DotnetTypeRef::new(None, SOFTWARE_I128_NAME),
+        Type::U128 => DotnetTypeRef::new(None, SOFTWARE_U128_NAME),
+        _ => panic!("{tpe:?} is not a 128 bit intiger!"),
+    }
+}
+/// 2^64, as a float.
+const TWO_POW_64: f64 = 18_446_744_073_709_551_616.0;
+/// Returns the .NET type implementing the 128 bit intiger type `tpe`.
+pub fn class(tpe: &Type) -> DotnetTypeRef {
+    match tpe {
+        Type::I128 => DotnetTypeRef::int_128(),
+        Type::U128 => DotnetTypeRef::uint_128(),
+        _ => panic!("{tpe:?} is not a 128 bit intiger!"),
+    }
+}
+/// If `ty` is a 128 bit intiger, returns its type.
+pub fn int128_type(ty: Ty) -> Option<Type> {
+    match ty.kind() {
+        TyKind::Int(IntTy::I128) => Some(Type::I128),
+        TyKind::Uint(UintTy::U128) => Some(Type::U128),
+        _ => None,
+    }
+}
+/// Returns a call to the static operator `name` of the 128 bit intiger type `tpe`.
+pub fn operator(tpe: &Type, name: &str, inputs: &[Type], output: &Type) -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        Some(class(tpe)),
+        name.into(),
+        FnSig::new(inputs, output),
+        true,
+    ))
+}
+/// Returns a call to the binary operator `name`(eg. `op_Addition`) of `tpe`.
+pub fn binop(tpe: &Type, name: &str) -> CILOp {
+    operator(tpe, name, &[tpe.clone(), tpe.clone()], tpe)
+}
+/// Returns a call to the comparison operator `name`(eg. `op_LessThan`) of `tpe`.
+pub fn cmp(tpe: &Type, name: &str) -> CILOp {
+    operator(tpe, name, &[tpe.clone(), tpe.clone()], &Type::Bool)
+}
+/// Returns a call to the shift operator `name` of `tpe`. The shift amount is an `i32`.
+pub fn shift(tpe: &Type, name: &str) -> CILOp {
+    operator(tpe, name, &[tpe.clone(), Type::I32], tpe)
+}
+/// Returns a call to the unary operator `name`(eg. `op_UnaryNegation`) of `tpe`.
+pub fn unop(tpe: &Type, name: &str) -> CILOp {
+    operator(tpe, name, &[tpe.clone()], tpe)
+}
+/// Constructor of `tpe`, taking the upper and lower 64 bits of the value.
+fn ctor(tpe: &Type) -> CallSite {
+    let class = class(tpe);
+    CallSite::new(
+        Some(class.clone()),
+        ".ctor".into(),
+        FnSig::new(&[class.into(), Type::U64, Type::U64], &Type::Void),
+        false,
+    )
+}
+/// Returns the ops loading the constant `value` of the 128 bit intiger type `tpe`.
+pub fn load_const(tpe: &Type, value: u128) -> Vec<CILOp> {
+    vec![
+        CILOp::LdcI64((value >> 64) as u64 as i64),
+        CILOp::LdcI64(value as u64 as i64),
+        CILOp::NewObj(ctor(tpe).into()),
+    ]
+}
+/// Name of the operator implementing `op`.
+fn operator_name(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "op_Addition",
+        BinOp::Sub => "op_Subtraction",
+        BinOp::Mul | BinOp::MulUnchecked => "op_Multiply",
+        _ => panic!("Can't preform checked {op:?} on 128 bit intigers!"),
+    }
+}
+/// Call site of the method checking if `op` overflows for the 2 values of type `tpe` it takes.
+fn overflow_check_site(tpe: &Type, op: BinOp) -> CallSite {
+    let prefix = if *tpe == Type::I128 { "i128" } else { "u128" };
+    CallSite::new(
+        None,
+        format!("{prefix}_{op}_overflows", op = operator_name(op)).into(),
+        FnSig::new(&[tpe.clone(), tpe.clone()], &Type::Bool),
+        true,
+    )
+}
+/// Preforms the checked operation `op` on the 2 values of type `tpe` on top of the stack, returning a tuple of the wrapped result and a flag indicating overflow.
+pub fn checked(tpe: Type, op: BinOp) -> Vec<CILOp> {
+    let tuple = crate::r#type::simple_tuple(&[tpe.clone(), Type::Bool]);
+    let tuple_ty: Type = tuple.clone().into();
+    vec![
+        // Save the operands
+        CILOp::NewTMPLocal(tpe.clone().into()),
+        CILOp::SetTMPLocal,
+        CILOp::NewTMPLocal(tpe.clone().into()),
+        CILOp::SetTMPLocal,
+        CILOp::NewTMPLocal(tuple_ty.into()),
+        // Set the tuples first field to the wrapped result
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::LoadUnderTMPLocal(1),
+        CILOp::LoadUnderTMPLocal(2),
+        binop(&tpe, operator_name(op)),
+        CILOp::STField(FieldDescriptor::boxed(
+            tuple.clone(),
+            Type::GenericArg(0),
+            "Item1".into(),
+        )),
+        // Set the tuples second field to the overflow flag
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::LoadUnderTMPLocal(1),
+        CILOp::LoadUnderTMPLocal(2),
+        CILOp::Call(overflow_check_site(&tpe, op).into()),
+        CILOp::STField(FieldDescriptor::boxed(
+            tuple.clone(),
+            Type::GenericArg(1),
+            "Item2".into(),
+        )),
+        // Load results
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::FreeTMPLocal,
+    ]
+}
+/// Creates the method checking if `op` overflows for 2 values of type `tpe`.
+fn overflow_check(tpe: &Type, op: BinOp) -> Method {
+    let signed = *tpe == Type::I128;
+    let zero = load_const(tpe, 0);
+    let (locals, ops): (Vec<LocalDef>, Vec<CILOp>) = match (op, signed) {
+        // `a + b` wraps around to a value smaller than `a`.
+        (BinOp::Add, false) => (
+            vec![],
+            vec![
+                CILOp::LDArg(0),
+                CILOp::LDArg(1),
+                binop(tpe, "op_Addition"),
+                CILOp::LDArg(0),
+                cmp(tpe, "op_LessThan"),
+                CILOp::Ret,
+            ],
+        ),
+        (BinOp::Sub, false) => (
+            vec![],
+            vec![
+                CILOp::LDArg(0),
+                CILOp::LDArg(1),
+                cmp(tpe, "op_LessThan"),
+                CILOp::Ret,
+            ],
+        ),
+        // The sign of `a + b` differs from the signs of both `a` and `b`.
+        (BinOp::Add, true) => (
+            vec![(None, tpe.clone())],
+            [
+                vec![
+                    CILOp::LDArg(0),
+                    CILOp::LDArg(1),
+                    binop(tpe, "op_Addition"),
+                    CILOp::STLoc(0),
+                    CILOp::LDArg(0),
+                    CILOp::LDLoc(0),
+                    binop(tpe, "op_ExclusiveOr"),
+                    CILOp::LDArg(1),
+                    CILOp::LDLoc(0),
+                    binop(tpe, "op_ExclusiveOr"),
+                    binop(tpe, "op_BitwiseAnd"),
+                ],
+                zero,
+                vec![cmp(tpe, "op_LessThan"), CILOp::Ret],
+            ]
+            .concat(),
+        ),
+        // The signs of `a` and `b` differ, and the sign of `a - b` differs from the sign of `a`.
+        (BinOp::Sub, true) => (
+            vec![(None, tpe.clone())],
+            [
+                vec![
+                    CILOp::LDArg(0),
+                    CILOp::LDArg(1),
+                    binop(tpe, "op_Subtraction"),
+                    CILOp::STLoc(0),
+                    CILOp::LDArg(0),
+                    CILOp::LDArg(1),
+                    binop(tpe, "op_ExclusiveOr"),
+                    CILOp::LDArg(0),
+                    CILOp::LDLoc(0),
+                    binop(tpe, "op_ExclusiveOr"),
+                    binop(tpe, "op_BitwiseAnd"),
+                ],
+                zero,
+                vec![cmp(tpe, "op_LessThan"), CILOp::Ret],
+            ]
+            .concat(),
+        ),
+        // `(a * b) / a != b`. Since `MIN / -1` overflows itself, multiplying by -1 is checked separately.
+        (BinOp::Mul, _) => {
+            let mut ops = [
+                vec![CILOp::LDArg(0)],
+                zero,
+                vec![cmp(tpe, "op_Equality"), CILOp::BTrue(0)],
+            ]
+            .concat();
+            if signed {
+                ops.push(CILOp::LDArg(0));
+                ops.extend(load_const(tpe, u128::MAX));
+                ops.extend([cmp(tpe, "op_Equality"), CILOp::BTrue(1)]);
+            }
+            ops.extend([
+                CILOp::LDArg(0),
+                CILOp::LDArg(1),
+                binop(tpe, "op_Multiply"),
+                CILOp::LDArg(0),
+                binop(tpe, "op_Division"),
+                CILOp::LDArg(1),
+                cmp(tpe, "op_Inequality"),
+                CILOp::Ret,
+                CILOp::Label(0),
+                CILOp::LdcI32(0),
+                CILOp::Ret,
+            ]);
+            if signed {
+                ops.extend([CILOp::Label(1), CILOp::LDArg(1)]);
+                ops.extend(load_const(tpe, 1 << 127));
+                ops.extend([cmp(tpe, "op_Equality"), CILOp::Ret]);
+            }
+            (vec![], ops)
+        }
+        _ => panic!("Can't preform checked {op:?} on 128 bit intigers!"),
+    };
+    let site = overflow_check_site(tpe, op);
+    let mut method = Method::new(
+        AccessModifer::Private,
+        true,
+        site.signature().clone(),
+        site.name(),
+        locals,
+    );
+    method.set_ops(ops);
+    method
+}
+/// Inserts the methods used by checked 128 bit arithmetic into `asm`.
+pub fn insert_int128(asm: &mut Assembly) {
+    for tpe in [Type::I128, Type::U128] {
+        for op in [BinOp::Add, BinOp::Sub, BinOp::Mul] {
+            asm.add_method(overflow_check(&tpe, op));
+        }
+    }
+}
+/// Makes the linked assembly `asm` use 128 bit intigers implemented in software instead of `System.Int128` and `System.UInt128`. Used by the linker if the `software-128-bit-ints` option is set.
+/// The codegen always refers to the `System` types, whose operators the software types mirror, so the choice does not affect the compiled crates.
+pub fn use_software_int128(asm: &mut Assembly) {
+    for tpe in [Type::I128, Type::U128] {
+        asm.add_typedef(software_int128(&tpe));
+    }
+    let (int_128, uint_128) = (DotnetTypeRef::int_128(), DotnetTypeRef::uint_128());
+    asm.map_types(&|tpe| match tpe {
+        Type::I128 => Some(software_class(&Type::I128).into()),
+        Type::U128 => Some(software_class(&Type::U128).into()),
+        Type::DotnetType(dref) if **dref == int_128 => Some(software_class(&Type::I128).into()),
+        Type::DotnetType(dref) if **dref == uint_128 => Some(software_class(&Type::U128).into()),
+        _ => None,
+    });
+}
+/// Creates a public static method.
+fn static_method(
+    name: &str,
+    inputs: &[Type],
+    output: &Type,
+    locals: Vec<LocalDef>,
+    ops: Vec<CILOp>,
+) -> Method {
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(inputs, output),
+        name,
+        locals,
+    );
+    method.set_ops(ops);
+    method
+}
+/// Creates a type implementing all the operators of the 128 bit intiger `tpe` used by the codegen, for runtimes without `System.Int128` and `System.UInt128`.
+/// The value is stored as 2 64 bit halves. Multiplication and division are implemented using shifts and additions.
+/// Like the code using it, the type refers to `tpe` using the `System` type, which is replaced by [`use_software_int128`].
+fn software_int128(tpe: &Type) -> TypeDef {
+    let signed = *tpe == Type::I128;
+    let class = class(tpe);
+    let low = FieldDescriptor::new(class.clone(), Type::U64, "low".into());
+    let high = FieldDescriptor::new(class.clone(), Type::U64, "high".into());
+    let lo = |arg: u32| vec![CILOp::LDArg(arg), CILOp::LDField(low.clone().into())];
+    let hi = |arg: u32| vec![CILOp::LDArg(arg), CILOp::LDField(high.clone().into())];
+    let new = CILOp::NewObj(ctor(tpe).into());
+    let zero = || load_const(tpe, 0);
+    let one = || load_const(tpe, 1);
+    let binary = [tpe.clone(), tpe.clone()];
+    let shift_args = [tpe.clone(), Type::I32];
+    let mut methods = vec![];
+    // .ctor(upper, lower)
+    let ctor_site = ctor(tpe);
+    let mut ctor_method = Method::new(
+        AccessModifer::Public,
+        false,
+        ctor_site.signature().clone(),
+        ".ctor",
+        vec![],
+    );
+    ctor_method.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::STField(high.clone().into()),
+        CILOp::LDArg(0),
+        CILOp::LDArg(2),
+        CILOp::STField(low.clone().into()),
+        CILOp::Ret,
+    ]);
+    methods.push(ctor_method);
+    // The carry is set if the lower half of the sum is smaller than the lower half of `a`.
+    methods.push(static_method(
+        "op_Addition",
+        &binary,
+        tpe,
+        vec![(None, Type::U64)],
+        [
+            lo(0),
+            lo(1),
+            vec![CILOp::Add, CILOp::STLoc(0)],
+            hi(0),
+            hi(1),
+            vec![CILOp::Add, CILOp::LDLoc(0)],
+            lo(0),
+            vec![
+                CILOp::LtUn,
+                CILOp::ConvU64(false),
+                CILOp::Add,
+                CILOp::LDLoc(0),
+                new.clone(),
+                CILOp::Ret,
+            ],
+        ]
+        .concat(),
+    ));
+    methods.push(static_method(
+        "op_Subtraction",
+        &binary,
+        tpe,
+        vec![(None, Type::U64)],
+        [
+            lo(0),
+            lo(1),
+            vec![CILOp::Sub, CILOp::STLoc(0)],
+            hi(0),
+            hi(1),
+            vec![CILOp::Sub],
+            lo(0),
+            lo(1),
+            vec![
+                CILOp::LtUn,
+                CILOp::ConvU64(false),
+                CILOp::Sub,
+                CILOp::LDLoc(0),
+                new.clone(),
+                CILOp::Ret,
+            ],
+        ]
+        .concat(),
+    ));
+    for (name, op) in [
+        ("op_BitwiseAnd", CILOp::And),
+        ("op_BitwiseOr", CILOp::Or),
+        ("op_ExclusiveOr", CILOp::XOr),
+    ] {
+        methods.push(static_method(
+            name,
+            &binary,
+            tpe,
+            vec![],
+            [
+                hi(0),
+                hi(1),
+                vec![op.clone()],
+                lo(0),
+                lo(1),
+                vec![op, new.clone(), CILOp::Ret],
+            ]
+            .concat(),
+        ));
+    }
+    methods.push(static_method(
+        "op_OnesComplement",
+        &[tpe.clone()],
+        tpe,
+        vec![],
+        [
+            hi(0),
+            vec![CILOp::Not],
+            lo(0),
+            vec![CILOp::Not, new.clone(), CILOp::Ret],
+        ]
+        .concat(),
+    ));
+    methods.push(static_method(
+        "op_UnaryNegation",
+        &[tpe.clone()],
+        tpe,
+        vec![],
+        [
+            zero(),
+            vec![CILOp::LDArg(0), binop(tpe, "op_Subtraction"), CILOp::Ret],
+        ]
+        .concat(),
+    ));
+    methods.push(static_method(
+        "op_Equality",
+        &binary,
+        &Type::Bool,
+        vec![],
+        [
+            hi(0),
+            hi(1),
+            vec![CILOp::Eq],
+            lo(0),
+            lo(1),
+            vec![CILOp::Eq, CILOp::And, CILOp::Ret],
+        ]
+        .concat(),
+    ));
+    methods.push(static_method(
+        "op_Inequality",
+        &binary,
+        &Type::Bool,
+        vec![],
+        vec![
+            CILOp::LDArg(0),
+            CILOp::LDArg(1),
+            cmp(tpe, "op_Equality"),
+            CILOp::LdcI32(0),
+            CILOp::Eq,
+            CILOp::Ret,
+        ],
+    ));
+    // Only the upper half holds the sign.
+    let hi_lt = if signed { CILOp::Lt } else { CILOp::LtUn };
+    methods.push(static_method(
+        "op_LessThan",
+        &binary,
+        &Type::Bool,
+        vec![],
+        [
+            hi(0),
+            hi(1),
+            vec![hi_lt],
+            hi(0),
+            hi(1),
+            vec![CILOp::Eq],
+            lo(0),
+            lo(1),
+            vec![CILOp::LtUn, CILOp::And, CILOp::Or, CILOp::Ret],
+        ]
+        .concat(),
+    ));
+    methods.push(static_method(
+        "op_GreaterThan",
+        &binary,
+        &Type::Bool,
+        vec![],
+        vec![
+            CILOp::LDArg(1),
+            CILOp::LDArg(0),
+            cmp(tpe, "op_LessThan"),
+            CILOp::Ret,
+        ],
+    ));
+    // Masks the shift amount, returns `a` if it is 0, and jumps to label 0 if it is at least 64.
+    let shift_prologue = vec![
+        CILOp::LDArg(1),
+        CILOp::LdcI32(127),
+        CILOp::And,
+        CILOp::STArg(1),
+        CILOp::LDArg(1),
+        CILOp::BZero(1),
+        CILOp::LDArg(1),
+        CILOp::LdcI32(64),
+        CILOp::BGe(0),
+    ];
+    methods.push(static_method(
+        "op_LeftShift",
+        &shift_args,
+        tpe,
+        vec![],
+        [
+            shift_prologue.clone(),
+            hi(0),
+            vec![CILOp::LDArg(1), CILOp::Shl],
+            lo(0),
+            vec![
+                CILOp::LdcI32(64),
+                CILOp::LDArg(1),
+                CILOp::Sub,
+                CILOp::ShrUn,
+                CILOp::Or,
+            ],
+            lo(0),
+            vec![CILOp::LDArg(1), CILOp::Shl, new.clone(), CILOp::Ret],
+            vec![CILOp::Label(0)],
+            lo(0),
+            vec![
+                CILOp::LDArg(1),
+                CILOp::LdcI32(64),
+                CILOp::Sub,
+                CILOp::Shl,
+                CILOp::LdcI64(0),
+                new.clone(),
+                CILOp::Ret,
+                CILOp::Label(1),
+                CILOp::LDArg(0),
+                CILOp::Ret,
+            ],
+        ]
+        .concat(),
+    ));
+    let shr = if signed { CILOp::Shr } else { CILOp::ShrUn };
+    // The bits shifted in from the left are copies of the sign bit for signed intigers, and zeroes otherwise.
+    let fill = if signed {
+        [hi(0), vec![CILOp::LdcI32(63), CILOp::Shr]].concat()
+    } else {
+        vec![CILOp::LdcI64(0)]
+    };
+    methods.push(static_method(
+        "op_RightShift",
+        &shift_args,
+        tpe,
+        vec![],
+        [
+            shift_prologue,
+            hi(0),
+            vec![CILOp::LDArg(1), shr.clone()],
+            lo(0),
+            vec![CILOp::LDArg(1), CILOp::ShrUn],
+            hi(0),
+            vec![
+                CILOp::LdcI32(64),
+                CILOp::LDArg(1),
+                CILOp::Sub,
+                CILOp::Shl,
+                CILOp::Or,
+                new.clone(),
+                CILOp::Ret,
+                CILOp::Label(0),
+            ],
+            fill,
+            hi(0),
+            vec![
+                CILOp::LDArg(1),
+                CILOp::LdcI32(64),
+                CILOp::Sub,
+                shr,
+                new.clone(),
+                CILOp::Ret,
+                CILOp::Label(1),
+                CILOp::LDArg(0),
+                CILOp::Ret,
+            ],
+        ]
+        .concat(),
+    ));
+    // Conversions between the 2 128 bit intiger types keep all the bits.
+    let other = if signed { Type::U128 } else { Type::I128 };
+    methods.push(static_method(
+        "op_Explicit",
+        &[tpe.clone()],
+        &other,
+        vec![],
+        [
+            hi(0),
+            lo(0),
+            vec![CILOp::NewObj(ctor(&other).into()), CILOp::Ret],
+        ]
+        .concat(),
+    ));
+    // Conversions from smaller intigers. Signed ones are sign extended, unsigned ones are zero extended.
+    for src in [Type::I8, Type::I16, Type::I32, Type::I64, Type::ISize] {
+        let name = if signed { "op_Implicit" } else { "op_Explicit" };
+        methods.push(static_method(
+            name,
+            &[src],
+            tpe,
+            vec![],
+            vec![
+                CILOp::LDArg(0),
+                CILOp::ConvI64(false),
+                CILOp::LdcI32(63),
+                CILOp::Shr,
+                CILOp::LDArg(0),
+                CILOp::ConvI64(false),
+                new.clone(),
+                CILOp::Ret,
+            ],
+        ));
+    }
+    for src in [Type::U8, Type::U16, Type::U32, Type::U64, Type::USize] {
+        methods.push(static_method(
+            "op_Implicit",
+            &[src],
+            tpe,
+            vec![],
+            vec![
+                CILOp::LdcI64(0),
+                CILOp::LDArg(0),
+                CILOp::ConvU64(false),
+                new.clone(),
+                CILOp::Ret,
+            ],
+        ));
+    }
+    // Conversions to smaller intigers truncate the value.
+    for target in [
+        Type::I8,
+        Type::U8,
+        Type::I16,
+        Type::U16,
+        Type::I32,
+        Type::U32,
+        Type::I64,
+        Type::U64,
+        Type::ISize,
+        Type::USize,
+    ] {
+        methods.push(static_method(
+            "op_Explicit",
+            &[tpe.clone()],
+            &target,
+            vec![],
+            [
+                lo(0),
+                crate::casts::to_int(target.clone()),
+                vec![CILOp::Ret],
+            ]
+            .concat(),
+        ));
+    }
+    let to_unsigned = operator(&Type::I128, "op_Explicit", &[Type::I128], &Type::U128);
+    let to_signed = operator(&Type::U128, "op_Explicit", &[Type::U128], &Type::I128);
+    let negate = unop(&Type::I128, "op_UnaryNegation");
+    let unsigned_to_f64 = operator(&Type::U128, "op_Explicit", &[Type::U128], &Type::F64);
+    let unsigned_from_f64 = operator(&Type::U128, "op_Explicit", &[Type::F64], &Type::U128);
+    let unsigned_abs = CILOp::Call(CallSite::boxed(
+        Some(class.clone()),
+        "UnsignedAbs".into(),
+        FnSig::new(&[Type::I128], &Type::U128),
+        true,
+    ));
+    // Conversions to floats
+    let to_f64 = if signed {
+        [
+            vec![CILOp::LDArg(0)],
+            zero(),
+            vec![
+                cmp(tpe, "op_LessThan"),
+                CILOp::BTrue(0),
+                CILOp::LDArg(0),
+                to_unsigned.clone(),
+                unsigned_to_f64.clone(),
+                CILOp::Ret,
+                CILOp::Label(0),
+                CILOp::LDArg(0),
+                negate.clone(),
+                to_unsigned.clone(),
+                unsigned_to_f64,
+                CILOp::Neg,
+                CILOp::Ret,
+            ],
+        ]
+        .concat()
+    } else {
+        [
+            hi(0),
+            vec![
+                CILOp::ConvFUn,
+                CILOp::ConvF64(false),
+                CILOp::LdcF64(TWO_POW_64),
+                CILOp::Mul,
+            ],
+            lo(0),
+            vec![
+                CILOp::ConvFUn,
+                CILOp::ConvF64(false),
+                CILOp::Add,
+                CILOp::Ret,
+            ],
+        ]
+        .concat()
+    };
+    methods.push(static_method(
+        "op_Explicit",
+        &[tpe.clone()],
+        &Type::F64,
+        vec![],
+        to_f64,
+    ));
+    methods.push(static_method(
+        "op_Explicit",
+        &[tpe.clone()],
+        &Type::F32,
+        vec![],
+        vec![
+            CILOp::LDArg(0),
+            operator(tpe, "op_Explicit", &[tpe.clone()], &Type::F64),
+            CILOp::ConvF32(false),
+            CILOp::Ret,
+        ],
+    ));
+    // Conversions from floats saturate, and turn NaN into 0.
+    let from_f64 = if signed {
+        [
+            vec![CILOp::LDArg(0), CILOp::LDArg(0), CILOp::BEq(0)],
+            zero(),
+            vec![
+                CILOp::Ret,
+                CILOp::Label(0),
+                CILOp::LDArg(0),
+                CILOp::LdcF64(-TWO_POW_64 * TWO_POW_64 / 2.0),
+                CILOp::BGe(1),
+            ],
+            load_const(tpe, 1 << 127),
+            vec![
+                CILOp::Ret,
+                CILOp::Label(1),
+                CILOp::LDArg(0),
+                CILOp::LdcF64(TWO_POW_64 * TWO_POW_64 / 2.0),
+                CILOp::BGe(2),
+                CILOp::LDArg(0),
+                CILOp::LdcF64(0.0),
+                CILOp::BGe(3),
+                CILOp::LDArg(0),
+                CILOp::Neg,
+                unsigned_from_f64.clone(),
+                to_signed.clone(),
+                negate.clone(),
+                CILOp::Ret,
+                CILOp::Label(3),
+                CILOp::LDArg(0),
+                unsigned_from_f64,
+                to_signed.clone(),
+                CILOp::Ret,
+                CILOp::Label(2),
+            ],
+            load_const(tpe, i128::MAX as u128),
+            vec![CILOp::Ret],
+        ]
+        .concat()
+    } else {
+        let floor = CILOp::Call(CallSite::boxed(
+            Some(DotnetTypeRef::new(Some("System.Runtime"), "System.Math").with_valuetype(false)),
+            "Floor".into(),
+            FnSig::new(&[Type::F64], &Type::F64),
+            true,
+        ));
+        [
+            vec![CILOp::LDArg(0), CILOp::LdcF64(1.0), CILOp::BGe(0)],
+            zero(),
+            vec![
+                CILOp::Ret,
+                CILOp::Label(0),
+                CILOp::LDArg(0),
+                CILOp::LdcF64(TWO_POW_64 * TWO_POW_64),
+                CILOp::BGe(1),
+                // Upper half
+                CILOp::LDArg(0),
+                CILOp::LdcF64(TWO_POW_64),
+                CILOp::Div,
+                CILOp::ConvU64(false),
+                // Lower half
+                CILOp::LDArg(0),
+                CILOp::LDArg(0),
+                CILOp::LdcF64(TWO_POW_64),
+                CILOp::Div,
+                floor,
+                CILOp::LdcF64(TWO_POW_64),
+                CILOp::Mul,
+                CILOp::Sub,
+                CILOp::ConvU64(false),
+                new.clone(),
+                CILOp::Ret,
+                CILOp::Label(1),
+            ],
+            load_const(tpe, u128::MAX),
+            vec![CILOp::Ret],
+        ]
+        .concat()
+    };
+    methods.push(static_method(
+        "op_Explicit",
+        &[Type::F64],
+        tpe,
+        vec![],
+        from_f64,
+    ));
+    methods.push(static_method(
+        "op_Explicit",
+        &[Type::F32],
+        tpe,
+        vec![],
+        vec![
+            CILOp::LDArg(0),
+            CILOp::ConvF64(false),
+            operator(tpe, "op_Explicit", &[Type::F64], tpe),
+            CILOp::Ret,
+        ],
+    ));
+    // Multiplication and division
+    let unsigned_div = binop(&Type::U128, "op_Division");
+    if signed {
+        // The wrapped product of 2 signed intigers has the same bits as the product of their unsigned counterparts.
+        methods.push(static_method(
+            "op_Multiply",
+            &binary,
+            tpe,
+            vec![],
+            vec![
+                CILOp::LDArg(0),
+                to_unsigned.clone(),
+                CILOp::LDArg(1),
+                to_unsigned.clone(),
+                binop(&Type::U128, "op_Multiply"),
+                to_signed.clone(),
+                CILOp::Ret,
+            ],
+        ));
+        methods.push(static_method(
+            "UnsignedAbs",
+            &[Type::I128],
+            &Type::U128,
+            vec![],
+            [
+                vec![CILOp::LDArg(0)],
+                zero(),
+                vec![
+                    cmp(tpe, "op_LessThan"),
+                    CILOp::BTrue(0),
+                    CILOp::LDArg(0),
+                    to_unsigned.clone(),
+                    CILOp::Ret,
+                    CILOp::Label(0),
+                    CILOp::LDArg(0),
+                    negate.clone(),
+                    to_unsigned,
+                    CILOp::Ret,
+                ],
+            ]
+            .concat(),
+        ));
+        // Divides the absolute values, and negates the quotient if the signs of the operands differ.
+        methods.push(static_method(
+            "op_Division",
+            &binary,
+            tpe,
+            vec![(None, tpe.clone())],
+            [
+                vec![
+                    CILOp::LDArg(0),
+                    unsigned_abs.clone(),
+                    CILOp::LDArg(1),
+                    unsigned_abs,
+                    unsigned_div,
+                    to_signed,
+                    CILOp::STLoc(0),
+                    CILOp::LDArg(0),
+                ],
+                zero(),
+                vec![cmp(tpe, "op_LessThan"), CILOp::LDArg(1)],
+                zero(),
+                vec![
+                    cmp(tpe, "op_LessThan"),
+                    CILOp::Eq,
+                    CILOp::BTrue(0),
+                    CILOp::LDLoc(0),
+                    negate,
+                    CILOp::Ret,
+                    CILOp::Label(0),
+                    CILOp::LDLoc(0),
+                    CILOp::Ret,
+                ],
+            ]
+            .concat(),
+        ));
+    } else {
+        // Adds `a << n` for each bit `n` set in `b`.
+        methods.push(static_method(
+            "op_Multiply",
+            &binary,
+            tpe,
+            vec![(None, tpe.clone())],
+            [
+                zero(),
+                vec![CILOp::STLoc(0), CILOp::Label(0), CILOp::LDArg(1)],
+                zero(),
+                vec![cmp(tpe, "op_Equality"), CILOp::BTrue(1)],
+                lo(1),
+                vec![
+                    CILOp::LdcI64(1),
+                    CILOp::And,
+                    CILOp::LdcI64(0),
+                    CILOp::BEq(2),
+                    CILOp::LDLoc(0),
+                    CILOp::LDArg(0),
+                    binop(tpe, "op_Addition"),
+                    CILOp::STLoc(0),
+                    CILOp::Label(2),
+                    CILOp::LDArg(0),
+                    CILOp::LdcI32(1),
+                    shift(tpe, "op_LeftShift"),
+                    CILOp::STArg(0),
+                    CILOp::LDArg(1),
+                    CILOp::LdcI32(1),
+                    shift(tpe, "op_RightShift"),
+                    CILOp::STArg(1),
+                    CILOp::GoTo(0),
+                    CILOp::Label(1),
+                    CILOp::LDLoc(0),
+                    CILOp::Ret,
+                ],
+            ]
+            .concat(),
+        ));
+        // Long division, one bit at a time.
+        methods.push(static_method(
+            "op_Division",
+            &binary,
+            tpe,
+            vec![(None, tpe.clone()), (None, tpe.clone()), (None, Type::I32)],
+            [
+                zero(),
+                vec![CILOp::STLoc(0)],
+                zero(),
+                vec![
+                    CILOp::STLoc(1),
+                    CILOp::LdcI32(127),
+                    CILOp::STLoc(2),
+                    CILOp::Label(0),
+                    // remainder = (remainder << 1) | ((a >> i) & 1)
+                    CILOp::LDLoc(1),
+                    CILOp::LdcI32(1),
+                    shift(tpe, "op_LeftShift"),
+                    CILOp::LDArg(0),
+                    CILOp::LDLoc(2),
+                    shift(tpe, "op_RightShift"),
+                ],
+                one(),
+                vec![
+                    binop(tpe, "op_BitwiseAnd"),
+                    binop(tpe, "op_BitwiseOr"),
+                    CILOp::STLoc(1),
+                    // if remainder >= b { remainder -= b; quotient |= 1 << i }
+                    CILOp::LDLoc(1),
+                    CILOp::LDArg(1),
+                    cmp(tpe, "op_LessThan"),
+                    CILOp::BTrue(1),
+                    CILOp::LDLoc(1),
+                    CILOp::LDArg(1),
+                    binop(tpe, "op_Subtraction"),
+                    CILOp::STLoc(1),
+                    CILOp::LDLoc(0),
+                ],
+                one(),
+                vec![
+                    CILOp::LDLoc(2),
+                    shift(tpe, "op_LeftShift"),
+                    binop(tpe, "op_BitwiseOr"),
+                    CILOp::STLoc(0),
+                    CILOp::Label(1),
+                    CILOp::LDLoc(2),
+                    CILOp::LdcI32(1),
+                    CILOp::Sub,
+                    CILOp::Dup,
+                    CILOp::STLoc(2),
+                    CILOp::LdcI32(0),
+                    CILOp::BGe(0),
+                    CILOp::LDLoc(0),
+                    CILOp::Ret,
+                ],
+            ]
+            .concat(),
+        ));
+    }
+    // a % b = a - (a / b) * b
+    methods.push(static_method(
+        "op_Modulus",
+        &binary,
+        tpe,
+        vec![],
+        vec![
+            CILOp::LDArg(0),
+            CILOp::LDArg(0),
+            CILOp::LDArg(1),
+            binop(tpe, "op_Division"),
+            CILOp::LDArg(1),
+            binop(tpe, "op_Multiply"),
+            binop(tpe, "op_Subtraction"),
+            CILOp::Ret,
+        ],
+    ));
+    TypeDef::new(
+        AccessModifer::Public,
+        software_class(tpe).name_path().into(),
+        vec![],
+        vec![("low".into(), Type::U64), ("high".into(), Type::U64)],
+        methods,
+        None,
+        0,
+        None,
+    )
+}
+#[test]
+fn const_halves() {
+    let ops = load_const(&Type::U128, (7 << 64) | u128::from(u64::MAX));
+    assert_eq!(ops[0], CILOp::LdcI64(7));
+    assert_eq!(ops[1], CILOp::LdcI64(-1));
+}
+#[test]
+fn software_int128_replaces_system_types() {
+    let mut asm = Assembly::empty();
+    let mut add = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[Type::I128, Type::I128], &Type::I128),
+        "add",
+        vec![(None, Type::Ptr(Type::U128.into()))],
+    );
+    add.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        binop(&Type::I128, "op_Addition"),
+        CILOp::Ret,
+    ]);
+    asm.add_method(add);
+    use_software_int128(&mut asm);
+    let software: Type = software_class(&Type::I128).into();
+    let add = asm.methods().find(|method| method.name() == "add").unwrap();
+    assert_eq!(add.sig().inputs(), [software.clone(), software.clone()]);
+    assert_eq!(
+        add.locals()[0].1,
+        Type::Ptr(Box::new(software_class(&Type::U128).into()))
+    );
+    assert_eq!(
+        add.get_ops()[2],
+        CILOp::Call(CallSite::boxed(
+            Some(software_class(&Type::I128)),
+            "op_Addition".into(),
+            FnSig::new(&[software.clone(), software.clone()], &software),
+            true,
+        ))
+    );
+    assert!(asm.types().any(|tpe| tpe.name() == SOFTWARE_I128_NAME));
+    // Neither the code nor the software types refer to the `System` types anymore.
+    assert!(!format!("{asm:?}").contains("Int128"));
+}
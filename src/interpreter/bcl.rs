@@ -0,0 +1,522 @@
+use super::{
+    value::{OpError, Slot},
+    Interpreter, Object, Trap, Value,
+};
+use crate::{cil::CallSite, r#type::Type};
+const MARSHAL: &str = "System.Runtime.InteropServices.Marshal";
+const NATIVE_MEMORY: &str = "System.Runtime.InteropServices.NativeMemory";
+const GC_HANDLE: &str = "System.Runtime.InteropServices.GCHandle";
+/// Alignment of buffers allocated without a specified alignment.
+const DEFAULT_ALIGN: usize = 16;
+fn unsupported<T>(site: &CallSite) -> Result<T, Trap> {
+    Err(format!(
+        "unsupported BCL method {class}::{name}{inputs:?}",
+        class = site.class().map_or("", |class| class.name_path()),
+        name = site.name(),
+        inputs = site.signature().inputs()
+    )
+    .into())
+}
+/// Checks if `tpe` is a primitive integer type.
+fn is_int(tpe: &Type) -> bool {
+    matches!(
+        tpe,
+        Type::I8
+            | Type::U8
+            | Type::I16
+            | Type::U16
+            | Type::I32
+            | Type::U32
+            | Type::I64
+            | Type::U64
+            | Type::ISize
+            | Type::USize
+    )
+}
+fn ptr(addr: u64) -> Option<Value> {
+    Some(Value::NativeInt(addr as i64))
+}
+/// Calls the BCL method behind `site` with `args`, returning its result.
+pub(super) fn call(
+    interp: &mut Interpreter,
+    site: &CallSite,
+    args: &[Value],
+) -> Result<Option<Value>, Trap> {
+    let class = site.class().map_or("", |class| class.name_path());
+    Ok(match (class, site.name(), args) {
+        ("System.Console", "Write" | "WriteLine", _) => {
+            let mut text = match (site.explicit_inputs(), args) {
+                ([], []) => String::new(),
+                ([tpe], [value]) => format_value(interp, tpe, value)?,
+                _ => return unsupported(site),
+            };
+            if site.name() == "WriteLine" {
+                text.push('\n');
+            }
+            interp.stdout.push_str(&text);
+            None
+        }
+        (MARSHAL, "AllocHGlobal", [size]) | (NATIVE_MEMORY, "Alloc" | "AllocZeroed", [size]) => {
+            ptr(interp
+                .memory
+                .alloc(size.as_u64()? as usize, DEFAULT_ALIGN)?)
+        }
+        (NATIVE_MEMORY, "AlignedAlloc", [size, align]) => ptr(interp
+            .memory
+            .alloc(size.as_u64()? as usize, align.as_u64()? as usize)?),
+        (MARSHAL, "ReAllocHGlobal", [addr, size]) | (NATIVE_MEMORY, "Realloc", [addr, size]) => {
+            ptr(interp.memory.realloc(
+                addr.as_address()?,
+                size.as_u64()? as usize,
+                DEFAULT_ALIGN,
+            )?)
+        }
+        (NATIVE_MEMORY, "AlignedRealloc", [addr, size, align]) => ptr(interp.memory.realloc(
+            addr.as_address()?,
+            size.as_u64()? as usize,
+            align.as_u64()? as usize,
+        )?),
+        (MARSHAL, "FreeHGlobal", [addr]) | (NATIVE_MEMORY, "Free" | "AlignedFree", [addr]) => {
+            interp.memory.free(addr.as_address()?)?;
+            None
+        }
+        // Integer overloads must not lose precision or change their return type by going through `f64`.
+        ("System.Math", name, _) if site.explicit_inputs().first().is_some_and(is_int) => {
+            let signed = matches!(
+                site.explicit_inputs()[0],
+                Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::ISize
+            );
+            let args = args
+                .iter()
+                .map(|arg| {
+                    Ok(if signed {
+                        i128::from(arg.as_i64()?)
+                    } else {
+                        i128::from(arg.as_u64()?)
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let result = match (name, args.as_slice()) {
+                ("Abs", [x]) => x.abs(),
+                ("Min", [x, y]) => *x.min(y),
+                ("Max", [x, y]) => *x.max(y),
+                ("Clamp", [x, min, max]) if min <= max => *x.clamp(min, max),
+                ("Clamp", [_, min, max]) => {
+                    let message = format!("'{min}' cannot be greater than {max}.");
+                    return Err(Trap::Exception(
+                        interp.new_exception("System.ArgumentException", &message),
+                    ));
+                }
+                _ => return unsupported(site),
+            };
+            let slot = interp.layouts.slot(site.signature().output())?;
+            Some(slot.normalize(&Value::I64(result as i64))?)
+        }
+        ("System.Math" | "System.MathF", name, _) => {
+            let args = args
+                .iter()
+                .map(Value::as_f64)
+                .collect::<Result<Vec<_>, _>>()?;
+            let result = match (name, args.as_slice()) {
+                ("Sqrt", [x]) => x.sqrt(),
+                ("Cbrt", [x]) => x.cbrt(),
+                ("Floor", [x]) => x.floor(),
+                ("Ceiling", [x]) => x.ceil(),
+                ("Truncate", [x]) => x.trunc(),
+                ("Round", [x]) => x.round_ties_even(),
+                ("Abs", [x]) => x.abs(),
+                ("Exp", [x]) => x.exp(),
+                ("Log", [x]) => x.ln(),
+                ("Log2", [x]) => x.log2(),
+                ("Log10", [x]) => x.log10(),
+                ("Sin", [x]) => x.sin(),
+                ("Cos", [x]) => x.cos(),
+                ("Tan", [x]) => x.tan(),
+                ("Asin", [x]) => x.asin(),
+                ("Acos", [x]) => x.acos(),
+                ("Atan", [x]) => x.atan(),
+                ("Sinh", [x]) => x.sinh(),
+                ("Cosh", [x]) => x.cosh(),
+                ("Tanh", [x]) => x.tanh(),
+                ("Pow", [x, y]) => x.powf(*y),
+                ("Atan2", [y, x]) => y.atan2(*x),
+                ("Min", [x, y]) => x.min(*y),
+                ("Max", [x, y]) => x.max(*y),
+                ("CopySign", [x, y]) => x.copysign(*y),
+                ("FusedMultiplyAdd", [x, y, z]) => x.mul_add(*y, *z),
+                _ => return unsupported(site),
+            };
+            Some(match site.signature().output() {
+                Type::F32 => Value::F32(result as f32),
+                _ => Value::F64(result),
+            })
+        }
+        ("System.Convert", "ToString", _) => {
+            let string = match (site.explicit_inputs(), args) {
+                ([Type::U64], [value]) => value.as_u64()?.to_string(),
+                ([_], [value]) => value.as_i64()?.to_string(),
+                // Other bases print the bits of the two's complement representation.
+                ([tpe, _], [value, base]) => {
+                    let bits = match tpe {
+                        Type::I32 => value.as_i64()? as u32 as u64,
+                        _ => value.as_i64()? as u64,
+                    };
+                    match base.as_i64()? {
+                        2 => format!("{bits:b}"),
+                        8 => format!("{bits:o}"),
+                        10 => value.as_i64()?.to_string(),
+                        16 => format!("{bits:x}"),
+                        base => return Err(format!("invalid base {base}").into()),
+                    }
+                }
+                _ => return unsupported(site),
+            };
+            Some(interp.new_string(string))
+        }
+        ("System.String", "Concat", _) => {
+            let mut string = String::new();
+            for arg in args {
+                string.push_str(interp.string(arg)?);
+            }
+            Some(interp.new_string(string))
+        }
+        // Called by the constructors of classes derived from `System.Exception`.
+        ("System.Exception", ".ctor", [this, message]) => {
+            let message = interp.string(message)?.to_owned();
+            match interp.object_mut(this.as_obj()?)? {
+                Object::Instance {
+                    message: exception_message,
+                    ..
+                } => *exception_message = Some(message),
+                Object::String(_) | Object::StringBuilder(_) | Object::Array { .. } => {
+                    return Err("`this` of an exception is not an instance".into())
+                }
+            }
+            None
+        }
+        ("System.Exception", "get_Message", [this]) => {
+            let message = match interp.object(this.as_obj()?)? {
+                Object::Instance { message, .. } => message.clone().unwrap_or_default(),
+                Object::String(_) | Object::StringBuilder(_) | Object::Array { .. } => {
+                    return Err("`this` of an exception is not an instance".into())
+                }
+            };
+            Some(interp.new_string(message))
+        }
+        ("System.Int128" | "System.UInt128", _, _) => int128(interp, site, args)?,
+        (GC_HANDLE, _, _) => gc_handle(interp, site, args)?,
+        ("System.Text.StringBuilder", "Append", [this, value]) => {
+            let [tpe] = site.explicit_inputs() else {
+                return unsupported(site);
+            };
+            let text = format_value(interp, tpe, value)?;
+            string_builder(interp, this)?.push_str(&text);
+            Some(this.clone())
+        }
+        ("System.Text.StringBuilder", "get_Length", [this]) => {
+            let len = string_builder(interp, this)?.encode_utf16().count();
+            Some(Value::I32(len as i32))
+        }
+        ("System.Text.StringBuilder", "ToString", [this]) => {
+            let text = string_builder(interp, this)?.clone();
+            Some(interp.new_string(text))
+        }
+        _ => return unsupported(site),
+    })
+}
+/// Creates an instance of the BCL class of the constructor `site`, with the arguments `args`.
+pub(super) fn new_obj(
+    interp: &mut Interpreter,
+    site: &CallSite,
+    args: &[Value],
+) -> Result<Value, Trap> {
+    let class = site.class().ok_or("`newobj` without a class")?;
+    match (class.name_path(), args) {
+        // Creates a string from an UTF-8 buffer.
+        ("System.String", [addr, start, len]) => {
+            let addr = addr.as_address()? + start.as_i64()? as u64;
+            let bytes = interp.memory.bytes(addr, len.as_i64()? as usize)?;
+            let string = String::from_utf8_lossy(bytes).into_owned();
+            Ok(interp.new_string(string))
+        }
+        ("System.Text.StringBuilder", []) => Ok(Value::Obj(
+            interp.new_object(Object::StringBuilder(String::new())),
+        )),
+        ("System.DateTime", [year, month, day]) => date_time(interp, [year, month, day], &[]),
+        ("System.DateTime", [year, month, day, hour, minute, second]) => {
+            date_time(interp, [year, month, day], &[hour, minute, second])
+        }
+        (name, []) | (name, [_]) if name.ends_with("Exception") => {
+            let message = match args {
+                [message] => interp.string(message)?.to_owned(),
+                _ => format!("Exception of type '{name}' was thrown."),
+            };
+            Ok(Value::Obj(interp.new_object(Object::Instance {
+                class: class.clone(),
+                data: 0,
+                message: Some(message),
+            })))
+        }
+        _ => unsupported(site),
+    }
+}
+/// Formats `value` of type `tpe` like `Console.Write` does.
+fn format_value(interp: &Interpreter, tpe: &Type, value: &Value) -> Result<String, String> {
+    Ok(match tpe {
+        Type::DotnetChar => char::from_u32(value.as_u64()? as u16 as u32)
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+            .to_string(),
+        Type::Bool => if value.is_true()? { "True" } else { "False" }.into(),
+        Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::ISize => value.as_i64()?.to_string(),
+        Type::U8 | Type::U16 | Type::U32 | Type::U64 | Type::USize => value.as_u64()?.to_string(),
+        Type::F32 => format_float(value.as_f64()?, (value.as_f64()? as f32).to_string()),
+        Type::F64 => format_float(value.as_f64()?, value.as_f64()?.to_string()),
+        Type::DotnetType(dref) if dref.name_path() == "System.String" => {
+            interp.string(value)?.to_owned()
+        }
+        _ => return Err(format!("can't print a value of type {tpe:?}")),
+    })
+}
+/// Formats a float, using the names .NET uses for special values.
+fn format_float(value: f64, formatted: String) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value == f64::INFINITY {
+        "∞".into()
+    } else if value == f64::NEG_INFINITY {
+        "-∞".into()
+    } else {
+        formatted
+    }
+}
+/// Integer or float value, widened to 128 bits.
+#[derive(Clone, Copy)]
+enum Wide {
+    Signed(i128),
+    Unsigned(u128),
+    Float(f64),
+}
+impl Wide {
+    fn new(tpe: &Type, value: &Value) -> Result<Self, String> {
+        Ok(match tpe {
+            Type::I128 | Type::U128 => {
+                let Value::Struct(bytes) = value else {
+                    return Err(format!("expected a 128 bit integer, found {value:?}"));
+                };
+                let bytes: [u8; 16] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| "128 bit integer has the wrong size".to_owned())?;
+                if *tpe == Type::I128 {
+                    Self::Signed(i128::from_le_bytes(bytes))
+                } else {
+                    Self::Unsigned(u128::from_le_bytes(bytes))
+                }
+            }
+            Type::F32 | Type::F64 => Self::Float(value.as_f64()?),
+            Type::U8
+            | Type::U16
+            | Type::U32
+            | Type::U64
+            | Type::USize
+            | Type::Bool
+            | Type::DotnetChar => Self::Unsigned(value.as_u64()? as u128),
+            _ => Self::Signed(value.as_i64()? as i128),
+        })
+    }
+    /// Returns the bits of an integer. Floats are truncated.
+    fn bits(self) -> u128 {
+        match self {
+            Self::Signed(value) => value as u128,
+            Self::Unsigned(value) => value,
+            Self::Float(value) => value as i128 as u128,
+        }
+    }
+    /// Converts `self` to a value of type `tpe`, truncating integers and saturating floats.
+    fn convert(self, interp: &Interpreter, tpe: &Type) -> Result<Value, String> {
+        let signed = matches!(
+            tpe,
+            Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::I128 | Type::ISize
+        );
+        let bits = match self {
+            Self::Float(value) if !signed => value as u128,
+            _ => self.bits(),
+        };
+        let float = match self {
+            Self::Signed(value) => value as f64,
+            Self::Unsigned(value) => value as f64,
+            Self::Float(value) => value,
+        };
+        Ok(match tpe {
+            Type::F32 => Value::F32(float as f32),
+            Type::F64 => Value::F64(float),
+            Type::I128 | Type::U128 => Value::Struct(bits.to_le_bytes().to_vec()),
+            _ => interp
+                .layouts
+                .slot(tpe)?
+                .normalize(&Value::I64(bits as i64))?,
+        })
+    }
+}
+/// Creates a `System.DateTime` of kind `Unspecified` from its `date`(year, month and day) and `time`(hour, minute and second).
+fn date_time(interp: &mut Interpreter, date: [&Value; 3], time: &[&Value]) -> Result<Value, Trap> {
+    const TICKS_PER_SECOND: i64 = 10_000_000;
+    const DAYS_TO_MONTH: [i64; 13] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334, 365];
+    let mut out_of_range = |what: &str| {
+        Trap::Exception(interp.new_exception(
+            "System.ArgumentOutOfRangeException",
+            &format!("{what} parameters describe an un-representable DateTime."),
+        ))
+    };
+    let [year, month, day] = [date[0].as_i64()?, date[1].as_i64()?, date[2].as_i64()?];
+    let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    if !(1..=9999).contains(&year) || !(1..=12).contains(&month) {
+        return Err(out_of_range("Year, Month, and Day"));
+    }
+    let month = month as usize;
+    let month_len =
+        DAYS_TO_MONTH[month] - DAYS_TO_MONTH[month - 1] + i64::from(is_leap && month == 2);
+    if !(1..=month_len).contains(&day) {
+        return Err(out_of_range("Year, Month, and Day"));
+    }
+    let prev = year - 1;
+    let days = prev * 365 + prev / 4 - prev / 100
+        + prev / 400
+        + DAYS_TO_MONTH[month - 1]
+        + i64::from(is_leap && month > 2)
+        + day
+        - 1;
+    let mut seconds = 0;
+    for (value, limit) in time.iter().zip([24, 60, 60]) {
+        let value = value.as_i64()?;
+        if !(0..limit).contains(&value) {
+            return Err(out_of_range("Hour, Minute, and Second"));
+        }
+        seconds = seconds * limit + value;
+    }
+    let ticks = (days * 86_400 + seconds) * TICKS_PER_SECOND;
+    Ok(Value::Struct(ticks.to_le_bytes().to_vec()))
+}
+/// Returns the text of the `System.Text.StringBuilder` `this` refers to.
+fn string_builder<'a>(interp: &'a mut Interpreter, this: &Value) -> Result<&'a mut String, String> {
+    match interp.object_mut(this.as_obj()?)? {
+        Object::StringBuilder(text) => Ok(text),
+        _ => Err("`this` of a StringBuilder method is not a StringBuilder".into()),
+    }
+}
+/// Returns the index of the live `GCHandle` at the address `this`, which instance methods of `GCHandle` receive.
+fn gc_handle_index(interp: &mut Interpreter, this: &Value) -> Result<usize, Trap> {
+    let handle = interp
+        .memory
+        .read(this.as_address()?, Slot::NativeInt)?
+        .as_i64()?;
+    let index = usize::try_from(handle)
+        .ok()
+        .and_then(|handle| handle.checked_sub(1));
+    match index {
+        Some(index) if matches!(interp.gc_handles.get(index), Some(Some(_))) => Ok(index),
+        _ => Err(Trap::Exception(interp.new_exception(
+            "System.InvalidOperationException",
+            "Handle is not initialized.",
+        ))),
+    }
+}
+/// Emulates `System.Runtime.InteropServices.GCHandle`. Handles are indices into a table of objects, which lets the interpreter catch the use of freed handles.
+fn gc_handle(
+    interp: &mut Interpreter,
+    site: &CallSite,
+    args: &[Value],
+) -> Result<Option<Value>, Trap> {
+    Ok(match (site.name(), args) {
+        // The `GCHandleType` does not matter, since objects are never moved.
+        ("Alloc", [object] | [object, _]) => {
+            interp.gc_handles.push(Some(object.as_obj()?));
+            Some(Value::NativeInt(interp.gc_handles.len() as i64))
+        }
+        ("ToIntPtr" | "FromIntPtr", [handle]) => Some(Value::NativeInt(handle.as_i64()?)),
+        ("get_Target", [this]) => {
+            let index = gc_handle_index(interp, this)?;
+            interp.gc_handles[index].map(Value::Obj)
+        }
+        ("Free", [this]) => {
+            let index = gc_handle_index(interp, this)?;
+            interp.gc_handles[index] = None;
+            None
+        }
+        _ => return unsupported(site),
+    })
+}
+/// Emulates the operators of `System.Int128` and `System.UInt128`.
+fn int128(
+    interp: &mut Interpreter,
+    site: &CallSite,
+    args: &[Value],
+) -> Result<Option<Value>, Trap> {
+    let signed = site.class().map(|class| class.name_path()) == Some("System.Int128");
+    let values = site
+        .signature()
+        .inputs()
+        .iter()
+        .zip(args)
+        .map(|(tpe, value)| Wide::new(tpe, value))
+        .collect::<Result<Vec<_>, _>>()?;
+    let output = site.signature().output();
+    let compare = |a: Wide, b: Wide| {
+        if signed {
+            (a.bits() as i128).cmp(&(b.bits() as i128))
+        } else {
+            a.bits().cmp(&b.bits())
+        }
+    };
+    let result = match (site.name(), values.as_slice()) {
+        ("op_Explicit" | "op_Implicit", [value]) => {
+            return Ok(Some(value.convert(interp, output)?))
+        }
+        ("op_Equality", [a, b]) => return Ok(Some(Value::I32(i32::from(a.bits() == b.bits())))),
+        ("op_Inequality", [a, b]) => return Ok(Some(Value::I32(i32::from(a.bits() != b.bits())))),
+        ("op_LessThan", [a, b]) => return Ok(Some(Value::I32(i32::from(compare(*a, *b).is_lt())))),
+        ("op_LessThanOrEqual", [a, b]) => {
+            return Ok(Some(Value::I32(i32::from(compare(*a, *b).is_le()))))
+        }
+        ("op_GreaterThan", [a, b]) => {
+            return Ok(Some(Value::I32(i32::from(compare(*a, *b).is_gt()))))
+        }
+        ("op_GreaterThanOrEqual", [a, b]) => {
+            return Ok(Some(Value::I32(i32::from(compare(*a, *b).is_ge()))))
+        }
+        ("UnsignedAbs", [value]) => (value.bits() as i128).unsigned_abs(),
+        ("op_UnaryNegation", [value]) => value.bits().wrapping_neg(),
+        ("op_OnesComplement", [value]) => !value.bits(),
+        ("op_Addition", [a, b]) => a.bits().wrapping_add(b.bits()),
+        ("op_Subtraction", [a, b]) => a.bits().wrapping_sub(b.bits()),
+        ("op_Multiply", [a, b]) => a.bits().wrapping_mul(b.bits()),
+        ("op_BitwiseAnd", [a, b]) => a.bits() & b.bits(),
+        ("op_BitwiseOr", [a, b]) => a.bits() | b.bits(),
+        ("op_ExclusiveOr", [a, b]) => a.bits() ^ b.bits(),
+        ("op_Division" | "op_Modulus", [a, b]) => {
+            let (a, b) = (a.bits(), b.bits());
+            if b == 0 {
+                return Err(interp.op_error(OpError::DivideByZero));
+            }
+            let div = site.name() == "op_Division";
+            if signed {
+                let (a, b) = (a as i128, b as i128);
+                if a == i128::MIN && b == -1 {
+                    return Err(interp.op_error(OpError::Overflow));
+                }
+                (if div { a / b } else { a % b }) as u128
+            } else if div {
+                a / b
+            } else {
+                a % b
+            }
+        }
+        ("op_LeftShift", [a, b]) => a.bits() << (b.bits() as u32 & 127),
+        ("op_RightShift", [a, b]) if signed => {
+            ((a.bits() as i128) >> (b.bits() as u32 & 127)) as u128
+        }
+        ("op_RightShift" | "op_UnsignedRightShift", [a, b]) => a.bits() >> (b.bits() as u32 & 127),
+        _ => return unsupported(site),
+    };
+    Ok(Some(Value::Struct(result.to_le_bytes().to_vec())))
+}
@@ -0,0 +1,131 @@
+use super::value::Slot;
+use crate::{
+    assembly::Assembly,
+    r#type::{DotnetTypeRef, Type},
+    IString,
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+/// Memory layout of a value type, or of the fields of a class.
+#[derive(Debug)]
+pub struct StructLayout {
+    size: usize,
+    align: usize,
+    fields: Vec<(IString, usize, Slot)>,
+}
+impl StructLayout {
+    /// Size of the type, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// Alignment of the type, in bytes.
+    pub fn align(&self) -> usize {
+        self.align
+    }
+    /// Returns the offset and slot of the field `name`.
+    pub fn field(&self, name: &str) -> Result<(usize, Slot), String> {
+        self.fields
+            .iter()
+            .find(|(field_name, _, _)| field_name.as_ref() == name)
+            .map(|(_, offset, slot)| (*offset, *slot))
+            .ok_or_else(|| format!("type has no field named {name}"))
+    }
+}
+/// Computes and caches the layouts of types defined within an assembly.
+pub struct Layouts<'asm> {
+    asm: &'asm Assembly,
+    cache: RefCell<HashMap<DotnetTypeRef, Rc<StructLayout>>>,
+}
+impl<'asm> Layouts<'asm> {
+    pub fn new(asm: &'asm Assembly) -> Self {
+        Self {
+            asm,
+            cache: RefCell::default(),
+        }
+    }
+    /// Returns the slot values of type `tpe` are stored in.
+    pub fn slot(&self, tpe: &Type) -> Result<Slot, String> {
+        Ok(match tpe {
+            Type::Bool | Type::U8 => Slot::U8,
+            Type::I8 => Slot::I8,
+            Type::U16 | Type::DotnetChar => Slot::U16,
+            Type::I16 => Slot::I16,
+            Type::U32 | Type::I32 => Slot::I32,
+            Type::U64 | Type::I64 => Slot::I64,
+            Type::USize | Type::ISize | Type::Ptr(_) | Type::FnPtr(_) => Slot::NativeInt,
+            Type::F32 => Slot::F32,
+            Type::F64 => Slot::F64,
+            Type::U128 | Type::I128 => Slot::Struct(16),
+            // FnDefs are zero-sized, but .NET types can't be empty.
+            Type::FnDef(_) => Slot::Struct(1),
+            Type::DotnetArray(_) => Slot::Obj,
+            Type::DotnetType(dref) => match dref.name_path() {
+                "System.IntPtr" | "System.UIntPtr" => Slot::NativeInt,
+                "System.Int128" | "System.UInt128" => Slot::Struct(16),
+                // The ticks since 0001-01-01, with the kind in the top 2 bits.
+                "System.DateTime" => Slot::Struct(8),
+                // Handles are stored as their `native int` representation.
+                "System.Runtime.InteropServices.GCHandle" => Slot::NativeInt,
+                _ if !dref.is_valuetype() => Slot::Obj,
+                _ if dref.asm().is_none() => Slot::Struct(self.struct_layout(dref)?.size()),
+                _ => return Err(format!("layout of the .NET type {dref:?} is not known")),
+            },
+            Type::Void
+            | Type::Unresolved
+            | Type::Foreign
+            | Type::GenericArg(_)
+            | Type::MethodGenericArg(_) => {
+                return Err(format!("values of type {tpe:?} can't be stored"))
+            }
+        })
+    }
+    /// Returns the size and alignment of type `tpe`.
+    pub fn size_align(&self, tpe: &Type) -> Result<(usize, usize), String> {
+        match tpe {
+            Type::DotnetType(dref) if dref.is_valuetype() && dref.asm().is_none() => {
+                let layout = self.struct_layout(dref)?;
+                Ok((layout.size(), layout.align()))
+            }
+            _ => {
+                let size = self.slot(tpe)?.size();
+                Ok((size, size.clamp(1, 8)))
+            }
+        }
+    }
+    /// Returns the layout of the fields of a type defined within the assembly.
+    pub fn struct_layout(&self, dref: &DotnetTypeRef) -> Result<Rc<StructLayout>, String> {
+        if let Some(layout) = self.cache.borrow().get(dref) {
+            return Ok(layout.clone());
+        }
+        let type_def = self
+            .asm
+            .get_typedef_by_path(dref.name_path())
+            .ok_or_else(|| format!("type {name} is not defined", name = dref.name_path()))?;
+        let mut fields = Vec::with_capacity(type_def.fields().len());
+        let mut size: usize = 0;
+        let mut align: usize = 1;
+        for (index, (name, tpe)) in type_def.fields().iter().enumerate() {
+            let tpe = tpe
+                .map_generic(dref.generics())
+                .ok_or_else(|| format!("could not resolve the type of field {name}"))?;
+            let (field_size, field_align) = self.size_align(&tpe)?;
+            let offset = match type_def.explicit_offsets() {
+                Some(offsets) => *offsets
+                    .get(index)
+                    .ok_or_else(|| format!("field {name} has no explicit offset"))?
+                    as usize,
+                None => size.div_ceil(field_align) * field_align,
+            };
+            size = size.max(offset + field_size);
+            align = align.max(field_align);
+            fields.push((name.clone(), offset, self.slot(&tpe)?));
+        }
+        let size = size.max(1).div_ceil(align) * align;
+        let layout = Rc::new(StructLayout {
+            size,
+            align,
+            fields,
+        });
+        self.cache.borrow_mut().insert(dref.clone(), layout.clone());
+        Ok(layout)
+    }
+}
@@ -0,0 +1,153 @@
+use super::value::{Slot, Value};
+use std::collections::HashMap;
+/// Address of the first byte of the heap. Addresses below it are never valid, which makes null pointer dereferences detectable.
+const HEAP_BASE: u64 = 0x1_0000;
+/// Largest size of the heap.
+const HEAP_LIMIT: usize = 1 << 30;
+/// Address of the first byte of the stack, holding local variables, arguments and `localloc` buffers.
+const STACK_BASE: u64 = 0x7000_0000_0000;
+/// Largest size of the stack.
+const STACK_LIMIT: usize = 8 << 20;
+/// Unmanaged memory of the interpreted program.
+#[derive(Default)]
+pub struct Memory {
+    heap: Vec<u8>,
+    /// Sizes of all the live heap allocations.
+    allocations: HashMap<u64, usize>,
+    /// Freed heap allocations which can be reused, by size.
+    free: HashMap<usize, Vec<u64>>,
+    stack: Vec<u8>,
+}
+fn align_up(value: u64, align: usize) -> u64 {
+    let align = align.max(1) as u64;
+    value.div_ceil(align) * align
+}
+impl Memory {
+    /// Allocates a zeroed heap buffer of `size` bytes, aligned to `align`.
+    pub fn alloc(&mut self, size: usize, align: usize) -> Result<u64, String> {
+        // Each allocation gets an unique address, even if it is empty.
+        let size = size.max(1);
+        if let Some(free) = self.free.get_mut(&size) {
+            if let Some(index) = free
+                .iter()
+                .position(|addr| *addr % align.max(1) as u64 == 0)
+            {
+                let addr = free.swap_remove(index);
+                self.allocations.insert(addr, size);
+                self.bytes_mut(addr, size)?.fill(0);
+                return Ok(addr);
+            }
+        }
+        let addr = align_up(HEAP_BASE + self.heap.len() as u64, align);
+        let end = (addr - HEAP_BASE) as usize + size;
+        if end > HEAP_LIMIT {
+            return Err(format!("out of memory: could not allocate {size} bytes"));
+        }
+        self.heap.resize(end, 0);
+        self.allocations.insert(addr, size);
+        Ok(addr)
+    }
+    /// Frees the heap buffer at `addr`. Freeing `null` does nothing.
+    pub fn free(&mut self, addr: u64) -> Result<(), String> {
+        if addr == 0 {
+            return Ok(());
+        }
+        let size = self
+            .allocations
+            .remove(&addr)
+            .ok_or_else(|| format!("freed {addr:#x}, which is not a live heap allocation"))?;
+        self.free.entry(size).or_default().push(addr);
+        Ok(())
+    }
+    /// Resizes the heap buffer at `addr` to `size` bytes, moving it if needed. Reallocating `null` allocates a new buffer.
+    pub fn realloc(&mut self, addr: u64, size: usize, align: usize) -> Result<u64, String> {
+        if addr == 0 {
+            return self.alloc(size, align);
+        }
+        let old_size = *self
+            .allocations
+            .get(&addr)
+            .ok_or_else(|| format!("reallocated {addr:#x}, which is not a live heap allocation"))?;
+        let new = self.alloc(size, align)?;
+        self.copy(new, addr, old_size.min(size))?;
+        self.free(addr)?;
+        Ok(new)
+    }
+    /// Returns the current top of the stack.
+    pub fn stack_pointer(&self) -> u64 {
+        STACK_BASE + self.stack.len() as u64
+    }
+    /// Frees all the stack memory allocated after the stack pointer was `sp`.
+    pub fn reset_stack(&mut self, sp: u64) {
+        self.stack.truncate((sp - STACK_BASE) as usize);
+    }
+    /// Allocates a zeroed buffer of `size` bytes aligned to `align` on the stack.
+    pub fn stack_alloc(&mut self, size: usize, align: usize) -> Result<u64, String> {
+        let addr = align_up(self.stack_pointer(), align);
+        let end = (addr - STACK_BASE) as usize + size;
+        if end > STACK_LIMIT {
+            return Err("stack overflow".into());
+        }
+        self.stack.resize(end, 0);
+        Ok(addr)
+    }
+    /// Returns the `len` bytes at `addr`.
+    pub fn bytes(&self, addr: u64, len: usize) -> Result<&[u8], String> {
+        let (memory, offset) = if addr >= STACK_BASE {
+            (&self.stack, addr - STACK_BASE)
+        } else if addr >= HEAP_BASE {
+            (&self.heap, addr - HEAP_BASE)
+        } else {
+            return Err(format!("invalid memory access at {addr:#x}"));
+        };
+        memory
+            .get(offset as usize..offset as usize + len)
+            .ok_or_else(|| format!("invalid memory access of {len} bytes at {addr:#x}"))
+    }
+    /// Returns the `len` bytes at `addr`, for writing.
+    pub fn bytes_mut(&mut self, addr: u64, len: usize) -> Result<&mut [u8], String> {
+        let (memory, offset) = if addr >= STACK_BASE {
+            (&mut self.stack, addr - STACK_BASE)
+        } else if addr >= HEAP_BASE {
+            (&mut self.heap, addr - HEAP_BASE)
+        } else {
+            return Err(format!("invalid memory access at {addr:#x}"));
+        };
+        memory
+            .get_mut(offset as usize..offset as usize + len)
+            .ok_or_else(|| format!("invalid memory access of {len} bytes at {addr:#x}"))
+    }
+    /// Reads a value stored in `slot` at `addr`.
+    pub fn read(&self, addr: u64, slot: Slot) -> Result<Value, String> {
+        Ok(slot.load(self.bytes(addr, slot.size())?))
+    }
+    /// Writes `value` to the `slot` at `addr`.
+    pub fn write(&mut self, addr: u64, slot: Slot, value: &Value) -> Result<(), String> {
+        let bytes = slot.store(value)?;
+        self.bytes_mut(addr, bytes.len())?.copy_from_slice(&bytes);
+        Ok(())
+    }
+    /// Copies `len` bytes from `src` to `dst`. The buffers may overlap.
+    pub fn copy(&mut self, dst: u64, src: u64, len: usize) -> Result<(), String> {
+        let bytes = self.bytes(src, len)?.to_vec();
+        self.bytes_mut(dst, len)?.copy_from_slice(&bytes);
+        Ok(())
+    }
+}
+#[test]
+fn heap_and_stack() {
+    let mut memory = Memory::default();
+    let a = memory.alloc(16, 8).unwrap();
+    memory.write(a, Slot::I64, &Value::I64(-7)).unwrap();
+    let b = memory.realloc(a, 32, 8).unwrap();
+    assert_eq!(memory.read(b, Slot::I64).unwrap(), Value::I64(-7));
+    assert!(memory.free(a).is_err());
+    memory.free(b).unwrap();
+    assert!(memory.read(0, Slot::U8).is_err());
+    let sp = memory.stack_pointer();
+    let local = memory.stack_alloc(4, 4).unwrap();
+    memory.write(local, Slot::U8, &Value::I32(0x1ff)).unwrap();
+    assert_eq!(memory.read(local, Slot::U8).unwrap(), Value::I32(0xff));
+    memory.reset_stack(sp);
+    assert!(memory.read(local, Slot::U8).is_err());
+}
@@ -0,0 +1,1386 @@
+use crate::{
+    assembly::Assembly,
+    cil::{CILOp, CallSite, FieldDescriptor, StaticFieldDescriptor},
+    function_sig::FnSig,
+    method::Method,
+    r#type::{DotnetTypeRef, Type, TypeDef},
+    IString,
+};
+use layout::Layouts;
+use memory::Memory;
+use std::collections::HashMap;
+use value::{Cmp, OpError, Slot};
+/// Emulation of the small subset of the BCL used by compiled assemblies.
+mod bcl;
+/// Memory layouts of types defined within an assembly.
+mod layout;
+/// Heap and stack memory of the interpreted program.
+mod memory;
+/// Values on the evaluation stack, and the arithmetic operations on them.
+mod value;
+pub use value::Value;
+/// Fake address of the first function pointer created by `ldftn`. Function pointers can only be called, never dereferenced.
+const FN_PTR_BASE: u64 = 0x7f00_0000_0000;
+/// Maximal count of nested calls.
+const MAX_FRAMES: usize = 1 << 16;
+/// Exit code of a program killed by an unhandled exception, the same as the one of the .NET runtime on Linux.
+const UNHANDLED_EXCEPTION_EXIT_CODE: i32 = 134;
+/// Protected region of a method, and its exception handler.
+struct Region {
+    try_start: usize,
+    try_end: usize,
+    handler: usize,
+    handler_end: usize,
+    /// Type of exceptions caught by the handler, or `None` for `finally` handlers.
+    catch: Option<DotnetTypeRef>,
+}
+impl Region {
+    fn handler_contains(&self, pc: usize) -> bool {
+        self.handler < pc && pc < self.handler_end
+    }
+}
+/// A method, with its labels and protected regions resolved.
+struct LoadedMethod<'asm> {
+    method: &'asm Method,
+    /// Name of the class the method is defined in, or `None` for methods of the assembly.
+    class: Option<IString>,
+    labels: HashMap<u32, usize>,
+    /// Protected regions, inner regions before the ones they are nested in.
+    regions: Vec<Region>,
+}
+impl<'asm> LoadedMethod<'asm> {
+    fn new(method: &'asm Method, class: Option<IString>) -> Result<Self, String> {
+        let mut labels = HashMap::new();
+        let mut regions = Vec::new();
+        let mut open_tries = Vec::new();
+        let mut closed_tries = Vec::new();
+        let mut open_handlers = Vec::new();
+        let unbalanced = || format!("unbalanced protected regions in {}", method.name());
+        for (index, op) in method.get_ops().iter().enumerate() {
+            match op {
+                CILOp::Label(label) => {
+                    labels.insert(*label, index);
+                }
+                CILOp::BeginTry => open_tries.push(index),
+                CILOp::EndTry => {
+                    closed_tries.push((open_tries.pop().ok_or_else(unbalanced)?, index))
+                }
+                CILOp::BeginCatch(class) => {
+                    let try_range = closed_tries.pop().ok_or_else(unbalanced)?;
+                    open_handlers.push((try_range, index, Some(class.as_ref().clone())));
+                }
+                CILOp::BeginFinally => {
+                    let try_range = closed_tries.pop().ok_or_else(unbalanced)?;
+                    open_handlers.push((try_range, index, None));
+                }
+                CILOp::EndHandler => {
+                    let ((try_start, try_end), handler, catch) =
+                        open_handlers.pop().ok_or_else(unbalanced)?;
+                    regions.push(Region {
+                        try_start,
+                        try_end,
+                        handler,
+                        handler_end: index,
+                        catch,
+                    });
+                }
+                _ => (),
+            }
+        }
+        Ok(Self {
+            method,
+            class,
+            labels,
+            regions,
+        })
+    }
+    fn name(&self) -> IString {
+        match &self.class {
+            Some(class) => format!("{class}::{name}", name = self.method.name()).into(),
+            None => self.method.name().into(),
+        }
+    }
+}
+/// A managed object.
+enum Object {
+    String(String),
+    /// `System.Text.StringBuilder`, holding the text built so far.
+    StringBuilder(String),
+    /// Instance of a class. `data` is the address of its fields, and `message` is the message of an exception.
+    Instance {
+        class: DotnetTypeRef,
+        data: u64,
+        message: Option<String>,
+    },
+    /// Managed array of `len` elements stored as `slot`, starting at the address `data`. Arrays are never moved, so they are always pinned.
+    Array {
+        slot: Slot,
+        len: usize,
+        data: u64,
+    },
+}
+/// What to do with the value returned by a method.
+enum OnReturn {
+    /// Push the returned value, if any, onto the stack of the caller.
+    Push,
+    /// Push the object constructed by the method.
+    PushObj(u64),
+    /// Push the value type constructed by the method at an address.
+    PushStruct(u64, Slot),
+}
+/// State of a method being executed.
+struct Frame {
+    method: usize,
+    pc: usize,
+    stack: Vec<Value>,
+    args: Vec<(u64, Slot)>,
+    locals: Vec<(u64, Slot)>,
+    /// Stack pointer before the frame was allocated.
+    base_sp: u64,
+    /// Exceptions caught by the handlers being executed, with the indices of their regions.
+    caught: Vec<(u64, usize)>,
+    on_return: OnReturn,
+}
+/// Reason the execution of an op was interrupted.
+enum Trap {
+    /// A managed exception was thrown.
+    Exception(u64),
+    /// The program can't be executed further.
+    Error(String),
+}
+impl From<String> for Trap {
+    fn from(msg: String) -> Self {
+        Self::Error(msg)
+    }
+}
+impl From<&str> for Trap {
+    fn from(msg: &str) -> Self {
+        Self::Error(msg.into())
+    }
+}
+/// Where control goes after an op.
+enum Control {
+    Next,
+    Jump(u32),
+    /// A method was called or returned from, and the frame to execute was already set.
+    Switched,
+}
+/// Error preventing a program from being interpreted, eg. an unsupported op or an invalid memory access.
+#[derive(Debug)]
+pub struct InterpreterError {
+    message: String,
+    /// Methods being executed when the error occurred, innermost first.
+    backtrace: Vec<IString>,
+}
+impl InterpreterError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    pub fn backtrace(&self) -> &[IString] {
+        &self.backtrace
+    }
+}
+impl std::fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        for method in &self.backtrace {
+            write!(f, "\n    in {method}")?;
+        }
+        Ok(())
+    }
+}
+/// Output of an interpreted program.
+#[derive(Debug)]
+pub struct Execution {
+    stdout: String,
+    stderr: String,
+    exit_code: i32,
+}
+impl Execution {
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+}
+/// Runs the static constructor, and then the entrypoint of `asm`.
+pub fn run(asm: &Assembly) -> Result<Execution, InterpreterError> {
+    Interpreter::new(asm)?.run_entrypoint()
+}
+/// Executes the CIL of an [`Assembly`], without the need for a .NET runtime.
+pub struct Interpreter<'asm> {
+    asm: &'asm Assembly,
+    methods: Vec<LoadedMethod<'asm>>,
+    /// Indices of methods, by their class and name.
+    method_ids: HashMap<(Option<IString>, IString), Vec<usize>>,
+    layouts: Layouts<'asm>,
+    memory: Memory,
+    objects: Vec<Object>,
+    /// Objects referenced by `GCHandle`s, indexed by the handle minus one. Freed handles are `None`.
+    gc_handles: Vec<Option<u64>>,
+    /// Addresses of static fields, by their owner and name. Allocated on first use.
+    statics: HashMap<(Option<IString>, IString), (u64, Slot)>,
+    frames: Vec<Frame>,
+    /// Value returned by the outermost frame.
+    returned: Option<Value>,
+    stdout: String,
+    stderr: String,
+    /// Count of ops which can still be executed, or `None` if unlimited.
+    fuel: Option<u64>,
+}
+impl<'asm> Interpreter<'asm> {
+    /// Prepares the methods of `asm` for execution.
+    pub fn new(asm: &'asm Assembly) -> Result<Self, InterpreterError> {
+        let error = |message| InterpreterError {
+            message,
+            backtrace: vec![],
+        };
+        let mut methods = Vec::new();
+        for method in asm.methods() {
+            methods.push(LoadedMethod::new(method, None).map_err(error)?);
+        }
+        fn add_type_methods<'asm>(
+            type_def: &'asm TypeDef,
+            path: String,
+            methods: &mut Vec<LoadedMethod<'asm>>,
+        ) -> Result<(), String> {
+            for method in type_def.methods() {
+                methods.push(LoadedMethod::new(method, Some(path.clone().into()))?);
+            }
+            for inner in type_def.inner_types() {
+                add_type_methods(
+                    inner,
+                    format!("{path}/{name}", name = inner.name()),
+                    methods,
+                )?;
+            }
+            Ok(())
+        }
+        for type_def in asm.types() {
+            add_type_methods(type_def, type_def.name().into(), &mut methods).map_err(error)?;
+        }
+        let mut method_ids: HashMap<_, Vec<usize>> = HashMap::new();
+        for (index, method) in methods.iter().enumerate() {
+            method_ids
+                .entry((method.class.clone(), method.method.name().into()))
+                .or_default()
+                .push(index);
+        }
+        Ok(Self {
+            asm,
+            methods,
+            method_ids,
+            layouts: Layouts::new(asm),
+            memory: Memory::default(),
+            objects: Vec::new(),
+            gc_handles: Vec::new(),
+            statics: HashMap::new(),
+            frames: Vec::new(),
+            returned: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            fuel: None,
+        })
+    }
+    /// Limits the count of ops which can be executed, to stop programs which never finish.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel = Some(fuel);
+    }
+    /// Returns everything written to the standard output so far.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+    /// Runs the static constructor, and then the entrypoint of the assembly.
+    pub fn run_entrypoint(mut self) -> Result<Execution, InterpreterError> {
+        let entrypoint = self
+            .methods
+            .iter()
+            .position(|method| method.method.is_entrypoint())
+            .ok_or_else(|| InterpreterError {
+                message: "the assembly has no entrypoint".into(),
+                backtrace: vec![],
+            })?;
+        let cctor = self.find_method(None, ".cctor");
+        for method in cctor.into_iter().chain([entrypoint]) {
+            match self.execute(method, vec![]) {
+                Ok(_) => (),
+                Err(Trap::Exception(exception)) => {
+                    let exception = self.describe(exception);
+                    self.stderr
+                        .push_str(&format!("Unhandled exception. {exception}\n"));
+                    return Ok(self.finish(UNHANDLED_EXCEPTION_EXIT_CODE));
+                }
+                Err(Trap::Error(message)) => return Err(self.error(message)),
+            }
+        }
+        Ok(self.finish(0))
+    }
+    /// Calls the static method `name` of the assembly with `args`, returning its result.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Option<Value>, InterpreterError> {
+        let method = self
+            .find_method(None, name)
+            .ok_or_else(|| self.error(format!("method {name} is not defined")))?;
+        match self.execute(method, args.to_vec()) {
+            Ok(value) => Ok(value),
+            Err(Trap::Exception(exception)) => {
+                let message = format!("unhandled exception: {}", self.describe(exception));
+                Err(self.error(message))
+            }
+            Err(Trap::Error(message)) => Err(self.error(message)),
+        }
+    }
+    fn finish(self, exit_code: i32) -> Execution {
+        Execution {
+            stdout: self.stdout,
+            stderr: self.stderr,
+            exit_code,
+        }
+    }
+    /// Creates an error with the backtrace of the current frames, and abandons them.
+    fn error(&mut self, message: String) -> InterpreterError {
+        let backtrace = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| self.methods[frame.method].name())
+            .collect();
+        if let Some(frame) = self.frames.first() {
+            self.memory.reset_stack(frame.base_sp);
+        }
+        self.frames.clear();
+        InterpreterError { message, backtrace }
+    }
+    fn find_method(&self, class: Option<&str>, name: &str) -> Option<usize> {
+        self.method_ids
+            .get(&(class.map(Into::into), name.into()))
+            .and_then(|ids| ids.first().copied())
+    }
+    /// Returns the index of the method called by `site`, or `None` if it is a method of the BCL.
+    fn resolve(&self, site: &CallSite) -> Result<Option<usize>, String> {
+        let class = match site.class() {
+            Some(class) if class.asm().is_some() => return Ok(None),
+            class => class.map(|class| class.name_path().into()),
+        };
+        let ids = self
+            .method_ids
+            .get(&(class, site.name().into()))
+            .ok_or_else(|| format!("method {} is not defined", site.name()))?;
+        // Signatures of methods of generic types may not match the call site exactly.
+        ids.iter()
+            .copied()
+            .find(|id| self.methods[*id].method.sig() == site.signature())
+            .or_else(|| (ids.len() == 1).then(|| ids[0]))
+            .map(Some)
+            .ok_or_else(|| format!("no overload of {} matches {site:?}", site.name()))
+    }
+    /// Executes the method `method` with the arguments `args` until it returns.
+    fn execute(&mut self, method: usize, args: Vec<Value>) -> Result<Option<Value>, Trap> {
+        assert!(self.frames.is_empty(), "Reentered the interpreter!");
+        self.push_frame(method, args, OnReturn::Push)?;
+        while !self.frames.is_empty() {
+            if let Some(fuel) = &mut self.fuel {
+                if *fuel == 0 {
+                    return Err("ran out of fuel".into());
+                }
+                *fuel -= 1;
+            }
+            match self.step() {
+                Ok(()) => (),
+                Err(Trap::Exception(exception)) => self.throw(exception)?,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(self.returned.take())
+    }
+    /// Allocates the frame of a call to `method`, and makes it the one being executed.
+    fn push_frame(
+        &mut self,
+        method: usize,
+        args: Vec<Value>,
+        on_return: OnReturn,
+    ) -> Result<(), Trap> {
+        if self.frames.len() >= MAX_FRAMES {
+            return Err("stack overflow: too many nested calls".into());
+        }
+        let method_def: &'asm Method = self.methods[method].method;
+        let inputs = method_def.sig().inputs();
+        if inputs.len() != args.len() {
+            return Err(format!(
+                "{} expects {} arguments, but got {}",
+                method_def.name(),
+                inputs.len(),
+                args.len()
+            )
+            .into());
+        }
+        let base_sp = self.memory.stack_pointer();
+        let mut arg_slots = Vec::with_capacity(args.len());
+        for (index, (tpe, value)) in inputs.iter().zip(args).enumerate() {
+            // `this` of a value type is a pointer to it.
+            let slot = match tpe {
+                Type::DotnetType(dref)
+                    if index == 0 && !method_def.is_static() && dref.is_valuetype() =>
+                {
+                    Slot::NativeInt
+                }
+                _ => self.layouts.slot(tpe)?,
+            };
+            let addr = self.memory.stack_alloc(slot.size(), 8)?;
+            self.memory.write(addr, slot, &value)?;
+            arg_slots.push((addr, slot));
+        }
+        let mut locals = Vec::with_capacity(method_def.locals().len());
+        for (_, tpe) in method_def.locals() {
+            let slot = self.layouts.slot(tpe)?;
+            locals.push((self.memory.stack_alloc(slot.size(), 8)?, slot));
+        }
+        self.frames.push(Frame {
+            method,
+            pc: 0,
+            stack: Vec::new(),
+            args: arg_slots,
+            locals,
+            base_sp,
+            caught: Vec::new(),
+            on_return,
+        });
+        Ok(())
+    }
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("No frame is being executed!")
+    }
+    fn push(&mut self, value: Value) {
+        self.frame().stack.push(value);
+    }
+    fn pop(&mut self) -> Result<Value, Trap> {
+        Ok(self
+            .frame()
+            .stack
+            .pop()
+            .ok_or("popped a value from an empty stack")?)
+    }
+    /// Pops `count` values, returning them in the order they were pushed in.
+    fn pop_n(&mut self, count: usize) -> Result<Vec<Value>, Trap> {
+        let stack = &mut self.frame().stack;
+        if stack.len() < count {
+            return Err(format!("expected {count} values on the stack").into());
+        }
+        Ok(stack.split_off(stack.len() - count))
+    }
+    fn pop_pair(&mut self) -> Result<(Value, Value), Trap> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        Ok((a, b))
+    }
+    /// Converts an error of an arithmetic operation to the exception the .NET runtime would throw.
+    fn op_error(&mut self, error: OpError) -> Trap {
+        match error {
+            OpError::DivideByZero => Trap::Exception(self.new_exception(
+                "System.DivideByZeroException",
+                "Attempted to divide by zero.",
+            )),
+            OpError::Overflow => Trap::Exception(self.new_exception(
+                "System.OverflowException",
+                "Arithmetic operation resulted in an overflow.",
+            )),
+            OpError::Invalid(msg) => Trap::Error(msg),
+        }
+    }
+    fn new_object(&mut self, object: Object) -> u64 {
+        self.objects.push(object);
+        self.objects.len() as u64
+    }
+    fn new_string(&mut self, string: String) -> Value {
+        Value::Obj(self.new_object(Object::String(string)))
+    }
+    /// Creates an exception of the BCL class `class`.
+    fn new_exception(&mut self, class: &str, message: &str) -> u64 {
+        let class = DotnetTypeRef::new(Some("System.Runtime"), class).with_valuetype(false);
+        self.new_object(Object::Instance {
+            class,
+            data: 0,
+            message: Some(message.into()),
+        })
+    }
+    /// Creates an instance of a class defined within the assembly, with zeroed fields.
+    fn new_instance(&mut self, class: &DotnetTypeRef) -> Result<u64, Trap> {
+        let layout = self.layouts.struct_layout(class)?;
+        let data = self.memory.alloc(layout.size(), layout.align())?;
+        Ok(self.new_object(Object::Instance {
+            class: class.clone(),
+            data,
+            message: None,
+        }))
+    }
+    fn object(&self, handle: u64) -> Result<&Object, String> {
+        match handle {
+            0 => Err("null reference".into()),
+            _ => self
+                .objects
+                .get(handle as usize - 1)
+                .ok_or_else(|| format!("invalid object reference {handle}")),
+        }
+    }
+    fn object_mut(&mut self, handle: u64) -> Result<&mut Object, String> {
+        match handle {
+            0 => Err("null reference".into()),
+            _ => self
+                .objects
+                .get_mut(handle as usize - 1)
+                .ok_or_else(|| format!("invalid object reference {handle}")),
+        }
+    }
+    /// Returns the managed string `value` refers to. `null` is treated as an empty string.
+    fn string(&self, value: &Value) -> Result<&str, String> {
+        match value.as_obj()? {
+            0 => Ok(""),
+            handle => match self.object(handle)? {
+                Object::String(string) => Ok(string),
+                Object::Instance { class, .. } => Err(format!(
+                    "expected a string, found an instance of {}",
+                    class.name_path()
+                )),
+                Object::Array { .. } => Err("expected a string, found an array".into()),
+                Object::StringBuilder(_) => Err("expected a string, found a StringBuilder".into()),
+            },
+        }
+    }
+    /// Returns the class and message of an exception.
+    fn describe(&self, exception: u64) -> String {
+        match self.object(exception) {
+            Ok(Object::Instance { class, message, .. }) => format!(
+                "{class}: {message}",
+                class = class.name_path(),
+                message = message.as_deref().unwrap_or_default()
+            ),
+            Ok(Object::String(string)) => format!("System.String: {string}"),
+            Ok(Object::Array { len, .. }) => format!("System.Array of length {len}"),
+            Ok(Object::StringBuilder(text)) => format!("System.Text.StringBuilder: {text}"),
+            Err(err) => err,
+        }
+    }
+    /// Checks if `object` can be cast to `tpe` by `castclass`, and throws `System.InvalidCastException` if not. `null` can be cast to any type.
+    fn cast_class(&mut self, object: u64, tpe: &Type) -> Result<(), Trap> {
+        if object == 0 {
+            return Ok(());
+        }
+        let (is_instance, class) = match (self.object(object)?, tpe) {
+            (_, Type::DotnetType(base)) if base.name_path() == "System.Object" => return Ok(()),
+            (Object::Instance { class, .. }, Type::DotnetType(base)) => (
+                self.is_instance_of(class, base),
+                class.name_path().to_owned(),
+            ),
+            (Object::String(_), Type::DotnetType(base)) => (
+                base.name_path() == "System.String",
+                "System.String".to_owned(),
+            ),
+            (Object::StringBuilder(_), Type::DotnetType(base)) => (
+                base.name_path() == "System.Text.StringBuilder",
+                "System.Text.StringBuilder".to_owned(),
+            ),
+            (Object::Array { .. }, Type::DotnetArray(_)) => return Ok(()),
+            (Object::Array { .. }, _) => (false, "System.Array".to_owned()),
+            (_, _) => return Err(format!("`castclass` to the non-class type {tpe:?}").into()),
+        };
+        if is_instance {
+            return Ok(());
+        }
+        let target = match tpe {
+            Type::DotnetType(dref) => dref.name_path().to_owned(),
+            _ => format!("{tpe:?}"),
+        };
+        let message = format!("Unable to cast object of type '{class}' to type '{target}'.");
+        Err(Trap::Exception(
+            self.new_exception("System.InvalidCastException", &message),
+        ))
+    }
+    /// Checks if objects of class `class` are instances of `base`.
+    fn is_instance_of(&self, class: &DotnetTypeRef, base: &DotnetTypeRef) -> bool {
+        // Only exceptions can be thrown, so they are all instances of `System.Exception`.
+        if matches!(base.name_path(), "System.Exception" | "System.Object") {
+            return true;
+        }
+        let mut class = Some(class.clone());
+        while let Some(current) = class {
+            if current.name_path() == base.name_path() {
+                return true;
+            }
+            class = current
+                .asm()
+                .is_none()
+                .then(|| self.asm.get_typedef_by_path(current.name_path()))
+                .flatten()
+                .and_then(|type_def| type_def.extends().cloned());
+        }
+        false
+    }
+    /// Transfers control to the innermost handler catching `exception`, unwinding frames if needed.
+    fn throw(&mut self, exception: u64) -> Result<(), Trap> {
+        let class = match self.object(exception)? {
+            Object::Instance { class, .. } => class.clone(),
+            Object::String(_) => return Err("threw a string".into()),
+            Object::Array { .. } => return Err("threw an array".into()),
+            Object::StringBuilder(_) => return Err("threw a StringBuilder".into()),
+        };
+        while let Some(frame) = self.frames.last() {
+            let loaded = &self.methods[frame.method];
+            let mut handler = None;
+            for (index, region) in loaded.regions.iter().enumerate() {
+                if region.try_start < frame.pc && frame.pc < region.try_end {
+                    match &region.catch {
+                        Some(catch) if self.is_instance_of(&class, catch) => {
+                            handler = Some(index);
+                            break;
+                        }
+                        Some(_) => (),
+                        None => return Err("`finally` handlers are not supported".into()),
+                    }
+                }
+            }
+            match handler {
+                Some(index) => {
+                    let handler = loaded.regions[index].handler;
+                    let handler_regions: Vec<_> = loaded
+                        .regions
+                        .iter()
+                        .map(|region| region.handler_contains(handler))
+                        .collect();
+                    let frame = self.frame();
+                    // Handlers the exception escaped from are no longer executed.
+                    frame.caught.retain(|(_, region)| handler_regions[*region]);
+                    frame.caught.push((exception, index));
+                    frame.stack = vec![Value::Obj(exception)];
+                    frame.pc = handler + 1;
+                    return Ok(());
+                }
+                None => {
+                    let frame = self.frames.pop().expect("No frame is being executed!");
+                    self.memory.reset_stack(frame.base_sp);
+                }
+            }
+        }
+        Err(Trap::Exception(exception))
+    }
+    /// Returns from the method being executed.
+    fn ret(&mut self) -> Result<(), Trap> {
+        let method_id = self.frame().method;
+        let method: &'asm Method = self.methods[method_id].method;
+        let value = match method.sig().output() {
+            Type::Void => None,
+            output => {
+                let value = self.pop()?;
+                Some(self.layouts.slot(output)?.normalize(&value)?)
+            }
+        };
+        let frame = self.frames.pop().expect("No frame is being executed!");
+        self.memory.reset_stack(frame.base_sp);
+        let value = match frame.on_return {
+            OnReturn::Push => value,
+            OnReturn::PushObj(handle) => Some(Value::Obj(handle)),
+            OnReturn::PushStruct(addr, slot) => Some(self.memory.read(addr, slot)?),
+        };
+        match self.frames.last_mut() {
+            Some(caller) => {
+                caller.pc += 1;
+                caller.stack.extend(value);
+            }
+            None => self.returned = value,
+        }
+        Ok(())
+    }
+    fn call_site(&mut self, site: &CallSite) -> Result<Control, Trap> {
+        // `black_box` is not defined, and the exporters skip calls to it.
+        if site.is_nop() {
+            return Ok(Control::Next);
+        }
+        let args = self.pop_n(site.inputs().len())?;
+        match self.resolve(site)? {
+            Some(method) => {
+                self.push_frame(method, args, OnReturn::Push)?;
+                Ok(Control::Switched)
+            }
+            None => {
+                if let Some(value) = bcl::call(self, site, &args)? {
+                    self.push(value);
+                }
+                Ok(Control::Next)
+            }
+        }
+    }
+    fn new_obj(&mut self, site: &CallSite) -> Result<Control, Trap> {
+        let args = self.pop_n(site.explicit_inputs().len())?;
+        let Some(ctor) = self.resolve(site)? else {
+            let object = bcl::new_obj(self, site, &args)?;
+            self.push(object);
+            return Ok(Control::Next);
+        };
+        let class = site.class().ok_or("`newobj` without a class")?;
+        let (this, on_return) = if class.is_valuetype() {
+            let layout = self.layouts.struct_layout(class)?;
+            let addr = self.memory.stack_alloc(layout.size(), layout.align())?;
+            (
+                Value::NativeInt(addr as i64),
+                OnReturn::PushStruct(addr, Slot::Struct(layout.size())),
+            )
+        } else {
+            let handle = self.new_instance(class)?;
+            (Value::Obj(handle), OnReturn::PushObj(handle))
+        };
+        let mut ctor_args = vec![this];
+        ctor_args.extend(args);
+        self.push_frame(ctor, ctor_args, on_return)?;
+        Ok(Control::Switched)
+    }
+    /// Pops an index and a managed array, and returns the address of the indexed element, as well as its slot.
+    fn element_address(&mut self) -> Result<(u64, Slot), Trap> {
+        let index = self.pop()?.as_u64()?;
+        let array = self.pop()?.as_obj()?;
+        let Object::Array { slot, len, data } = *self.object(array)? else {
+            return Err("expected an array".into());
+        };
+        if index >= len as u64 {
+            return Err(Trap::Exception(self.new_exception(
+                "System.IndexOutOfRangeException",
+                "Index was outside the bounds of the array.",
+            )));
+        }
+        Ok((data + index * slot.size() as u64, slot))
+    }
+    /// Returns the address and slot of `field` of the object or pointer `target`.
+    fn field_address(&self, target: &Value, field: &FieldDescriptor) -> Result<(u64, Slot), Trap> {
+        let layout = self.layouts.struct_layout(field.owner())?;
+        let (offset, slot) = layout.field(field.name())?;
+        let base = match target {
+            Value::Obj(handle) => match self.object(*handle)? {
+                Object::Instance { data, .. } if *data != 0 => *data,
+                _ => return Err(format!("object has no field {}", field.name()).into()),
+            },
+            _ => target.as_address()?,
+        };
+        Ok((base + offset as u64, slot))
+    }
+    fn static_address(&mut self, field: &StaticFieldDescriptor) -> Result<(u64, Slot), Trap> {
+        let key = (
+            field.owner().map(|owner| owner.name_path().into()),
+            field.name().into(),
+        );
+        if let Some(address) = self.statics.get(&key) {
+            return Ok(*address);
+        }
+        let slot = self.layouts.slot(field.tpe())?;
+        let (size, align) = self.layouts.size_align(field.tpe())?;
+        let address = (self.memory.alloc(size, align)?, slot);
+        self.statics.insert(key, address);
+        Ok(address)
+    }
+    fn load(&mut self, slot: Slot) -> Result<(), Trap> {
+        let addr = self.pop()?.as_address()?;
+        let value = self.memory.read(addr, slot)?;
+        self.push(value);
+        Ok(())
+    }
+    fn store(&mut self, slot: Slot) -> Result<(), Trap> {
+        let value = self.pop()?;
+        let addr = self.pop()?.as_address()?;
+        self.memory.write(addr, slot, &value)?;
+        Ok(())
+    }
+    fn branch(&mut self, cmp: Cmp, label: u32) -> Result<Control, Trap> {
+        let (a, b) = self.pop_pair()?;
+        let taken = value::compare(cmp, &a, &b).map_err(|err| self.op_error(err))?;
+        Ok(if taken {
+            Control::Jump(label)
+        } else {
+            Control::Next
+        })
+    }
+    /// Executes a single op of the innermost frame.
+    fn step(&mut self) -> Result<(), Trap> {
+        let frame = self.frames.last().expect("No frame is being executed!");
+        let (method, pc) = (frame.method, frame.pc);
+        let method_def: &'asm Method = self.methods[method].method;
+        let op = method_def
+            .get_ops()
+            .get(pc)
+            .ok_or("control fell through the end of the method")?;
+        let control = match op {
+            CILOp::Label(_) | CILOp::Comment(_) | CILOp::Nop | CILOp::BeginTry => Control::Next,
+            CILOp::EndTry | CILOp::BeginCatch(_) | CILOp::BeginFinally | CILOp::EndHandler => {
+                return Err(format!("control fell through {op:?}").into())
+            }
+            CILOp::GoTo(label) => Control::Jump(*label),
+            CILOp::BEq(label) => self.branch(Cmp::Eq, *label)?,
+            CILOp::BNe(label) => self.branch(Cmp::NeUn, *label)?,
+            CILOp::BLt(label) => self.branch(Cmp::Lt, *label)?,
+            CILOp::BGe(label) => self.branch(Cmp::Ge, *label)?,
+            CILOp::BLe(label) => self.branch(Cmp::Le, *label)?,
+            CILOp::BZero(label) | CILOp::BTrue(label) => {
+                let is_true = self.pop()?.is_true()?;
+                if is_true == matches!(op, CILOp::BTrue(_)) {
+                    Control::Jump(*label)
+                } else {
+                    Control::Next
+                }
+            }
+            CILOp::Call(site) | CILOp::CallVirt(site) => self.call_site(site)?,
+            CILOp::CallI(sig) => self.call_indirect(sig)?,
+            CILOp::LDFtn(site) => {
+                let method = self
+                    .resolve(site)?
+                    .ok_or("can't take the address of a BCL method")?;
+                self.push(Value::NativeInt((FN_PTR_BASE + method as u64 * 8) as i64));
+                Control::Next
+            }
+            CILOp::NewObj(site) => self.new_obj(site)?,
+            CILOp::Throw => match self.pop()?.as_obj()? {
+                0 => return Err("threw null".into()),
+                exception => return Err(Trap::Exception(exception)),
+            },
+            CILOp::Rethrow => match self.frame().caught.last() {
+                Some((exception, _)) => return Err(Trap::Exception(*exception)),
+                None => return Err("`rethrow` outside of an exception handler".into()),
+            },
+            CILOp::Leave(label) => {
+                let target = self.label(*label)?;
+                let regions = &self.methods[method].regions;
+                let frame = self.frames.last_mut().expect("No frame is being executed!");
+                frame.stack.clear();
+                // Leaving a handler ends the handling of its exception.
+                while let Some((_, region)) = frame.caught.last() {
+                    let region = &regions[*region];
+                    if region.handler_contains(pc) && !region.handler_contains(target) {
+                        frame.caught.pop();
+                    } else {
+                        break;
+                    }
+                }
+                frame.pc = target;
+                Control::Switched
+            }
+            CILOp::EndFinally => return Err("`finally` handlers are not supported".into()),
+            CILOp::Ret => {
+                self.ret()?;
+                Control::Switched
+            }
+            CILOp::LDLoc(index) | CILOp::LDArg(index) => {
+                let (addr, slot) = self.variable(op, *index)?;
+                let value = self.memory.read(addr, slot)?;
+                self.push(value);
+                Control::Next
+            }
+            CILOp::STLoc(index) | CILOp::STArg(index) => {
+                let (addr, slot) = self.variable(op, *index)?;
+                let value = self.pop()?;
+                self.memory.write(addr, slot, &value)?;
+                Control::Next
+            }
+            CILOp::LDLocA(index) | CILOp::LDArgA(index) => {
+                let (addr, _) = self.variable(op, *index)?;
+                self.push(Value::NativeInt(addr as i64));
+                Control::Next
+            }
+            CILOp::NewTMPLocal(_)
+            | CILOp::FreeTMPLocal
+            | CILOp::LoadTMPLocal
+            | CILOp::LoadUnderTMPLocal(_)
+            | CILOp::LoadAdressUnderTMPLocal(_)
+            | CILOp::LoadAddresOfTMPLocal
+            | CILOp::SetTMPLocal
+            | CILOp::LoadGlobalAllocPtr { .. }
+            | CILOp::LoadGlobalStaticPtr { .. } => {
+                return Err(format!("synthetic op {op:?} was not lowered").into())
+            }
+            CILOp::LdcI32(value) => {
+                self.push(Value::I32(*value));
+                Control::Next
+            }
+            CILOp::LdcI64(value) => {
+                self.push(Value::I64(*value));
+                Control::Next
+            }
+            CILOp::LdcF32(value) => {
+                self.push(Value::F32(*value));
+                Control::Next
+            }
+            CILOp::LdcF64(value) => {
+                self.push(Value::F64(*value));
+                Control::Next
+            }
+            CILOp::LdStr(string) => {
+                let string = self.new_string(string.to_string());
+                self.push(string);
+                Control::Next
+            }
+            CILOp::LdNull => {
+                self.push(Value::Obj(0));
+                Control::Next
+            }
+            CILOp::ConvI8(_)
+            | CILOp::ConvI16(_)
+            | CILOp::ConvI32(_)
+            | CILOp::ConvI64(_)
+            | CILOp::ConvISize(_)
+            | CILOp::ConvU8(_)
+            | CILOp::ConvU16(_)
+            | CILOp::ConvU32(_)
+            | CILOp::ConvU64(_)
+            | CILOp::ConvUSize(_)
+            | CILOp::ConvF32(_)
+            | CILOp::ConvF64(_)
+            | CILOp::ConvFUn => {
+                let value = self.pop()?;
+                let value = value::convert(op, &value).map_err(|err| self.op_error(err))?;
+                self.push(value);
+                Control::Next
+            }
+            CILOp::LDIndI8 => self.load(Slot::I8).map(|_| Control::Next)?,
+            CILOp::LDIndI16 => self.load(Slot::I16).map(|_| Control::Next)?,
+            CILOp::LDIndI32 => self.load(Slot::I32).map(|_| Control::Next)?,
+            CILOp::LDIndI64 => self.load(Slot::I64).map(|_| Control::Next)?,
+            CILOp::LDIndISize => self.load(Slot::NativeInt).map(|_| Control::Next)?,
+            CILOp::LDIndF32 => self.load(Slot::F32).map(|_| Control::Next)?,
+            CILOp::LDIndF64 => self.load(Slot::F64).map(|_| Control::Next)?,
+            CILOp::LDIndRef => self.load(Slot::Obj).map(|_| Control::Next)?,
+            CILOp::STIndI8 => self.store(Slot::I8).map(|_| Control::Next)?,
+            CILOp::STIndI16 => self.store(Slot::I16).map(|_| Control::Next)?,
+            CILOp::STIndI32 => self.store(Slot::I32).map(|_| Control::Next)?,
+            CILOp::STIndI64 => self.store(Slot::I64).map(|_| Control::Next)?,
+            CILOp::STIndISize => self.store(Slot::NativeInt).map(|_| Control::Next)?,
+            CILOp::STIndF32 => self.store(Slot::F32).map(|_| Control::Next)?,
+            CILOp::STIndF64 => self.store(Slot::F64).map(|_| Control::Next)?,
+            CILOp::Add
+            | CILOp::AddOvf
+            | CILOp::AddOvfUn
+            | CILOp::And
+            | CILOp::Div
+            | CILOp::DivUn
+            | CILOp::Rem
+            | CILOp::RemUn
+            | CILOp::Sub
+            | CILOp::SubOvf
+            | CILOp::SubOvfUn
+            | CILOp::Mul
+            | CILOp::MulOvf
+            | CILOp::Or
+            | CILOp::XOr => {
+                let (a, b) = self.pop_pair()?;
+                let value = value::binop(op, &a, &b).map_err(|err| self.op_error(err))?;
+                self.push(value);
+                Control::Next
+            }
+            CILOp::Shl | CILOp::Shr | CILOp::ShrUn => {
+                let (a, b) = self.pop_pair()?;
+                let value = value::shift(op, &a, &b).map_err(|err| self.op_error(err))?;
+                self.push(value);
+                Control::Next
+            }
+            CILOp::Not | CILOp::Neg => {
+                let value = self.pop()?;
+                let value = value::unop(op, &value).map_err(|err| self.op_error(err))?;
+                self.push(value);
+                Control::Next
+            }
+            CILOp::Eq | CILOp::Lt | CILOp::LtUn | CILOp::Gt | CILOp::GtUn => {
+                let cmp = match op {
+                    CILOp::Eq => Cmp::Eq,
+                    CILOp::Lt => Cmp::Lt,
+                    CILOp::LtUn => Cmp::LtUn,
+                    CILOp::Gt => Cmp::Gt,
+                    _ => Cmp::GtUn,
+                };
+                let (a, b) = self.pop_pair()?;
+                let result = value::compare(cmp, &a, &b).map_err(|err| self.op_error(err))?;
+                self.push(Value::I32(i32::from(result)));
+                Control::Next
+            }
+            CILOp::Pop => {
+                self.pop()?;
+                Control::Next
+            }
+            CILOp::Dup => {
+                let value = self.pop()?;
+                self.push(value.clone());
+                self.push(value);
+                Control::Next
+            }
+            CILOp::LocAlloc => {
+                let size = self.pop()?.as_u64()? as usize;
+                let addr = self.memory.stack_alloc(size, 16)?;
+                self.push(Value::NativeInt(addr as i64));
+                Control::Next
+            }
+            CILOp::LDField(field) => {
+                let target = self.pop()?;
+                let value = match &target {
+                    // Fields of value types on the stack are read directly.
+                    Value::Struct(bytes) => {
+                        let layout = self.layouts.struct_layout(field.owner())?;
+                        let (offset, slot) = layout.field(field.name())?;
+                        let bytes = bytes
+                            .get(offset..offset + slot.size())
+                            .ok_or("field is out of the bounds of its value type")?;
+                        slot.load(bytes)
+                    }
+                    _ => {
+                        let (addr, slot) = self.field_address(&target, field)?;
+                        self.memory.read(addr, slot)?
+                    }
+                };
+                self.push(value);
+                Control::Next
+            }
+            CILOp::LDFieldAdress(field) => {
+                let target = self.pop()?;
+                let (addr, _) = self.field_address(&target, field)?;
+                self.push(Value::NativeInt(addr as i64));
+                Control::Next
+            }
+            CILOp::STField(field) => {
+                let value = self.pop()?;
+                let target = self.pop()?;
+                let (addr, slot) = self.field_address(&target, field)?;
+                self.memory.write(addr, slot, &value)?;
+                Control::Next
+            }
+            CILOp::LdObj(tpe) => self.load(self.layouts.slot(tpe)?).map(|_| Control::Next)?,
+            CILOp::STObj(tpe) => self.store(self.layouts.slot(tpe)?).map(|_| Control::Next)?,
+            CILOp::CastClass(tpe) => {
+                let object = self.pop()?.as_obj()?;
+                self.cast_class(object, tpe)?;
+                self.push(Value::Obj(object));
+                Control::Next
+            }
+            CILOp::SizeOf(tpe) => {
+                let (size, _) = self.layouts.size_align(tpe)?;
+                self.push(Value::I32(size as i32));
+                Control::Next
+            }
+            CILOp::LDStaticField(field) => {
+                let (addr, slot) = self.static_address(field)?;
+                let value = self.memory.read(addr, slot)?;
+                self.push(value);
+                Control::Next
+            }
+            CILOp::STStaticField(field) => {
+                let value = self.pop()?;
+                let (addr, slot) = self.static_address(field)?;
+                self.memory.write(addr, slot, &value)?;
+                Control::Next
+            }
+            CILOp::CpBlk => {
+                let len = self.pop()?.as_u64()? as usize;
+                let src = self.pop()?.as_address()?;
+                let dst = self.pop()?.as_address()?;
+                self.memory.copy(dst, src, len)?;
+                Control::Next
+            }
+            CILOp::NewArr(tpe) => {
+                let len = self.pop()?.as_u64()? as usize;
+                let slot = self.layouts.slot(tpe)?;
+                let (_, align) = self.layouts.size_align(tpe)?;
+                let data = self.memory.alloc(len * slot.size(), align)?;
+                let array = self.new_object(Object::Array { slot, len, data });
+                self.push(Value::Obj(array));
+                Control::Next
+            }
+            CILOp::LDElem(_) => {
+                let (addr, slot) = self.element_address()?;
+                let value = self.memory.read(addr, slot)?;
+                self.push(value);
+                Control::Next
+            }
+            CILOp::STElem(_) => {
+                let value = self.pop()?;
+                let (addr, slot) = self.element_address()?;
+                self.memory.write(addr, slot, &value)?;
+                Control::Next
+            }
+            CILOp::LDElemA(_) => {
+                let (addr, _) = self.element_address()?;
+                self.push(Value::NativeInt(addr as i64));
+                Control::Next
+            }
+            CILOp::LDLen => {
+                let array = self.pop()?.as_obj()?;
+                let Object::Array { len, .. } = *self.object(array)? else {
+                    return Err("`ldlen` expects an array".into());
+                };
+                self.push(Value::NativeInt(len as i64));
+                Control::Next
+            }
+        };
+        match control {
+            Control::Next => self.frame().pc += 1,
+            Control::Jump(label) => {
+                let target = self.label(label)?;
+                self.frame().pc = target;
+            }
+            Control::Switched => (),
+        }
+        Ok(())
+    }
+    fn call_indirect(&mut self, sig: &FnSig) -> Result<Control, Trap> {
+        let ptr = self.pop()?.as_address()?;
+        let args = self.pop_n(sig.inputs().len())?;
+        let method = ptr
+            .checked_sub(FN_PTR_BASE)
+            .filter(|offset| offset % 8 == 0)
+            .map(|offset| (offset / 8) as usize)
+            .filter(|method| *method < self.methods.len())
+            .ok_or_else(|| format!("called an invalid function pointer {ptr:#x}"))?;
+        self.push_frame(method, args, OnReturn::Push)?;
+        Ok(Control::Switched)
+    }
+    /// Returns the index of the op marked with `label` in the innermost frame.
+    fn label(&self, label: u32) -> Result<usize, Trap> {
+        let frame = self.frames.last().expect("No frame is being executed!");
+        Ok(*self.methods[frame.method]
+            .labels
+            .get(&label)
+            .ok_or_else(|| format!("branch to undefined label {label}"))?)
+    }
+    /// Returns the address and slot of the local or argument accessed by `op`.
+    fn variable(&self, op: &CILOp, index: u32) -> Result<(u64, Slot), Trap> {
+        let frame = self.frames.last().expect("No frame is being executed!");
+        let variables = match op {
+            CILOp::LDArg(_) | CILOp::STArg(_) | CILOp::LDArgA(_) => &frame.args,
+            _ => &frame.locals,
+        };
+        Ok(*variables
+            .get(index as usize)
+            .ok_or_else(|| format!("{op:?} is out of range"))?)
+    }
+}
+#[cfg(test)]
+fn console_write(input: Type) -> CILOp {
+    let console =
+        DotnetTypeRef::new(Some("System.Console"), "System.Console").with_valuetype(false);
+    CILOp::Call(CallSite::boxed(
+        Some(console),
+        "Write".into(),
+        FnSig::new(&[input], &Type::Void),
+        true,
+    ))
+}
+#[cfg(test)]
+fn test_assembly(ops: Vec<CILOp>, locals: Vec<crate::method::LocalDef>) -> Assembly {
+    let mut asm = Assembly::empty();
+    let mut main = Method::new(
+        crate::access_modifier::AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::Void),
+        "main",
+        locals,
+    );
+    main.set_ops(ops);
+    let site = main.call_site();
+    asm.add_method(main);
+    asm.set_entrypoint(site);
+    asm
+}
+#[test]
+fn interpret_loop() {
+    // Prints numbers from 0 to 4, separated by commas.
+    let asm = test_assembly(
+        vec![
+            CILOp::Label(0),
+            CILOp::LDLoc(0),
+            console_write(Type::I32),
+            CILOp::LdcI32(',' as i32),
+            console_write(Type::DotnetChar),
+            CILOp::LDLoc(0),
+            CILOp::LdcI32(1),
+            CILOp::Add,
+            CILOp::STLoc(0),
+            CILOp::LDLoc(0),
+            CILOp::LdcI32(5),
+            CILOp::BLt(0),
+            CILOp::Ret,
+        ],
+        vec![(None, Type::I32)],
+    );
+    let execution = run(&asm).unwrap();
+    assert_eq!(execution.stdout(), "0,1,2,3,4,");
+    assert_eq!(execution.exit_code(), 0);
+}
+#[test]
+fn interpret_exceptions() {
+    let exception =
+        DotnetTypeRef::new(Some("System.Runtime"), "System.Exception").with_valuetype(false);
+    let asm = test_assembly(
+        vec![
+            CILOp::BeginTry,
+            CILOp::LdcI32(1),
+            CILOp::LdcI32(0),
+            CILOp::Div,
+            CILOp::Pop,
+            CILOp::Leave(0),
+            CILOp::EndTry,
+            CILOp::BeginCatch(exception.into()),
+            CILOp::Pop,
+            CILOp::LdStr("caught".into()),
+            console_write(crate::utilis::string_class().into()),
+            CILOp::Leave(0),
+            CILOp::EndHandler,
+            CILOp::Label(0),
+            CILOp::LdStr("uncaught".into()),
+            CILOp::NewObj(CallSite::boxed(
+                Some(
+                    DotnetTypeRef::new(Some("System.Runtime"), "System.Exception")
+                        .with_valuetype(false),
+                ),
+                ".ctor".into(),
+                FnSig::new(
+                    &[
+                        Type::DotnetType(
+                            DotnetTypeRef::new(Some("System.Runtime"), "System.Exception")
+                                .with_valuetype(false)
+                                .into(),
+                        ),
+                        crate::utilis::string_class().into(),
+                    ],
+                    &Type::Void,
+                ),
+                false,
+            )),
+            CILOp::Throw,
+        ],
+        vec![],
+    );
+    let execution = run(&asm).unwrap();
+    assert_eq!(execution.stdout(), "caught");
+    assert_eq!(
+        execution.stderr(),
+        "Unhandled exception. System.Exception: uncaught\n"
+    );
+    assert_eq!(execution.exit_code(), UNHANDLED_EXCEPTION_EXIT_CODE);
+}
+#[test]
+fn interpret_gc_handles() {
+    // Appends to a `StringBuilder` through a handle, and then uses the handle after freeing it.
+    let builder = DotnetTypeRef::new(Some("System.Runtime"), "System.Text.StringBuilder")
+        .with_valuetype(false);
+    let builder_type = Type::DotnetType(builder.clone().into());
+    let handle = DotnetTypeRef::new(
+        Some("System.Runtime"),
+        "System.Runtime.InteropServices.GCHandle",
+    );
+    let handle_type = Type::DotnetType(handle.clone().into());
+    let handle_call = |name: &str, output: &Type| {
+        CILOp::Call(CallSite::boxed(
+            Some(handle.clone()),
+            name.into(),
+            FnSig::new(&[Type::Ptr(handle_type.clone().into())], output),
+            false,
+        ))
+    };
+    let object = Type::DotnetType(DotnetTypeRef::object_type().into());
+    let builder_call = |name: &str, inputs: &[Type], output: &Type| {
+        CILOp::Call(CallSite::boxed(
+            Some(builder.clone()),
+            name.into(),
+            FnSig::new(inputs, output),
+            false,
+        ))
+    };
+    let asm = test_assembly(
+        vec![
+            CILOp::NewObj(CallSite::boxed(
+                Some(builder.clone()),
+                ".ctor".into(),
+                FnSig::new(&[builder_type.clone()], &Type::Void),
+                false,
+            )),
+            CILOp::Call(CallSite::boxed(
+                Some(handle.clone()),
+                "Alloc".into(),
+                FnSig::new(&[object.clone()], &handle_type),
+                true,
+            )),
+            CILOp::STLoc(0),
+            CILOp::LDLocA(0),
+            handle_call("get_Target", &object),
+            CILOp::CastClass(builder_type.clone().into()),
+            CILOp::LdcI32(7),
+            builder_call("Append", &[builder_type.clone(), Type::I32], &builder_type),
+            builder_call(
+                "ToString",
+                &[builder_type.clone()],
+                &crate::utilis::string_class().into(),
+            ),
+            console_write(crate::utilis::string_class().into()),
+            CILOp::LDLocA(0),
+            handle_call("Free", &Type::Void),
+            CILOp::LDLocA(0),
+            handle_call("get_Target", &object),
+            CILOp::Pop,
+            CILOp::Ret,
+        ],
+        vec![(None, handle_type.clone())],
+    );
+    let execution = run(&asm).unwrap();
+    assert_eq!(execution.stdout(), "7");
+    assert_eq!(
+        execution.stderr(),
+        "Unhandled exception. System.InvalidOperationException: Handle is not initialized.\n"
+    );
+}
+#[test]
+fn interpret_pointers() {
+    // Writes 2 bytes through a pointer to a local, and reads them back as a single `u16`.
+    let asm = test_assembly(
+        vec![
+            CILOp::LDLocA(0),
+            CILOp::LdcI32(0x34),
+            CILOp::STIndI8,
+            CILOp::LDLocA(0),
+            CILOp::LdcI32(1),
+            CILOp::Add,
+            CILOp::LdcI32(0x12),
+            CILOp::STIndI8,
+            CILOp::LDLoc(0),
+            console_write(Type::U16),
+            CILOp::Ret,
+        ],
+        vec![(None, Type::U16)],
+    );
+    assert_eq!(run(&asm).unwrap().stdout(), (0x1234).to_string());
+}
+#[test]
+fn interpret_arrays() {
+    // Sets an element of an array, reads it back through its address, and prints the length of the array.
+    let array = Type::DotnetArray(
+        crate::r#type::DotnetArray {
+            element: Type::I32,
+            dimensions: 1,
+        }
+        .into(),
+    );
+    let asm = test_assembly(
+        vec![
+            CILOp::LdcI32(3),
+            CILOp::NewArr(Type::I32.into()),
+            CILOp::STLoc(0),
+            CILOp::LDLoc(0),
+            CILOp::LdcI32(1),
+            CILOp::LdcI32(42),
+            CILOp::STElem(Type::I32.into()),
+            CILOp::LDLoc(0),
+            CILOp::LdcI32(1),
+            CILOp::LDElemA(Type::I32.into()),
+            CILOp::LDIndI32,
+            console_write(Type::I32),
+            CILOp::LDLoc(0),
+            CILOp::LDLen,
+            CILOp::ConvI32(false),
+            console_write(Type::I32),
+            CILOp::Ret,
+        ],
+        vec![(None, array)],
+    );
+    assert_eq!(run(&asm).unwrap().stdout(), "423");
+}
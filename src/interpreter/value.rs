@@ -0,0 +1,457 @@
+use crate::cil::CILOp;
+use std::cmp::Ordering;
+/// A value on the evaluation stack of the interpreter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// `int32`, also used for all smaller integers, bools and chars.
+    I32(i32),
+    /// `int64`
+    I64(i64),
+    /// `native int`, also used for pointers. The interpreter emulates a 64 bit runtime.
+    NativeInt(i64),
+    /// `float32`
+    F32(f32),
+    /// `float64`
+    F64(f64),
+    /// Reference to a managed object, `0` being `null`.
+    Obj(u64),
+    /// Value type, stored as its raw bytes.
+    Struct(Vec<u8>),
+}
+/// Error raised by an arithmetic operation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OpError {
+    /// Integer division by zero. Becomes a `System.DivideByZeroException`.
+    DivideByZero,
+    /// Checked operation overflowed. Becomes a `System.OverflowException`.
+    Overflow,
+    /// The operands are not valid for this operation.
+    Invalid(String),
+}
+impl From<String> for OpError {
+    fn from(msg: String) -> Self {
+        Self::Invalid(msg)
+    }
+}
+/// Width of an integer on the evaluation stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Width {
+    W32,
+    Native,
+    W64,
+}
+impl Width {
+    fn bits(self) -> u32 {
+        match self {
+            Self::W32 => 32,
+            Self::Native | Self::W64 => 64,
+        }
+    }
+    /// Wraps the integer `value` into a value of this width.
+    fn wrap(self, value: i64) -> Value {
+        match self {
+            Self::W32 => Value::I32(value as i32),
+            Self::Native => Value::NativeInt(value),
+            Self::W64 => Value::I64(value),
+        }
+    }
+    /// Checks if `value` fits within an integer of this width.
+    fn fits(self, value: i128, signed: bool) -> bool {
+        fits(value, self.bits(), signed)
+    }
+    /// Reinterprets the sign-extended `value` of this width as unsigned.
+    fn unsigned(self, value: i64) -> u64 {
+        match self {
+            Self::W32 => value as u32 as u64,
+            Self::Native | Self::W64 => value as u64,
+        }
+    }
+}
+/// Checks if `value` fits within a `bits` wide integer.
+fn fits(value: i128, bits: u32, signed: bool) -> bool {
+    if signed {
+        value >= -(1_i128 << (bits - 1)) && value < (1_i128 << (bits - 1))
+    } else {
+        value >= 0 && value < (1_i128 << bits)
+    }
+}
+impl Value {
+    /// Returns the integer value of `self` sign-extended to 64 bits, and its width.
+    fn int(&self) -> Option<(i64, Width)> {
+        match self {
+            Self::I32(value) => Some((*value as i64, Width::W32)),
+            Self::I64(value) => Some((*value, Width::W64)),
+            Self::NativeInt(value) => Some((*value, Width::Native)),
+            _ => None,
+        }
+    }
+    fn float(&self) -> Option<f64> {
+        match self {
+            Self::F32(value) => Some(*value as f64),
+            Self::F64(value) => Some(*value),
+            _ => None,
+        }
+    }
+    /// Returns the integer value of `self`, sign-extended to 64 bits. Object references are returned as their handles.
+    pub fn as_i64(&self) -> Result<i64, String> {
+        match self {
+            Self::Obj(handle) => Ok(*handle as i64),
+            _ => self
+                .int()
+                .map(|(value, _)| value)
+                .ok_or_else(|| format!("expected an integer, found {self:?}")),
+        }
+    }
+    /// Returns the integer value of `self`, zero-extended to 64 bits.
+    pub fn as_u64(&self) -> Result<u64, String> {
+        self.int()
+            .map(|(value, width)| width.unsigned(value))
+            .ok_or_else(|| format!("expected an integer, found {self:?}"))
+    }
+    /// Returns `self` as an address.
+    pub fn as_address(&self) -> Result<u64, String> {
+        self.as_u64()
+    }
+    /// Returns `self` as a float.
+    pub fn as_f64(&self) -> Result<f64, String> {
+        self.float()
+            .ok_or_else(|| format!("expected a float, found {self:?}"))
+    }
+    /// Returns the handle of the object `self` refers to.
+    pub fn as_obj(&self) -> Result<u64, String> {
+        match self {
+            Self::Obj(handle) => Ok(*handle),
+            _ => Err(format!("expected an object reference, found {self:?}")),
+        }
+    }
+    /// Checks if `self` is non-zero, or a non-null reference, as checked by `brtrue`.
+    pub fn is_true(&self) -> Result<bool, String> {
+        match self {
+            Self::F32(_) | Self::F64(_) | Self::Struct(_) => {
+                Err(format!("can't branch on the value {self:?}"))
+            }
+            _ => Ok(self.as_i64()? != 0),
+        }
+    }
+}
+/// Returns the values of `a` and `b`, sign-extended to 64 bits, and the width of the result of a binary operation on them.
+fn int_pair(a: &Value, b: &Value) -> Option<(i64, i64, Width)> {
+    let (a, a_width) = a.int()?;
+    let (b, b_width) = b.int()?;
+    Some((a, b, a_width.max(b_width)))
+}
+/// Applies the binary arithmetic or bitwise operation `op` to `a` and `b`.
+pub fn binop(op: &CILOp, a: &Value, b: &Value) -> Result<Value, OpError> {
+    if let (Some(fa), Some(fb)) = (a.float(), b.float()) {
+        let result = match op {
+            CILOp::Add => fa + fb,
+            CILOp::Sub => fa - fb,
+            CILOp::Mul => fa * fb,
+            CILOp::Div => fa / fb,
+            CILOp::Rem => fa % fb,
+            _ => return Err(format!("can't apply {op:?} to floats").into()),
+        };
+        // Operations on 2 `float32`s are preformed with `float32` precision.
+        return Ok(match (a, b) {
+            (Value::F32(_), Value::F32(_)) => Value::F32(result as f32),
+            _ => Value::F64(result),
+        });
+    }
+    let (a, b, width) =
+        int_pair(a, b).ok_or_else(|| format!("can't apply {op:?} to {a:?} and {b:?}"))?;
+    let signed_min = i64::MIN >> (64 - width.bits());
+    Ok(match op {
+        CILOp::Add => width.wrap(a.wrapping_add(b)),
+        CILOp::Sub => width.wrap(a.wrapping_sub(b)),
+        CILOp::Mul => width.wrap(a.wrapping_mul(b)),
+        CILOp::And => width.wrap(a & b),
+        CILOp::Or => width.wrap(a | b),
+        CILOp::XOr => width.wrap(a ^ b),
+        CILOp::Div | CILOp::Rem => {
+            if b == 0 {
+                return Err(OpError::DivideByZero);
+            }
+            if a == signed_min && b == -1 {
+                return Err(OpError::Overflow);
+            }
+            width.wrap(if *op == CILOp::Div { a / b } else { a % b })
+        }
+        CILOp::DivUn | CILOp::RemUn => {
+            let (a, b) = (width.unsigned(a), width.unsigned(b));
+            if b == 0 {
+                return Err(OpError::DivideByZero);
+            }
+            width.wrap(if *op == CILOp::DivUn { a / b } else { a % b } as i64)
+        }
+        CILOp::AddOvf | CILOp::SubOvf | CILOp::MulOvf => {
+            let (a, b) = (a as i128, b as i128);
+            let result = match op {
+                CILOp::AddOvf => a + b,
+                CILOp::SubOvf => a - b,
+                _ => a * b,
+            };
+            if !width.fits(result, true) {
+                return Err(OpError::Overflow);
+            }
+            width.wrap(result as i64)
+        }
+        CILOp::AddOvfUn | CILOp::SubOvfUn => {
+            let (a, b) = (width.unsigned(a) as i128, width.unsigned(b) as i128);
+            let result = if *op == CILOp::AddOvfUn { a + b } else { a - b };
+            if !width.fits(result, false) {
+                return Err(OpError::Overflow);
+            }
+            width.wrap(result as i64)
+        }
+        _ => return Err(format!("{op:?} is not a binary operation").into()),
+    })
+}
+/// Shifts `value` by `amount` bits. The amount is masked to the width of `value`, like on x86_64.
+pub fn shift(op: &CILOp, value: &Value, amount: &Value) -> Result<Value, OpError> {
+    let (value, width) = value
+        .int()
+        .ok_or_else(|| format!("can't shift the value {value:?}"))?;
+    let amount = (amount.as_i64()? as u32) & (width.bits() - 1);
+    Ok(match op {
+        CILOp::Shl => width.wrap(value.wrapping_shl(amount)),
+        CILOp::Shr => width.wrap(value >> amount),
+        CILOp::ShrUn => width.wrap((width.unsigned(value) >> amount) as i64),
+        _ => return Err(format!("{op:?} is not a shift").into()),
+    })
+}
+/// Applies the unary operation `op`(`not` or `neg`) to `value`.
+pub fn unop(op: &CILOp, value: &Value) -> Result<Value, OpError> {
+    Ok(match (op, value) {
+        (CILOp::Neg, Value::F32(value)) => Value::F32(-value),
+        (CILOp::Neg, Value::F64(value)) => Value::F64(-value),
+        _ => {
+            let (value, width) = value
+                .int()
+                .ok_or_else(|| format!("can't apply {op:?} to {value:?}"))?;
+            match op {
+                CILOp::Neg => width.wrap(value.wrapping_neg()),
+                CILOp::Not => width.wrap(!value),
+                _ => return Err(format!("{op:?} is not an unary operation").into()),
+            }
+        }
+    })
+}
+/// Kind of a comparison.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cmp {
+    /// `a == b`
+    Eq,
+    /// `a != b`, or unordered.
+    NeUn,
+    /// Signed `a < b`
+    Lt,
+    /// Unsigned `a < b`, or unordered.
+    LtUn,
+    /// Signed `a > b`
+    Gt,
+    /// Unsigned `a > b`, or unordered.
+    GtUn,
+    /// Signed `a >= b`
+    Ge,
+    /// Signed `a <= b`
+    Le,
+}
+/// Compares `a` and `b`.
+pub fn compare(cmp: Cmp, a: &Value, b: &Value) -> Result<bool, OpError> {
+    let unsigned = matches!(cmp, Cmp::LtUn | Cmp::GtUn);
+    let ordering = match (a, b) {
+        // `cgt.un` is used to check if a reference is not null.
+        (Value::Obj(a), Value::Obj(b)) => Some(a.cmp(b)),
+        (Value::Obj(_), _) | (_, Value::Obj(_)) | (Value::Struct(_), _) | (_, Value::Struct(_)) => {
+            return Err(format!("can't compare {a:?} and {b:?}").into())
+        }
+        _ => match (a.float(), b.float()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => {
+                let (a, b, width) =
+                    int_pair(a, b).ok_or_else(|| format!("can't compare {a:?} and {b:?}"))?;
+                if unsigned {
+                    Some(width.unsigned(a).cmp(&width.unsigned(b)))
+                } else {
+                    Some(a.cmp(&b))
+                }
+            }
+        },
+    };
+    Ok(match cmp {
+        Cmp::Eq => ordering == Some(Ordering::Equal),
+        Cmp::NeUn => ordering != Some(Ordering::Equal),
+        Cmp::Lt => ordering == Some(Ordering::Less),
+        Cmp::LtUn => !matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        Cmp::Gt => ordering == Some(Ordering::Greater),
+        Cmp::GtUn => !matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Cmp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        Cmp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+    })
+}
+/// Applies the conversion `op` to `value`.
+pub fn convert(op: &CILOp, value: &Value) -> Result<Value, OpError> {
+    let (bits, signed, checked) = match op {
+        CILOp::ConvF32(_) => return Ok(Value::F32(to_float(value, false)? as f32)),
+        CILOp::ConvF64(_) => return Ok(Value::F64(to_float(value, false)?)),
+        CILOp::ConvFUn => return Ok(Value::F64(to_float(value, true)?)),
+        CILOp::ConvI8(checked) => (8, true, *checked),
+        CILOp::ConvI16(checked) => (16, true, *checked),
+        CILOp::ConvI32(checked) => (32, true, *checked),
+        CILOp::ConvI64(checked) | CILOp::ConvISize(checked) => (64, true, *checked),
+        CILOp::ConvU8(checked) => (8, false, *checked),
+        CILOp::ConvU16(checked) => (16, false, *checked),
+        CILOp::ConvU32(checked) => (32, false, *checked),
+        CILOp::ConvU64(checked) | CILOp::ConvUSize(checked) => (64, false, *checked),
+        _ => return Err(format!("{op:?} is not a conversion").into()),
+    };
+    let converted: i64 = match (value.int(), value.float()) {
+        (Some((int, width)), _) => {
+            if checked && !fits(int as i128, bits, signed) {
+                return Err(OpError::Overflow);
+            }
+            // Unsigned conversions zero-extend `int32`s.
+            if !signed && bits == 64 {
+                width.unsigned(int) as i64
+            } else {
+                int
+            }
+        }
+        (None, Some(float)) => {
+            if checked && (float.is_nan() || !fits(float.trunc() as i128, bits, signed)) {
+                return Err(OpError::Overflow);
+            }
+            if signed {
+                float as i64
+            } else {
+                float as u64 as i64
+            }
+        }
+        _ => return Err(format!("can't convert {value:?}").into()),
+    };
+    Ok(match (bits, signed, op) {
+        (8, true, _) => Value::I32(converted as i8 as i32),
+        (8, false, _) => Value::I32(converted as u8 as i32),
+        (16, true, _) => Value::I32(converted as i16 as i32),
+        (16, false, _) => Value::I32(converted as u16 as i32),
+        (32, _, _) => Value::I32(converted as i32),
+        (_, _, CILOp::ConvISize(_) | CILOp::ConvUSize(_)) => Value::NativeInt(converted),
+        _ => Value::I64(converted),
+    })
+}
+/// Converts `value` to a float. If `unsigned`, integers are treated as unsigned.
+fn to_float(value: &Value, unsigned: bool) -> Result<f64, String> {
+    match (value.int(), value.float()) {
+        (Some((int, width)), _) if unsigned => Ok(width.unsigned(int) as f64),
+        (Some((int, _)), _) => Ok(int as f64),
+        (None, Some(float)) => Ok(float),
+        _ => Err(format!("can't convert {value:?} to a float")),
+    }
+}
+/// How a value of some type is stored in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    I64,
+    NativeInt,
+    F32,
+    F64,
+    /// Reference to a managed object.
+    Obj,
+    /// Value type of the specified size.
+    Struct(usize),
+}
+impl Slot {
+    /// Size of the slot, in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::F32 => 4,
+            Self::I64 | Self::NativeInt | Self::F64 | Self::Obj => 8,
+            Self::Struct(size) => size,
+        }
+    }
+    /// Loads a value from `bytes`, which must be [`Self::size`] long.
+    pub fn load(self, bytes: &[u8]) -> Value {
+        let mut buffer = [0; 8];
+        let len = bytes.len().min(8);
+        buffer[..len].copy_from_slice(&bytes[..len]);
+        let raw = u64::from_le_bytes(buffer);
+        match self {
+            Self::I8 => Value::I32(raw as i8 as i32),
+            Self::U8 => Value::I32(raw as u8 as i32),
+            Self::I16 => Value::I32(raw as i16 as i32),
+            Self::U16 => Value::I32(raw as u16 as i32),
+            Self::I32 => Value::I32(raw as i32),
+            Self::I64 => Value::I64(raw as i64),
+            Self::NativeInt => Value::NativeInt(raw as i64),
+            Self::F32 => Value::F32(f32::from_bits(raw as u32)),
+            Self::F64 => Value::F64(f64::from_bits(raw)),
+            Self::Obj => Value::Obj(raw),
+            Self::Struct(_) => Value::Struct(bytes.to_vec()),
+        }
+    }
+    /// Returns the bytes `value` is stored as. Integers are truncated to the size of the slot, like by `stind` or `stloc`.
+    pub fn store(self, value: &Value) -> Result<Vec<u8>, String> {
+        let bytes = match (self, value) {
+            (Self::Struct(size), Value::Struct(bytes)) => {
+                if bytes.len() != size {
+                    return Err(format!(
+                        "can't store a value type of size {len} in a slot of size {size}",
+                        len = bytes.len()
+                    ));
+                }
+                return Ok(bytes.clone());
+            }
+            (Self::F32, _) => (value.as_f64()? as f32).to_bits().to_le_bytes().to_vec(),
+            (Self::F64, _) => value.as_f64()?.to_bits().to_le_bytes().to_vec(),
+            (Self::Struct(_), _) | (_, Value::Struct(_) | Value::F32(_) | Value::F64(_)) => {
+                return Err(format!("can't store {value:?} in a {self:?} slot"))
+            }
+            _ => value.as_i64()?.to_le_bytes().to_vec(),
+        };
+        Ok(bytes[..self.size()].to_vec())
+    }
+    /// Normalizes `value` to the range of values this slot can hold, eg. truncates an `int32` stored in an `u8` slot.
+    pub fn normalize(self, value: &Value) -> Result<Value, String> {
+        Ok(self.load(&self.store(value)?))
+    }
+}
+#[test]
+fn int_arithmetic() {
+    assert_eq!(
+        binop(&CILOp::Add, &Value::I32(i32::MAX), &Value::I32(1)),
+        Ok(Value::I32(i32::MIN))
+    );
+    assert_eq!(
+        binop(&CILOp::AddOvf, &Value::I32(i32::MAX), &Value::I32(1)),
+        Err(OpError::Overflow)
+    );
+    assert_eq!(
+        binop(&CILOp::DivUn, &Value::I32(-2), &Value::I32(2)),
+        Ok(Value::I32(i32::MAX))
+    );
+    assert_eq!(
+        binop(&CILOp::Rem, &Value::I64(1), &Value::I64(0)),
+        Err(OpError::DivideByZero)
+    );
+    assert_eq!(
+        convert(&CILOp::ConvU64(false), &Value::I32(-1)),
+        Ok(Value::I64(u32::MAX as i64))
+    );
+    assert_eq!(
+        convert(&CILOp::ConvI8(false), &Value::I32(255)),
+        Ok(Value::I32(-1))
+    );
+    assert_eq!(
+        compare(Cmp::LtUn, &Value::I32(1), &Value::I32(-1)),
+        Ok(true)
+    );
+    assert_eq!(compare(Cmp::Lt, &Value::I32(1), &Value::I32(-1)), Ok(false));
+    assert_eq!(Slot::U8.normalize(&Value::I32(256)), Ok(Value::I32(0)));
+}
@@ -43,7 +43,9 @@ extern crate rustc_codegen_ssa;
 extern crate rustc_data_structures;
 extern crate rustc_driver;
 extern crate rustc_errors;
+extern crate rustc_fs_util;
 extern crate rustc_hir;
+extern crate rustc_incremental;
 extern crate rustc_index;
 extern crate rustc_metadata;
 extern crate rustc_middle;
@@ -52,30 +54,11 @@ extern crate rustc_span;
 extern crate rustc_symbol_mangling;
 extern crate rustc_target;
 extern crate stable_mir;
-// Debug config
+// Debug config. Options which can be changed without rebuilding the backend are in [`config::CodegenConfig`].
 
-/// Tells the codegen to insert comments containing the MIR statemtens after each one of them.
-const INSERT_MIR_DEBUG_COMMENTS: bool = false;
 const PRINT_LOCAL_TYPES: bool = false;
-/// Tells the codegen to optmize the emiited CIL.
-const OPTIMIZE_CIL: bool = (!INSERT_MIR_DEBUG_COMMENTS) && (false);
-/// Turns on the struct spliting optimzation.
-const SPLIT_LOCAL_STRUCTS: bool = false;
-/// Turns on the local removal optimization.
-const REMOVE_UNSUED_LOCALS: bool = false;
 /// Prints debug info during type handling.
 const PRINT_TY_CONVERTION: bool = false;
-/// Changes `.locals` into `.locals init`. Causes the runtime to always initialize local variables.
-/// Try turining on in cause of issues. If it fixes them, then their root cause is UB(eg. use of uninitailized memory).
-pub const ALWAYS_INIT_LOCALS: bool = false;
-/// Should the codegen stop working when ecountering an error, or try to press on, replacing unusuported code with exceptions throws?
-pub const ABORT_ON_ERROR: bool = false;
-/// Should the codegen continue working after it encoutnered a miscompilation?
-pub const ALLOW_MISCOMPILATIONS: bool = true;
-/// Preapends each function call with a debug message
-pub const TRACE_CALLS: bool = true;
-/// Preapends each statement with a debug message
-pub const TRACE_STATEMENTS: bool = false;
 // Modules
 
 /// Specifies if a method/type is private or public.
@@ -96,16 +79,30 @@ mod casts;
 mod checked_binop;
 /// A representation of C# IL op.
 pub mod cil;
+/// Code handling closures, and the "rust-call" ABI used to call them.
+mod closure;
 /// Runtime errors and utlity functions/macros related to them
 mod codegen_error;
 /// Test harnesses.
 mod compile_test;
+/// Options changing the behaviour of the codegen, read from the backend options and environment variables.
+pub mod config;
 /// Code handling loading constant values in CIL.
 mod constant;
+/// Link-time elimination of the methods, types and static fields which can't be reached from the entrypoint.
+pub mod dead_code;
 /// Code detecting and inserting wrappers around entrypoints.
 mod entrypoint;
+/// Exports of Rust functions as public methods, callable from other .NET languages.
+pub mod export;
 /// Signature of a function (inputs)->output
 pub mod function_sig;
+/// Code deciding which functions and types are emitted as CIL generics, and refering to their instantiations.
+mod generics;
+/// Code handling 128 bit integers: calls to their operators, checked arithmetic and the software implementation.
+pub mod int128;
+/// Interpreter executing the CIL of linked assemblies, allowing them to be tested without a .NET runtime.
+pub mod interpreter;
 /// Implementation of some libc functions in CIL assembly. Will likely be removed and mostly replaced by functions implmented using mycorrhize.
 pub mod libc;
 /// A representation of a .NET method
@@ -113,9 +110,11 @@ pub mod method;
 /// Handles a MIR operand.
 mod operand;
 /// Method-level CIL opitimizations
-mod opt;
+pub mod opt;
 /// Code handling getting/setting/adressing memory locations.
 mod place;
+/// Machine-readable report of all the MIR constructs which could not be compiled.
+pub mod report;
 /// Converts righthandside of a MIR statement into CIL ops.
 mod rvalue;
 /// Code dealing with truning an individual MIR statement into CIL ops.
@@ -129,17 +128,25 @@ pub mod r#type;
 
 /// Implementations of unary operations.
 mod unop;
+/// Lowers MIR unwinding into CIL exception handling regions.
+mod unwind;
 /// Contains small helper functions(debug assertions, functions used to get field names, etc), which are frequently used, but are not specific to a part of the coodegen.
 mod utilis;
+/// Checks the types of values on the CIL evaluation stack, detecting invalid CIL without the need for PEVerify.
+pub mod verifier;
+/// Code handling trait objects and their vtables.
+mod vtable;
 // rustc functions used here.
 use rustc_codegen_ssa::{
+    assert_module_sources::CguReuse,
     back::archive::{
         get_native_object_symbols, ArArchiveBuilder, ArchiveBuilder, ArchiveBuilderBuilder,
     },
+    base::determine_cgu_reuse,
     traits::CodegenBackend,
     CodegenResults, CompiledModule, CrateInfo, ModuleKind,
 };
-use rustc_data_structures::fx::FxIndexMap;
+use rustc_data_structures::{fx::FxIndexMap, sync::par_map};
 use rustc_metadata::EncodedMetadata;
 use rustc_middle::{
     dep_graph::{WorkProduct, WorkProductId},
@@ -149,7 +156,7 @@ use rustc_session::{
     config::{OutputFilenames, OutputType},
     Session,
 };
-use rustc_span::ErrorGuaranteed;
+use rustc_span::{ErrorGuaranteed, Symbol};
 
 use std::{
     any::Any,
@@ -159,6 +166,81 @@ use std::{
 pub type IString = Box<str>;
 
 use assembly::Assembly;
+use config::CodegenConfig;
+/// Kind of the file saved in the incremental cache for each codegen unit: its serialized assembly fragment.
+const ASSEMBLY_WORK_PRODUCT: &str = "bc";
+/// Returns the kind of the file an assembly fragment compiled with `config` is saved as. Options set using environment variables are not tracked by
+/// incremental compilation, so fragments are only reused if they were saved with the same fingerprint of the options.
+fn work_product_kind(config: &CodegenConfig) -> String {
+    format!("{ASSEMBLY_WORK_PRODUCT}-{:x}", config.codegen_fingerprint())
+}
+/// Extension of the JSON report listing all the MIR constructs which could not be compiled.
+pub const REPORT_EXTENSION: &str = "clr_report.json";
+/// Extension of the serialized final assembly saved next to each linked executable, which can be run by the [`interpreter`].
+pub const LINKED_ASSEMBLY_EXTENSION: &str = "clr_asm";
+/// Assembly fragment built from a single codegen unit.
+enum CguAssembly {
+    /// Fragment compiled in this session.
+    Compiled(Assembly),
+    /// Serialized fragment copied from the incremental cache, and the work product it was reused from.
+    Reused(PathBuf, WorkProduct),
+}
+/// Compiles the codegen unit `cgu_name` into an assembly fragment. Runs as a dep graph task, which allows rustc to tell when the fragment can be reused.
+/// Uses its own type cache, so separate codegen units can be compiled on separate threads.
+fn codegen_cgu(tcx: TyCtxt<'_>, (cgu_name, config): (Symbol, CodegenConfig)) -> Assembly {
+    let cgu = tcx.codegen_unit(cgu_name);
+    let mut codegen = Assembly::empty();
+    let mut cache = crate::r#type::TyCache::new(config);
+    //println!("codegen {} has {} items.", cgu.name(), cgu.items().len());
+    for (item, _data) in cgu.items() {
+        if let Err(err) = codegen.add_item(*item, tcx, &mut cache, &config) {
+            err.emit(tcx, tcx.def_span(item.def_id()));
+        }
+    }
+    // The optimization passes are iterated until a fixed point is reached, so one run is enough for inlining.
+    let stats = codegen.opt(tcx, &config);
+    if config.print_opt_stats {
+        eprint!("Optimization statistics of codegen unit {cgu_name}:\n{stats}");
+    }
+    codegen
+}
+/// Copies the assembly fragment of `cgu_name` saved by a previous session from the incremental cache into the temporary files of this session.
+/// Returns `None` if the fragment was saved with different options.
+fn reuse_cgu(
+    tcx: TyCtxt<'_>,
+    cgu_name: &str,
+    work_product_id: &WorkProductId,
+    config: &CodegenConfig,
+) -> Option<CguAssembly> {
+    let work_product = tcx
+        .dep_graph
+        .previous_work_product(work_product_id)
+        .unwrap_or_else(|| panic!("No work product of codegen unit {cgu_name} to reuse!"));
+    let saved_file = work_product
+        .saved_files
+        .get(work_product_kind(config).as_str())?;
+    let source = rustc_incremental::in_incr_comp_dir_sess(tcx.sess, saved_file);
+    let target = tcx
+        .output_filenames(())
+        .temp_path(OutputType::Bitcode, Some(cgu_name));
+    rustc_fs_util::link_or_copy(&source, &target)
+        .expect("Could not copy a reused assembly fragment out of the incremental cache!");
+    Some(CguAssembly::Reused(target, work_product))
+}
+/// Deserializes the assembly saved in the temporary file `path`.
+fn load_assembly(path: &Path) -> Assembly {
+    let asm_bytes = std::fs::read(path).expect("Could not load the tmp assembly file!");
+    postcard::from_bytes(&asm_bytes).expect("Could not deserialize the tmp assembly file!")
+}
+/// Serializes `asm` into the temporary file `path`.
+fn save_assembly(asm: &Assembly, path: &Path) {
+    use std::io::Write;
+    let mut asm_out = std::fs::File::create(path)
+        .expect("Could not create the temporary files necessary for building the assembly!");
+    asm_out
+        .write_all(&postcard::to_stdvec(asm).expect("Could not serialize the tmp assembly file!"))
+        .expect("Could not save the tmp assembly file!");
+}
 /// An instance of the codegen.
 struct MyBackend;
 impl CodegenBackend for MyBackend {
@@ -173,19 +255,46 @@ impl CodegenBackend for MyBackend {
         _need_metadata_module: bool,
     ) -> Box<dyn Any> {
         {
+            let mut config = CodegenConfig::from_options(&tcx.sess.opts.cg.llvm_args)
+                .unwrap_or_else(|err| panic!("Invalid codegen options: {err}"));
+            config.print_opt_stats |= tcx.sess.opts.unstable_opts.time_passes;
             let (_defid_set, cgus) = tcx.collect_and_partition_mono_items(());
-
+            let cgu_reuse: Vec<_> = cgus
+                .iter()
+                .map(|cgu| determine_cgu_reuse(tcx, cgu))
+                .collect();
+            // Each codegen unit becomes a separate fragment, which can be reused by incremental compilation.
+            // Fragments are compiled in parallel(if rustc runs with `-Z threads`), each with its own type cache.
+            let fragments: Vec<_> = par_map(cgus.iter().zip(cgu_reuse), |(cgu, reuse)| {
+                let cgu_name: IString = cgu.name().to_string().into();
+                let fragment = match reuse {
+                    CguReuse::No => {
+                        let (codegen, _) = tcx.dep_graph.with_task(
+                            cgu.codegen_dep_node(tcx),
+                            tcx,
+                            (cgu.name(), config),
+                            codegen_cgu,
+                            None,
+                        );
+                        CguAssembly::Compiled(codegen)
+                    }
+                    CguReuse::PreLto | CguReuse::PostLto => {
+                        reuse_cgu(tcx, &cgu_name, &cgu.work_product_id(), &config).unwrap_or_else(
+                            || {
+                                // The dep node is already green, so the fragment is compiled outside of a task.
+                                let codegen = tcx
+                                    .dep_graph
+                                    .with_ignore(|| codegen_cgu(tcx, (cgu.name(), config)));
+                                CguAssembly::Compiled(codegen)
+                            },
+                        )
+                    }
+                };
+                (cgu_name, fragment)
+            });
+            // Things not belonging to any codegen unit are always compiled anew.
             let mut codegen = Assembly::empty();
-            let mut cache = crate::r#type::TyCache::empty();
-            for cgu in cgus {
-                //println!("codegen {} has {} items.", cgu.name(), cgu.items().len());
-                for (item, _data) in cgu.items() {
-                    codegen
-                        .add_item(*item, tcx, &mut cache)
-                        .expect("Could not add function");
-                }
-            }
-
+            let mut cache = crate::r#type::TyCache::new(config);
             if let Some((entrypoint, _kind)) = tcx.entry_fn(()) {
                 let penv = rustc_middle::ty::ParamEnv::reveal_all();
                 let entrypoint = rustc_middle::ty::Instance::resolve(
@@ -203,14 +312,16 @@ impl CodegenBackend for MyBackend {
                 let cs = cil::CallSite::new(None, symbol.into(), sig, true);
                 codegen.set_entrypoint(cs);
             }
-            codegen.opt();
-            // Done twice for inlining!
-            codegen.opt();
             libc::insert_libc(&mut codegen, tcx);
-            let name: IString = cgus.iter().next().unwrap().name().to_string().into();
+            let name: IString = tcx
+                .crate_name(rustc_span::def_id::LOCAL_CRATE)
+                .to_string()
+                .into();
 
             Box::new((
                 name,
+                fragments,
+                work_product_kind(&config),
                 codegen,
                 metadata,
                 CrateInfo::new(tcx, "clr".to_string()),
@@ -221,32 +332,70 @@ impl CodegenBackend for MyBackend {
     fn join_codegen(
         &self,
         ongoing_codegen: Box<dyn Any>,
-        _sess: &Session,
+        sess: &Session,
         outputs: &OutputFilenames,
     ) -> Result<(CodegenResults, FxIndexMap<WorkProductId, WorkProduct>), ErrorGuaranteed> {
         std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            use std::io::Write;
-            let (_asm_name, asm, metadata, crate_info) = *ongoing_codegen
-                .downcast::<(IString, Assembly, EncodedMetadata, CrateInfo)>()
-                .expect("in join_codegen: ongoing_codegen is not an Assembly");
-            let asm_name = "";
-            let serialized_asm_path = outputs.temp_path(OutputType::Bitcode, Some(asm_name));
-            //std::fs::create_dir_all(&serialized_asm_path).expect("Could not create the directory temporary files are supposed to be in.");
-            let mut asm_out = std::fs::File::create(&serialized_asm_path).expect(
-                "Could not create the temporary files necessary for building the assembly!",
-            );
-            asm_out
-                .write_all(
-                    &postcard::to_stdvec(&asm).expect("Could not serialize the tmp assembly file!"),
-                )
-                .expect("Could not save the tmp assembly file!");
-            let modules = vec![CompiledModule {
-                name: asm_name.into(),
+            let (asm_name, fragments, work_product_kind, asm, metadata, crate_info) =
+                *ongoing_codegen
+                    .downcast::<(
+                        IString,
+                        Vec<(IString, CguAssembly)>,
+                        String,
+                        Assembly,
+                        EncodedMetadata,
+                        CrateInfo,
+                    )>()
+                    .expect("in join_codegen: ongoing_codegen is not an Assembly");
+            let mut modules = Vec::with_capacity(fragments.len() + 1);
+            let mut work_products = FxIndexMap::default();
+            let mut report = asm.report().clone();
+            for (cgu_name, fragment) in fragments {
+                let (serialized_asm_path, work_product) = match fragment {
+                    CguAssembly::Compiled(fragment) => {
+                        let path = outputs.temp_path(OutputType::Bitcode, Some(&cgu_name));
+                        save_assembly(&fragment, &path);
+                        report = report.join(fragment.report().clone());
+                        // Only returns a work product if incremental compilation is on.
+                        let work_product =
+                            rustc_incremental::copy_cgu_workproduct_to_incr_comp_cache_dir(
+                                sess,
+                                &cgu_name,
+                                &[(&work_product_kind, &path)],
+                            );
+                        (path, work_product)
+                    }
+                    CguAssembly::Reused(path, work_product) => {
+                        report = report.join(load_assembly(&path).report().clone());
+                        (
+                            path,
+                            Some((WorkProductId::from_cgu_name(&cgu_name), work_product)),
+                        )
+                    }
+                };
+                if let Some((id, work_product)) = work_product {
+                    work_products.insert(id, work_product);
+                }
+                modules.push(CompiledModule {
+                    name: cgu_name.to_string(),
+                    kind: ModuleKind::Regular,
+                    object: Some(serialized_asm_path),
+                    bytecode: None,
+                    dwarf_object: None,
+                });
+            }
+            let crate_asm_name = format!("{asm_name}.crate");
+            let serialized_asm_path = outputs.temp_path(OutputType::Bitcode, Some(&crate_asm_name));
+            save_assembly(&asm, &serialized_asm_path);
+            // The report on the constructs which could not be compiled is saved next to the assemblies.
+            report.save(&asm_name, &outputs.temp_path_ext(REPORT_EXTENSION, None));
+            modules.push(CompiledModule {
+                name: crate_asm_name,
                 kind: ModuleKind::Regular,
                 object: Some(serialized_asm_path),
                 bytecode: None,
                 dwarf_object: None,
-            }];
+            });
             let codegen_results = CodegenResults {
                 modules,
                 allocator_module: None,
@@ -254,7 +403,7 @@ impl CodegenBackend for MyBackend {
                 metadata,
                 crate_info,
             };
-            Ok((codegen_results, FxIndexMap::default()))
+            Ok((codegen_results, work_products))
         }))
         .expect("Could not join_codegen")
     }
@@ -93,9 +93,13 @@ pub fn insert_libc(asm: &mut Assembly, tyctx: TyCtxt) {
     asm.add_typedef(crate::r#type::TypeDef::nameonly("Foreign"));
     asm.add_typedef(crate::r#type::TypeDef::nameonly("RustStr"));
     rust_slice(asm);
+    rust_panic_exception(asm);
+    crate::int128::insert_int128(asm);
     math(asm);
     io(asm);
     unlikely(asm);
+    catch_unwind(asm);
+    raise_exception(asm);
     //malloc(asm);
     let mut marshal = DotnetTypeRef::new(
         Some("System.Runtime.InteropServices"),
@@ -248,6 +252,112 @@ fn rust_slice(asm: &mut Assembly) {
     //
 }
 
+/// Adds `RustPanicException`, the exception thrown by Rust panics. It contains the panic message and location, and the payload passed to `_Unwind_RaiseException` by std's panic runtime.
+fn rust_panic_exception(asm: &mut Assembly) {
+    let class = crate::utilis::panic_exception_class();
+    let string: Type = crate::utilis::string_class().into();
+    let mut exception = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
+    exception.set_valuetype(false);
+    let mut panic_exception = crate::r#type::TypeDef::new(
+        AccessModifer::Public,
+        class.name_path().into(),
+        vec![],
+        vec![
+            ("message".into(), string.clone()),
+            ("file".into(), string.clone()),
+            ("line".into(), Type::U32),
+            ("column".into(), Type::U32),
+            ("payload".into(), Type::Ptr(Type::U8.into())),
+        ],
+        vec![],
+        None,
+        0,
+        Some(exception.clone()),
+    );
+    // Creates a managed string from an UTF-8 buffer pointed to by the argument `ptr`, with length `len`.
+    let utf8_string = |ptr: u32, len: u32| {
+        [
+            CILOp::LDArg(ptr),
+            CILOp::LdcI32(0),
+            CILOp::LDArg(len),
+            CILOp::ConvI32(false),
+            CILOp::NewObj(CallSite::boxed(
+                Some(crate::utilis::string_class()),
+                ".ctor".into(),
+                FnSig::new(
+                    &[
+                        string.clone(),
+                        Type::Ptr(Type::I8.into()),
+                        Type::I32,
+                        Type::I32,
+                    ],
+                    &Type::Void,
+                ),
+                false,
+            )),
+        ]
+    };
+    let field = |name: &str, tpe: &Type| {
+        CILOp::STField(
+            crate::cil::FieldDescriptor::new(class.clone(), tpe.clone(), name.into()).into(),
+        )
+    };
+    // .ctor(message, file, line, column)
+    let ctor_site = crate::utilis::panic_exception_ctor();
+    let mut ctor = Method::new(
+        AccessModifer::Public,
+        false,
+        ctor_site.signature().clone(),
+        ".ctor",
+        vec![],
+    );
+    ctor.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::Call(CallSite::boxed(
+            Some(exception.clone()),
+            ".ctor".into(),
+            FnSig::new(&[exception.into(), string.clone()], &Type::Void),
+            false,
+        )),
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        field("message", &string),
+        CILOp::LDArg(0),
+        CILOp::LDArg(2),
+        field("file", &string),
+        CILOp::LDArg(0),
+        CILOp::LDArg(3),
+        field("line", &Type::U32),
+        CILOp::LDArg(0),
+        CILOp::LDArg(4),
+        field("column", &Type::U32),
+        CILOp::Ret,
+    ]);
+    panic_exception.add_method(ctor);
+    // .ctor(message, message_len, file, file_len, line, column)
+    let mut utf8_ctor = Method::new(
+        AccessModifer::Public,
+        false,
+        crate::utilis::panic_exception_utf8_ctor()
+            .signature()
+            .clone(),
+        ".ctor",
+        vec![],
+    );
+    let mut ops = vec![CILOp::LDArg(0)];
+    ops.extend(utf8_string(1, 2));
+    ops.extend(utf8_string(3, 4));
+    ops.extend([
+        CILOp::LDArg(5),
+        CILOp::LDArg(6),
+        CILOp::Call(ctor_site.into()),
+        CILOp::Ret,
+    ]);
+    utf8_ctor.set_ops(ops);
+    panic_exception.add_method(utf8_ctor);
+    asm.add_typedef(panic_exception);
+}
 fn math(asm: &mut Assembly) {
     sqrtf32(asm);
 }
@@ -295,6 +405,8 @@ add_method!(
         CILOp::STArg(0),
         CILOp::GoTo(0),
         CILOp::Label(1),
+        // Like in C, `puts` ends the printed string with a newline.
+        CILOp::new_line(),
         CILOp::Ret
     ],
     [(None, Type::U8)]
@@ -305,5 +417,88 @@ add_method!(
     &Type::Bool,
     [CILOp::LDArg(0), CILOp::Ret,]
 );
+/// Adds `catch_unwind`, which implements the `try` intrinsic: it calls `try_fn(data)`, and if that throws a Rust panic, calls `catch_fn(data, payload)` and returns 1.
+/// The payload is the one the panic was raised with by `_Unwind_RaiseException`. Panics thrown by the panic handler of a `no_std` program have a null payload.
+/// Other managed exceptions are not caught, since they have no payload `catch_fn` could handle.
+fn catch_unwind(asm: &mut Assembly) {
+    let byte_ptr = Type::Ptr(Type::U8.into());
+    let try_sig = FnSig::new(&[byte_ptr.clone()], &Type::Void);
+    let catch_sig = FnSig::new(&[byte_ptr.clone(), byte_ptr.clone()], &Type::Void);
+    let payload = crate::cil::FieldDescriptor::new(
+        crate::utilis::panic_exception_class(),
+        byte_ptr.clone(),
+        "payload".into(),
+    );
+    let mut method = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(
+            &[
+                Type::FnPtr(try_sig.clone().into()),
+                byte_ptr.clone(),
+                Type::FnPtr(catch_sig.clone().into()),
+            ],
+            &Type::I32,
+        ),
+        "catch_unwind",
+        vec![(None, Type::I32), (None, byte_ptr)],
+    );
+    method.set_ops(vec![
+        CILOp::BeginTry,
+        CILOp::LDArg(1),
+        CILOp::LDArg(0),
+        CILOp::CallI(try_sig.into()),
+        CILOp::Leave(0),
+        CILOp::EndTry,
+        CILOp::BeginCatch(crate::utilis::panic_exception_class().into()),
+        CILOp::LDField(payload.into()),
+        CILOp::STLoc(1),
+        CILOp::LDArg(1),
+        CILOp::LDLoc(1),
+        CILOp::LDArg(2),
+        CILOp::CallI(catch_sig.into()),
+        CILOp::LdcI32(1),
+        CILOp::STLoc(0),
+        CILOp::Leave(1),
+        CILOp::EndHandler,
+        CILOp::Label(0),
+        CILOp::LdcI32(0),
+        CILOp::STLoc(0),
+        CILOp::Label(1),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ]);
+    asm.add_method(method);
+}
+/// Adds `rust_raise_exception`, which replaces `_Unwind_RaiseException`: it throws a `RustPanicException` carrying the `_Unwind_Exception` std's panic runtime raised.
+/// That pointer is passed to `catch_fn` by [`catch_unwind`], so that `__rust_panic_cleanup` can read the panic payload from it.
+fn raise_exception(asm: &mut Assembly) {
+    let byte_ptr = Type::Ptr(Type::U8.into());
+    let payload = crate::cil::FieldDescriptor::new(
+        crate::utilis::panic_exception_class(),
+        byte_ptr.clone(),
+        "payload".into(),
+    );
+    let mut method = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[byte_ptr], &Type::Void),
+        "rust_raise_exception",
+        vec![],
+    );
+    method.set_ops(vec![
+        // std's panic hook has already printed the message and location.
+        CILOp::LdStr("Rust panic".into()),
+        CILOp::LdStr("".into()),
+        CILOp::LdcI32(0),
+        CILOp::LdcI32(0),
+        CILOp::NewObj(crate::utilis::panic_exception_ctor().into()),
+        CILOp::Dup,
+        CILOp::LDArg(0),
+        CILOp::STField(payload.into()),
+        CILOp::Throw,
+    ]);
+    asm.add_method(method);
+}
 //System.Environment.Exit(a_ExitCode)
 add_method!(abort, &[], &Type::Void, CILOp::throw_msg("Called abort!"));
@@ -17,6 +17,7 @@ pub struct Method {
     locals: Vec<LocalDef>,
     ops: Vec<CILOp>,
     attributes: Vec<Attribute>,
+    generic_count: u32,
 }
 /// Local varaible. Consists of an optional name and type.
 pub type LocalDef = (Option<IString>, Type);
@@ -50,6 +51,7 @@ impl Method {
             locals,
             ops: Vec::new(),
             attributes: Vec::new(),
+            generic_count: 0,
         }
     }
     pub(crate) fn ensure_valid(&mut self) {
@@ -101,6 +103,14 @@ impl Method {
     pub fn sig(&self) -> &FnSig {
         &self.sig
     }
+    /// Returns the number of generic arguments of this method.
+    pub fn generic_count(&self) -> u32 {
+        self.generic_count
+    }
+    /// Makes this method generic, with `generic_count` generic arguments.
+    pub fn set_generic_count(&mut self, generic_count: u32) {
+        self.generic_count = generic_count;
+    }
     /// Returns the list of local types.
     pub fn locals(&self) -> &[(Option<IString>, Type)] {
         &self.locals
@@ -176,4 +186,14 @@ impl Method {
     pub fn set_locals(&mut self, locals: impl Into<Vec<(Option<IString>, Type)>>) {
         self.locals = locals.into();
     }
+    /// Replaces the types used by the signature, locals and ops of this method, as specified by `map`. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        self.sig.map_types(map);
+        for (_, local) in &mut self.locals {
+            local.map_types(map);
+        }
+        for op in &mut self.ops {
+            op.map_types(map);
+        }
+    }
 }
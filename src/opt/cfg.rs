@@ -0,0 +1,560 @@
+use crate::cil::CILOp;
+use std::collections::HashMap;
+/// A sequence of ops which can only be entered at its start, and only left at its end.
+#[derive(Debug)]
+struct Block {
+    ops: Vec<CILOp>,
+    /// Innermost protected region(`2 * region`) or handler(`2 * region + 1`) the block is in.
+    scope: Option<usize>,
+}
+impl Block {
+    /// Returns the labels at the start of the block.
+    fn labels(&self) -> impl Iterator<Item = u32> + '_ {
+        self.ops.iter().map_while(|op| match op {
+            CILOp::Label(label) => Some(*label),
+            _ => None,
+        })
+    }
+    /// Returns the ops of the block, without its labels.
+    fn body(&self) -> &[CILOp] {
+        &self.ops[self.labels().count()..]
+    }
+    /// Checks if the control can continue from the end of this block to the next one.
+    fn falls_through(&self) -> bool {
+        !matches!(
+            self.ops.last(),
+            Some(
+                CILOp::GoTo(_)
+                    | CILOp::Leave(_)
+                    | CILOp::Ret
+                    | CILOp::Throw
+                    | CILOp::Rethrow
+                    | CILOp::EndFinally
+                    | CILOp::EndTry
+                    | CILOp::EndHandler
+            )
+        )
+    }
+}
+/// Checks if `op` marks the start or end of an exception handling region.
+fn is_marker(op: &CILOp) -> bool {
+    matches!(
+        op,
+        CILOp::BeginTry
+            | CILOp::EndTry
+            | CILOp::BeginCatch(_)
+            | CILOp::BeginFinally
+            | CILOp::EndHandler
+    )
+}
+/// Checks if the control never continues to the op after `op`.
+fn ends_block(op: &CILOp) -> bool {
+    op.branch_target().is_some()
+        || matches!(
+            op,
+            CILOp::Ret | CILOp::Throw | CILOp::Rethrow | CILOp::EndFinally
+        )
+}
+/// A protected region and its handler, as indices of the blocks marking their bounds.
+#[derive(Debug)]
+struct Region {
+    begin_try: usize,
+    end_try: usize,
+    begin_handler: usize,
+    end_handler: usize,
+}
+/// Control flow graph of a method, whose blocks are in the same order as the ops they were built from.
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    blocks: Vec<Block>,
+    regions: Vec<Region>,
+    /// Block each label is in.
+    labels: HashMap<u32, usize>,
+}
+impl ControlFlowGraph {
+    /// Splits `ops` into basic blocks.
+    pub fn new(ops: &[CILOp]) -> Self {
+        let mut blocks = Vec::new();
+        let mut regions: Vec<Region> = Vec::new();
+        // Regions whose handlers did not end yet.
+        let mut open: Vec<usize> = Vec::new();
+        let mut current = Vec::new();
+        for op in ops {
+            let starts_block = is_marker(op)
+                || (matches!(op, CILOp::Label(_))
+                    && !matches!(current.last(), Some(CILOp::Label(_))));
+            if starts_block && !current.is_empty() {
+                let scope = scope(&regions, &open);
+                blocks.push(Block {
+                    ops: std::mem::take(&mut current),
+                    scope,
+                });
+            }
+            if is_marker(op) {
+                let index = blocks.len();
+                match op {
+                    CILOp::BeginTry => {
+                        open.push(regions.len());
+                        regions.push(Region {
+                            begin_try: index,
+                            end_try: usize::MAX,
+                            begin_handler: usize::MAX,
+                            end_handler: usize::MAX,
+                        });
+                    }
+                    CILOp::EndTry => {
+                        regions[*open.last().expect("EndTry outside a protected region!")].end_try =
+                            index
+                    }
+                    CILOp::BeginCatch(_) | CILOp::BeginFinally => {
+                        regions[*open.last().expect("Handler outside a protected region!")]
+                            .begin_handler = index
+                    }
+                    _ => {
+                        regions[open.pop().expect("EndHandler outside a handler!")].end_handler =
+                            index
+                    }
+                }
+                let scope = match op {
+                    // The marker of the start of a region is outside of it.
+                    CILOp::BeginTry => scope(&regions, &open[..open.len() - 1]),
+                    _ => scope(&regions, &open),
+                };
+                blocks.push(Block {
+                    ops: vec![op.clone()],
+                    scope,
+                });
+                continue;
+            }
+            current.push(op.clone());
+            if ends_block(op) {
+                let scope = scope(&regions, &open);
+                blocks.push(Block {
+                    ops: std::mem::take(&mut current),
+                    scope,
+                });
+            }
+        }
+        if !current.is_empty() {
+            let scope = scope(&regions, &open);
+            blocks.push(Block {
+                ops: current,
+                scope,
+            });
+        }
+        let labels = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, block)| block.labels().map(move |label| (label, index)))
+            .collect();
+        Self {
+            blocks,
+            regions,
+            labels,
+        }
+    }
+    /// Turns the graph back into a sequence of ops.
+    pub fn into_ops(self) -> Vec<CILOp> {
+        self.blocks
+            .into_iter()
+            .flat_map(|block| block.ops)
+            .collect()
+    }
+    /// Returns the blocks the control may go to after block `index`, including exception handlers.
+    fn successors(&self, index: usize) -> Vec<usize> {
+        let block = &self.blocks[index];
+        let mut successors = Vec::with_capacity(2);
+        if let Some(target) = block.ops.last().and_then(CILOp::branch_target) {
+            if let Some(target) = self.labels.get(&target) {
+                successors.push(*target);
+            }
+        }
+        if block.falls_through() && index + 1 < self.blocks.len() {
+            successors.push(index + 1);
+        }
+        successors.extend(
+            self.regions
+                .iter()
+                .filter(|region| region.begin_try < index && index < region.end_try)
+                .map(|region| region.begin_handler),
+        );
+        successors
+    }
+    /// Returns which blocks can be reached from the start of the method. Markers of regions are reachable if their protected block is.
+    fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut to_visit = vec![0];
+        while let Some(index) = to_visit.pop() {
+            if index >= self.blocks.len() || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            to_visit.extend(self.successors(index));
+        }
+        for region in &self.regions {
+            if reachable[region.begin_try] {
+                reachable[region.end_try] = true;
+                reachable[region.begin_handler] = true;
+                reachable[region.end_handler] = true;
+            }
+        }
+        reachable
+    }
+    /// Removes blocks which can't be reached.
+    fn remove_dead_blocks(&mut self) {
+        let reachable = self.reachable();
+        for (block, reachable) in self.blocks.iter_mut().zip(reachable) {
+            if !reachable {
+                block.ops.clear();
+            }
+        }
+    }
+    /// Makes branches to blocks which only jump or return go to the final target directly.
+    fn thread_jumps(&mut self) {
+        let forwards: HashMap<u32, u32> = self
+            .blocks
+            .iter()
+            .filter_map(|block| match block.body() {
+                [CILOp::GoTo(target)] => Some(block.labels().map(|label| (label, *target))),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let final_target = |mut label: u32| {
+            // Limits the number of steps, since the jumps may form a cycle.
+            for _ in 0..forwards.len() {
+                match forwards.get(&label) {
+                    Some(next) if *next != label => label = *next,
+                    _ => break,
+                }
+            }
+            label
+        };
+        let returns: Vec<u32> = self
+            .blocks
+            .iter()
+            .filter(|block| block.body() == [CILOp::Ret])
+            .flat_map(Block::labels)
+            .collect();
+        for op in self
+            .blocks
+            .iter_mut()
+            .flat_map(|block| block.ops.iter_mut())
+        {
+            let Some(target) = op.branch_target() else {
+                continue;
+            };
+            let new_target = final_target(target);
+            op.replace_target(target, new_target);
+            // The stack is the same at the jump and at its target, so the target `ret` can be executed in place of the jump.
+            if *op == CILOp::GoTo(new_target) && returns.contains(&new_target) {
+                *op = CILOp::Ret;
+            }
+        }
+    }
+    /// Removes branches to the next block, and inverts conditional branches jumping over an unconditional one.
+    fn simplify_branches(&mut self) {
+        for index in 0..self.blocks.len() {
+            let Some(next) =
+                (index + 1..self.blocks.len()).find(|next| !self.blocks[*next].ops.is_empty())
+            else {
+                continue;
+            };
+            let Some(last) = self.blocks[index].ops.last().cloned() else {
+                continue;
+            };
+            let Some(target) = last.branch_target() else {
+                continue;
+            };
+            let goes_to_next = self.labels.get(&target) == Some(&next);
+            let ops = &mut self.blocks[index].ops;
+            match last {
+                CILOp::GoTo(_) if goes_to_next => {
+                    ops.pop();
+                }
+                CILOp::BZero(_) | CILOp::BTrue(_) if goes_to_next => {
+                    *ops.last_mut().unwrap() = CILOp::Pop;
+                }
+                CILOp::BEq(_) | CILOp::BNe(_) | CILOp::BLt(_) | CILOp::BGe(_) | CILOp::BLe(_)
+                    if goes_to_next =>
+                {
+                    *ops.last_mut().unwrap() = CILOp::Pop;
+                    ops.push(CILOp::Pop);
+                }
+                // `bcond a; br b; a:` becomes `b!cond b; a:`. The jump must not have a label, so it is only reached from the branch.
+                CILOp::BZero(_) | CILOp::BTrue(_) | CILOp::BEq(_) | CILOp::BNe(_) => {
+                    let [CILOp::GoTo(jump_target)] = self.blocks[next].ops.as_slice() else {
+                        continue;
+                    };
+                    let jump_target = *jump_target;
+                    let after_jump = (next + 1..self.blocks.len())
+                        .find(|after| !self.blocks[*after].ops.is_empty());
+                    if self.labels.get(&target).copied() != after_jump {
+                        continue;
+                    }
+                    *self.blocks[index].ops.last_mut().unwrap() = match last {
+                        CILOp::BZero(_) => CILOp::BTrue(jump_target),
+                        CILOp::BTrue(_) => CILOp::BZero(jump_target),
+                        CILOp::BEq(_) => CILOp::BNe(jump_target),
+                        _ => CILOp::BEq(jump_target),
+                    };
+                    self.blocks[next].ops.clear();
+                }
+                _ => (),
+            }
+        }
+    }
+    /// Moves blocks which are only jumped to from one place, and never continue to the next block, in place of that jump.
+    fn merge_blocks(&mut self) {
+        let mut references: HashMap<u32, usize> = HashMap::new();
+        for target in self
+            .blocks
+            .iter()
+            .flat_map(|block| block.ops.iter())
+            .filter_map(CILOp::branch_target)
+        {
+            *references.entry(target).or_default() += 1;
+        }
+        for index in 0..self.blocks.len() {
+            while let Some(CILOp::GoTo(target)) = self.blocks[index].ops.last() {
+                let Some(&merged) = self.labels.get(target) else {
+                    break;
+                };
+                let block = &self.blocks[merged];
+                let previous_falls_through = !matches!(
+                    self.blocks[..merged].iter().rev().find(|block| !block.ops.is_empty()),
+                    Some(block) if !block.falls_through()
+                );
+                // Handlers are entered by falling through from their start marker, so they are never moved.
+                let can_merge = merged != index
+                    && block.scope == self.blocks[index].scope
+                    && !block.falls_through()
+                    && !previous_falls_through
+                    && block
+                        .labels()
+                        .all(|label| references.get(&label) == Some(&1));
+                if !can_merge {
+                    break;
+                }
+                let moved: Vec<_> = block.body().to_vec();
+                self.blocks[merged].ops.clear();
+                let ops = &mut self.blocks[index].ops;
+                ops.pop();
+                ops.extend(moved);
+            }
+        }
+    }
+    /// Returns the locals which are live at the start of each block, and at its end.
+    fn liveness(&self, local_count: usize) -> (Vec<Vec<bool>>, Vec<Vec<bool>>) {
+        // Locals read before being written to, and locals written to, by each block.
+        let (uses, defs): (Vec<_>, Vec<_>) = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut uses = vec![false; local_count];
+                let mut defs = vec![false; local_count];
+                for op in &block.ops {
+                    match op {
+                        CILOp::LDLoc(local) | CILOp::LDLocA(local) => {
+                            if !defs[*local as usize] {
+                                uses[*local as usize] = true;
+                            }
+                        }
+                        CILOp::STLoc(local) => defs[*local as usize] = true,
+                        _ => (),
+                    }
+                }
+                (uses, defs)
+            })
+            .unzip();
+        let successors: Vec<_> = (0..self.blocks.len())
+            .map(|index| self.successors(index))
+            .collect();
+        let mut live_in = uses.clone();
+        let mut live_out = vec![vec![false; local_count]; self.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in (0..self.blocks.len()).rev() {
+                for successor in &successors[index] {
+                    for local in 0..local_count {
+                        if live_in[*successor][local] && !live_out[index][local] {
+                            live_out[index][local] = true;
+                            changed = true;
+                        }
+                    }
+                }
+                for local in 0..local_count {
+                    if live_out[index][local] && !defs[index][local] && !live_in[index][local] {
+                        live_in[index][local] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        (live_in, live_out)
+    }
+}
+/// Returns the scope of blocks inside the open regions `open`.
+fn scope(regions: &[Region], open: &[usize]) -> Option<usize> {
+    open.last().map(|region| {
+        if regions[*region].begin_handler == usize::MAX {
+            2 * region
+        } else {
+            2 * region + 1
+        }
+    })
+}
+/// Removes unreachable blocks, threads jumps, removes and inverts branches and merges blocks.
+pub fn simplify_cfg(ops: &mut Vec<CILOp>) {
+    let mut cfg = ControlFlowGraph::new(ops);
+    cfg.remove_dead_blocks();
+    cfg.thread_jumps();
+    let mut cfg = ControlFlowGraph::new(&cfg.into_ops());
+    cfg.simplify_branches();
+    let mut cfg = ControlFlowGraph::new(&cfg.into_ops());
+    cfg.merge_blocks();
+    let mut cfg = ControlFlowGraph::new(&cfg.into_ops());
+    cfg.remove_dead_blocks();
+    *ops = cfg.into_ops();
+}
+/// Replaces writes to locals which are never read afterwards, on any path, with pops.
+pub fn remove_dead_stores(ops: &mut Vec<CILOp>) {
+    let local_count = ops
+        .iter()
+        .filter_map(|op| match op {
+            CILOp::LDLoc(local) | CILOp::LDLocA(local) | CILOp::STLoc(local) => {
+                Some(*local as usize + 1)
+            }
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    // Locals whose address is taken may be read through a pointer.
+    let mut address_taken = vec![false; local_count];
+    for op in ops.iter() {
+        if let CILOp::LDLocA(local) = op {
+            address_taken[*local as usize] = true;
+        }
+    }
+    let mut cfg = ControlFlowGraph::new(ops);
+    let (live_in, live_out) = cfg.liveness(local_count);
+    for (index, block) in cfg.blocks.iter_mut().enumerate() {
+        // An exception may be thrown anywhere within a protected block, so locals read by its handlers are always live.
+        let mut always_live = vec![false; local_count];
+        for region in cfg
+            .regions
+            .iter()
+            .filter(|region| region.begin_try < index && index < region.end_try)
+        {
+            for (local, live) in live_in[region.begin_handler].iter().enumerate() {
+                always_live[local] |= live;
+            }
+        }
+        let mut live = live_out[index].clone();
+        for op in block.ops.iter_mut().rev() {
+            match op {
+                CILOp::STLoc(local) => {
+                    let local = *local as usize;
+                    if !(live[local] || address_taken[local] || always_live[local]) {
+                        *op = CILOp::Pop;
+                    }
+                    live[local] = false;
+                }
+                CILOp::LDLoc(local) | CILOp::LDLocA(local) => live[*local as usize] = true,
+                _ => (),
+            }
+        }
+    }
+    *ops = cfg.into_ops();
+}
+#[test]
+fn cfg_simplification() {
+    let mut ops = vec![
+        CILOp::LdcI32(1),
+        CILOp::BTrue(1),
+        CILOp::GoTo(2),
+        CILOp::Label(1),
+        CILOp::GoTo(3),
+        // Unreachable
+        CILOp::LdcI32(7),
+        CILOp::Pop,
+        CILOp::Label(2),
+        CILOp::Ret,
+        CILOp::Label(3),
+        CILOp::LdcI32(0),
+        CILOp::Pop,
+        CILOp::Ret,
+    ];
+    simplify_cfg(&mut ops);
+    assert_eq!(
+        ops,
+        [
+            CILOp::LdcI32(1),
+            CILOp::BTrue(3),
+            CILOp::Ret,
+            CILOp::Label(3),
+            CILOp::LdcI32(0),
+            CILOp::Pop,
+            CILOp::Ret,
+        ]
+    );
+    let mut ops = vec![
+        CILOp::LDArg(0),
+        CILOp::BTrue(1),
+        CILOp::GoTo(2),
+        CILOp::Label(1),
+        CILOp::LdcI32(1),
+        CILOp::Ret,
+        CILOp::Label(2),
+        CILOp::LdcI32(2),
+        CILOp::Ret,
+    ];
+    simplify_cfg(&mut ops);
+    assert_eq!(
+        &ops[..3],
+        [CILOp::LDArg(0), CILOp::BZero(2), CILOp::Label(1)]
+    );
+    let mut ops = vec![
+        CILOp::GoTo(1),
+        CILOp::Label(2),
+        CILOp::Ret,
+        CILOp::Label(1),
+        CILOp::LdcI32(1),
+        CILOp::Pop,
+        CILOp::GoTo(2),
+    ];
+    simplify_cfg(&mut ops);
+    assert_eq!(ops, [CILOp::LdcI32(1), CILOp::Pop, CILOp::Ret]);
+}
+#[test]
+fn dead_stores() {
+    let mut ops = vec![
+        CILOp::LdcI32(1),
+        CILOp::STLoc(0),
+        CILOp::LdcI32(2),
+        CILOp::STLoc(0),
+        CILOp::BeginTry,
+        CILOp::LdcI32(3),
+        CILOp::STLoc(1),
+        CILOp::Leave(1),
+        CILOp::EndTry,
+        CILOp::BeginCatch(Box::new(crate::r#type::DotnetTypeRef::new(
+            Some("System.Runtime"),
+            "System.Exception",
+        ))),
+        CILOp::Pop,
+        CILOp::LDLoc(1),
+        CILOp::Pop,
+        CILOp::Rethrow,
+        CILOp::EndHandler,
+        CILOp::Label(1),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ];
+    remove_dead_stores(&mut ops);
+    // The first store is overwritten, and the store in the protected block is read by its handler.
+    assert_eq!(ops[1], CILOp::Pop);
+    assert_eq!(ops[3], CILOp::STLoc(0));
+    assert_eq!(ops[6], CILOp::STLoc(1));
+}
@@ -1,12 +1,22 @@
-use std::ops::Range;
+use std::{collections::HashSet, ops::Range};
 
 use crate::{
     assembly::Assembly,
     cil::{CILOp, CallSite},
+    config::CodegenConfig,
     method::Method,
     r#type::Type,
 };
+/// Control flow graph of the ops of a method, and the optimizations using it.
+mod cfg;
+/// Pass manager running the optimizations, and collecting their statistics.
+mod pass_manager;
+pub use pass_manager::{OptStats, Pass, PassSet};
+/// Maximal number of times the optimization passes are iterated, if the method does not stop changing.
 const MAX_PASS: u32 = 16;
+/// Maximal number of calls inlined into a single method at link time.
+const MAX_INLINED_CALLS: usize = 64;
+/// Replaces the call at `target` within `caller` with the body of `inlined`. Returns false if `inlined` is not simple enough to be inlined.
 pub fn try_inline(caller: &mut Method, inlined: &Method, target: usize) -> bool {
     // Inlining is still sometimes quite buggy.
     if true {
@@ -37,19 +47,29 @@ pub fn try_inline(caller: &mut Method, inlined: &Method, target: usize) -> bool
     {
         return false;
     }
+    // Can't yet inline methods with exception handling.
+    if inlined
+        .get_ops()
+        .iter()
+        .any(|op| matches!(op, CILOp::BeginTry))
+    {
+        return false;
+    }
     // inlined first validation
-    if cfg!(debug_assertions) {
-        crate::utilis::check_debugable(
+    if cfg!(debug_assertions)
+        && !crate::utilis::check_debugable(
             inlined.get_ops(),
             inlined,
             *inlined.sig().output() == Type::Void,
-        );
+        )
+    {
+        return false;
     }
+    // The return value gets a local, followed by one for each argument.
     let arg_beg = caller.locals().len();
-    caller.add_local(inlined.sig().output().clone());
-    caller.extend_locals(inlined.sig().inputs().iter());
     let mut inlined_call = Vec::new();
-    for (index, atype) in inlined.sig().inputs().iter().enumerate() {
+    // The arguments are passed on the stack, and the last one is on top, so they are stored in reverse order.
+    for (index, atype) in inlined.sig().inputs().iter().enumerate().rev() {
         if *atype == Type::Void {
             continue;
         }
@@ -59,7 +79,8 @@ pub fn try_inline(caller: &mut Method, inlined: &Method, target: usize) -> bool
     inlined_method_ops.iter_mut().for_each(|op| match op {
         CILOp::LDArg(id) => *op = CILOp::LDLoc((arg_beg + 1 + *id as usize) as u32),
         CILOp::LDArgA(id) => *op = CILOp::LDLocA((arg_beg + 1 + *id as usize) as u32),
-        CILOp::STArg(id) => *op = CILOp::STArg((arg_beg + 1 + *id as usize) as u32),
+        // Arguments are locals of the inlined body, so writes to them must not change the arguments of the caller.
+        CILOp::STArg(id) => *op = CILOp::STLoc((arg_beg + 1 + *id as usize) as u32),
         CILOp::LDLoc(_) | CILOp::LDLocA(_) | CILOp::STLoc(_) => {
             todo!("Inlining with locals not supported yet!")
         }
@@ -75,8 +96,12 @@ pub fn try_inline(caller: &mut Method, inlined: &Method, target: usize) -> bool
     new_ops.extend(preamble.iter().cloned());
     new_ops.extend(inlined_call);
     new_ops.extend(epilouge.iter().cloned());
-    // Validate method AFTER inline
-    crate::utilis::check_debugable(&new_ops, &new_ops, *caller.sig().output() == Type::Void);
+    // Validate method AFTER inline. If the inlined ops are broken, the call is kept.
+    if !crate::utilis::check_debugable(&new_ops, &new_ops, *caller.sig().output() == Type::Void) {
+        return false;
+    }
+    caller.add_local(inlined.sig().output().clone());
+    caller.extend_locals(inlined.sig().inputs().iter());
     caller.set_ops(new_ops);
     // Inlining succcedded.
     true
@@ -93,53 +118,58 @@ fn get_inlline_candidates(method: &Method) -> Vec<(usize, Box<CallSite>)> {
         .filter(|(_, site)| site.is_static() && site.class().is_none())
         .collect()
 }
-fn try_inline_all(method: &mut Method, asm: &Assembly) {
+/// Inlines the first call which can be inlined, and returns the call site of the inlined method.
+fn try_inline_all(method: &mut Method, asm: &Assembly) -> Option<CallSite> {
     //Inlining
     let inline_candidates = get_inlline_candidates(method);
+    let own_site = method.call_site();
     for (target, candidate) in inline_candidates {
         debug_assert_eq!(method.get_ops()[target], CILOp::Call(candidate.clone()));
-        let linlined = if let Some(method) = asm.methods().find(|method| {
-            method.name() == candidate.name()
-                && method.sig() == candidate.signature()
-                && method.is_static()
-        }) {
+        // Inlining a recursive call would never end.
+        if *candidate == own_site {
+            continue;
+        }
+        let linlined = if let Some(method) = asm
+            .get_method(&candidate)
+            .filter(|method| method.is_static())
+        {
             method
         } else {
             continue;
         };
         // If inline succeds, then the positions of all inline targets will become wrong, and rebuilding of the inline target list becomes necessary.
         if try_inline(method, linlined, target) {
-            //try_inline_all(method, asm);
-            return;
+            return Some(*candidate);
         }
     }
+    None
+}
+/// Inlines calls within `method` until no more calls can be inlined, adding the call sites of the inlined methods to `inlined`. Returns the number of inlined calls.
+pub fn inline_calls(method: &mut Method, asm: &Assembly, inlined: &mut HashSet<CallSite>) -> usize {
+    let mut count = 0;
+    // Mutually recursive methods could be inlined into each other forever.
+    while count < MAX_INLINED_CALLS {
+        let Some(site) = try_inline_all(method, asm) else {
+            break;
+        };
+        inlined.insert(site);
+        count += 1;
+    }
+    count
 }
 //pub fn try_turn_locals_into_bools(method:&Method){}
-pub fn opt_method(method: &mut Method, asm: &Assembly) {
-    if !crate::OPTIMIZE_CIL {
+/// Optimizes `method`, adding the statistics of the optimization passes to `stats`.
+pub fn opt_method(
+    method: &mut Method,
+    asm: &Assembly,
+    config: &CodegenConfig,
+    stats: &mut OptStats,
+) {
+    // MIR debug comments would be removed or misplaced by the optimizations.
+    if !config.optimize_cil || config.insert_mir_debug_comments {
         return;
     };
-    //panic!("opt");
-    method.ops_mut().retain(|op| match op {
-        CILOp::Call(site) => !site.is_nop(),
-        _ => true,
-    });
-    repalce_const_sizes(method.ops_mut());
-    for _ in 0..MAX_PASS {
-        op2_combos(method.ops_mut());
-        op3_combos(method.ops_mut());
-        op4_combos(method.ops_mut());
-        remove_zombie_sets(method.ops_mut());
-        method.ops_mut().retain(|op| *op != CILOp::Nop);
-        try_alias_locals(method.ops_mut());
-        if crate::SPLIT_LOCAL_STRUCTS {
-            try_split_locals(method, asm);
-        }
-        if crate::REMOVE_UNSUED_LOCALS {
-            remove_unused_locals(method);
-        }
-        //try_inline_all(method, asm);
-    }
+    pass_manager::run_passes(method, asm, config, stats);
 }
 fn repalce_const_sizes(ops: &mut [CILOp]) {
     ops.iter_mut().for_each(|op| match op {
@@ -229,9 +259,14 @@ fn op2_combos(ops: &mut Vec<CILOp>) {
                     ops[idx] = CILOp::Nop;
                 }
             }
-            (CILOp::GoTo(_), op2) => {
-                //TODO: Handle exception handling constructs!
-                if let CILOp::Label(_) = op2 {
+            (CILOp::GoTo(_) | CILOp::Leave(_), op2) => {
+                // Labels and exception handling region boundaries may still be reached after a GOTO.
+                if let CILOp::Label(_)
+                | CILOp::EndTry
+                | CILOp::BeginCatch(_)
+                | CILOp::BeginFinally
+                | CILOp::EndHandler = op2
+                {
                 }
                 // Any op after GOTO and not preceded by a label is unreachable.
                 else {
@@ -479,6 +514,7 @@ fn is_label_unsused(ops: &[CILOp], label: u32) -> bool {
         CILOp::BLe(target) => label == *target,
         CILOp::BZero(target) => label == *target,
         CILOp::BTrue(target) => label == *target,
+        CILOp::Leave(target) => label == *target,
         _ => false,
     })
 }
@@ -764,3 +800,43 @@ fn can_split_local(local: u32, ops: &[CILOp]) -> bool {
 
     true
 }
+#[test]
+fn inline_two_args() {
+    use crate::{access_modifier::AccessModifer, function_sig::FnSig};
+    let sig = FnSig::new(&[Type::I32, Type::I32], &Type::I32);
+    // Overwrites its first argument, which must not touch the arguments of the caller.
+    let mut sub = Method::new(AccessModifer::Private, true, sig.clone(), "sub", vec![]);
+    sub.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::Sub,
+        CILOp::STArg(0),
+        CILOp::LDArg(0),
+        CILOp::Ret,
+    ]);
+    let mut caller = Method::new(AccessModifer::Private, true, sig.clone(), "caller", vec![]);
+    caller.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LdcI32(3),
+        CILOp::Call(CallSite::boxed(None, "sub".into(), sig, true)),
+        CILOp::Ret,
+    ]);
+    assert!(try_inline(&mut caller, &sub, 2));
+    assert_eq!(caller.locals().len(), 3);
+    assert_eq!(
+        caller.get_ops(),
+        [
+            CILOp::LDArg(0),
+            CILOp::LdcI32(3),
+            CILOp::STLoc(2),
+            CILOp::STLoc(1),
+            CILOp::LDLoc(1),
+            CILOp::LDLoc(2),
+            CILOp::Sub,
+            CILOp::STLoc(1),
+            CILOp::LDLoc(1),
+            CILOp::Nop,
+            CILOp::Ret,
+        ]
+    );
+}
@@ -0,0 +1,250 @@
+use super::MAX_PASS;
+use crate::{assembly::Assembly, cil::CILOp, config::CodegenConfig, method::Method};
+use std::time::{Duration, Instant};
+/// A single optimization pass, transforming one method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    /// Removes calls which do nothing, like `black_box`.
+    RemoveNopCalls,
+    /// Replaces `sizeof` of types with a known size with constants.
+    ReplaceConstSizes,
+    /// Simplifies sequences of 2 ops.
+    Op2Combos,
+    /// Simplifies sequences of 3 ops.
+    Op3Combos,
+    /// Simplifies sequences of 4 ops.
+    Op4Combos,
+    /// Removes unreachable blocks, threads jumps, and removes, inverts or merges branches.
+    SimplifyCfg,
+    /// Replaces writes to locals which are never read afterwards with pops.
+    RemoveDeadStores,
+    /// Replaces writes to locals which are never read with pops, and removes unused labels.
+    RemoveZombieSets,
+    /// Removes `nop`s.
+    RemoveNops,
+    /// Merges locals copied into each other, which are never used at the same time.
+    AliasLocals,
+    /// Splits local structs whose fields are only accessed separately into a local per field.
+    SplitLocalStructs,
+    /// Removes locals which are never used.
+    RemoveUnusedLocals,
+    /// Inlines calls to simple static methods. Off by default, and, like all passes, only run if `optimize-cil` is on:
+    /// turn it on using `optimize-cil opt-inline-calls`. The linker then also inlines calls across crates.
+    InlineCalls,
+}
+impl Pass {
+    /// All the passes, in the order they are run in.
+    pub const ALL: [Self; 13] = [
+        Self::RemoveNopCalls,
+        Self::ReplaceConstSizes,
+        Self::Op2Combos,
+        Self::Op3Combos,
+        Self::Op4Combos,
+        Self::SimplifyCfg,
+        Self::RemoveDeadStores,
+        Self::RemoveZombieSets,
+        Self::RemoveNops,
+        Self::AliasLocals,
+        Self::SplitLocalStructs,
+        Self::RemoveUnusedLocals,
+        Self::InlineCalls,
+    ];
+    /// Name of the pass, used by the `opt-<name>` codegen option, dumps and statistics.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::RemoveNopCalls => "remove-nop-calls",
+            Self::ReplaceConstSizes => "replace-const-sizes",
+            Self::Op2Combos => "op2-combos",
+            Self::Op3Combos => "op3-combos",
+            Self::Op4Combos => "op4-combos",
+            Self::SimplifyCfg => "simplify-cfg",
+            Self::RemoveDeadStores => "remove-dead-stores",
+            Self::RemoveZombieSets => "remove-zombie-sets",
+            Self::RemoveNops => "remove-nops",
+            Self::AliasLocals => "alias-locals",
+            Self::SplitLocalStructs => "split-local-structs",
+            Self::RemoveUnusedLocals => "remove-unused-locals",
+            Self::InlineCalls => "inline-calls",
+        }
+    }
+    /// Returns the pass called `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pass| pass.name() == name)
+    }
+    /// Passes which are still buggy have to be turned on explicitly.
+    fn enabled_by_default(self) -> bool {
+        !matches!(
+            self,
+            Self::SplitLocalStructs | Self::RemoveUnusedLocals | Self::InlineCalls
+        )
+    }
+    /// Passes which only need to run once, before the other ones are iterated until a fixed point is reached.
+    fn runs_once(self) -> bool {
+        matches!(self, Self::RemoveNopCalls | Self::ReplaceConstSizes)
+    }
+    /// Runs the pass on `method`, and returns the number of calls it inlined.
+    fn run(self, method: &mut Method, asm: &Assembly) -> u64 {
+        match self {
+            Self::RemoveNopCalls => method.ops_mut().retain(|op| match op {
+                CILOp::Call(site) => !site.is_nop(),
+                _ => true,
+            }),
+            Self::ReplaceConstSizes => super::repalce_const_sizes(method.ops_mut()),
+            Self::Op2Combos => super::op2_combos(method.ops_mut()),
+            Self::Op3Combos => super::op3_combos(method.ops_mut()),
+            Self::Op4Combos => super::op4_combos(method.ops_mut()),
+            Self::SimplifyCfg => super::cfg::simplify_cfg(method.ops_mut()),
+            Self::RemoveDeadStores => super::cfg::remove_dead_stores(method.ops_mut()),
+            Self::RemoveZombieSets => super::remove_zombie_sets(method.ops_mut()),
+            Self::RemoveNops => method.ops_mut().retain(|op| *op != CILOp::Nop),
+            Self::AliasLocals => super::try_alias_locals(method.ops_mut()),
+            Self::SplitLocalStructs => super::try_split_locals(method, asm),
+            Self::RemoveUnusedLocals => super::remove_unused_locals(method),
+            Self::InlineCalls => return super::try_inline_all(method, asm).is_some().into(),
+        }
+        0
+    }
+}
+/// A set of optimization passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PassSet(u32);
+impl PassSet {
+    /// Checks if `pass` is in the set.
+    pub fn contains(self, pass: Pass) -> bool {
+        self.0 & (1 << pass as u32) != 0
+    }
+    /// Adds `pass` to the set if `enabled` is true, and removes it otherwise.
+    pub fn set(&mut self, pass: Pass, enabled: bool) {
+        if enabled {
+            self.0 |= 1 << pass as u32;
+        } else {
+            self.0 &= !(1 << pass as u32);
+        }
+    }
+}
+impl Default for PassSet {
+    /// Returns the set of passes enabled by default.
+    fn default() -> Self {
+        let mut set = Self(0);
+        for pass in Pass::ALL {
+            set.set(pass, pass.enabled_by_default());
+        }
+        set
+    }
+}
+/// Statistics of a single optimization pass.
+#[derive(Clone, Copy, Debug, Default)]
+struct PassStats {
+    runs: u64,
+    time: Duration,
+    ops_removed: i64,
+    locals_removed: i64,
+    calls_inlined: u64,
+}
+/// Statistics of all the optimization passes, printed when `-Z time-passes` or the `print-opt-stats` option is used.
+#[derive(Clone, Debug, Default)]
+pub struct OptStats {
+    passes: [PassStats; Pass::ALL.len()],
+}
+impl OptStats {
+    /// Adds the statistics in `other` to `self`.
+    pub fn merge(&mut self, other: &Self) {
+        for (stats, other) in self.passes.iter_mut().zip(other.passes.iter()) {
+            stats.runs += other.runs;
+            stats.time += other.time;
+            stats.ops_removed += other.ops_removed;
+            stats.locals_removed += other.locals_removed;
+            stats.calls_inlined += other.calls_inlined;
+        }
+    }
+}
+impl std::fmt::Display for OptStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (pass, stats) in Pass::ALL.iter().zip(self.passes.iter()) {
+            if stats.runs == 0 {
+                continue;
+            }
+            writeln!(
+                f,
+                "opt: {time:7.3}; runs: {runs:7}; ops removed: {ops:8}; locals removed: {locals:6}; calls inlined: {calls:5}\t{name}",
+                time = stats.time.as_secs_f64(),
+                runs = stats.runs,
+                ops = stats.ops_removed,
+                locals = stats.locals_removed,
+                calls = stats.calls_inlined,
+                name = pass.name()
+            )?;
+        }
+        Ok(())
+    }
+}
+/// Runs the optimization passes enabled in `config` on `method`, until it stops changing.
+pub fn run_passes(
+    method: &mut Method,
+    asm: &Assembly,
+    config: &CodegenConfig,
+    stats: &mut OptStats,
+) {
+    if config.dump_opt_passes {
+        dump(method, "before optimization");
+    }
+    for pass in Pass::ALL.into_iter().filter(|pass| pass.runs_once()) {
+        run_pass(pass, method, asm, config, stats);
+    }
+    for _ in 0..MAX_PASS {
+        let ops = method.get_ops().to_vec();
+        let locals = method.locals().len();
+        for pass in Pass::ALL.into_iter().filter(|pass| !pass.runs_once()) {
+            run_pass(pass, method, asm, config, stats);
+        }
+        if method.get_ops() == ops.as_slice() && method.locals().len() == locals {
+            break;
+        }
+    }
+}
+/// Runs `pass` on `method` if it is enabled, recording its statistics.
+fn run_pass(
+    pass: Pass,
+    method: &mut Method,
+    asm: &Assembly,
+    config: &CodegenConfig,
+    stats: &mut OptStats,
+) {
+    if !config.opt_passes.contains(pass) {
+        return;
+    }
+    let ops = method.get_ops().len() as i64;
+    let locals = method.locals().len() as i64;
+    let old_ops = config.dump_opt_passes.then(|| method.get_ops().to_vec());
+    let start = Instant::now();
+    let calls_inlined = pass.run(method, asm);
+    let pass_stats = &mut stats.passes[pass as usize];
+    pass_stats.runs += 1;
+    pass_stats.time += start.elapsed();
+    pass_stats.ops_removed += ops - method.get_ops().len() as i64;
+    pass_stats.locals_removed += locals - method.locals().len() as i64;
+    pass_stats.calls_inlined += calls_inlined;
+    if let Some(old_ops) = old_ops {
+        if old_ops != method.get_ops() {
+            dump(method, &format!("after {name}", name = pass.name()));
+        }
+    }
+}
+/// Prints the ops of `method`.
+fn dump(method: &Method, when: &str) {
+    eprintln!("{name} {when}:", name = method.name());
+    for op in method.get_ops() {
+        eprintln!("    {op:?}");
+    }
+}
+#[test]
+fn pass_names() {
+    for pass in Pass::ALL {
+        assert_eq!(Pass::from_name(pass.name()), Some(pass));
+    }
+    let mut set = PassSet::default();
+    assert!(set.contains(Pass::Op2Combos));
+    assert!(!set.contains(Pass::InlineCalls));
+    set.set(Pass::Op2Combos, false);
+    assert!(!set.contains(Pass::Op2Combos));
+}
@@ -12,6 +12,8 @@ pub fn local_adress(local: usize, method: &rustc_middle::mir::Body) -> CILOp {
         CILOp::LDLocA(0)
     } else if local > method.arg_count {
         CILOp::LDLocA((local - method.arg_count) as u32)
+    } else if Some(local.into()) == method.spread_arg {
+        CILOp::LDLocA(super::spread_local(method))
     } else {
         CILOp::LDArgA((local - 1) as u32)
     }
@@ -89,6 +91,10 @@ pub fn place_elem_adress<'ctx>(
                 .as_ty()
                 .expect("Can't get enum variant of an enum varaint!");
             let curr_type = crate::utilis::monomorphize(&method_instance, curr_type, tyctx);
+            // The variants of a coroutine don't overlap, so their fields are accessed directly.
+            if let TyKind::Coroutine(..) = curr_type.kind() {
+                return vec![];
+            }
             let curr_dotnet_type =
                 type_cache.type_from_cache(curr_type, tyctx, Some(method_instance));
             let curr_dotnet_type =
@@ -115,10 +121,8 @@ pub fn place_elem_adress<'ctx>(
             let curr_ty = curr_type
                 .as_ty()
                 .expect("INVALID PLACE: Indexing into enum variant???");
-            let index = crate::place::local_get(
-                index.as_usize(),
-                tyctx.optimized_mir(method_instance.def_id()),
-            );
+            let index =
+                crate::place::local_get(index.as_usize(), tyctx.instance_mir(method_instance.def));
             match curr_ty.kind() {
                 TyKind::Slice(inner) => {
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
@@ -84,6 +84,10 @@ pub fn place_elem_body<'ctx>(
                 .as_ty()
                 .expect("Can't get enum variant of an enum varaint!");
             let curr_type = crate::utilis::monomorphize(&method_instance, curr_type, tyctx);
+            // The variants of a coroutine don't overlap, so their fields are accessed directly.
+            if let TyKind::Coroutine(..) = curr_type.kind() {
+                return (PlaceTy::EnumVariant(curr_type, variant.as_u32()), vec![]);
+            }
             let curr_dotnet_type =
                 type_cache.type_from_cache(curr_type, tyctx, Some(method_instance));
             let curr_dotnet_type =
@@ -150,10 +154,8 @@ pub fn place_elem_body<'ctx>(
             let curr_ty = curr_type
                 .as_ty()
                 .expect("INVALID PLACE: Indexing into enum variant???");
-            let index = crate::place::local_get(
-                index.as_usize(),
-                tyctx.optimized_mir(method_instance.def_id()),
-            );
+            let index =
+                crate::place::local_get(index.as_usize(), tyctx.instance_mir(method_instance.def));
             match curr_ty.kind() {
                 TyKind::Slice(inner) => {
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
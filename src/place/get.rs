@@ -10,6 +10,8 @@ pub(super) fn local_get(local: usize, method: &rustc_middle::mir::Body) -> CILOp
         CILOp::LDLoc(0)
     } else if local > method.arg_count {
         CILOp::LDLoc((local - method.arg_count) as u32)
+    } else if Some(local.into()) == method.spread_arg {
+        CILOp::LDLoc(super::spread_local(method))
     } else {
         CILOp::LDArg((local - 1) as u32)
     }
@@ -92,10 +94,8 @@ fn place_elem_get<'a>(
             let curr_ty = curr_type
                 .as_ty()
                 .expect("INVALID PLACE: Indexing into enum variant???");
-            let index = crate::place::local_get(
-                index.as_usize(),
-                tyctx.optimized_mir(method_instance.def_id()),
-            );
+            let index =
+                crate::place::local_get(index.as_usize(), tyctx.instance_mir(method_instance.def));
             match curr_ty.kind() {
                 TyKind::Slice(inner) => {
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
@@ -19,6 +19,10 @@ fn slice_head<T>(slice: &[T]) -> (&T, &[T]) {
     let last = &slice[slice.len() - 1];
     (last, &slice[..(slice.len() - 1)])
 }
+/// Returns the index of the local variable holding the tuple of arguments spread by the "rust-call" ABI. It is placed after all other locals.
+pub(crate) fn spread_local(method: &rustc_middle::mir::Body) -> u32 {
+    (method.local_decls.len() - method.arg_count) as u32
+}
 fn pointed_type(ty: PlaceTy) -> Ty {
     if let PlaceTy::Ty(ty) = ty {
         if let TyKind::Ref(_region, inner, _mut) = ty.kind() {
@@ -42,6 +46,9 @@ fn body_ty_is_by_adress(last_ty: &Ty) -> bool {
         //TODO: check if slices are handled propely
         TyKind::Slice(_) => true,
         TyKind::Str => true,
+        TyKind::Dynamic(..) => true,
+        TyKind::Closure(..) => true,
+        TyKind::Coroutine(..) => true,
 
         TyKind::Int(_) => false,
         TyKind::Float(_) => false,
@@ -49,6 +56,7 @@ fn body_ty_is_by_adress(last_ty: &Ty) -> bool {
 
         TyKind::Ref(_region, _inner, _mut) => false,
         TyKind::RawPtr(_) => false,
+        TyKind::FnPtr(_) => false,
         TyKind::Bool => false,
         TyKind::Char => false,
 
@@ -126,7 +134,7 @@ pub fn deref_op<'ctx>(
             TyKind::Bool => vec![CILOp::LDIndI8], // Both Rust bool and a managed bool are 1 byte wide. .NET bools are 4 byte wide only in the context of Marshaling/PInvoke,
             // due to historic reasons(BOOL was an alias for int in early Windows, and it stayed this way.) - FractalFir
             TyKind::Char => vec![CILOp::LDIndI32], // always 4 bytes wide: https://doc.rust-lang.org/std/primitive.char.html#representation
-            TyKind::Adt(_, _) => {
+            TyKind::Adt(_, _) | TyKind::Closure(..) | TyKind::Coroutine(..) | TyKind::Param(_) => {
                 let derefed_type =
                     type_cache.type_from_cache(derefed_type, tyctx, Some(*method_instance));
                 vec![CILOp::LdObj(derefed_type.into())]
@@ -143,7 +151,7 @@ pub fn deref_op<'ctx>(
                         .type_from_cache(derefed_type, tyctx, Some(*method_instance))
                         .into(),
                 )],
-                TyKind::Str => vec![CILOp::LdObj(
+                TyKind::Str | TyKind::Dynamic(..) => vec![CILOp::LdObj(
                     type_cache
                         .type_from_cache(derefed_type, tyctx, Some(*method_instance))
                         .into(),
@@ -156,7 +164,7 @@ pub fn deref_op<'ctx>(
                         .type_from_cache(derefed_type, tyctx, Some(*method_instance))
                         .into(),
                 )],
-                TyKind::Str => vec![CILOp::LdObj(
+                TyKind::Str | TyKind::Dynamic(..) => vec![CILOp::LdObj(
                     type_cache
                         .type_from_cache(derefed_type, tyctx, Some(*method_instance))
                         .into(),
@@ -168,6 +176,7 @@ pub fn deref_op<'ctx>(
                     type_cache.type_from_cache(derefed_type, tyctx, Some(*method_instance));
                 vec![CILOp::LdObj(derefed_type.into())]
             }
+            TyKind::FnPtr(_) => vec![CILOp::LDIndISize],
             _ => todo!("TODO: can't deref type {derefed_type:?} yet"),
         }
     } else {
@@ -11,6 +11,8 @@ pub fn local_set(local: usize, method: &rustc_middle::mir::Body) -> CILOp {
         CILOp::STLoc(0)
     } else if local > method.arg_count {
         CILOp::STLoc((local - method.arg_count) as u32)
+    } else if Some(local.into()) == method.spread_arg {
+        CILOp::STLoc(super::spread_local(method))
     } else {
         CILOp::STArg((local - 1) as u32)
     }
@@ -82,10 +84,8 @@ pub fn place_elem_set<'a>(
             let curr_ty = curr_type
                 .as_ty()
                 .expect("INVALID PLACE: Indexing into enum variant???");
-            let index = crate::place::local_get(
-                index.as_usize(),
-                ctx.optimized_mir(method_instance.def_id()),
-            );
+            let index =
+                crate::place::local_get(index.as_usize(), ctx.instance_mir(method_instance.def));
 
             match curr_ty.kind() {
                 TyKind::Slice(inner) => {
@@ -259,7 +259,7 @@ fn ptr_set_op<'ctx>(
             TyKind::Bool => vec![CILOp::STIndI8], // Both Rust bool and a managed bool are 1 byte wide. .NET bools are 4 byte wide only in the context of Marshaling/PInvoke,
             // due to historic reasons(BOOL was an alias for int in early Windows, and it stayed this way.) - FractalFir
             TyKind::Char => vec![CILOp::STIndI32], // always 4 bytes wide: https://doc.rust-lang.org/std/primitive.char.html#representation
-            TyKind::Adt(_, _) => {
+            TyKind::Adt(_, _) | TyKind::Closure(..) | TyKind::Coroutine(..) | TyKind::Param(_) => {
                 let pointed_type =
                     type_cache.type_from_cache(pointed_type, tyctx, Some(*method_instance));
                 vec![CILOp::STObj(pointed_type.into())]
@@ -270,8 +270,16 @@ fn ptr_set_op<'ctx>(
                 // This is interpreted as a System.ValueTuple and can be treated as an ADT
                 vec![CILOp::STObj(pointed_type.into())]
             }
-            TyKind::Ref(_, _, _) => vec![CILOp::STIndISize],
-            TyKind::RawPtr(_) => vec![CILOp::STIndISize],
+            TyKind::Ref(_, _, _) | TyKind::RawPtr(_) => {
+                match type_cache.type_from_cache(pointed_type, tyctx, Some(*method_instance)) {
+                    // Fat pointers are structs.
+                    fat_ptr @ crate::r#type::Type::DotnetType(_) => {
+                        vec![CILOp::STObj(fat_ptr.into())]
+                    }
+                    _ => vec![CILOp::STIndISize],
+                }
+            }
+            TyKind::FnPtr(_) => vec![CILOp::STIndISize],
             TyKind::Array(_, _) => {
                 let pointed_type =
                     type_cache.type_from_cache(pointed_type, tyctx, Some(*method_instance));
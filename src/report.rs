@@ -0,0 +1,222 @@
+use crate::IString;
+use rustc_middle::{
+    mir::{NonDivergingIntrinsic, Rvalue, StatementKind, TerminatorKind},
+    ty::TyCtxt,
+};
+use rustc_span::{def_id::LOCAL_CRATE, Span};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+/// Part of a method which could not be compiled.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Construct {
+    /// The whole method failed to compile.
+    Method,
+    /// A statement was replaced with a throw.
+    Statement,
+    /// A terminator was replaced with a throw.
+    Terminator,
+    /// The method was never compiled, and the linker replaced it with a throwing stub.
+    MissingMethod,
+}
+/// A method which failed to compile, or had a part of it replaced with a throw.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Failure {
+    /// Crate the method was compiled in, if known.
+    krate: Option<IString>,
+    method: IString,
+    construct: Construct,
+    /// Kind of the construct, eg. `Assign(Cast(IntToFloat))` for a statement, or `Call` for a terminator.
+    kind: IString,
+    message: IString,
+    span: Option<IString>,
+}
+impl Failure {
+    /// Creates a failure of the `construct` of kind `kind` at `span`, in `method` of the currently compiled crate.
+    pub fn new(
+        tcx: TyCtxt,
+        method: &str,
+        construct: Construct,
+        kind: &str,
+        message: &str,
+        span: Span,
+    ) -> Self {
+        Self {
+            krate: Some(tcx.crate_name(LOCAL_CRATE).to_string().into()),
+            method: method.into(),
+            construct,
+            kind: kind.into(),
+            message: message.into(),
+            span: Some(tcx.sess.source_map().span_to_diagnostic_string(span).into()),
+        }
+    }
+    /// Creates a failure representing the method `method`, which was replaced with a throwing stub by the linker.
+    pub fn missing_method(method: &str) -> Self {
+        Self {
+            krate: None,
+            method: method.into(),
+            construct: Construct::MissingMethod,
+            kind: "MissingMethod".into(),
+            message: format!("Tried to invoke missing method {method}").into(),
+            span: None,
+        }
+    }
+    /// Returns the part of the method which could not be compiled.
+    pub fn construct(&self) -> Construct {
+        self.construct
+    }
+    /// Returns a short, human-readable description of this failure.
+    pub fn describe(&self) -> String {
+        match self.construct {
+            Construct::Method | Construct::MissingMethod => {
+                format!("could not compile method `{method}`", method = self.method)
+            }
+            Construct::Statement | Construct::Terminator => format!(
+                "unsupported {construct} `{kind}` in method `{method}`",
+                construct = format!("{:?}", self.construct).to_lowercase(),
+                kind = self.kind,
+                method = self.method
+            ),
+        }
+    }
+    /// Returns the message explaining why the construct could not be compiled.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    /// Key under which this failure is counted in the report.
+    fn count_key(&self) -> String {
+        format!(
+            "{construct:?}::{kind}",
+            construct = self.construct,
+            kind = self.kind
+        )
+    }
+}
+/// Report of all the MIR constructs which could not be compiled. Travels with the assembly, so the linker can report on all the crates it links.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CodegenReport {
+    failures: Vec<Failure>,
+}
+/// Format of the JSON report file.
+#[derive(Serialize)]
+struct ReportFile<'a> {
+    name: &'a str,
+    failed_methods: usize,
+    counts: BTreeMap<String, usize>,
+    failures: &'a [Failure],
+}
+impl CodegenReport {
+    /// Creates an empty report.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+    /// Adds `failure` to the report.
+    pub fn add(&mut self, failure: Failure) {
+        self.failures.push(failure);
+    }
+    /// Returns all the failures in this report.
+    pub fn failures(&self) -> &[Failure] {
+        &self.failures
+    }
+    /// Joins 2 reports together.
+    pub fn join(mut self, other: Self) -> Self {
+        self.failures.extend(other.failures);
+        self
+    }
+    /// Returns the number of failures of each kind of construct.
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for failure in &self.failures {
+            *counts.entry(failure.count_key()).or_insert(0) += 1;
+        }
+        counts
+    }
+    /// Returns the number of methods with at least one failure.
+    pub fn failed_methods(&self) -> usize {
+        let mut methods: Vec<_> = self
+            .failures
+            .iter()
+            .map(|failure| (&failure.krate, &failure.method))
+            .collect();
+        methods.sort();
+        methods.dedup();
+        methods.len()
+    }
+    /// Turns this report about the crate or executable `name` into JSON.
+    pub fn to_json(&self, name: &str) -> String {
+        serde_json::to_string_pretty(&ReportFile {
+            name,
+            failed_methods: self.failed_methods(),
+            counts: self.counts(),
+            failures: &self.failures,
+        })
+        .expect("Could not serialize the codegen report!")
+    }
+    /// Saves the JSON report about `name` to `path`.
+    pub fn save(&self, name: &str, path: &Path) {
+        std::fs::write(path, self.to_json(name)).expect("Could not save the codegen report!");
+    }
+}
+/// Returns the kind of a statement, as listed in the report.
+pub fn statement_kind(kind: &StatementKind) -> String {
+    match kind {
+        StatementKind::Assign(assign) => format!("Assign({})", rvalue_kind(&assign.1)),
+        StatementKind::Intrinsic(intrinsic) => match intrinsic.as_ref() {
+            NonDivergingIntrinsic::Assume(_) => "Intrinsic(Assume)".into(),
+            NonDivergingIntrinsic::CopyNonOverlapping(_) => "Intrinsic(CopyNonOverlapping)".into(),
+        },
+        StatementKind::SetDiscriminant { .. } => "SetDiscriminant".into(),
+        StatementKind::Deinit(_) => "Deinit".into(),
+        StatementKind::StorageLive(_) => "StorageLive".into(),
+        StatementKind::StorageDead(_) => "StorageDead".into(),
+        _ => "Other".into(),
+    }
+}
+/// Returns the kind of an rvalue, as listed in the report.
+fn rvalue_kind(rvalue: &Rvalue) -> String {
+    match rvalue {
+        Rvalue::Use(_) => "Use".into(),
+        Rvalue::Repeat(..) => "Repeat".into(),
+        Rvalue::Ref(..) => "Ref".into(),
+        Rvalue::ThreadLocalRef(_) => "ThreadLocalRef".into(),
+        Rvalue::AddressOf(..) => "AddressOf".into(),
+        Rvalue::Len(_) => "Len".into(),
+        Rvalue::Cast(kind, ..) => format!("Cast({kind:?})"),
+        Rvalue::BinaryOp(op, _) => format!("BinaryOp({op:?})"),
+        Rvalue::CheckedBinaryOp(op, _) => format!("CheckedBinaryOp({op:?})"),
+        Rvalue::NullaryOp(op, _) => format!("NullaryOp({op:?})"),
+        Rvalue::UnaryOp(op, _) => format!("UnaryOp({op:?})"),
+        Rvalue::Discriminant(_) => "Discriminant".into(),
+        Rvalue::Aggregate(..) => "Aggregate".into(),
+        Rvalue::ShallowInitBox(..) => "ShallowInitBox".into(),
+        Rvalue::CopyForDeref(_) => "CopyForDeref".into(),
+    }
+}
+/// Returns the kind of a terminator, as listed in the report.
+pub fn terminator_kind(kind: &TerminatorKind) -> &'static str {
+    match kind {
+        TerminatorKind::Goto { .. } => "Goto",
+        TerminatorKind::SwitchInt { .. } => "SwitchInt",
+        TerminatorKind::UnwindResume => "UnwindResume",
+        TerminatorKind::UnwindTerminate(_) => "UnwindTerminate",
+        TerminatorKind::Return => "Return",
+        TerminatorKind::Unreachable => "Unreachable",
+        TerminatorKind::Drop { .. } => "Drop",
+        TerminatorKind::Call { .. } => "Call",
+        TerminatorKind::Assert { .. } => "Assert",
+        TerminatorKind::Yield { .. } => "Yield",
+        TerminatorKind::CoroutineDrop => "CoroutineDrop",
+        TerminatorKind::InlineAsm { .. } => "InlineAsm",
+        _ => "Other",
+    }
+}
+#[test]
+fn report_counts() {
+    let mut report = CodegenReport::empty();
+    report.add(Failure::missing_method("a"));
+    report.add(Failure::missing_method("b"));
+    report.add(Failure::missing_method("a"));
+    assert_eq!(report.counts()["MissingMethod::MissingMethod"], 3);
+    assert_eq!(report.failed_methods(), 2);
+    let joined = report.clone().join(report);
+    assert_eq!(joined.failures().len(), 6);
+}
@@ -1,4 +1,4 @@
-use crate::cil::{CILOp, FieldDescriptor};
+use crate::cil::{CILOp, CallSite, FieldDescriptor};
 use crate::operand::handle_operand;
 use crate::place::deref_op;
 use crate::r#type::{DotnetTypeRef, TyCache, Type};
@@ -6,7 +6,7 @@ use rustc_middle::mir::{CastKind, NullOp};
 use rustc_middle::ty::adjustment::PointerCoercion;
 use rustc_middle::{
     mir::{Place, Rvalue},
-    ty::{Instance, Ty, TyCtxt, TyKind, UintTy},
+    ty::{ClosureKind, Instance, ParamEnv, Ty, TyCtxt, TyKind, UintTy},
 };
 pub fn handle_rvalue<'tcx>(
     rvalue: &Rvalue<'tcx>,
@@ -98,6 +98,54 @@ pub fn handle_rvalue<'tcx>(
                 TyKind::Ref(_, inner, _) => *inner,
                 _ => panic!("Non ptr type:{source:?}"),
             };
+            let derefed_target = match target.kind() {
+                TyKind::RawPtr(tpe) => tpe.ty,
+                TyKind::Ref(_, inner, _) => *inner,
+                _ => panic!("Non ptr type:{target:?}"),
+            };
+            if let TyKind::Dynamic(..) = derefed_target.kind() {
+                let (metadata, vtable_ptr) = crate::vtable::vtable_ptr_fields(
+                    derefed_target,
+                    tyctx,
+                    method_instance,
+                    tycache,
+                );
+                let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
+                res.extend([
+                    CILOp::NewTMPLocal(source_type.into()),
+                    CILOp::SetTMPLocal,
+                    // a:*T = stack_top;
+                    CILOp::NewTMPLocal(target_type.into()),
+                    // b:*dyn Trait = unint();
+                    CILOp::LoadAddresOfTMPLocal,
+                    CILOp::LoadUnderTMPLocal(1),
+                    CILOp::STField(
+                        FieldDescriptor::new(
+                            target_dotnet,
+                            Type::Ptr(Type::Void.into()),
+                            "data_address".into(),
+                        )
+                        .into(),
+                    ),
+                    // b.data_address = (a as *mut RustVoid);
+                    CILOp::LoadAddresOfTMPLocal,
+                    CILOp::LDFieldAdress(metadata.into()),
+                ]);
+                res.extend(crate::vtable::vtable_alloc(
+                    derefed_source,
+                    derefed_target,
+                    tyctx,
+                ));
+                res.extend([
+                    CILOp::STField(vtable_ptr.into()),
+                    // b.metadata.vtable_ptr = vtable;
+                    CILOp::LoadTMPLocal,
+                    // stack_top = b;
+                    CILOp::FreeTMPLocal,
+                    CILOp::FreeTMPLocal,
+                ]);
+                return res;
+            }
             let length = if let TyKind::Array(element, length) = derefed_source.kind() {
                 crate::utilis::try_resolve_const_size(&length).unwrap()
             } else {
@@ -139,6 +187,40 @@ pub fn handle_rvalue<'tcx>(
             //todo!("Array to slice {res:?}!")
             //
         }
+        Rvalue::Cast(CastKind::PointerCoercion(PointerCoercion::ReifyFnPointer), operand, _) => {
+            let fn_ty =
+                crate::utilis::monomorphize(&method_instance, operand.ty(method, tyctx), tyctx);
+            let TyKind::FnDef(def_id, subst) = fn_ty.kind() else {
+                panic!("Can't reify {fn_ty:?}, because it is not a function definition!");
+            };
+            let instance =
+                Instance::resolve_for_fn_ptr(tyctx, ParamEnv::reveal_all(), *def_id, subst)
+                    .expect("Could not resolve the instance of a reified function");
+            vec![CILOp::LDFtn(
+                CallSite::from_instance(instance, tyctx, tycache).into(),
+            )]
+        }
+        Rvalue::Cast(
+            CastKind::PointerCoercion(PointerCoercion::ClosureFnPointer(_)),
+            operand,
+            _,
+        ) => {
+            let closure_ty =
+                crate::utilis::monomorphize(&method_instance, operand.ty(method, tyctx), tyctx);
+            let TyKind::Closure(def_id, subst) = closure_ty.kind() else {
+                panic!("Can't turn {closure_ty:?} into a function pointer, because it is not a closure!");
+            };
+            let instance = Instance::resolve_closure(tyctx, *def_id, subst, ClosureKind::FnOnce)
+                .expect("Could not resolve the instance of a closure")
+                .polymorphize(tyctx);
+            vec![CILOp::LDFtn(
+                crate::closure::fn_ptr_thunk_site(instance, tyctx, tycache).into(),
+            )]
+        }
+        // Safe and unsafe function pointers have the same representation.
+        Rvalue::Cast(CastKind::PointerCoercion(PointerCoercion::UnsafeFnPointer), operand, _) => {
+            handle_operand(operand, tyctx, method, method_instance, tycache)
+        }
         Rvalue::BinaryOp(binop, operands) => crate::binop::binop_unchecked(
             *binop,
             &operands.0,
@@ -356,7 +438,11 @@ pub fn handle_rvalue<'tcx>(
             let owner_ty = crate::utilis::monomorphize(&method_instance, owner_ty, tyctx);
             let owner = tycache.type_from_cache(owner_ty, tyctx, Some(method_instance));
             //TODO: chose proper tag type based on variant count of `owner`
-            let discr_type = crate::r#type::Type::U8; //owner_ty
+            let discr_type = if let TyKind::Coroutine(..) = owner_ty.kind() {
+                crate::r#type::Type::U32
+            } else {
+                crate::r#type::Type::U8
+            }; //owner_ty
             let owner = if let crate::r#type::Type::DotnetType(dotnet_type) = owner {
                 dotnet_type.as_ref().clone()
             } else {
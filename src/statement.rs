@@ -1,7 +1,11 @@
-use crate::{cil::CILOp, r#type::TyCache};
+use crate::{
+    cil::{CILOp, FieldDescriptor},
+    config::CodegenConfig,
+    r#type::{TyCache, Type},
+};
 use rustc_middle::{
     mir::{Body, CopyNonOverlapping, NonDivergingIntrinsic, Statement, StatementKind},
-    ty::{Instance, TyCtxt},
+    ty::{Instance, TyCtxt, TyKind},
 };
 pub fn handle_statement<'tcx>(
     statement: &Statement<'tcx>,
@@ -9,6 +13,7 @@ pub fn handle_statement<'tcx>(
     method: &Body<'tcx>,
     method_instance: Instance<'tcx>,
     type_cache: &mut TyCache,
+    config: &CodegenConfig,
 ) -> Vec<CILOp> {
     let kind = &statement.kind;
     let mut res = match kind {
@@ -37,8 +42,7 @@ pub fn handle_statement<'tcx>(
                 method_instance,
                 type_cache,
             );
-            if crate::TRACE_STATEMENTS {
-                use crate::r#type::Type;
+            if config.trace_statements {
                 rustc_middle::ty::print::with_no_trimmed_paths! {res.extend(CILOp::debug_msg(&format!("{statement:?}")))};
                 let place_ty = type_cache.type_from_cache(
                     crate::utilis::monomorphize(
@@ -172,7 +176,7 @@ pub fn handle_statement<'tcx>(
                     res.push(CILOp::SizeOf(pointed));
                     res.push(CILOp::Mul);
                     res.push(CILOp::CpBlk);
-                    if crate::TRACE_STATEMENTS {
+                    if config.trace_statements {
                         rustc_middle::ty::print::with_no_trimmed_paths! {res.extend(CILOp::debug_msg(&format!("{statement:?}")))};
                     }
                     res
@@ -182,6 +186,32 @@ pub fn handle_statement<'tcx>(
                 }
             }
         }
+        StatementKind::SetDiscriminant {
+            place,
+            variant_index,
+        } => {
+            let owner_ty =
+                crate::utilis::monomorphize(&method_instance, place.ty(method, tyctx).ty, tyctx);
+            let owner = type_cache
+                .type_from_cache(owner_ty, tyctx, Some(method_instance))
+                .as_dotnet()
+                .expect("Can't set the discriminant of a non-object type!");
+            // Must match the tag type used by `Rvalue::Discriminant`.
+            let discr_type = if let TyKind::Coroutine(..) = owner_ty.kind() {
+                Type::U32
+            } else {
+                Type::U8
+            };
+            let mut ops =
+                crate::place::place_adress(place, tyctx, method, method_instance, type_cache);
+            ops.push(CILOp::LdcI32(variant_index.as_u32() as i32));
+            ops.push(CILOp::STField(FieldDescriptor::boxed(
+                owner,
+                discr_type,
+                "_tag".into(),
+            )));
+            ops
+        }
         _ => {
             rustc_middle::ty::print::with_no_trimmed_paths! {todo!("Unsuported statement kind {kind:?}")}
         }
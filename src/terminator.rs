@@ -1,20 +1,191 @@
 use crate::utilis::garg_to_string;
 use crate::{
-    cil::{CILOp, CallSite},
+    cil::{CILOp, CallSite, FieldDescriptor},
+    codegen_error::CodegenError,
     function_sig::FnSig,
     operand::handle_operand,
     r#type::DotnetTypeRef,
     utilis::monomorphize,
     utilis::CTOR_FN_NAME,
+    utilis::GC_HANDLE_FN_NAME,
+    utilis::MANAGED_ARRAY_FN_NAME,
     utilis::MANAGED_CALL_FN_NAME,
     utilis::MANAGED_CALL_VIRT_FN_NAME,
+    utilis::THROW_PANIC_FN_NAME,
 };
 use rustc_middle::ty::InstanceDef;
 use rustc_middle::{
-    mir::{Body, Operand, Place, SwitchTargets, Terminator, TerminatorKind},
+    mir::{Body, Operand, Place, SourceInfo, SwitchTargets, Terminator, TerminatorKind},
     ty::{GenericArg, Instance, ParamEnv, Ty, TyCtxt, TyKind},
 };
 use rustc_span::def_id::DefId;
+use rustc_target::spec::abi::Abi;
+/// Returns the number of arguments of a fixed-arity interop function, encoded in its name after `prefix`(eg. 2 for `rustc_clr_interop_managed_call2_`),
+/// or [`None`] for the variadic interop function, which takes a tuple of any number of arguments(eg. `rustc_clr_interop_managed_call_`).
+fn interop_argc(function_name: &str, prefix: &str) -> Option<u32> {
+    let argc_start = function_name.find(prefix).unwrap() + prefix.len();
+    let argc_end = argc_start + function_name[argc_start..].find('_').unwrap();
+    let argc = &function_name[argc_start..argc_end];
+    if argc.is_empty() {
+        None
+    } else {
+        Some(argc.parse::<u32>().unwrap())
+    }
+}
+/// The `System.Runtime.InteropServices.GCHandle` value type.
+fn gc_handle() -> DotnetTypeRef {
+    DotnetTypeRef::new(
+        Some("System.Runtime"),
+        "System.Runtime.InteropServices.GCHandle",
+    )
+}
+/// Returns the op converting the `GCHandle` on top of the stack to a `native int`, so that it can be stored by Rust code.
+fn gc_handle_to_int() -> CILOp {
+    let handle = crate::r#type::Type::DotnetType(gc_handle().into());
+    CILOp::Call(CallSite::boxed(
+        Some(gc_handle()),
+        "ToIntPtr".into(),
+        FnSig::new(&[handle], &crate::r#type::Type::ISize),
+        true,
+    ))
+}
+/// Returns the ops calling the instance method `name` of the `GCHandle` stored as the `native int` on top of the stack.
+fn gc_handle_call(name: &str, output: crate::r#type::Type) -> Vec<CILOp> {
+    let handle = crate::r#type::Type::DotnetType(gc_handle().into());
+    vec![
+        CILOp::Call(CallSite::boxed(
+            Some(gc_handle()),
+            "FromIntPtr".into(),
+            FnSig::new(&[crate::r#type::Type::ISize], &handle),
+            true,
+        )),
+        CILOp::NewTMPLocal(handle.clone().into()),
+        CILOp::SetTMPLocal,
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::Call(CallSite::boxed(
+            Some(gc_handle()),
+            name.into(),
+            FnSig::new(&[crate::r#type::Type::Ptr(handle.into())], &output),
+            false,
+        )),
+        CILOp::FreeTMPLocal,
+    ]
+}
+/// Returns the name of the operation performed by an interop function, encoded in its name after `prefix`(eg. `get` for `rustc_clr_interop_managed_array_get_`).
+fn interop_op<'a>(function_name: &'a str, prefix: &str) -> &'a str {
+    let op_start = function_name.find(prefix).unwrap() + prefix.len();
+    let op_end = op_start + function_name[op_start..].find('_').unwrap();
+    &function_name[op_start..op_end]
+}
+/// Returns the ops loading `args`.
+fn load_args<'ctx>(
+    args: &[Operand<'ctx>],
+    tyctx: TyCtxt<'ctx>,
+    method: &'ctx Body<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let mut ops = Vec::new();
+    for arg in args {
+        ops.extend(handle_operand(
+            arg,
+            tyctx,
+            method,
+            method_instance,
+            type_cache,
+        ));
+    }
+    ops
+}
+/// Allocates, dereferences or frees a `GCHandle` keeping a managed object alive(used for interop). The handle is stored by Rust code as a `native int`.
+fn gc_handle_op<'ctx>(
+    tyctx: TyCtxt<'ctx>,
+    function_name: &str,
+    args: &[Operand<'ctx>],
+    destination: &Place<'ctx>,
+    method: &'ctx Body<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let mut ops = load_args(args, tyctx, method, method_instance, type_cache);
+    let object = crate::r#type::Type::DotnetType(DotnetTypeRef::object_type().into());
+    match interop_op(function_name, GC_HANDLE_FN_NAME) {
+        "alloc" => {
+            let handle = crate::r#type::Type::DotnetType(gc_handle().into());
+            ops.push(CILOp::Call(CallSite::boxed(
+                Some(gc_handle()),
+                "Alloc".into(),
+                FnSig::new(&[object], &handle),
+                true,
+            )));
+            ops.push(gc_handle_to_int());
+        }
+        "target" => {
+            ops.extend(gc_handle_call("get_Target", object));
+            // `get_Target` returns a `System.Object`, which has to be cast to the type of the target.
+            let target = monomorphize(&method_instance, destination.ty(method, tyctx).ty, tyctx);
+            let target = type_cache.type_from_cache(target, tyctx, Some(method_instance));
+            ops.push(CILOp::CastClass(target.into()));
+        }
+        "free" => {
+            ops.extend(gc_handle_call("Free", crate::r#type::Type::Void));
+            return ops;
+        }
+        op => panic!("Unknown GC handle operation {op:?}!"),
+    }
+    crate::place::place_set(destination, tyctx, ops, method, method_instance, type_cache)
+}
+/// Creates, indexes, or pins a managed array(used for interop). The operation is encoded in the name of the function, after [`MANAGED_ARRAY_FN_NAME`](eg. `get` for `rustc_clr_interop_managed_array_get_`).
+fn managed_array<'ctx>(
+    tyctx: TyCtxt<'ctx>,
+    subst_ref: &[GenericArg<'ctx>],
+    function_name: &str,
+    args: &[Operand<'ctx>],
+    destination: &Place<'ctx>,
+    method: &'ctx Body<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let mut ops = load_args(args, tyctx, method, method_instance, type_cache);
+    let mut element = || {
+        let element = subst_ref[0]
+            .as_type()
+            .expect("Managed array element type must be specified!");
+        Box::new(type_cache.type_from_cache(element, tyctx, Some(method_instance)))
+    };
+    match interop_op(function_name, MANAGED_ARRAY_FN_NAME) {
+        "new" => ops.push(CILOp::NewArr(element())),
+        "len" => ops.extend([CILOp::LDLen, CILOp::ConvUSize(false)]),
+        "get" => ops.push(CILOp::LDElem(element())),
+        "set" => {
+            ops.push(CILOp::STElem(element()));
+            return ops;
+        }
+        "addr" => ops.extend([CILOp::LDElemA(element()), CILOp::ConvUSize(false)]),
+        "pin" => {
+            let object = crate::r#type::Type::DotnetType(DotnetTypeRef::object_type().into());
+            let handle_type = crate::r#type::Type::DotnetType(
+                DotnetTypeRef::new(
+                    Some("System.Runtime"),
+                    "System.Runtime.InteropServices.GCHandleType",
+                )
+                .into(),
+            );
+            let handle = crate::r#type::Type::DotnetType(gc_handle().into());
+            // `GCHandleType.Pinned`
+            ops.push(CILOp::LdcI32(3));
+            ops.push(CILOp::Call(CallSite::boxed(
+                Some(gc_handle()),
+                "Alloc".into(),
+                FnSig::new(&[object, handle_type], &handle),
+                true,
+            )));
+            ops.push(gc_handle_to_int());
+        }
+        op => panic!("Unknown managed array operation {op:?}!"),
+    }
+    crate::place::place_set(destination, tyctx, ops, method, method_instance, type_cache)
+}
 /// Calls a non-virtual managed function(used for interop)
 fn call_managed<'ctx>(
     tyctx: TyCtxt<'ctx>,
@@ -28,14 +199,10 @@ fn call_managed<'ctx>(
     fn_instance: Instance<'ctx>,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
-    let argc_start =
-        function_name.find(MANAGED_CALL_FN_NAME).unwrap() + (MANAGED_CALL_FN_NAME.len());
-    let argc_end = argc_start + function_name[argc_start..].find('_').unwrap();
-    let argc = &function_name[argc_start..argc_end];
-    let argc = argc.parse::<u32>().unwrap();
+    let argc = interop_argc(function_name, MANAGED_CALL_FN_NAME);
     //FIXME: figure out the proper argc.
     //assert!(subst_ref.len() as u32 == argc + 3 || subst_ref.len() as u32 == argc + 4);
-    assert!(args.len() as u32 == argc);
+    assert!(argc.map_or(true, |argc| args.len() as u32 == argc));
     let asm = garg_to_string(&subst_ref[0], tyctx);
     let asm = Some(asm).filter(|asm| !asm.is_empty());
     let class_name = garg_to_string(&subst_ref[1], tyctx);
@@ -46,7 +213,7 @@ fn call_managed<'ctx>(
     let signature = FnSig::sig_from_instance_(fn_instance, tyctx, type_cache)
         .expect("Can't get the function signature");
 
-    if argc == 0 {
+    if argc == Some(0) {
         let ret = crate::r#type::Type::Void;
         let call = vec![CILOp::Call(CallSite::boxed(
             Some(tpe.clone()),
@@ -68,17 +235,15 @@ fn call_managed<'ctx>(
         }
     } else {
         let is_static = crate::utilis::garag_to_bool(&subst_ref[4], tyctx);
-
-        let mut call = Vec::new();
-        for arg in args {
-            call.extend(crate::operand::handle_operand(
-                arg,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-        }
+        // The variadic function takes its arguments as a tuple, using the "rust-call" ABI.
+        let mut call = crate::closure::call_args(
+            args,
+            argc.is_none(),
+            method,
+            tyctx,
+            method_instance,
+            type_cache,
+        );
         call.push(CILOp::Call(CallSite::boxed(
             Some(tpe.clone()),
             managed_fn_name.into(),
@@ -112,13 +277,9 @@ fn callvirt_managed<'ctx>(
     fn_instance: Instance<'ctx>,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
-    let argc_start =
-        function_name.find(MANAGED_CALL_VIRT_FN_NAME).unwrap() + (MANAGED_CALL_VIRT_FN_NAME.len());
-    let argc_end = argc_start + function_name[argc_start..].find('_').unwrap();
-    let argc = &function_name[argc_start..argc_end];
-    let argc = argc.parse::<u32>().unwrap();
+    let argc = interop_argc(function_name, MANAGED_CALL_VIRT_FN_NAME);
     //assert!(subst_ref.len() as u32 == argc + 3 || subst_ref.len() as u32 == argc + 4);
-    assert!(args.len() as u32 == argc);
+    assert!(argc.map_or(true, |argc| args.len() as u32 == argc));
     let asm = garg_to_string(&subst_ref[0], tyctx);
     let asm = Some(asm).filter(|asm| !asm.is_empty());
     let class_name = garg_to_string(&subst_ref[1], tyctx);
@@ -132,7 +293,7 @@ fn callvirt_managed<'ctx>(
     tpe.set_valuetype(is_valuetype);
     let signature = FnSig::sig_from_instance_(fn_instance, tyctx, type_cache)
         .expect("Can't get the function signature");
-    if argc == 0 {
+    if argc == Some(0) {
         let ret = crate::r#type::Type::Void;
         let call = vec![CILOp::Call(CallSite::boxed(
             Some(tpe.clone()),
@@ -154,17 +315,15 @@ fn callvirt_managed<'ctx>(
         }
     } else {
         let is_static = crate::utilis::garag_to_bool(&subst_ref[4], tyctx);
-
-        let mut call = Vec::new();
-        for arg in args {
-            call.extend(crate::operand::handle_operand(
-                arg,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-        }
+        // The variadic function takes its arguments as a tuple, using the "rust-call" ABI.
+        let mut call = crate::closure::call_args(
+            args,
+            argc.is_none(),
+            method,
+            tyctx,
+            method_instance,
+            type_cache,
+        );
         call.push(CILOp::CallVirt(CallSite::boxed(
             Some(tpe.clone()),
             managed_fn_name.into(),
@@ -197,14 +356,11 @@ fn call_ctor<'ctx>(
     method_instance: Instance<'ctx>,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
-    let argc_start = function_name.find(CTOR_FN_NAME).unwrap() + (CTOR_FN_NAME.len());
-    let argc_end = argc_start + function_name[argc_start..].find('_').unwrap();
-    let argc = &function_name[argc_start..argc_end];
-    let argc = argc.parse::<u32>().unwrap();
-    // Check that there are enough function path and argument specifers
-    assert!(subst_ref.len() as u32 == argc + 3);
+    let argc = interop_argc(function_name, CTOR_FN_NAME);
+    // Check that there are enough function path and argument specifers(the variadic constructor takes a single tuple of arguments)
+    assert!(subst_ref.len() as u32 == argc.unwrap_or(1) + 3);
     // Check that a proper number of arguments is used
-    assert!(args.len() as u32 == argc);
+    assert!(args.len() as u32 == argc.unwrap_or(1));
     // Get the name of the assembly the constructed object resides in
     let asm = garg_to_string(&subst_ref[0], tyctx);
     // If empty, make it none(for consitent encoing of No-assembly)
@@ -216,7 +372,7 @@ fn call_ctor<'ctx>(
     let mut tpe = DotnetTypeRef::new(asm.as_deref(), &class_name);
     tpe.set_valuetype(is_valuetype);
     // If no arguments, inputs don't have to be handled, so a simpler call handling is used.
-    if argc == 0 {
+    if argc == Some(0) {
         crate::place::place_set(
             destination,
             tyctx,
@@ -231,30 +387,36 @@ fn call_ctor<'ctx>(
             type_cache,
         )
     } else {
-        let mut inputs: Vec<_> = subst_ref[3..]
+        let arg_tys: Vec<_> = subst_ref[3..]
             .iter()
             .map(|ty| {
-                let ty = crate::utilis::monomorphize(&method_instance, *ty, tyctx);
-                type_cache.type_from_cache(
-                    ty.as_type()
-                        .expect("Expceted generic type but got something that was not a type!"),
-                    tyctx,
-                    Some(method_instance),
-                )
+                crate::utilis::monomorphize(&method_instance, *ty, tyctx)
+                    .as_type()
+                    .expect("Expceted generic type but got something that was not a type!")
             })
             .collect();
+        // The variadic constructor takes its arguments as a tuple, using the "rust-call" ABI.
+        let arg_tys = match (argc, arg_tys.as_slice()) {
+            (None, [tuple]) => match tuple.kind() {
+                TyKind::Tuple(elements) => elements.to_vec(),
+                _ => panic!("The arguments of a variadic managed constructor must be a tuple, but they are {tuple:?}!"),
+            },
+            _ => arg_tys,
+        };
+        let mut inputs: Vec<_> = arg_tys
+            .into_iter()
+            .map(|ty| type_cache.type_from_cache(ty, tyctx, Some(method_instance)))
+            .collect();
         inputs.insert(0, tpe.clone().into());
         let sig = FnSig::new(&inputs, &crate::r#type::Type::Void);
-        let mut call = Vec::new();
-        for arg in args {
-            call.extend(crate::operand::handle_operand(
-                arg,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-        }
+        let mut call = crate::closure::call_args(
+            args,
+            argc.is_none(),
+            method,
+            tyctx,
+            method_instance,
+            type_cache,
+        );
         call.push(CILOp::NewObj(CallSite::boxed(
             Some(tpe.clone()),
             ".ctor".into(),
@@ -271,6 +433,244 @@ fn call_ctor<'ctx>(
         )
     }
 }
+/// Throws a `RustPanicException` with the message and location passed by a panic handler.
+fn throw_panic<'ctx>(
+    args: &[Operand<'ctx>],
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let mut ops = Vec::new();
+    for arg in args {
+        ops.extend(handle_operand(
+            arg,
+            tyctx,
+            body,
+            method_instance,
+            type_cache,
+        ));
+    }
+    ops.push(CILOp::NewObj(
+        crate::utilis::panic_exception_utf8_ctor().into(),
+    ));
+    ops.push(CILOp::Throw);
+    ops
+}
+/// Returns the ops loading the `&'static core::panic::Location` passed to `#[track_caller]` functions as their last argument.
+fn caller_location<'ctx>(
+    source_info: &SourceInfo,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    // Walk up the inlined scopes, since the location of a call inlined from a `#[track_caller]` function is the location of the inlined call.
+    let mut span = source_info.span;
+    let mut scope = source_info.scope;
+    loop {
+        let scope_data = &body.source_scopes[scope];
+        if let Some((callee, callsite_span)) = scope_data.inlined {
+            if !callee.def.requires_caller_location(tyctx) {
+                return crate::constant::load_caller_location(
+                    span,
+                    tyctx,
+                    body,
+                    method_instance,
+                    type_cache,
+                );
+            }
+            span = callsite_span;
+        }
+        match scope_data.inlined_parent_scope {
+            Some(parent) => scope = parent,
+            None => break,
+        }
+    }
+    // A `#[track_caller]` function passes on the location of its own caller.
+    if method_instance.def.requires_caller_location(tyctx) {
+        vec![CILOp::LDArg(body.arg_count as u32)]
+    } else {
+        crate::constant::load_caller_location(span, tyctx, body, method_instance, type_cache)
+    }
+}
+/// Calls the function pointer `func` of type `fn_type` with `args`, placing the return value in destination.
+fn call_fn_ptr<'ctx>(
+    func: &Operand<'ctx>,
+    fn_type: Ty<'ctx>,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    args: &[Operand<'ctx>],
+    destination: &Place<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Result<Vec<CILOp>, CodegenError> {
+    let TyKind::FnPtr(fn_sig) = fn_type.kind() else {
+        panic!("Called func of type {fn_type:?} is neither a function definition nor a function pointer!");
+    };
+    let signature = FnSig::sig_from_fn_ptr(*fn_sig, tyctx, type_cache)?;
+    let mut call = Vec::new();
+    for arg in args {
+        call.extend(handle_operand(
+            arg,
+            tyctx,
+            body,
+            method_instance,
+            type_cache,
+        ));
+    }
+    call.extend(handle_operand(
+        func,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    ));
+    let is_void = matches!(signature.output(), crate::r#type::Type::Void);
+    call.push(CILOp::CallI(signature.into()));
+    if is_void {
+        Ok(call)
+    } else {
+        Ok(crate::place::place_set(
+            destination,
+            tyctx,
+            call,
+            body,
+            method_instance,
+            type_cache,
+        ))
+    }
+}
+/// Calls the `idx`-th method in the vtable of the trait object the first of `args` points to, placing the return value in destination.
+fn call_virtual<'ctx>(
+    instance: Instance<'ctx>,
+    idx: usize,
+    signature: FnSig,
+    rust_call: bool,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    args: &[Operand<'ctx>],
+    destination: &Place<'ctx>,
+    source_info: &SourceInfo,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Result<Vec<CILOp>, CodegenError> {
+    let self_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+    let dyn_ty = match self_ty.kind() {
+        TyKind::Ref(_, inner, _) => *inner,
+        TyKind::RawPtr(type_and_mut) => type_and_mut.ty,
+        _ => {
+            return Err(CodegenError::Error(
+                format!("Can't call virtual methods with receivers of type {self_ty:?} yet!")
+                    .into(),
+            ))
+        }
+    };
+    let fat_ptr = type_cache.type_from_cache(self_ty, tyctx, Some(method_instance));
+    let data_address = FieldDescriptor::new(
+        fat_ptr
+            .as_dotnet()
+            .expect("Pointer to a trait object is not a fat pointer!"),
+        crate::r#type::Type::Ptr(crate::r#type::Type::Void.into()),
+        "data_address".into(),
+    );
+    // The receiver is passed as a thin pointer to the data.
+    let mut call = handle_operand(&args[0], tyctx, body, method_instance, type_cache);
+    call.extend([
+        CILOp::NewTMPLocal(fat_ptr.into()),
+        CILOp::SetTMPLocal,
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::LDField(data_address.into()),
+    ]);
+    call.extend(crate::closure::call_args(
+        &args[1..],
+        rust_call,
+        body,
+        tyctx,
+        method_instance,
+        type_cache,
+    ));
+    if instance.def.requires_caller_location(tyctx) {
+        call.extend(caller_location(
+            source_info,
+            body,
+            tyctx,
+            method_instance,
+            type_cache,
+        ));
+    }
+    call.push(CILOp::LoadAddresOfTMPLocal);
+    call.extend(crate::vtable::load_vtable_ptr(
+        dyn_ty,
+        tyctx,
+        method_instance,
+        type_cache,
+    ));
+    call.extend(crate::vtable::load_vtable_entry(idx, tyctx));
+    call.push(CILOp::FreeTMPLocal);
+    let mut inputs = signature.inputs().to_vec();
+    inputs[0] = crate::r#type::Type::Ptr(crate::r#type::Type::Void.into());
+    let is_void = matches!(signature.output(), crate::r#type::Type::Void);
+    call.push(CILOp::CallI(FnSig::new(&inputs, signature.output()).into()));
+    if is_void {
+        Ok(call)
+    } else {
+        Ok(crate::place::place_set(
+            destination,
+            tyctx,
+            call,
+            body,
+            method_instance,
+            type_cache,
+        ))
+    }
+}
+/// Drops the trait object `dyn_ty`, using the drop glue at index `idx` of its vtable. The fat pointer to the object must be on top of the stack.
+/// Jumps to `target` if the vtable has no drop glue.
+fn drop_virtual<'ctx>(
+    dyn_ty: Ty<'ctx>,
+    idx: usize,
+    target: u32,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let fat_ptr =
+        type_cache.type_from_cache(Ty::new_mut_ptr(tyctx, dyn_ty), tyctx, Some(method_instance));
+    let data_address = FieldDescriptor::new(
+        fat_ptr
+            .as_dotnet()
+            .expect("Pointer to a trait object is not a fat pointer!"),
+        crate::r#type::Type::Ptr(crate::r#type::Type::Void.into()),
+        "data_address".into(),
+    );
+    let mut drop_fn = vec![CILOp::LoadAddresOfTMPLocal];
+    drop_fn.extend(crate::vtable::load_vtable_ptr(
+        dyn_ty,
+        tyctx,
+        method_instance,
+        type_cache,
+    ));
+    drop_fn.extend(crate::vtable::load_vtable_entry(idx, tyctx));
+    let mut ops = vec![CILOp::NewTMPLocal(fat_ptr.into()), CILOp::SetTMPLocal];
+    // Types without drop glue have a null drop glue pointer.
+    ops.extend(drop_fn.iter().cloned());
+    ops.push(CILOp::BZero(target));
+    ops.extend([
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::LDField(data_address.into()),
+    ]);
+    ops.extend(drop_fn);
+    ops.push(CILOp::FreeTMPLocal);
+    ops.push(CILOp::CallI(
+        FnSig::new(
+            &[crate::r#type::Type::Ptr(crate::r#type::Type::Void.into())],
+            &crate::r#type::Type::Void,
+        )
+        .into(),
+    ));
+    ops
+}
 /// Calls `fn_type` with `args`, placing the return value in destination.
 fn call<'ctx>(
     fn_type: &Ty<'ctx>,
@@ -278,9 +678,10 @@ fn call<'ctx>(
     tyctx: TyCtxt<'ctx>,
     args: &[Operand<'ctx>],
     destination: &Place<'ctx>,
+    source_info: &SourceInfo,
     method_instance: Instance<'ctx>,
     type_cache: &mut crate::r#type::TyCache,
-) -> Vec<CILOp> {
+) -> Result<Vec<CILOp>, CodegenError> {
     let fn_type = crate::utilis::monomorphize(&method_instance, *fn_type, tyctx);
     let (instance, def_id, subst_ref) = if let TyKind::FnDef(def_id, subst_ref) = fn_type.kind() {
         let env = ParamEnv::reveal_all();
@@ -294,13 +695,67 @@ fn call<'ctx>(
     } else {
         todo!("Trying to call a type which is not a function definition!");
     };
-    let signature = FnSig::sig_from_instance_(instance, tyctx, type_cache)
-        .expect("Could not resolve function sig");
+    let signature = FnSig::sig_from_instance_(instance, tyctx, type_cache)?;
+    // Closures and their shims use the "rust-call" ABI, which untuples the last argument.
+    let rust_call = fn_type.fn_sig(tyctx).abi() == Abi::RustCall;
+    if let InstanceDef::Virtual(_, idx) = instance.def {
+        return call_virtual(
+            instance,
+            idx,
+            signature,
+            rust_call,
+            body,
+            tyctx,
+            args,
+            destination,
+            source_info,
+            method_instance,
+            type_cache,
+        );
+    }
+    // The `try` intrinsic (used by `catch_unwind`) is implemented by a helper method, inserted by `insert_libc`.
+    if let InstanceDef::Intrinsic(_) = instance.def {
+        if matches!(tyctx.item_name(*def_id).as_str(), "try" | "catch_unwind") {
+            let mut call =
+                crate::closure::call_args(args, false, body, tyctx, method_instance, type_cache);
+            call.push(CILOp::Call(
+                CallSite::new(None, "catch_unwind".into(), signature, true).into(),
+            ));
+            return Ok(crate::place::place_set(
+                destination,
+                tyctx,
+                call,
+                body,
+                method_instance,
+                type_cache,
+            ));
+        }
+    }
     let function_name = crate::utilis::function_name(tyctx.symbol_name(instance));
+    // std's panic runtime raises panics with `_Unwind_RaiseException`, which is replaced by a helper throwing them as managed exceptions.
+    if &*function_name == "_Unwind_RaiseException" {
+        let mut call =
+            crate::closure::call_args(args, false, body, tyctx, method_instance, type_cache);
+        call.push(CILOp::Call(
+            CallSite::new(
+                None,
+                "rust_raise_exception".into(),
+                FnSig::new(
+                    &[crate::r#type::Type::Ptr(crate::r#type::Type::U8.into())],
+                    &crate::r#type::Type::Void,
+                ),
+                true,
+            )
+            .into(),
+        ));
+        return Ok(call);
+    }
     // Checks if function is "magic"
-    if function_name.contains(CTOR_FN_NAME) {
+    if function_name.contains(THROW_PANIC_FN_NAME) {
+        return Ok(throw_panic(args, body, tyctx, method_instance, type_cache));
+    } else if function_name.contains(CTOR_FN_NAME) {
         // Constructor
-        return call_ctor(
+        return Ok(call_ctor(
             tyctx,
             *def_id,
             subst_ref,
@@ -310,10 +765,10 @@ fn call<'ctx>(
             body,
             method_instance,
             type_cache,
-        );
+        ));
     } else if function_name.contains(MANAGED_CALL_VIRT_FN_NAME) {
         // Virtual (for interop)
-        return callvirt_managed(
+        return Ok(callvirt_managed(
             tyctx,
             *def_id,
             subst_ref,
@@ -324,10 +779,33 @@ fn call<'ctx>(
             method_instance,
             instance,
             type_cache,
-        );
+        ));
+    } else if function_name.contains(GC_HANDLE_FN_NAME) {
+        // GC handles (for interop)
+        return Ok(gc_handle_op(
+            tyctx,
+            &function_name,
+            args,
+            destination,
+            body,
+            method_instance,
+            type_cache,
+        ));
+    } else if function_name.contains(MANAGED_ARRAY_FN_NAME) {
+        // Managed arrays (for interop)
+        return Ok(managed_array(
+            tyctx,
+            subst_ref,
+            &function_name,
+            args,
+            destination,
+            body,
+            method_instance,
+            type_cache,
+        ));
     } else if function_name.contains(MANAGED_CALL_FN_NAME) {
         // Not-Virtual (for interop)
-        return call_managed(
+        return Ok(call_managed(
             tyctx,
             *def_id,
             subst_ref,
@@ -338,45 +816,40 @@ fn call<'ctx>(
             method_instance,
             instance,
             type_cache,
-        );
+        ));
     }
-    let mut call = Vec::new();
-    for arg in args {
-        call.extend(crate::operand::handle_operand(
-            arg,
-            tyctx,
+    let mut call =
+        crate::closure::call_args(args, rust_call, body, tyctx, method_instance, type_cache);
+
+    // `#[track_caller]` functions take the location of their caller as a hidden last argument.
+    if instance.def.requires_caller_location(tyctx) {
+        call.extend(caller_location(
+            source_info,
             body,
+            tyctx,
             method_instance,
             type_cache,
         ));
     }
-
-    if args.len() < signature.inputs().len() {
-        let tpe: crate::r#type::Type = signature.inputs()[signature.inputs().len() - 1].clone();
-        // let arg_len = args.len();
-        //assert_eq!(args.len() + 1,signature.inputs().len(),"ERROR: mismatched argument count. \nsignature inputs:{:?} \narguments:{args:?}\narg_len:{arg_len}\n",signature.inputs());
-        // assert_eq!(signature.inputs()[signature.inputs().len() - 1],tpe);
-        //FIXME:This assembles a panic location from uninitialized memory. This WILL lead to bugs once unwinding is added. The fields `file`,`col`, and `line` should be set there.
-        call.extend([
-            CILOp::NewTMPLocal(Box::new(tpe)),
-            CILOp::LoadTMPLocal,
-            CILOp::FreeTMPLocal,
-        ]);
-        //panic!("Call with PanicLocation!");
-    }
     //assert_eq!(args.len(),signature.inputs().len(),"CALL SIGNATURE ARG COUNT MISMATCH!");
     let is_void = matches!(signature.output(), crate::r#type::Type::Void);
-    call.push(CILOp::Call(CallSite::boxed(
-        None,
-        function_name,
-        signature,
-        true,
-    )));
+    let call_site = match crate::generics::preserved_instance(instance, tyctx, type_cache) {
+        Some(generic) => crate::generics::generic_call_site(instance, generic, tyctx, type_cache),
+        None => CallSite::new(None, function_name, signature, true),
+    };
+    call.push(CILOp::Call(call_site.into()));
     // Hande
     if is_void {
-        call
+        Ok(call)
     } else {
-        crate::place::place_set(destination, tyctx, call, body, method_instance, type_cache)
+        Ok(crate::place::place_set(
+            destination,
+            tyctx,
+            call,
+            body,
+            method_instance,
+            type_cache,
+        ))
     }
 }
 pub fn handle_terminator<'ctx>(
@@ -386,8 +859,8 @@ pub fn handle_terminator<'ctx>(
     method: &rustc_middle::mir::Body<'ctx>,
     method_instance: Instance<'ctx>,
     type_cache: &mut crate::r#type::TyCache,
-) -> Vec<CILOp> {
-    match &terminator.kind {
+) -> Result<Vec<CILOp>, CodegenError> {
+    Ok(match &terminator.kind {
         TerminatorKind::Call {
             func,
             args,
@@ -397,29 +870,30 @@ pub fn handle_terminator<'ctx>(
             call_source: _,
             fn_span: _,
         } => {
-            let mut ops = Vec::new();
-            match func {
-                Operand::Constant(fn_const) => {
-                    let fn_ty = fn_const.ty();
-                    assert!(
-                        fn_ty.is_fn(),
-                        "fn_ty{fn_ty:?} in call is not a function type!"
-                    );
-                    let fn_ty = monomorphize(&method_instance, fn_ty, tyctx);
-                    //let fn_instance = Instance::resolve(tyctx,ParamEnv::reveal_all,fn_ty.did,List::empty());
-                    let call_ops = call(
-                        &fn_ty,
-                        body,
-                        tyctx,
-                        args,
-                        destination,
-                        method_instance,
-                        type_cache,
-                    );
-                    ops.extend(call_ops);
-                }
-                _ => panic!("called func must be const!"),
-            }
+            let fn_ty = monomorphize(&method_instance, func.ty(body, tyctx), tyctx);
+            let mut ops = if let TyKind::FnDef(..) = fn_ty.kind() {
+                call(
+                    &fn_ty,
+                    body,
+                    tyctx,
+                    args,
+                    destination,
+                    &terminator.source_info,
+                    method_instance,
+                    type_cache,
+                )?
+            } else {
+                call_fn_ptr(
+                    func,
+                    fn_ty,
+                    body,
+                    tyctx,
+                    args,
+                    destination,
+                    method_instance,
+                    type_cache,
+                )?
+            };
             if let Some(target) = target {
                 ops.push(CILOp::GoTo(target.as_u32()));
             }
@@ -442,35 +916,31 @@ pub fn handle_terminator<'ctx>(
             handle_switch(ty, discr, targets)
         }
         TerminatorKind::Assert {
-            cond: _,
-            expected: _,
-            msg: _,
+            cond,
+            expected,
+            msg,
             target,
             unwind: _,
         } => {
-            //let mut ops = handle_operand(cond, tyctx, method, method_instance, type_cache);
-            //ops.push(CILOp::LdcI32(i32::from(*expected)));
-            //ops.push(CILOp::BEq(target.as_u32()));
-            //ops.extend(throw_assert_msg(
-            //msg,
-            //tyctx,
-            //method,
-            //method_instance,
-            //type_cache,
-            //));
-            //ops
-            vec![CILOp::GoTo(target.as_u32())]
+            let mut ops = handle_operand(cond, tyctx, method, method_instance, type_cache);
+            ops.push(CILOp::LdcI32(i32::from(*expected)));
+            ops.push(CILOp::BEq(target.as_u32()));
+            ops.extend(throw_assert_msg(
+                msg,
+                terminator.source_info.span,
+                tyctx,
+                method,
+                method_instance,
+                type_cache,
+            ));
+            ops
         }
         TerminatorKind::Goto { target } => vec![CILOp::GoTo((*target).into())],
-        TerminatorKind::UnwindResume => {
-            eprintln!("WARNING: stack unwiniding is not supported yet in rustc_codegen_clr!");
-            vec![CILOp::Comment(
-                "WARNING: stack unwiniding is not supported yet in rustc_codegen_clr!".into(),
-            )]
-        }
+        // Cleanup blocks are only ever emitted inside catch handlers, so the exception can be rethrown.
+        TerminatorKind::UnwindResume => vec![CILOp::Rethrow],
         TerminatorKind::Drop {
             place,
-            target: _,
+            target,
             unwind: _,
             replace: _,
         } => {
@@ -479,7 +949,20 @@ pub fn handle_terminator<'ctx>(
             let drop_instance = Instance::resolve_drop_in_place(tyctx, ty);
             if let InstanceDef::DropGlue(_, None) = drop_instance.def {
                 //Empty drop, nothing needs to happen.
-                vec![]
+                vec![CILOp::GoTo(target.as_u32())]
+            } else if let InstanceDef::Virtual(_, idx) = drop_instance.def {
+                let mut call =
+                    crate::place::place_adress(place, tyctx, method, method_instance, type_cache);
+                call.extend(drop_virtual(
+                    ty,
+                    idx,
+                    target.as_u32(),
+                    tyctx,
+                    method_instance,
+                    type_cache,
+                ));
+                call.push(CILOp::GoTo(target.as_u32()));
+                call
             } else {
                 let sig = FnSig::sig_from_instance_(drop_instance, tyctx, type_cache).unwrap();
                 let function_name = crate::utilis::function_name(tyctx.symbol_name(drop_instance));
@@ -488,9 +971,15 @@ pub fn handle_terminator<'ctx>(
 
                 call.push(CILOp::Call(CallSite::boxed(None, function_name, sig, true)));
                 eprintln!("drop call:{call:?}");
+                call.push(CILOp::GoTo(target.as_u32()));
                 call
             }
         }
+        // The coroutine state transform turns these into returns and jumps, before MIR reaches codegen.
+        TerminatorKind::Yield { .. } | TerminatorKind::CoroutineDrop => panic!(
+            "{kind:?} should have been removed by the coroutine state transform!",
+            kind = terminator.kind
+        ),
         TerminatorKind::Unreachable => {
             /*
             let string_type = crate::r#type::Type::DotnetType(Box::new(DotnetTypeRef::new(
@@ -507,268 +996,141 @@ pub fn handle_terminator<'ctx>(
             vec![]
         }
         _ => todo!("Unhandled terminator kind {kind:?}", kind = terminator.kind),
-    }
+    })
 }
+/// Returns the ops converting `operand` into a managed string. Integers are printed in base `base`(2, 8, 10 or 16).
+fn operand_to_string<'ctx>(
+    operand: &Operand<'ctx>,
+    base: i32,
+    tyctx: TyCtxt<'ctx>,
+    method: &rustc_middle::mir::Body<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let ty = monomorphize(&method_instance, operand.ty(method, tyctx), tyctx);
+    let string_type: crate::r#type::Type = crate::utilis::string_class().into();
+    let mut convert = DotnetTypeRef::new(Some("System.Runtime"), "System.Convert");
+    convert.set_valuetype(false);
+    let (conv, int_type) = match ty.kind() {
+        TyKind::Int(_) => (CILOp::ConvI64(false), crate::r#type::Type::I64),
+        TyKind::Uint(_) if base == 10 => (CILOp::ConvU64(false), crate::r#type::Type::U64),
+        // `Convert.ToString` only supports other bases for signed integers, which print the same bits.
+        TyKind::Uint(_) => (CILOp::ConvU64(false), crate::r#type::Type::I64),
+        _ => return vec![CILOp::LdStr(format!("{ty:?}").into())],
+    };
+    let mut ops = handle_operand(operand, tyctx, method, method_instance, type_cache);
+    ops.push(conv);
+    let sig = if base == 10 {
+        FnSig::new(&[int_type], &string_type)
+    } else {
+        ops.push(CILOp::LdcI32(base));
+        FnSig::new(&[int_type, crate::r#type::Type::I32], &string_type)
+    };
+    ops.push(CILOp::Call(CallSite::boxed(
+        Some(convert),
+        "ToString".into(),
+        sig,
+        true,
+    )));
+    ops
+}
+/// Throws a `RustPanicException` with the message native Rust would panic with when the assertion `msg` fails at `span`.
 fn throw_assert_msg<'ctx>(
     msg: &rustc_middle::mir::AssertMessage<'ctx>,
+    span: rustc_span::Span,
     tyctx: TyCtxt<'ctx>,
     method: &rustc_middle::mir::Body<'ctx>,
     method_instance: Instance<'ctx>,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
     use rustc_middle::mir::AssertKind;
-    // Assertion messages cause miscomplations.
-    if true {
-        return vec![CILOp::LdNull, CILOp::Throw];
-    };
-    match msg {
+    let string_type: crate::r#type::Type = crate::utilis::string_class().into();
+    // Ops pushing the panic message.
+    let mut ops = match msg {
         AssertKind::BoundsCheck { len, index } => {
-            let mut ops = Vec::with_capacity(8);
-            ops.push(CILOp::LdStr("index out of bounds: the len is ".into()));
-            ops.extend(handle_operand(
+            let mut ops = vec![CILOp::LdStr("index out of bounds: the len is ".into())];
+            ops.extend(operand_to_string(
                 len,
+                10,
                 tyctx,
                 method,
                 method_instance,
                 type_cache,
             ));
-            let usize_class = crate::utilis::usize_class();
-            let string_class = crate::utilis::string_class();
-            let string_type = crate::r#type::Type::DotnetType(Box::new(string_class.clone()));
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr(" but the index is".into()));
-            ops.extend(handle_operand(
+            ops.push(CILOp::LdStr(" but the index is ".into()));
+            ops.extend(operand_to_string(
                 index,
+                10,
                 tyctx,
                 method,
                 method_instance,
                 type_cache,
             ));
-            ops.push(CILOp::Call(usize_to_string.clone()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
-            );
-            let out_of_range_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.IndexOutOfRangeException");
-            ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
-                "Concat".into(),
-                sig,
-                true,
-            )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(out_of_range_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
-        }
-        AssertKind::DivisionByZero(_operand) => {
-            let mut ops = Vec::with_capacity(8);
-
-            let sig = FnSig::new(&[], &crate::r#type::Type::Void);
-            let div_by_zero_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.DivideByZeroException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(div_by_zero_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
-        }
-        AssertKind::RemainderByZero(_operand) => {
-            let mut ops = Vec::with_capacity(8);
-
-            let sig = FnSig::new(&[], &crate::r#type::Type::Void);
-            let div_by_zero_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.DivideByZeroException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(div_by_zero_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
-        }
-        AssertKind::Overflow(binop, a, b) => {
-            let mut ops = Vec::with_capacity(8);
-            let string_class = crate::utilis::string_class();
-            ops.push(CILOp::LdStr(
-                format!("attempt to {binop:?} with overflow lhs:").into(),
-            ));
-            ops.extend(handle_operand(
-                a,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            let usize_class = crate::utilis::usize_class();
-            let string_type = crate::r#type::Type::DotnetType(Box::new(string_class.clone()));
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr("rhs:".into()));
-            ops.extend(handle_operand(
-                b,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            ops.push(CILOp::Call(usize_to_string.clone()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
-            );
             ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
+                Some(crate::utilis::string_class()),
                 "Concat".into(),
-                sig,
+                FnSig::new(
+                    &[
+                        string_type.clone(),
+                        string_type.clone(),
+                        string_type.clone(),
+                        string_type.clone(),
+                    ],
+                    &string_type,
+                ),
                 true,
             )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            let ovefow_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.ArithmeticException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(ovefow_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
             ops
         }
-        AssertKind::MisalignedPointerDereference {
-            required: _,
-            found: _,
-        } => {
-            /*
-            let mut ops = Vec::with_capacity(8);
-            let string_class = crate::utilis::string_class();
-            ops.push(CILOp::LdStr(
-                "Missaligned pointer dereference. required: "
-                    .to_string()
-                    .into(),
-            ));
-            ops.extend(handle_operand(
+        AssertKind::MisalignedPointerDereference { required, found } => {
+            let mut ops = vec![CILOp::LdStr(
+                "misaligned pointer dereference: address must be a multiple of 0x".into(),
+            )];
+            ops.extend(operand_to_string(
                 required,
+                16,
                 tyctx,
                 method,
                 method_instance,
                 type_cache,
             ));
-            let usize_class = crate::utilis::usize_class();
-            let string_type = crate::r#type::Type::DotnetType(string_class.clone().into());
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr(" found: ".into()));
-            ops.extend(handle_operand(
+            ops.push(CILOp::LdStr(" but is 0x".into()));
+            ops.extend(operand_to_string(
                 found,
+                16,
                 tyctx,
                 method,
                 method_instance,
                 type_cache,
             ));
-            ops.push(CILOp::Call(usize_to_string.clone()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
-            );
-            ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
-                "Concat".into(),
-                sig,
-                true,
-            )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            let ovefow_exception = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(ovefow_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops*/
-            vec![]
-        }
-        AssertKind::OverflowNeg(value) => {
-            let mut ops = Vec::with_capacity(8);
-            let string_class = crate::utilis::string_class();
-            ops.push(CILOp::LdStr(
-                "attempt to neg with overflow value:".to_string().into(),
-            ));
-            ops.extend(handle_operand(
-                value,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            let usize_class = crate::utilis::usize_class();
-            let string_type = crate::r#type::Type::DotnetType(Box::new(string_class.clone()));
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr("rhs:".into()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
-            );
             ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
+                Some(crate::utilis::string_class()),
                 "Concat".into(),
-                sig,
+                FnSig::new(
+                    &[
+                        string_type.clone(),
+                        string_type.clone(),
+                        string_type.clone(),
+                        string_type.clone(),
+                    ],
+                    &string_type,
+                ),
                 true,
             )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            let ovefow_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.ArithmeticException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(ovefow_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
             ops
         }
-        _ => todo!("unsuported assertion message:{msg:?}"),
-    }
+        // Overflow, division by zero and resumed coroutines have constant messages.
+        _ => vec![CILOp::LdStr(msg.description().into())],
+    };
+    let (file, line, column) = crate::constant::span_location(span, tyctx);
+    ops.extend([
+        CILOp::LdStr(file.as_str().into()),
+        CILOp::LdcI32(line as i32),
+        CILOp::LdcI32(column as i32),
+        CILOp::NewObj(crate::utilis::panic_exception_ctor().into()),
+        CILOp::Throw,
+    ]);
+    ops
 }
 fn handle_switch(ty: Ty, discr: Vec<CILOp>, switch: &SwitchTargets) -> Vec<CILOp> {
     let mut ops = Vec::new();
@@ -1,9 +1,11 @@
 use super::{DotnetTypeRef, Type, TypeDef};
 use crate::{
-    access_modifier::AccessModifer, r#type::escape_field_name, utilis::enum_tag_size, IString,
+    access_modifier::AccessModifer, config::CodegenConfig, r#type::escape_field_name,
+    utilis::enum_tag_size, IString,
 };
 use rustc_middle::ty::{
-    AdtDef, AdtKind, GenericArg, Instance, List, ParamEnv, Ty, TyCtxt, TyKind, UintTy,
+    AdtDef, AdtKind, EarlyBinder, GenericArg, GenericArgs, GenericArgsRef, Instance, List,
+    ParamEnv, Ty, TyCtxt, TyKind, UintTy,
 };
 use rustc_span::def_id::DefId;
 use std::collections::HashMap;
@@ -12,18 +14,35 @@ pub struct TyCache {
     type_def_cache: HashMap<IString, TypeDef>,
     cycle_prevention: Vec<IString>,
     ptr_components: Option<DefId>,
+    /// Functions known to be emittable(or not) as CIL generic methods. See [`crate::generics::preserved_instance`].
+    preservable: HashMap<DefId, bool>,
+    config: CodegenConfig,
 }
 impl TyCache {
-    pub fn empty() -> Self {
+    pub fn new(config: CodegenConfig) -> Self {
         Self {
             type_def_cache: HashMap::new(),
             cycle_prevention: vec![],
             ptr_components: None,
+            preservable: HashMap::new(),
+            config,
         }
     }
+    /// Returns the config of the compilation this cache belongs to.
+    pub fn config(&self) -> &CodegenConfig {
+        &self.config
+    }
     pub fn defs(&self) -> impl Iterator<Item = &TypeDef> {
         self.type_def_cache.values()
     }
+    /// Returns the cached result of checking if the function `def_id` can be emitted as a CIL generic method.
+    pub fn preservable(&self, def_id: DefId) -> Option<bool> {
+        self.preservable.get(&def_id).copied()
+    }
+    /// Caches the result of checking if the function `def_id` can be emitted as a CIL generic method.
+    pub fn set_preservable(&mut self, def_id: DefId, preservable: bool) {
+        self.preservable.insert(def_id, preservable);
+    }
     pub fn ptr_components(&mut self, tyctx: TyCtxt) -> DefId {
         if self.ptr_components.is_none() {
             self.ptr_components = Some(try_find_ptr_components(tyctx));
@@ -82,6 +101,50 @@ impl TyCache {
         self.cycle_prevention.pop();
         DotnetTypeRef::new(None, name)
     }
+    /// Returns a reference to the CIL generic class `def` is emitted as, instantiated with `subst`. See [`crate::generics::is_adt_shared`].
+    fn shared_adt<'tyctx>(
+        &mut self,
+        def: AdtDef<'tyctx>,
+        subst: &'tyctx List<rustc_middle::ty::GenericArg<'tyctx>>,
+        tyctx: TyCtxt<'tyctx>,
+        method: Option<Instance<'tyctx>>,
+    ) -> DotnetTypeRef {
+        let identity = GenericArgs::identity_for_item(tyctx, def.did());
+        let name = crate::utilis::adt_name(&def, tyctx, identity);
+        if self.type_def_cache.get(&name).is_none()
+            && !self
+                .cycle_prevention
+                .iter()
+                .any(|c_name| c_name.as_ref() == name.as_ref())
+        {
+            self.cycle_prevention.push(name.clone());
+            let mut fields = Vec::new();
+            for field in def.all_fields() {
+                let field_name = escape_field_name(&field.name.to_string());
+                let field_ty = self.type_from_cache(field.ty(tyctx, identity), tyctx, None);
+                fields.push((field_name, crate::generics::class_generics(field_ty)));
+            }
+            let type_def = TypeDef::new(
+                AccessModifer::Public,
+                name.clone(),
+                vec![],
+                fields,
+                vec![],
+                None,
+                identity.len() as u32,
+                None,
+            );
+            self.type_def_cache.insert(name.clone(), type_def);
+            self.cycle_prevention.pop();
+        }
+        let generics: Vec<_> = subst
+            .types()
+            .map(|ty| self.type_from_cache(ty, tyctx, method))
+            .collect();
+        let mut dotnet = DotnetTypeRef::new(None, &name);
+        dotnet.set_generics(generics);
+        dotnet
+    }
     pub fn recover_from_panic(&mut self) {
         self.cycle_prevention.clear()
     }
@@ -200,13 +263,57 @@ impl TyCache {
             None,
         )
     }
+    /// Returns the definition of the type holding the captured variables `upvars` of a closure.
+    fn closure_<'tyctx>(
+        &mut self,
+        name: &str,
+        upvars: &'tyctx List<Ty<'tyctx>>,
+        tyctx: TyCtxt<'tyctx>,
+        method: Option<Instance<'tyctx>>,
+    ) -> TypeDef {
+        let mut fields = Vec::new();
+        for (idx, mut field_ty) in upvars.iter().enumerate() {
+            method.inspect(|method_instance| {
+                field_ty = crate::utilis::monomorphize(method_instance, field_ty, tyctx)
+            });
+            let field_ty = self.type_from_cache(field_ty, tyctx, method);
+            fields.push((crate::utilis::upvar_name(idx as u32), field_ty));
+        }
+        let access = AccessModifer::Public;
+        TypeDef::new(access, name.into(), vec![], fields, vec![], None, 0, None)
+    }
+    /// Returns the definition of the type holding the state of a coroutine: its captured variables, the index of its current state
+    /// and all locals saved across suspension points.
+    fn coroutine_<'tyctx>(
+        &mut self,
+        name: &str,
+        def_id: DefId,
+        args: GenericArgsRef<'tyctx>,
+        tyctx: TyCtxt<'tyctx>,
+        method: Option<Instance<'tyctx>>,
+    ) -> TypeDef {
+        let mut def = self.closure_(name, args.as_coroutine().upvar_tys(), tyctx, method);
+        def.add_field("_tag".into(), Type::U32);
+        let layout = tyctx
+            .coroutine_layout(def_id)
+            .expect("Coroutine has no layout!");
+        for (local, saved) in layout.field_tys.iter_enumerated() {
+            let mut field_ty = EarlyBinder::bind(saved.ty).instantiate(tyctx, args);
+            method.inspect(|method_instance| {
+                field_ty = crate::utilis::monomorphize(method_instance, field_ty, tyctx)
+            });
+            let field_ty = self.type_from_cache(field_ty, tyctx, method);
+            def.add_field(crate::utilis::saved_local_name(local.as_u32()), field_ty);
+        }
+        def
+    }
     pub fn slice_ty<'tyctx>(
         &mut self,
         inner: Ty<'tyctx>,
         tyctx: TyCtxt<'tyctx>,
         method: Option<Instance<'tyctx>>,
     ) -> Type {
-        slice_ref_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
+        fat_ptr_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
     }
     pub fn type_from_cache<'tyctx>(
         &mut self,
@@ -242,21 +349,39 @@ impl TyCache {
                     } else {
                         *inner
                     };
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
-                }
-                TyKind::Str => {
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, u8_ty(tyctx)), method)
+                    fat_ptr_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
                 }
+                TyKind::Str => fat_ptr_to(tyctx, self, Ty::new_slice(tyctx, u8_ty(tyctx)), method),
+                TyKind::Dynamic(..) => fat_ptr_to(tyctx, self, type_and_mut.ty, method),
                 _ => Type::Ptr(self.type_from_cache(type_and_mut.ty, tyctx, method).into()),
             },
             TyKind::Adt(def, subst) => {
                 let name = crate::utilis::adt_name(def, tyctx, subst);
                 if super::is_name_magic(name.as_ref()) {
-                    return super::magic_type(name.as_ref(), def, subst, tyctx);
+                    return super::magic_type(name.as_ref(), def, subst, tyctx, self, method);
+                }
+                if crate::generics::is_instance_shared(*def, subst, tyctx, self) {
+                    return self.shared_adt(*def, subst, tyctx, method).into();
                 }
                 //println!("mangled:{mangled:?}");
                 self.adt(&name, *def, subst, tyctx, method).into()
             }
+            TyKind::Closure(def_id, args) => {
+                let name = crate::utilis::closure_name(*def_id, args, tyctx);
+                if self.type_def_cache.get(&name).is_none() {
+                    let def = self.closure_(&name, args.as_closure().upvar_tys(), tyctx, method);
+                    self.type_def_cache.insert(name.clone(), def);
+                }
+                DotnetTypeRef::new(None, &name).into()
+            }
+            TyKind::Coroutine(def_id, args, _) => {
+                let name = crate::utilis::closure_name(*def_id, args, tyctx);
+                if self.type_def_cache.get(&name).is_none() {
+                    let def = self.coroutine_(&name, *def_id, args, tyctx, method);
+                    self.type_def_cache.insert(name.clone(), def);
+                }
+                DotnetTypeRef::new(None, &name).into()
+            }
             TyKind::Dynamic(trait_, _, dyn_kind) => {
                 println!("trait:{trait_:?} dyn_kind:{dyn_kind:?}");
                 Type::Unresolved
@@ -268,11 +393,10 @@ impl TyCache {
                     } else {
                         *inner
                     };
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
-                }
-                TyKind::Str => {
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, u8_ty(tyctx)), method)
+                    fat_ptr_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
                 }
+                TyKind::Str => fat_ptr_to(tyctx, self, Ty::new_slice(tyctx, u8_ty(tyctx)), method),
+                TyKind::Dynamic(..) => fat_ptr_to(tyctx, self, *inner, method),
                 _ => Type::Ptr(self.type_from_cache(*inner, tyctx, method).into()),
             },
             TyKind::Foreign(foregin) => {
@@ -280,7 +404,13 @@ impl TyCache {
                 Type::Foreign
             }
             TyKind::Bound(_, _inner) => Type::Foreign,
-            TyKind::FnPtr(_) => Type::USize,
+            // Only present in functions emitted as CIL generic methods.
+            TyKind::Param(param) => Type::MethodGenericArg(param.index),
+            TyKind::FnPtr(sig) => Type::FnPtr(
+                crate::function_sig::FnSig::sig_from_fn_ptr(*sig, tyctx, self)
+                    .expect("Could not resolve the signature of a function pointer")
+                    .into(),
+            ),
             TyKind::Slice(_inner) => {
                 //let match self.type_from_cache(*inner, tyctx)
                 //let mut slice_tpe = DotnetTypeRef::new(None, "RustSlice".into());
@@ -327,7 +457,8 @@ impl TyCache {
         }
     }
 }
-fn slice_ref_to<'tyctx>(
+/// Returns the type of a fat pointer to `inner`, which is either a slice, a `str` or a trait object.
+fn fat_ptr_to<'tyctx>(
     tyctx: TyCtxt<'tyctx>,
     cache: &mut TyCache,
     mut inner: Ty<'tyctx>,
@@ -1,4 +1,4 @@
-use crate::IString;
+use crate::{function_sig::FnSig, IString};
 use rustc_middle::middle::exported_symbols::ExportedSymbol;
 use rustc_middle::ty::{
     AdtDef, ConstKind, FloatTy, GenericArg, IntTy, List, Ty, TyCtxt, TyKind, UintTy,
@@ -40,9 +40,13 @@ pub enum Type {
     Foreign,
     /// Generic argument
     GenericArg(u32),
+    /// Generic argument of a method
+    MethodGenericArg(u32),
     DotnetChar,
     /// Rust FnDefs
     FnDef(IString),
+    /// Pointer to a function with the specified signature
+    FnPtr(Box<FnSig>),
 }
 #[derive(Serialize, Deserialize, PartialEq, Clone, Eq, Hash, Debug)]
 pub struct DotnetArray {
@@ -58,9 +62,11 @@ pub struct DotnetTypeRef {
     is_valuetype: bool,
 }
 impl DotnetTypeRef {
+    /// Returns `System.Int128`. If the `software-128-bit-ints` option is set, the linker replaces it with [`crate::int128::SOFTWARE_I128_NAME`].
     pub fn int_128() -> Self {
         Self::new(Some("System.Runtime"), "System.Int128")
     }
+    /// Returns `System.UInt128`. If the `software-128-bit-ints` option is set, the linker replaces it with [`crate::int128::SOFTWARE_U128_NAME`].
     pub fn uint_128() -> Self {
         Self::new(Some("System.Runtime"), "System.UInt128")
     }
@@ -70,6 +76,9 @@ impl DotnetTypeRef {
     pub fn isize_type() -> Self {
         Self::new(Some("System.Runtime"), "System.IntPtr")
     }
+    pub fn object_type() -> Self {
+        Self::new(Some("System.Runtime"), "System.Object").with_valuetype(false)
+    }
     pub fn with_valuetype(mut self, valuetype: bool) -> Self {
         self.set_valuetype(valuetype);
         self
@@ -121,6 +130,16 @@ impl DotnetTypeRef {
     pub fn set_generics_identity(&mut self) {
         self.generics = crate::r#type::ident_gargs(self.generics.len()).into();
     }
+    /// Replaces `self` with the .NET type `map` returns for it, or else replaces the types within its generic arguments. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        if let Some(Type::DotnetType(mapped)) = map(&Type::DotnetType(self.clone().into())) {
+            *self = *mapped;
+            return;
+        }
+        for generic in &mut self.generics {
+            generic.map_types(map);
+        }
+    }
     fn generic_from_adt<'ctx>(
         adt_def: &AdtDef<'ctx>,
         subst: &'ctx List<GenericArg<'ctx>>,
@@ -220,12 +239,33 @@ impl Type {
             _ => Self::Void,
         }
     }
+    /// Returns the type used when `self` is a generic argument. Function pointers can't be generic arguments, so they are replaced with `USize`.
+    pub fn as_generic_arg(&self) -> &Self {
+        match self {
+            Self::FnPtr(_) => &Type::USize,
+            _ => self,
+        }
+    }
     pub fn as_dotnet(&self) -> Option<DotnetTypeRef> {
         match self {
             Self::DotnetType(inner) => Some(inner.as_ref().clone()),
             _ => None,
         }
     }
+    /// Replaces `self` with the type `map` returns for it, or else the types `self` is made of(eg. the pointed-to type of a pointer).
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        if let Some(mapped) = map(self) {
+            *self = mapped;
+            return;
+        }
+        match self {
+            Self::DotnetType(dref) => dref.map_types(map),
+            Self::DotnetArray(array) => array.element.map_types(map),
+            Self::Ptr(inner) => inner.map_types(map),
+            Self::FnPtr(sig) => sig.map_types(map),
+            _ => (),
+        }
+    }
     /*
     pub fn from_ty<'ctx>(rust_tpe: Ty<'ctx>, tyctx: TyCtxt<'ctx>, method: &Instance<'ctx>) -> Self {
         if crate::PRINT_TY_CONVERTION {
@@ -307,7 +347,8 @@ pub fn magic_type<'tyctx>(
     _adt: &AdtDef<'tyctx>,
     subst: &[GenericArg<'tyctx>],
     ctx: TyCtxt<'tyctx>,
-    //method: &Instance<'tyctx>,
+    cache: &mut super::TyCache,
+    method: Option<rustc_middle::ty::Instance<'tyctx>>,
 ) -> Type {
     if name.contains(INTEROP_CLASS_TPE_NAME) {
         if subst.len() != 2 {
@@ -339,10 +380,12 @@ pub fn magic_type<'tyctx>(
         Type::DotnetType(dotnet_tpe.into())
     } else if name.contains(INTEROP_ARR_TPE_NAME) {
         if subst.len() != 2 {
-            panic!("Managed array size is not");
+            panic!("Managed array must have exactly 2 generic arguments!");
         }
-        let element = &subst[0].as_type().expect("Arrat type must be specified!");
-        let element = todo!();
+        let element = subst[0]
+            .as_type()
+            .expect("Managed array element type must be specified!");
+        let element = cache.type_from_cache(element, ctx, method);
         let dimensions = garag_to_usize(&subst[1], ctx);
         Type::DotnetArray(
             DotnetArray {
@@ -17,6 +17,8 @@ pub struct TypeDef {
     explicit_offsets: Option<Vec<u32>>,
     gargc: u32,
     extends: Option<DotnetTypeRef>,
+    /// Is this a class holding the wrappers of exported functions?
+    is_export_class: bool,
 }
 impl TypeDef {
     pub fn ptr_components(name: &str, metadata: Type) -> Self {
@@ -85,6 +87,29 @@ impl TypeDef {
     pub fn methods(&self) -> impl Iterator<Item = &Method> {
         self.functions.iter()
     }
+    /// Checks if `self` is a class holding the wrappers of exported functions.
+    pub fn is_export_class(&self) -> bool {
+        self.is_export_class
+    }
+    /// Marks `self` as a class holding the wrappers of exported functions.
+    pub fn set_export_class(&mut self) {
+        self.is_export_class = true;
+    }
+    /// Replaces the types used by the fields, methods, inner types and base class of `self`, as specified by `map`. See [`Type::map_types`].
+    pub fn map_types(&mut self, map: &impl Fn(&Type) -> Option<Type>) {
+        for (_, tpe) in &mut self.fields {
+            tpe.map_types(map);
+        }
+        for method in &mut self.functions {
+            method.map_types(map);
+        }
+        for inner in &mut self.inner_types {
+            inner.map_types(map);
+        }
+        if let Some(extends) = &mut self.extends {
+            extends.map_types(map);
+        }
+    }
     pub fn nameonly(name: &str) -> Self {
         Self {
             access: AccessModifer::Public,
@@ -95,6 +120,7 @@ impl TypeDef {
             gargc: 0,
             extends: None,
             explicit_offsets: None,
+            is_export_class: false,
         }
     }
     pub fn new(
@@ -116,6 +142,7 @@ impl TypeDef {
             explicit_offsets,
             gargc,
             extends,
+            is_export_class: false,
         }
     }
 }
@@ -201,6 +228,7 @@ pub fn get_array_type(element_count: usize) -> TypeDef {
         explicit_offsets: None,
         gargc: 1,
         extends: None,
+        is_export_class: false,
     };
     // set_Item(usize offset, G0 value)
     let mut set_usize = Method::new(
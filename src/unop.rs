@@ -12,10 +12,13 @@ pub fn unop<'ctx>(
     tycache: &mut TyCache,
 ) -> Vec<CILOp> {
     let mut ops = crate::operand::handle_operand(operand, tcx, method, method_instance, tycache);
-    let _ty = operand.ty(&method.local_decls, tcx);
-    match unnop {
-        UnOp::Neg => ops.push(CILOp::Neg),
-        UnOp::Not => ops.push(CILOp::Not),
+    let ty = operand.ty(&method.local_decls, tcx);
+    let ty = crate::utilis::monomorphize(&method_instance, ty, tcx);
+    match (unnop, crate::int128::int128_type(ty)) {
+        (UnOp::Neg, Some(tpe)) => ops.push(crate::int128::unop(&tpe, "op_UnaryNegation")),
+        (UnOp::Not, Some(tpe)) => ops.push(crate::int128::unop(&tpe, "op_OnesComplement")),
+        (UnOp::Neg, None) => ops.push(CILOp::Neg),
+        (UnOp::Not, None) => ops.push(CILOp::Not),
     };
     ops
 }
@@ -0,0 +1,146 @@
+use crate::{cil::CILOp, r#type::DotnetTypeRef};
+use rustc_middle::mir::{BasicBlock, Body, Terminator, TerminatorKind, UnwindAction};
+/// Returns the cleanup block `terminator` unwinds to, if it has one.
+pub fn cleanup_target(terminator: &Terminator) -> Option<BasicBlock> {
+    let unwind = match &terminator.kind {
+        TerminatorKind::Call { unwind, .. }
+        | TerminatorKind::Drop { unwind, .. }
+        | TerminatorKind::Assert { unwind, .. } => unwind,
+        _ => return None,
+    };
+    match unwind {
+        UnwindAction::Cleanup(cleanup) => Some(*cleanup),
+        _ => None,
+    }
+}
+/// Label id of the `copy`-th copy of label `label`. Copies of cleanup blocks need their own labels, since each handler gets its own copy.
+fn copy_label(label: u32, copy: u32, block_count: u32) -> u32 {
+    copy * block_count + label
+}
+/// Creates the `copy`-th copy of the handler running the cleanup block `cleanup`, and all the cleanup blocks reachable from it.
+/// `block_ops` contains the statement and terminator ops of each block of `method`. `copy` must be greater than 0.
+pub fn cleanup_handler(
+    method: &Body,
+    block_ops: &[(Vec<CILOp>, Vec<CILOp>)],
+    cleanup: BasicBlock,
+    copy: u32,
+) -> Vec<CILOp> {
+    assert_ne!(
+        copy, 0,
+        "Copy 0 of a cleanup block would use the label of the original block!"
+    );
+    let block_count = method.basic_blocks.len() as u32;
+    // Collect the cleanup blocks reachable from `cleanup`.
+    let mut handler_blocks = vec![cleanup];
+    let mut to_visit = vec![cleanup];
+    while let Some(block) = to_visit.pop() {
+        for succesor in method.basic_blocks[block].terminator().successors() {
+            if method.basic_blocks[succesor].is_cleanup && !handler_blocks.contains(&succesor) {
+                handler_blocks.push(succesor);
+                to_visit.push(succesor);
+            }
+        }
+    }
+    // The handler is entered at its first block, so `cleanup` must stay first.
+    handler_blocks[1..].sort();
+    let mut ops = Vec::new();
+    for block in &handler_blocks {
+        let (statement_ops, term_ops) = &block_ops[block.as_usize()];
+        ops.push(CILOp::Label(block.as_u32()));
+        ops.extend(statement_ops.iter().cloned());
+        ops.extend(term_ops.iter().cloned());
+    }
+    for block in &handler_blocks {
+        let label = copy_label(block.as_u32(), copy, block_count);
+        ops.iter_mut().for_each(|op| match op {
+            CILOp::Label(id) if *id == block.as_u32() => *id = label,
+            _ => op.replace_target(block.as_u32(), label),
+        });
+    }
+    ops
+}
+/// Wraps the ops of a terminator in a `.try` block, whose exceptions are caught by a handler running `handler` and then rethrowing them.
+/// `copy` and `block_count` must be the same as the ones used to create `handler`.
+pub fn wrap_in_try(
+    term_ops: &[CILOp],
+    handler: Vec<CILOp>,
+    copy: u32,
+    block_count: u32,
+) -> Vec<CILOp> {
+    let mut ops = Vec::with_capacity(term_ops.len() + handler.len() + 6);
+    ops.push(CILOp::BeginTry);
+    ops.extend(term_ops.iter().cloned());
+    // A protected region can't be exited by a branch, nor can it fall through.
+    match ops.last() {
+        Some(CILOp::GoTo(target)) => {
+            let target = *target;
+            ops.pop();
+            ops.push(CILOp::Leave(target));
+        }
+        Some(CILOp::Throw | CILOp::Rethrow) => (),
+        _ => ops.extend(CILOp::throw_msg(
+            "Diverging function returned, or a terminator inside a try block did not branch!",
+        )),
+    }
+    // Other branches out of the protected region jump to a `leave` at its end instead.
+    // Only non-cleanup blocks are branched to, so those labels are not used by the handler.
+    let mut trampolines = Vec::new();
+    for op in ops.iter_mut() {
+        match op.branch_target() {
+            Some(target) if !matches!(op, CILOp::Leave(_)) => {
+                op.replace_target(target, copy_label(target, copy, block_count));
+                if !trampolines.contains(&target) {
+                    trampolines.push(target);
+                }
+            }
+            _ => (),
+        }
+    }
+    for target in trampolines {
+        ops.push(CILOp::Label(copy_label(target, copy, block_count)));
+        ops.push(CILOp::Leave(target));
+    }
+    ops.push(CILOp::EndTry);
+    let mut exception = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
+    exception.set_valuetype(false);
+    ops.push(CILOp::BeginCatch(exception.into()));
+    // The exception object is not needed, since it is rethrown by `UnwindResume`.
+    ops.push(CILOp::Pop);
+    ops.extend(handler);
+    ops.push(CILOp::EndHandler);
+    ops
+}
+#[test]
+fn try_leaves_target() {
+    let ops = wrap_in_try(
+        &[CILOp::LdcI32(0), CILOp::Pop, CILOp::GoTo(2)],
+        vec![CILOp::Rethrow],
+        1,
+        4,
+    );
+    assert_eq!(ops[0], CILOp::BeginTry);
+    assert_eq!(ops[3], CILOp::Leave(2));
+    assert_eq!(ops[4], CILOp::EndTry);
+    assert_eq!(ops[ops.len() - 2], CILOp::Rethrow);
+    assert_eq!(ops[ops.len() - 1], CILOp::EndHandler);
+}
+#[test]
+fn try_branches_use_trampolines() {
+    let ops = wrap_in_try(
+        &[
+            CILOp::LdcI32(1),
+            CILOp::BTrue(3),
+            CILOp::LdNull,
+            CILOp::Throw,
+        ],
+        vec![CILOp::Rethrow],
+        2,
+        4,
+    );
+    assert_eq!(ops[2], CILOp::BTrue(11));
+    assert_eq!(ops[5], CILOp::Label(11));
+    assert_eq!(ops[6], CILOp::Leave(3));
+    assert_eq!(ops[7], CILOp::EndTry);
+    assert_eq!(ops[ops.len() - 2], CILOp::Rethrow);
+    assert_eq!(ops[ops.len() - 1], CILOp::EndHandler);
+}
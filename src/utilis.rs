@@ -1,8 +1,10 @@
 use rustc_middle::mir::interpret::AllocId;
 use rustc_middle::ty::{
-    AdtDef, AdtKind, Binder, Const, ConstKind, EarlyBinder, FloatTy, GenericArg, Instance, List,
-    ParamEnv, SymbolName, Ty, TyCtxt, TyKind, TypeFoldable,
+    AdtDef, AdtKind, Binder, Const, ConstKind, EarlyBinder, FloatTy, GenericArg, GenericArgs,
+    GenericArgsRef, Instance, List, ParamEnv, SymbolName, Ty, TyCtxt, TyKind, TypeFoldable,
 };
+use rustc_span::def_id::DefId;
+use rustc_target::abi::{FieldIdx, VariantIdx};
 pub const BEGIN_TRY: &str = "rustc_clr_interop_begin_try";
 pub const END_TRY: &str = "rustc_clr_interop_end_try";
 pub const BEGIN_CATCH: &str = "rustc_clr_interop_begin_catch";
@@ -10,12 +12,20 @@ pub const END_CATCH: &str = "rustc_clr_interop_end_catch";
 pub const CTOR_FN_NAME: &str = "rustc_clr_interop_managed_ctor";
 pub const MANAGED_CALL_FN_NAME: &str = "rustc_clr_interop_managed_call";
 pub const MANAGED_CALL_VIRT_FN_NAME: &str = "rustc_clr_interop_managed_call_virt";
+pub const THROW_PANIC_FN_NAME: &str = "rustc_clr_interop_throw_panic";
+pub const MANAGED_ARRAY_FN_NAME: &str = "rustc_clr_interop_managed_array_";
+pub const GC_HANDLE_FN_NAME: &str = "rustc_clr_interop_gc_handle_";
 pub fn is_function_magic(name: &str) -> bool {
-    name.contains(CTOR_FN_NAME) || name.contains(MANAGED_CALL_FN_NAME)
+    name.contains(CTOR_FN_NAME)
+        || name.contains(MANAGED_CALL_FN_NAME)
+        || name.contains(THROW_PANIC_FN_NAME)
+        || name.contains(MANAGED_ARRAY_FN_NAME)
+        || name.contains(GC_HANDLE_FN_NAME)
 }
 use crate::{
-    cil::{CILOp, FieldDescriptor},
+    cil::{CILOp, CallSite, FieldDescriptor},
     codegen_error::MethodCodegenError,
+    function_sig::FnSig,
     r#type::TyCache,
     r#type::{DotnetTypeRef, Type},
     IString,
@@ -77,7 +87,20 @@ pub fn adt_name<'tyctx>(
 
     escape_class_name(&auto_mangled)
 }
-
+/// Gets the name of the type holding the captured variables of the closure `def_id`, or the state of the coroutine `def_id`.
+pub fn closure_name<'tyctx>(
+    def_id: DefId,
+    args: GenericArgsRef<'tyctx>,
+    tyctx: TyCtxt<'tyctx>,
+) -> crate::IString {
+    let prefix = if tyctx.is_coroutine(def_id) {
+        "coroutine"
+    } else {
+        "closure"
+    };
+    let mangled = tyctx.symbol_name(Instance::new(def_id, args));
+    escape_class_name(&format!("{prefix}_{mangled}"))
+}
 pub fn escape_class_name(name: &str) -> IString {
     name.replace("::", ".")
         .replace("..", ".")
@@ -114,9 +137,18 @@ pub fn field_name(ty: Ty, idx: u32) -> crate::IString {
             crate::r#type::escape_field_name(&field_def.name.to_string())
         }
         TyKind::Tuple(_) => format!("Item{}", idx + 1).into(),
+        TyKind::Closure(..) | TyKind::Coroutine(..) => upvar_name(idx),
         _ => todo!("Can't yet get fields of typr {ty:?}"),
     }
 }
+/// Gets the name of the field holding the captured variable with index `idx`.
+pub fn upvar_name(idx: u32) -> crate::IString {
+    format!("upvar_{idx}").into()
+}
+/// Gets the name of the field holding the coroutine local saved across suspension points with index `idx`.
+pub fn saved_local_name(idx: u32) -> crate::IString {
+    format!("saved_{idx}").into()
+}
 /// Gets the name of a enum variant with index `idx`
 pub fn variant_name(ty: Ty, idx: u32) -> crate::IString {
     match ty.kind() {
@@ -154,6 +186,22 @@ pub fn enum_field_descriptor<'ctx>(
     method_instance: Instance<'ctx>,
     type_cache: &mut TyCache,
 ) -> FieldDescriptor {
+    if let TyKind::Coroutine(def_id, args, _) = owner_ty.kind() {
+        // Locals saved across suspension points get their own fields, so the variants of a coroutine don't overlap.
+        let layout = ctx
+            .coroutine_layout(*def_id)
+            .expect("Coroutine has no layout!");
+        let saved_local =
+            layout.variant_fields[VariantIdx::from_u32(variant_idx)][FieldIdx::from_u32(field_idx)];
+        let field_ty = EarlyBinder::bind(layout.field_tys[saved_local].ty).instantiate(ctx, args);
+        let field_ty = crate::utilis::monomorphize(&method_instance, field_ty, ctx);
+        let field_ty = type_cache.type_from_cache(field_ty, ctx, Some(method_instance));
+        let owner_ty = type_cache
+            .type_from_cache(owner_ty, ctx, Some(method_instance))
+            .as_dotnet()
+            .expect("Error: tried to set a field of a non-object type!");
+        return FieldDescriptor::new(owner_ty, field_ty, saved_local_name(saved_local.as_u32()));
+    }
     let (adt, subst) = as_adt(owner_ty).expect("Tried to get a field of a non ADT or tuple type!");
     let variant = adt
         .variants()
@@ -210,15 +258,39 @@ pub fn field_descrptor<'ctx>(
             format!("Item{}", field_idx + 1).into(),
         );
     }
+    let upvar_tys = match owner_ty.kind() {
+        TyKind::Closure(_, args) => Some(args.as_closure().upvar_tys()),
+        TyKind::Coroutine(_, args, _) => Some(args.as_coroutine().upvar_tys()),
+        _ => None,
+    };
+    if let Some(upvar_tys) = upvar_tys {
+        let field_ty = upvar_tys
+            .iter()
+            .nth(field_idx as usize)
+            .expect("No captured variable with provided index!");
+        let field_ty = crate::utilis::monomorphize(&method_instance, field_ty, ctx);
+        let field_ty = type_cache.type_from_cache(field_ty, ctx, Some(method_instance));
+        let owner_ty = type_cache
+            .type_from_cache(owner_ty, ctx, Some(method_instance))
+            .as_dotnet()
+            .expect("Error: tried to set a field of a non-object type!");
+        return FieldDescriptor::new(owner_ty, field_ty, upvar_name(field_idx));
+    }
     let (adt, subst) = as_adt(owner_ty).expect("Tried to get a field of a non ADT or tuple type!");
     let field = adt
         .all_fields()
         .nth(field_idx as usize)
         .expect("No field with provided index!");
     let field_name = crate::r#type::escape_field_name(&field.name.to_string());
-    let field_ty = field.ty(ctx, subst);
-    let field_ty = crate::utilis::monomorphize(&method_instance, field_ty, ctx);
-    let field_ty = type_cache.type_from_cache(field_ty, ctx, Some(method_instance));
+    let subst = crate::utilis::monomorphize(&method_instance, subst, ctx);
+    let field_ty = if crate::generics::is_instance_shared(adt, subst, ctx, type_cache) {
+        // Fields of generic classes are refered to using their declared type.
+        let field_ty = field.ty(ctx, GenericArgs::identity_for_item(ctx, adt.did()));
+        crate::generics::class_generics(type_cache.type_from_cache(field_ty, ctx, None))
+    } else {
+        let field_ty = field.ty(ctx, subst);
+        type_cache.type_from_cache(field_ty, ctx, Some(method_instance))
+    };
     let owner_ty = type_cache
         .type_from_cache(owner_ty, ctx, Some(method_instance))
         .as_dotnet()
@@ -429,18 +501,57 @@ pub fn string_class() -> DotnetTypeRef {
     string.set_valuetype(false);
     string
 }
+/// The managed exception Rust panics are thrown as.
+pub fn panic_exception_class() -> DotnetTypeRef {
+    let mut class = DotnetTypeRef::new(None, "RustPanicException");
+    class.set_valuetype(false);
+    class
+}
+/// Constructor of [`panic_exception_class`], taking the message, file, line and column of a panic.
+pub fn panic_exception_ctor() -> CallSite {
+    let class = panic_exception_class();
+    let string: Type = string_class().into();
+    let sig = FnSig::new(
+        &[
+            class.clone().into(),
+            string.clone(),
+            string,
+            Type::U32,
+            Type::U32,
+        ],
+        &Type::Void,
+    );
+    CallSite::new(Some(class), ".ctor".into(), sig, false)
+}
+/// Constructor of [`panic_exception_class`], taking the message and file as UTF-8 buffers(pointer and length), followed by the line and column.
+pub fn panic_exception_utf8_ctor() -> CallSite {
+    let class = panic_exception_class();
+    let sig = FnSig::new(
+        &[
+            class.clone().into(),
+            Type::Ptr(Type::U8.into()),
+            Type::USize,
+            Type::Ptr(Type::U8.into()),
+            Type::USize,
+            Type::U32,
+            Type::U32,
+        ],
+        &Type::Void,
+    );
+    CallSite::new(Some(class), ".ctor".into(), sig, false)
+}
 pub fn usize_class() -> DotnetTypeRef {
     let mut string = DotnetTypeRef::new(Some("System.Runtime"), "System.UIntPtr");
     //TODO: Inwestigate this. The valuetype prefix seems to be missing from UIntPtr in compiled C# code
     string.set_valuetype(false);
     string
 }
-/// Translated MIR statements should have the total stack diff of 0.
+/// Translated MIR statements should have the total stack diff of 0. Returns false, and prints the stack changes, if `ops` don't pass this check.
 pub fn check_debugable(
     ops: &[crate::cil::CILOp],
     debugable: impl std::fmt::Debug,
     does_return_void: bool,
-) {
+) -> bool {
     use colored::Colorize;
 
     let mut stack = 0;
@@ -473,10 +584,9 @@ pub fn check_debugable(
                 eprintln!("{index}:\t{op:?} changed stack by 0, to {stack}");
             }
         }
-        if !crate::ALLOW_MISCOMPILATIONS {
-            panic!("Miscompiled  {debugable:?}.")
-        };
+        return false;
     }
+    true
 }
 pub(crate) fn alloc_id_to_u64(alloc_id: AllocId) -> u64 {
     unsafe { std::mem::transmute(alloc_id) }
@@ -0,0 +1,759 @@
+use crate::{
+    cil::{CILOp, CallSite},
+    function_sig::FnSig,
+    method::{LocalDef, Method},
+    r#type::{DotnetTypeRef, Type},
+    IString,
+};
+use std::collections::HashMap;
+/// Type of a value on the CIL evaluation stack, as described in ECMA-335 III.1.1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackType {
+    /// `int32`: all integers smaller than 64 bits, bools and chars.
+    Int32,
+    /// `int64`
+    Int64,
+    /// `native int`: also used for unmanaged pointers and function pointers.
+    NativeInt,
+    /// `F`: both `float32` and `float64`.
+    F,
+    /// `O`: a reference to a managed object.
+    O,
+    /// `null`, which can be used as an object reference or a pointer.
+    Null,
+    /// `&`: a managed pointer.
+    Ref,
+    /// A value type with the specified name.
+    ValueType(IString),
+    /// A value type defined in another assembly, with the specified name. It may be an enum, whose values are verified as its underlying integer type.
+    ExternalValueType(IString),
+    /// A value whose type can't be tracked by the verifier(eg. a generic argument). Matches any other type.
+    Unknown,
+}
+impl StackType {
+    /// Returns the stack type of a value of type `tpe`, or `None` if `tpe` is `Void`.
+    pub fn from_type(tpe: &Type) -> Option<Self> {
+        Some(match tpe {
+            Type::Void => return None,
+            Type::Bool
+            | Type::DotnetChar
+            | Type::U8
+            | Type::U16
+            | Type::U32
+            | Type::I8
+            | Type::I16
+            | Type::I32 => Self::Int32,
+            Type::U64 | Type::I64 => Self::Int64,
+            Type::USize | Type::ISize | Type::Ptr(_) | Type::FnPtr(_) => Self::NativeInt,
+            Type::F32 | Type::F64 => Self::F,
+            Type::U128 => Self::from_dotnet(&DotnetTypeRef::uint_128()),
+            Type::I128 => Self::from_dotnet(&DotnetTypeRef::int_128()),
+            Type::FnDef(name) => Self::ValueType(format!("fn_{name}").into()),
+            Type::DotnetType(tpe) => Self::from_dotnet(tpe),
+            Type::DotnetArray(_) => Self::O,
+            Type::Unresolved | Type::Foreign | Type::GenericArg(_) | Type::MethodGenericArg(_) => {
+                Self::Unknown
+            }
+        })
+    }
+    /// Returns the stack type of a value of the .NET type `tpe`.
+    fn from_dotnet(tpe: &DotnetTypeRef) -> Self {
+        match tpe.name_path() {
+            "System.IntPtr" | "System.UIntPtr" => Self::NativeInt,
+            name if tpe.is_valuetype() && tpe.asm().is_some() => {
+                Self::ExternalValueType(name.into())
+            }
+            name if tpe.is_valuetype() => Self::ValueType(name.into()),
+            _ => Self::O,
+        }
+    }
+    /// Checks if a value of type `self` can be used where a value of type `expected` is expected.
+    /// Follows the unverifiable, but valid, CIL rules: `int32` can be used as a `native int`, and pointers can be used as managed references.
+    /// Integers can be used as external value types, since those may be enums.
+    pub fn is_assignable_to(&self, expected: &Self) -> bool {
+        match (self, expected) {
+            (Self::Unknown, _) | (_, Self::Unknown) => true,
+            (Self::Null, Self::O | Self::Ref | Self::NativeInt) => true,
+            (Self::Int32 | Self::NativeInt | Self::Ref, Self::NativeInt | Self::Ref) => true,
+            (Self::NativeInt, Self::Int32) => true,
+            (Self::Int32 | Self::Int64, Self::ExternalValueType(_)) => true,
+            _ => self == expected,
+        }
+    }
+    /// Checks if a value of type `self` can be used as an address by `ldind`, `stind`, `ldobj`, `stobj` or `cpblk`.
+    fn is_address(&self) -> bool {
+        matches!(self, Self::NativeInt | Self::Ref | Self::Unknown)
+    }
+    /// Checks if a value of this type is an integer.
+    fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Self::Int32 | Self::Int64 | Self::NativeInt | Self::Unknown
+        )
+    }
+}
+/// Describes why a method did not pass verification.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationError {
+    index: usize,
+    op: CILOp,
+    message: String,
+}
+impl VerificationError {
+    /// Index of the op which did not pass verification.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// The op which did not pass verification.
+    pub fn op(&self) -> &CILOp {
+        &self.op
+    }
+    /// Message describing the issue.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "op {index}({op:?}): {message}",
+            index = self.index,
+            op = self.op,
+            message = self.message
+        )
+    }
+}
+/// State of the evaluation stack and the synthetic temporary locals before an op.
+#[derive(Clone, Debug, Default)]
+struct State {
+    stack: Vec<StackType>,
+    tmps: Vec<Type>,
+}
+impl State {
+    fn push(&mut self, tpe: StackType) {
+        self.stack.push(tpe);
+    }
+    fn push_type(&mut self, tpe: &Type) {
+        if let Some(tpe) = StackType::from_type(tpe) {
+            self.stack.push(tpe);
+        }
+    }
+    fn pop(&mut self) -> Result<StackType, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "evaluation stack underflow".to_owned())
+    }
+    /// Pops a value which must be assignable to `expected`. `what` describes the value, for error messages.
+    fn pop_expected(&mut self, expected: &StackType, what: &str) -> Result<StackType, String> {
+        let found = self.pop()?;
+        if found.is_assignable_to(expected) {
+            Ok(found)
+        } else {
+            Err(format!("{what} expected {expected:?}, found {found:?}"))
+        }
+    }
+    /// Pops a value which must be assignable to a value of type `expected`.
+    fn pop_type(&mut self, expected: &Type, what: &str) -> Result<StackType, String> {
+        match StackType::from_type(expected) {
+            Some(expected) => self.pop_expected(&expected, what),
+            None => Err(format!("{what} has type void")),
+        }
+    }
+    fn pop_address(&mut self, what: &str) -> Result<(), String> {
+        let found = self.pop()?;
+        if found.is_address() {
+            Ok(())
+        } else {
+            Err(format!("{what} expected an address, found {found:?}"))
+        }
+    }
+    /// Pops the arguments of a call to a method with signature `sig`. If `has_this`, the first argument is the `this` object,
+    /// whose type is not checked, since it may be passed by reference.
+    fn pop_args(&mut self, inputs: &[Type], has_this: bool) -> Result<(), String> {
+        for (index, input) in inputs.iter().enumerate().rev() {
+            if index == 0 && has_this {
+                self.pop()?;
+            } else {
+                self.pop_type(input, &format!("argument {index}"))?;
+            }
+        }
+        Ok(())
+    }
+    /// Returns the `depth`-th temporary local, counting from the most recently allocated one.
+    fn tmp(&self, depth: usize) -> Result<&Type, String> {
+        self.tmps
+            .iter()
+            .rev()
+            .nth(depth)
+            .ok_or_else(|| format!("no temporary local at depth {depth}"))
+    }
+}
+/// Where the control goes after an op.
+enum Flow {
+    /// Continues to the next op.
+    Next,
+    /// May jump to the label, or continue to the next op.
+    Branch(u32),
+    /// Always jumps to the label.
+    Jump(u32),
+    /// Leaves a protected region, emptying the stack, and jumps to the label.
+    Leave(u32),
+    /// Control does not continue within this method, or region.
+    Exit,
+}
+/// Verifies `method`, simulating the types of values on the evaluation stack across all its branches.
+pub fn verify_method(method: &Method) -> Result<(), VerificationError> {
+    verify_ops(method.get_ops(), method.sig(), method.locals())
+}
+/// Verifies `ops` of a method with signature `sig` and locals `locals`. Checks that:
+/// each op receives values of the types it expects, all branches to a label agree on the shape of the stack,
+/// `ret` returns a value matching `sig`, and call arguments match the signatures of their call sites.
+pub fn verify_ops(
+    ops: &[CILOp],
+    sig: &FnSig,
+    locals: &[LocalDef],
+) -> Result<(), VerificationError> {
+    let labels: HashMap<u32, usize> = ops
+        .iter()
+        .enumerate()
+        .filter_map(|(index, op)| match op {
+            CILOp::Label(label) => Some((*label, index)),
+            _ => None,
+        })
+        .collect();
+    let error = |index: usize, message: String| VerificationError {
+        index,
+        op: ops[index].clone(),
+        message,
+    };
+    let mut states: Vec<Option<State>> = vec![None; ops.len()];
+    let mut to_visit = Vec::new();
+    // The method, and each exception handler, starts with an empty stack. Handlers are not reachable by ordinary control flow.
+    for (index, op) in ops.iter().enumerate() {
+        if index == 0 || matches!(op, CILOp::BeginCatch(_) | CILOp::BeginFinally) {
+            states[index] = Some(State::default());
+            to_visit.push(index);
+        }
+    }
+    while let Some(index) = to_visit.pop() {
+        let mut state = states[index].clone().expect("Visited an op with no state!");
+        let flow =
+            apply_op(&ops[index], &mut state, sig, locals).map_err(|msg| error(index, msg))?;
+        let mut successors = Vec::with_capacity(2);
+        match flow {
+            Flow::Next => successors.push(index + 1),
+            Flow::Branch(label) | Flow::Jump(label) | Flow::Leave(label) => {
+                let target = *labels
+                    .get(&label)
+                    .ok_or_else(|| error(index, format!("branch to undefined label {label}")))?;
+                if matches!(flow, Flow::Branch(_)) {
+                    successors.push(index + 1);
+                }
+                if matches!(flow, Flow::Leave(_)) {
+                    state.stack.clear();
+                }
+                successors.push(target);
+            }
+            Flow::Exit => (),
+        }
+        for successor in successors {
+            if successor >= ops.len() {
+                return Err(error(
+                    index,
+                    "control falls through the end of the method".into(),
+                ));
+            }
+            match &states[successor] {
+                Some(existing) => {
+                    let agrees = existing.stack.len() == state.stack.len()
+                        && existing
+                            .stack
+                            .iter()
+                            .zip(state.stack.iter())
+                            .all(|(a, b)| a.is_assignable_to(b) || b.is_assignable_to(a));
+                    if !agrees {
+                        return Err(error(
+                            index,
+                            format!(
+                                "stack {new:?} does not match the stack {existing:?} at op {successor}",
+                                new = state.stack,
+                                existing = existing.stack
+                            ),
+                        ));
+                    }
+                }
+                None => {
+                    states[successor] = Some(state.clone());
+                    to_visit.push(successor);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+/// Applies the effects of `op` to `state`, checking the types of values it consumes.
+fn apply_op(
+    op: &CILOp,
+    state: &mut State,
+    sig: &FnSig,
+    locals: &[LocalDef],
+) -> Result<Flow, String> {
+    match op {
+        CILOp::Nop | CILOp::Comment(_) | CILOp::Label(_) => (),
+        CILOp::GoTo(label) => return Ok(Flow::Jump(*label)),
+        CILOp::BZero(label) | CILOp::BTrue(label) => {
+            let cond = state.pop()?;
+            if matches!(
+                cond,
+                StackType::F | StackType::ValueType(_) | StackType::ExternalValueType(_)
+            ) {
+                return Err(format!("can't branch on a value of type {cond:?}"));
+            }
+            return Ok(Flow::Branch(*label));
+        }
+        CILOp::BEq(label) | CILOp::BNe(label) => {
+            binary_compare(state, true)?;
+            return Ok(Flow::Branch(*label));
+        }
+        CILOp::BLt(label) | CILOp::BGe(label) | CILOp::BLe(label) => {
+            binary_compare(state, false)?;
+            return Ok(Flow::Branch(*label));
+        }
+        CILOp::BeginTry => {
+            if !state.stack.is_empty() {
+                return Err(format!(
+                    "entered a protected region with a non-empty stack {stack:?}",
+                    stack = state.stack
+                ));
+            }
+        }
+        CILOp::EndTry | CILOp::EndHandler => {
+            return Err("control falls through the end of a protected region or handler".into())
+        }
+        CILOp::BeginCatch(_) => state.push(StackType::O),
+        CILOp::BeginFinally => (),
+        CILOp::Leave(label) => return Ok(Flow::Leave(*label)),
+        CILOp::EndFinally => return Ok(Flow::Exit),
+        CILOp::Throw => {
+            state.pop_expected(&StackType::O, "thrown exception")?;
+            return Ok(Flow::Exit);
+        }
+        CILOp::Rethrow => return Ok(Flow::Exit),
+        CILOp::Ret => {
+            if *sig.output() != Type::Void {
+                state.pop_type(sig.output(), "return value")?;
+            }
+            if !state.stack.is_empty() {
+                return Err(format!(
+                    "returned with values {stack:?} left on the stack",
+                    stack = state.stack
+                ));
+            }
+            return Ok(Flow::Exit);
+        }
+        CILOp::LDLoc(local) => state.push_type(local_type(locals, *local)?),
+        CILOp::STLoc(local) => {
+            state.pop_type(local_type(locals, *local)?, &format!("local {local}"))?;
+        }
+        CILOp::LDLocA(local) => {
+            local_type(locals, *local)?;
+            state.push(StackType::Ref);
+        }
+        CILOp::LDArg(arg) => state.push_type(arg_type(sig, *arg)?),
+        CILOp::STArg(arg) => {
+            state.pop_type(arg_type(sig, *arg)?, &format!("argument {arg}"))?;
+        }
+        CILOp::LDArgA(arg) => {
+            arg_type(sig, *arg)?;
+            state.push(StackType::Ref);
+        }
+        CILOp::NewTMPLocal(tpe) => state.tmps.push(tpe.as_ref().clone()),
+        CILOp::FreeTMPLocal => {
+            state
+                .tmps
+                .pop()
+                .ok_or_else(|| "freed a temporary local which was never allocated".to_owned())?;
+        }
+        CILOp::LoadTMPLocal => {
+            let tpe = state.tmp(0)?.clone();
+            state.push_type(&tpe);
+        }
+        CILOp::LoadUnderTMPLocal(depth) => {
+            let tpe = state.tmp(*depth as usize)?.clone();
+            state.push_type(&tpe);
+        }
+        CILOp::SetTMPLocal => {
+            let tpe = state.tmp(0)?.clone();
+            state.pop_type(&tpe, "temporary local")?;
+        }
+        CILOp::LoadAddresOfTMPLocal => {
+            state.tmp(0)?;
+            state.push(StackType::Ref);
+        }
+        CILOp::LoadAdressUnderTMPLocal(depth) => {
+            state.tmp(*depth as usize)?;
+            state.push(StackType::Ref);
+        }
+        CILOp::LoadGlobalAllocPtr { .. } | CILOp::LoadGlobalStaticPtr { .. } => {
+            state.push(StackType::NativeInt)
+        }
+        CILOp::LdcI32(_) | CILOp::SizeOf(_) => state.push(StackType::Int32),
+        CILOp::LdcI64(_) => state.push(StackType::Int64),
+        CILOp::LdcF32(_) | CILOp::LdcF64(_) => state.push(StackType::F),
+        CILOp::LdStr(_) => state.push(StackType::O),
+        CILOp::LdNull => state.push(StackType::Null),
+        CILOp::ConvI8(_)
+        | CILOp::ConvI16(_)
+        | CILOp::ConvI32(_)
+        | CILOp::ConvU8(_)
+        | CILOp::ConvU16(_)
+        | CILOp::ConvU32(_) => convert(state, StackType::Int32)?,
+        CILOp::ConvI64(_) | CILOp::ConvU64(_) => convert(state, StackType::Int64)?,
+        CILOp::ConvISize(_) | CILOp::ConvUSize(_) => convert(state, StackType::NativeInt)?,
+        CILOp::ConvF32(_) | CILOp::ConvF64(_) | CILOp::ConvFUn => convert(state, StackType::F)?,
+        CILOp::LDIndI8 | CILOp::LDIndI16 | CILOp::LDIndI32 => {
+            state.pop_address("ldind")?;
+            state.push(StackType::Int32);
+        }
+        CILOp::LDIndI64 => {
+            state.pop_address("ldind")?;
+            state.push(StackType::Int64);
+        }
+        CILOp::LDIndISize => {
+            state.pop_address("ldind")?;
+            state.push(StackType::NativeInt);
+        }
+        CILOp::LDIndF32 | CILOp::LDIndF64 => {
+            state.pop_address("ldind")?;
+            state.push(StackType::F);
+        }
+        CILOp::LDIndRef => {
+            state.pop_address("ldind")?;
+            state.push(StackType::O);
+        }
+        CILOp::STIndI8 | CILOp::STIndI16 | CILOp::STIndI32 => store_ind(state, StackType::Int32)?,
+        CILOp::STIndI64 => store_ind(state, StackType::Int64)?,
+        CILOp::STIndISize => store_ind(state, StackType::NativeInt)?,
+        CILOp::STIndF32 | CILOp::STIndF64 => store_ind(state, StackType::F)?,
+        CILOp::Add | CILOp::Sub => {
+            let b = state.pop()?;
+            let a = state.pop()?;
+            let result = pointer_arithmetic(&a, &b, matches!(op, CILOp::Sub))
+                .or_else(|| numeric_result(&a, &b, true))
+                .ok_or_else(|| format!("can't add or subtract {a:?} and {b:?}"))?;
+            state.push(result);
+        }
+        CILOp::Mul | CILOp::Div | CILOp::Rem => {
+            let b = state.pop()?;
+            let a = state.pop()?;
+            let result = numeric_result(&a, &b, true)
+                .ok_or_else(|| format!("can't apply {op:?} to {a:?} and {b:?}"))?;
+            state.push(result);
+        }
+        CILOp::AddOvf
+        | CILOp::AddOvfUn
+        | CILOp::SubOvf
+        | CILOp::SubOvfUn
+        | CILOp::MulOvf
+        | CILOp::DivUn
+        | CILOp::RemUn
+        | CILOp::And
+        | CILOp::Or
+        | CILOp::XOr => {
+            let b = state.pop()?;
+            let a = state.pop()?;
+            let result = numeric_result(&a, &b, false)
+                .ok_or_else(|| format!("can't apply {op:?} to {a:?} and {b:?}"))?;
+            state.push(result);
+        }
+        CILOp::Shl | CILOp::Shr | CILOp::ShrUn => {
+            let amount = state.pop()?;
+            let value = state.pop()?;
+            if !matches!(
+                amount,
+                StackType::Int32 | StackType::NativeInt | StackType::Unknown
+            ) {
+                return Err(format!("shift amount expected Int32, found {amount:?}"));
+            }
+            if !value.is_integer() {
+                return Err(format!("can't shift a value of type {value:?}"));
+            }
+            state.push(value);
+        }
+        CILOp::Not => {
+            let value = state.pop()?;
+            if !value.is_integer() {
+                return Err(format!("can't negate bits of a value of type {value:?}"));
+            }
+            state.push(value);
+        }
+        CILOp::Neg => {
+            let value = state.pop()?;
+            if !value.is_integer() && value != StackType::F {
+                return Err(format!("can't negate a value of type {value:?}"));
+            }
+            state.push(value);
+        }
+        CILOp::Eq => {
+            binary_compare(state, true)?;
+            state.push(StackType::Int32);
+        }
+        CILOp::Lt | CILOp::LtUn | CILOp::Gt | CILOp::GtUn => {
+            binary_compare(state, false)?;
+            state.push(StackType::Int32);
+        }
+        CILOp::Pop => {
+            state.pop()?;
+        }
+        CILOp::Dup => {
+            let value = state.pop()?;
+            state.push(value.clone());
+            state.push(value);
+        }
+        CILOp::LocAlloc => {
+            state.pop_expected(&StackType::NativeInt, "localloc size")?;
+            state.push(StackType::NativeInt);
+        }
+        CILOp::CpBlk => {
+            state.pop_expected(&StackType::Int32, "cpblk size")?;
+            state.pop_address("cpblk source")?;
+            state.pop_address("cpblk destination")?;
+        }
+        CILOp::Call(site) | CILOp::CallVirt(site) => {
+            state.pop_args(site.inputs(), !site.is_static())?;
+            state.push_type(site.signature().output());
+        }
+        CILOp::CallI(sig) => {
+            state.pop_expected(&StackType::NativeInt, "function pointer")?;
+            state.pop_args(sig.inputs(), false)?;
+            state.push_type(sig.output());
+        }
+        CILOp::NewObj(site) => {
+            state.pop_args(site.explicit_inputs(), false)?;
+            state.push(constructed_type(site));
+        }
+        CILOp::CastClass(tpe) => {
+            state.pop_expected(&StackType::O, "castclass object")?;
+            state.push_type(tpe);
+        }
+        CILOp::LDFtn(_) => state.push(StackType::NativeInt),
+        CILOp::LDField(field) => {
+            state.pop()?;
+            state.push_type(field.tpe());
+        }
+        CILOp::LDFieldAdress(_) => {
+            state.pop()?;
+            state.push(StackType::Ref);
+        }
+        CILOp::STField(field) => {
+            state.pop_type(field.tpe(), &format!("field {name}", name = field.name()))?;
+            state.pop()?;
+        }
+        CILOp::LDStaticField(field) => state.push_type(field.tpe()),
+        CILOp::STStaticField(field) => {
+            state.pop_type(
+                field.tpe(),
+                &format!("static field {name}", name = field.name()),
+            )?;
+        }
+        CILOp::LdObj(tpe) => {
+            state.pop_address("ldobj")?;
+            state.push_type(tpe);
+        }
+        CILOp::STObj(tpe) => {
+            state.pop_type(tpe, "stobj value")?;
+            state.pop_address("stobj")?;
+        }
+        CILOp::NewArr(_) => {
+            state.pop_expected(&StackType::NativeInt, "newarr length")?;
+            state.push(StackType::O);
+        }
+        CILOp::LDElem(tpe) => {
+            pop_element(state, "ldelem")?;
+            state.push_type(tpe);
+        }
+        CILOp::LDElemA(_) => {
+            pop_element(state, "ldelema")?;
+            state.push(StackType::Ref);
+        }
+        CILOp::STElem(tpe) => {
+            state.pop_type(tpe, "stelem value")?;
+            pop_element(state, "stelem")?;
+        }
+        CILOp::LDLen => {
+            state.pop_expected(&StackType::O, "ldlen array")?;
+            state.push(StackType::NativeInt);
+        }
+    }
+    Ok(Flow::Next)
+}
+fn local_type(locals: &[LocalDef], local: u32) -> Result<&Type, String> {
+    locals
+        .get(local as usize)
+        .map(|(_, tpe)| tpe)
+        .ok_or_else(|| {
+            format!(
+                "local {local} out of range, the method has {} locals",
+                locals.len()
+            )
+        })
+}
+fn arg_type(sig: &FnSig, arg: u32) -> Result<&Type, String> {
+    sig.inputs().get(arg as usize).ok_or_else(|| {
+        format!(
+            "argument {arg} out of range, the method has {} arguments",
+            sig.inputs().len()
+        )
+    })
+}
+/// Type of the object created by the constructor `site`.
+fn constructed_type(site: &CallSite) -> StackType {
+    match site.class() {
+        Some(class) => StackType::from_dotnet(class),
+        None => StackType::Unknown,
+    }
+}
+/// Pops a value to convert, and pushes the result of the conversion.
+fn convert(state: &mut State, result: StackType) -> Result<(), String> {
+    let value = state.pop()?;
+    if matches!(
+        value,
+        StackType::O | StackType::ValueType(_) | StackType::ExternalValueType(_)
+    ) {
+        return Err(format!("can't convert a value of type {value:?}"));
+    }
+    state.push(result);
+    Ok(())
+}
+/// Pops a value of type `value` and an address, and checks their types.
+fn store_ind(state: &mut State, value: StackType) -> Result<(), String> {
+    state.pop_expected(&value, "stind value")?;
+    state.pop_address("stind")
+}
+/// Pops an index and the array it indexes, and checks their types.
+fn pop_element(state: &mut State, what: &str) -> Result<(), String> {
+    state.pop_expected(&StackType::NativeInt, &format!("{what} index"))?;
+    state.pop_expected(&StackType::O, &format!("{what} array"))?;
+    Ok(())
+}
+/// Pops 2 compared values, and checks that they can be compared. Only equality comparisons can be applied to object references.
+fn binary_compare(state: &mut State, equality: bool) -> Result<(), String> {
+    let b = state.pop()?;
+    let a = state.pop()?;
+    let comparable = match (&a, &b) {
+        (StackType::O | StackType::Null, StackType::O | StackType::Null) => equality,
+        (StackType::Ref, StackType::Ref | StackType::NativeInt)
+        | (StackType::NativeInt, StackType::Ref)
+        | (StackType::Null, StackType::Ref | StackType::NativeInt)
+        | (StackType::Ref | StackType::NativeInt, StackType::Null) => true,
+        _ => numeric_result(&a, &b, true).is_some(),
+    };
+    if comparable {
+        Ok(())
+    } else {
+        Err(format!("can't compare {a:?} and {b:?}"))
+    }
+}
+/// Result type of a binary numeric operation on `a` and `b`, as described in ECMA-335 III.1.5.
+fn numeric_result(a: &StackType, b: &StackType, allow_float: bool) -> Option<StackType> {
+    match (a, b) {
+        (StackType::Unknown, other) | (other, StackType::Unknown) => Some(other.clone()),
+        (StackType::Int32, StackType::Int32) => Some(StackType::Int32),
+        (StackType::Int64, StackType::Int64) => Some(StackType::Int64),
+        (StackType::Int32 | StackType::NativeInt, StackType::Int32 | StackType::NativeInt) => {
+            Some(StackType::NativeInt)
+        }
+        (StackType::F, StackType::F) if allow_float => Some(StackType::F),
+        _ => None,
+    }
+}
+/// Result type of adding or subtracting an offset to/from a managed pointer.
+fn pointer_arithmetic(a: &StackType, b: &StackType, is_sub: bool) -> Option<StackType> {
+    match (a, b) {
+        (StackType::Ref, StackType::Int32 | StackType::NativeInt) => Some(StackType::Ref),
+        (StackType::Int32 | StackType::NativeInt, StackType::Ref) if !is_sub => {
+            Some(StackType::Ref)
+        }
+        (StackType::Ref, StackType::Ref) if is_sub => Some(StackType::NativeInt),
+        _ => None,
+    }
+}
+#[test]
+fn verify_branches() {
+    let sig = FnSig::new(&[Type::I32], &Type::I32);
+    let ops = [
+        CILOp::LDArg(0),
+        CILOp::BTrue(1),
+        CILOp::LdcI32(0),
+        CILOp::GoTo(2),
+        CILOp::Label(1),
+        CILOp::LdcI32(1),
+        CILOp::Label(2),
+        CILOp::Ret,
+    ];
+    verify_ops(&ops, &sig, &[]).unwrap();
+    // One branch leaves an int64 on the stack, the other an int32.
+    let mut bad_ops = ops.to_vec();
+    bad_ops[5] = CILOp::LdcI64(1);
+    assert!(verify_ops(&bad_ops, &sig, &[]).is_err());
+}
+#[test]
+fn verify_calls_and_ret() {
+    let sig = FnSig::new(&[], &Type::U64);
+    let callee = CallSite::new(
+        None,
+        "callee".into(),
+        FnSig::new(&[Type::I64, Type::F32], &Type::U64),
+        true,
+    );
+    let ops = [
+        CILOp::LdcI64(1),
+        CILOp::LdcF32(0.0),
+        CILOp::Call(callee.clone().into()),
+        CILOp::Ret,
+    ];
+    verify_ops(&ops, &sig, &[]).unwrap();
+    let swapped = [
+        CILOp::LdcF32(0.0),
+        CILOp::LdcI64(1),
+        CILOp::Call(callee.into()),
+        CILOp::Ret,
+    ];
+    assert!(verify_ops(&swapped, &sig, &[]).is_err());
+    let bad_ret = [CILOp::LdcI32(1), CILOp::Ret];
+    let err = verify_ops(&bad_ret, &sig, &[]).unwrap_err();
+    assert_eq!(err.index(), 1);
+}
+#[test]
+fn verify_external_enums() {
+    // Enums from other assemblies are passed as their underlying integer type.
+    let enum_type = Type::DotnetType(
+        DotnetTypeRef::new(Some("System.Runtime"), "System.DayOfWeek")
+            .with_valuetype(true)
+            .into(),
+    );
+    let callee = CallSite::new(
+        None,
+        "callee".into(),
+        FnSig::new(&[enum_type], &Type::Void),
+        true,
+    );
+    let sig = FnSig::new(&[], &Type::Void);
+    let ops = [CILOp::LdcI32(1), CILOp::Call(callee.into()), CILOp::Ret];
+    verify_ops(&ops, &sig, &[]).unwrap();
+    // Value types of the assembly itself are never enums.
+    let local_type = Type::DotnetType(
+        DotnetTypeRef::new(None, "Local")
+            .with_valuetype(true)
+            .into(),
+    );
+    let callee = CallSite::new(
+        None,
+        "callee".into(),
+        FnSig::new(&[local_type], &Type::Void),
+        true,
+    );
+    let ops = [CILOp::LdcI32(1), CILOp::Call(callee.into()), CILOp::Ret];
+    assert!(verify_ops(&ops, &sig, &[]).is_err());
+}
@@ -0,0 +1,68 @@
+use crate::{
+    cil::{CILOp, FieldDescriptor},
+    r#type::TyCache,
+};
+use rustc_hir::LangItem;
+use rustc_middle::ty::{Instance, Ty, TyCtxt, TyKind};
+/// Returns the descriptors of the `metadata` field of a fat pointer to the trait object `dyn_ty`, and the `vtable_ptr` field of that metadata.
+pub fn vtable_ptr_fields<'ctx>(
+    dyn_ty: Ty<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    tycache: &mut TyCache,
+) -> (FieldDescriptor, FieldDescriptor) {
+    assert!(
+        matches!(dyn_ty.kind(), TyKind::Dynamic(..)),
+        "{dyn_ty:?} is not a trait object!"
+    );
+    let ptr_components = tycache.ptr_components(tyctx);
+    let components_ty = Ty::new_adt(
+        tyctx,
+        tyctx.adt_def(ptr_components),
+        tyctx.mk_args(&[dyn_ty.into()]),
+    );
+    let metadata =
+        crate::utilis::field_descrptor(components_ty, 1, tyctx, method_instance, tycache);
+    let dyn_metadata = tyctx.require_lang_item(LangItem::DynMetadata, None);
+    let dyn_metadata_ty = Ty::new_adt(
+        tyctx,
+        tyctx.adt_def(dyn_metadata),
+        tyctx.mk_args(&[dyn_ty.into()]),
+    );
+    let vtable_ptr =
+        crate::utilis::field_descrptor(dyn_metadata_ty, 0, tyctx, method_instance, tycache);
+    (metadata, vtable_ptr)
+}
+/// Returns the ops loading the vtable pointer of a fat pointer to the trait object `dyn_ty`. The address of the fat pointer must be on top of the stack.
+pub fn load_vtable_ptr<'ctx>(
+    dyn_ty: Ty<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    tycache: &mut TyCache,
+) -> Vec<CILOp> {
+    let (metadata, vtable_ptr) = vtable_ptr_fields(dyn_ty, tyctx, method_instance, tycache);
+    vec![
+        CILOp::LDFieldAdress(metadata.into()),
+        CILOp::LDField(vtable_ptr.into()),
+    ]
+}
+/// Returns the ops loading a pointer to the vtable used when `src_ty` is coerced into the trait object `dyn_ty`.
+pub fn vtable_alloc<'ctx>(src_ty: Ty<'ctx>, dyn_ty: Ty<'ctx>, tyctx: TyCtxt<'ctx>) -> Vec<CILOp> {
+    let TyKind::Dynamic(predicates, ..) = dyn_ty.kind() else {
+        panic!("Can't create a vtable for {dyn_ty:?}, because it is not a trait object!");
+    };
+    let alloc_id = tyctx.vtable_allocation((src_ty, predicates.principal()));
+    vec![CILOp::LoadGlobalAllocPtr {
+        alloc_id: crate::utilis::alloc_id_to_u64(alloc_id),
+    }]
+}
+/// Returns the ops loading the `idx`-th entry of the vtable, whose address is on top of the stack.
+pub fn load_vtable_entry(idx: usize, tyctx: TyCtxt) -> Vec<CILOp> {
+    let offset = idx as u64 * tyctx.data_layout.pointer_size.bytes();
+    vec![
+        CILOp::LdcI64(offset as i64),
+        CILOp::ConvUSize(false),
+        CILOp::Add,
+        CILOp::LDIndISize,
+    ]
+}
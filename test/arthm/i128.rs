@@ -0,0 +1,57 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn main(){
+    // Arithmetic
+    test_eq!(black_box(2_i128) + 2, 4);
+    test_eq!(black_box(2_u128) + 2, 4);
+    test_eq!(black_box(2_i128) - 4, -2);
+    test_eq!(black_box(6_u128) - 4, 2);
+    test_eq!(black_box(3_i128) * black_box(-7), -21);
+    test_eq!(black_box(0x1_0000_0000_0000_0000_u128) * 3, 0x3_0000_0000_0000_0000);
+    test_eq!(black_box(-21_i128) / 7, -3);
+    test_eq!(black_box(u128::MAX) / 3, 0x5555_5555_5555_5555_5555_5555_5555_5555);
+    test_eq!(black_box(-22_i128) % 7, -1);
+    test_eq!(black_box(22_u128) % 7, 1);
+    test_eq!(-black_box(5_i128), -5);
+    test_eq!(!black_box(0_u128), u128::MAX);
+    // Bitwise operations and shifts
+    test_eq!(black_box(0b1100_u128) & 0b1010, 0b1000);
+    test_eq!(black_box(0b1100_i128) | 0b1010, 0b1110);
+    test_eq!(black_box(0b1100_u128) ^ 0b1010, 0b0110);
+    test_eq!(black_box(1_u128) << black_box(100), 1 << 100);
+    test_eq!(black_box(1_u128 << 100) >> black_box(99_u128), 2);
+    test_eq!(black_box(i128::MIN) >> black_box(127), -1);
+    test_eq!(black_box(1_u32) << black_box(3_u128), 8);
+    // Comparisons
+    test!(black_box(-1_i128) < 0);
+    test!(black_box(u128::MAX) > 0);
+    test!(black_box(1_u128 << 64) >= 1 << 64);
+    test!(black_box(i128::MIN) <= i128::MAX);
+    test!(black_box(1_i128 << 70) != 1 << 71);
+    // Casts
+    test_eq!(black_box(-1_i8) as i128, -1);
+    test_eq!(black_box(-1_i32) as u128, u128::MAX);
+    test_eq!(black_box(u64::MAX) as u128, 0xFFFF_FFFF_FFFF_FFFF);
+    test_eq!(black_box(0x1_0000_0000_0000_0001_u128) as u64, 1);
+    test_eq!(black_box(-1_i128) as u128, u128::MAX);
+    test_eq!(black_box(true) as u128, 1);
+    test_eq!(black_box(1_u128 << 100) as f64, 1267650600228229401496703205376.0);
+    test_eq!(black_box(-2_i128) as f32, -2.0);
+    test_eq!(black_box(-1.5e20_f64) as i128, -150_000_000_000_000_000_000);
+    test_eq!(black_box(-1.0_f32) as u128, 0);
+    test_eq!(black_box(f64::MAX) as u128, u128::MAX);
+    // Checked arithmetic
+    test_eq!(black_box(u128::MAX).checked_add(1), None);
+    test_eq!(black_box(u128::MAX - 1).checked_add(1), Some(u128::MAX));
+    test_eq!(black_box(0_u128).checked_sub(1), None);
+    test_eq!(black_box(i128::MAX).checked_add(1), None);
+    test_eq!(black_box(i128::MIN).checked_sub(1), None);
+    test_eq!(black_box(-5_i128).checked_sub(5), Some(-10));
+    test_eq!(black_box(1_u128 << 64).checked_mul(1 << 64), None);
+    test_eq!(black_box(1_u128 << 63).checked_mul(2), Some(1 << 64));
+    test_eq!(black_box(i128::MIN).checked_mul(-1), None);
+    test_eq!(black_box(-3_i128).checked_mul(-3), Some(9));
+    test_eq!(black_box(u128::MAX).overflowing_add(2), (1, true));
+}
@@ -1,4 +1,8 @@
 use std::hint::black_box;
 pub fn simplest_closure(){
     black_box(||{black_box(0)});
-}
\ No newline at end of file
+}
+pub fn capturing_closure(a:i32)->i32{
+    let add = |b:i32|a + b;
+    black_box(add)(black_box(1))
+}
@@ -10,8 +10,10 @@ extern "C"{
     fn realloc(ptr:*mut core::ffi::c_void,size:usize)->*mut core::ffi::c_void;
 } 
 #[panic_handler]
-fn panic(_panic: &PanicInfo<'_>) -> ! {
-    core::intrinsics::abort();
+fn panic(panic: &PanicInfo<'_>) -> ! {
+    let message = panic.message().and_then(|message| message.as_str()).unwrap_or("Rust code panicked");
+    let (file, line, column) = panic.location().map(|location| (location.file(), location.line(), location.column())).unwrap_or(("", 0, 0));
+    rustc_clr_interop_throw_panic(message.as_ptr(), message.len(), file.as_ptr(), file.len(), line, column)
 }
 #[start]
 fn start(_argc:isize,_argv: *const *const u8) -> isize{
@@ -51,6 +53,11 @@ macro_rules! test_ne{
 //Interop thingies
 #[allow(dead_code)]
 #[inline(never)]
+fn rustc_clr_interop_throw_panic(_message:*const u8,_message_len:usize,_file:*const u8,_file_len:usize,_line:u32,_column:u32)->!{
+    core::intrinsics::abort();
+}
+#[allow(dead_code)]
+#[inline(never)]
 fn rustc_clr_interop_managed_call0_<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str,const IS_VALUETYPE:bool,const METHOD:&'static str,Ret>()->Ret{
     core::intrinsics::abort();
 }
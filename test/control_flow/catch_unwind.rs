@@ -0,0 +1,23 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code,unused_imports)]
+#![no_std]
+include!("../common.rs");
+fn panics(data: *mut u8) {
+    unsafe { *data = 1 };
+    panic!("Caught panic");
+}
+fn does_not_panic(data: *mut u8) {
+    unsafe { *data = 2 };
+}
+fn catch(data: *mut u8, _payload: *mut u8) {
+    unsafe { *data += 10 };
+}
+fn main(){
+    let mut state = 0_u8;
+    let caught = unsafe { core::intrinsics::r#try(panics, core::ptr::addr_of_mut!(state), catch) };
+    test_eq!(caught, 1);
+    test_eq!(state, 11);
+    let caught = unsafe { core::intrinsics::r#try(does_not_panic, core::ptr::addr_of_mut!(state), catch) };
+    test_eq!(caught, 0);
+    test_eq!(state, 2);
+}
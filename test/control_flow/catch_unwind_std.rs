@@ -0,0 +1,17 @@
+fn main(){
+    // The default hook would print the caught panics to stderr.
+    std::panic::set_hook(Box::new(|_| {}));
+    // The payload the panic was raised with is handed back by `catch_unwind`.
+    let payload = std::panic::catch_unwind(|| {
+        std::panic::panic_any(42_u32);
+    })
+    .expect_err("The panic was not caught");
+    assert_eq!(payload.downcast_ref::<u32>(), Some(&42));
+    let result = std::panic::catch_unwind(|| 7);
+    assert_eq!(result.ok(), Some(7));
+    let message = std::panic::catch_unwind(|| {
+        panic!("Caught panic {}", std::hint::black_box(1));
+    })
+    .expect_err("The panic was not caught");
+    assert_eq!(message.downcast_ref::<String>().map(String::as_str), Some("Caught panic 1"));
+}
@@ -0,0 +1,48 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message,coroutines,coroutine_trait)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+use core::ops::{Coroutine, CoroutineState};
+use core::pin::Pin;
+fn call_fn(f:impl Fn(u32)->u32,arg:u32)->u32{
+    f(arg)
+}
+fn call_fn_mut(mut f:impl FnMut(u32),arg:u32){
+    f(arg);
+    f(arg);
+}
+fn call_fn_once(f:impl FnOnce()->u32)->u32{
+    f()
+}
+fn main(){
+    let offset = black_box(5_u32);
+    test_eq!(call_fn(|x|x + offset,2),7);
+    let mut sum = 0;
+    call_fn_mut(|x|sum += x,3);
+    test_eq!(sum,6);
+    let moved = black_box(11_u32);
+    test_eq!(call_fn_once(move||moved * 2),22);
+    let dyn_fn:&dyn Fn(u32)->u32 = &|x|x * offset;
+    test_eq!(dyn_fn(3),15);
+    let fn_ptr:fn(u32,u32)->u32 = |a,b|a - b;
+    test_eq!(black_box(fn_ptr)(9,4),5);
+    let evens = (0..10_u32).filter(|x|x % 2 == 0).map(|x|x * offset).fold(0,|acc,x|acc + x);
+    test_eq!(evens,100);
+    let mut coroutine = ||{
+        yield offset;
+        yield offset + 1;
+        offset + 2
+    };
+    match Pin::new(&mut coroutine).resume(()){
+        CoroutineState::Yielded(value)=>test_eq!(value,5),
+        CoroutineState::Complete(_)=>core::intrinsics::abort(),
+    }
+    match Pin::new(&mut coroutine).resume(()){
+        CoroutineState::Yielded(value)=>test_eq!(value,6),
+        CoroutineState::Complete(_)=>core::intrinsics::abort(),
+    }
+    match Pin::new(&mut coroutine).resume(()){
+        CoroutineState::Yielded(_)=>core::intrinsics::abort(),
+        CoroutineState::Complete(value)=>test_eq!(value,7),
+    }
+}
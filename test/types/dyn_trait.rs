@@ -0,0 +1,39 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+trait Shape{
+    fn area(&self)->u32;
+    fn scale(&mut self,by:u32);
+}
+struct Square(u32);
+struct Rect{width:u32,height:u32}
+impl Shape for Square{
+    fn area(&self)->u32{
+        self.0 * self.0
+    }
+    fn scale(&mut self,by:u32){
+        self.0 *= by;
+    }
+}
+impl Shape for Rect{
+    fn area(&self)->u32{
+        self.width * self.height
+    }
+    fn scale(&mut self,by:u32){
+        self.width *= by;
+        self.height *= by;
+    }
+}
+fn area(shape:&dyn Shape)->u32{
+    shape.area()
+}
+fn main(){
+    let mut square = Square(2);
+    let rect = Rect{width:2,height:3};
+    test_eq!(area(black_box(&square)),4);
+    test_eq!(area(black_box(&rect)),6);
+    let shape:&mut dyn Shape = black_box(&mut square);
+    shape.scale(3);
+    test_eq!(shape.area(),36);
+}
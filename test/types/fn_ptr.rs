@@ -0,0 +1,20 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn add(a:u32,b:u32)->u32{
+    a + b
+}
+fn mul(a:u32,b:u32)->u32{
+    a * b
+}
+fn apply(op:fn(u32,u32)->u32,a:u32,b:u32)->u32{
+    op(a,b)
+}
+fn main(){
+    let op:fn(u32,u32)->u32 = black_box(add);
+    test_eq!(op(2,3),5);
+    test_eq!(apply(black_box(mul),2,3),6);
+    let ops:[fn(u32,u32)->u32;2] = black_box([add,mul]);
+    test_eq!(ops[1](4,5),20);
+}
@@ -0,0 +1,34 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+#[derive(Clone,Copy)]
+struct Pair<T>{
+    first:T,
+    second:T,
+}
+fn identity<T>(value:T)->T{
+    value
+}
+fn first<T:Copy>(pair:&Pair<T>)->T{
+    pair.first
+}
+fn swap<T>(pair:Pair<T>)->Pair<T>{
+    Pair{first:pair.second,second:pair.first}
+}
+fn select<T>(pick_first:bool,a:T,b:T)->T{
+    if pick_first { identity(a) } else { identity(b) }
+}
+fn main(){
+    test_eq!(identity(black_box(5_u32)),5);
+    test_eq!(identity(black_box(-7_i64)),-7);
+    let pair = Pair{first:black_box(1_u16),second:2};
+    test_eq!(first(&pair),1);
+    let swapped = swap(pair);
+    test_eq!(swapped.first,2);
+    test_eq!(swapped.second,1);
+    let floats = swap(Pair{first:black_box(0.5_f32),second:1.5});
+    test_eq!(first(&floats),1.5);
+    test_eq!(select(black_box(false),3_u8,4),4);
+    test_eq!(select(black_box(true),3_i32,4),3);
+}
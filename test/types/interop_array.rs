@@ -0,0 +1,62 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code,improper_ctypes_definitions,improper_ctypes)]
+#![no_std]
+include!("../common.rs");
+#[derive(Clone,Copy)]
+struct RustcCLRInteropManagedArray<T,const DIMENSIONS:usize>{
+    pd:core::marker::PhantomData<T>,
+    prevent_construction:usize,
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_array_new_<T>(len:usize)->RustcCLRInteropManagedArray<T,1>{
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_array_len_<T>(array:RustcCLRInteropManagedArray<T,1>)->usize{
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_array_get_<T>(array:RustcCLRInteropManagedArray<T,1>,index:usize)->T{
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_array_set_<T>(array:RustcCLRInteropManagedArray<T,1>,index:usize,value:T){
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_array_addr_<T>(array:RustcCLRInteropManagedArray<T,1>,index:usize)->*mut T{
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_array_pin_<T>(array:RustcCLRInteropManagedArray<T,1>)->isize{
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_gc_handle_free_(handle:isize){
+    core::intrinsics::abort();
+}
+fn main(){
+    let arr = rustc_clr_interop_managed_array_new_::<i32>(black_box(8));
+    test_eq!(rustc_clr_interop_managed_array_len_(arr),8);
+    // New arrays are zeroed.
+    test_eq!(rustc_clr_interop_managed_array_get_(arr,black_box(3)),0);
+    for index in 0..8{
+        rustc_clr_interop_managed_array_set_(arr,index,(index * index) as i32);
+    }
+    test_eq!(rustc_clr_interop_managed_array_get_(arr,black_box(5)),25);
+    // Writes trough the element address are visible to managed code.
+    unsafe{*rustc_clr_interop_managed_array_addr_(arr,black_box(2)) = 100};
+    test_eq!(rustc_clr_interop_managed_array_get_(arr,black_box(2)),100);
+    let handle = rustc_clr_interop_managed_array_pin_(arr);
+    let slice = unsafe{core::slice::from_raw_parts(rustc_clr_interop_managed_array_addr_(arr,0),8)};
+    test_eq!(slice[7],49);
+    let mut sum = 0;
+    for value in slice{
+        sum += *value;
+    }
+    test_eq!(sum,236);
+    rustc_clr_interop_gc_handle_free_(handle);
+    let floats = rustc_clr_interop_managed_array_new_::<f64>(black_box(2));
+    rustc_clr_interop_managed_array_set_(floats,1,black_box(2.5));
+    test_eq!(rustc_clr_interop_managed_array_get_(floats,1),2.5);
+}
@@ -0,0 +1,126 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code,improper_ctypes_definitions,improper_ctypes)]
+#![no_std]
+include!("../common.rs");
+extern "C"{
+    fn free(ptr:*mut core::ffi::c_void);
+}
+#[derive(Clone,Copy)]
+struct RustcCLRInteropManagedClass<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str>{
+    prevent_construction:usize,
+}
+type StringBuilder = RustcCLRInteropManagedClass<"System.Runtime","System.Text.StringBuilder">;
+#[inline(never)]
+fn rustc_clr_interop_managed_ctor0_<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str,const IS_VALUETYPE:bool>()->RustcCLRInteropManagedClass<ASSEMBLY,CLASS_PATH>{
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_call1_<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str,const IS_VALUETYPE:bool,const METHOD:&'static str,const IS_STATIC:bool,Ret,Arg1>(arg1:Arg1)->Ret{
+    core::intrinsics::abort();
+}
+#[inline(never)]
+fn rustc_clr_interop_managed_call2_<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str,const IS_VALUETYPE:bool,const METHOD:&'static str,const IS_STATIC:bool,Ret,Arg1,Arg2>(arg1:Arg1,arg2:Arg2)->Ret{
+    core::intrinsics::abort();
+}
+// The intrinsics mycorrhiza's `GcHandle` is built on, which is included below.
+mod intrinsics{
+    use super::RustcCLRInteropManagedClass;
+    pub unsafe trait ManagedObject: Copy {}
+    unsafe impl<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str> ManagedObject for RustcCLRInteropManagedClass<ASSEMBLY,CLASS_PATH>{}
+    #[inline(never)]
+    pub fn rustc_clr_interop_gc_handle_alloc_<T:ManagedObject>(object:T)->isize{
+        core::intrinsics::abort();
+    }
+    #[inline(never)]
+    pub fn rustc_clr_interop_gc_handle_target_<T:ManagedObject>(handle:isize)->T{
+        core::intrinsics::abort();
+    }
+    #[inline(never)]
+    pub fn rustc_clr_interop_gc_handle_free_(handle:isize){
+        core::intrinsics::abort();
+    }
+}
+#[path = "../../mycorrhiza/src/system/runtime/interop_services/mod.rs"]
+mod interop_services;
+use interop_services::GcHandle;
+struct Box<T>{
+    ptr:*mut T,
+}
+impl<T> Box<T>{
+    fn new(value:T)->Self{
+        let ptr:*mut T = unsafe{malloc(core::mem::size_of::<T>())}.cast();
+        unsafe{ptr.write(value)};
+        Self{ptr}
+    }
+}
+impl<T> core::ops::Deref for Box<T>{
+    type Target = T;
+    fn deref(&self)->&T{
+        unsafe{&*self.ptr}
+    }
+}
+impl<T> Drop for Box<T>{
+    fn drop(&mut self){
+        unsafe{
+            core::ptr::drop_in_place(self.ptr);
+            free(self.ptr.cast());
+        }
+    }
+}
+struct Vec<T>{
+    ptr:*mut T,
+    cap:usize,
+    len:usize,
+}
+impl<T> Vec<T>{
+    fn new()->Self{
+        Self{ptr:core::ptr::null_mut(),cap:0,len:0}
+    }
+    fn push(&mut self,value:T){
+        if self.len == self.cap{
+            let new_cap = if self.cap < 4 { 4 } else { self.cap * 2 };
+            let byte_size = new_cap * core::mem::size_of::<T>();
+            self.ptr = if self.cap == 0 { unsafe{malloc(byte_size)} } else { unsafe{realloc(self.ptr.cast(),byte_size)} }.cast();
+            self.cap = new_cap;
+        }
+        unsafe{self.ptr.add(self.len).write(value)};
+        self.len += 1;
+    }
+    fn get(&self,index:usize)->&T{
+        test!(index < self.len);
+        unsafe{&*self.ptr.add(index)}
+    }
+}
+impl<T> Drop for Vec<T>{
+    fn drop(&mut self){
+        for index in 0..self.len{
+            unsafe{core::ptr::drop_in_place(self.ptr.add(index))};
+        }
+        if self.cap > 0{
+            unsafe{free(self.ptr.cast())};
+        }
+    }
+}
+fn append(sb:StringBuilder,value:i32){
+    rustc_clr_interop_managed_call2_::<"System.Runtime","System.Text.StringBuilder",false,"Append",false,StringBuilder,StringBuilder,i32>(sb,value);
+}
+fn length(sb:StringBuilder)->i32{
+    rustc_clr_interop_managed_call1_::<"System.Runtime","System.Text.StringBuilder",false,"get_Length",false,i32,StringBuilder>(sb)
+}
+fn main(){
+    // Only the handles are stored in unmanaged memory, the objects stay on the managed heap.
+    let mut handles:Vec<GcHandle<StringBuilder>> = Vec::new();
+    for _ in 0..5{
+        handles.push(GcHandle::new(rustc_clr_interop_managed_ctor0_::<"System.Runtime","System.Text.StringBuilder",false>()));
+    }
+    append(handles.get(1).target(),black_box(1234));
+    // The object behind the handle is the one modified above.
+    test_eq!(length(handles.get(1).target()),4);
+    test_eq!(length(handles.get(0).target()),0);
+    // A clone is a separate handle to the same object, so it stays valid after the original is freed.
+    let boxed = Box::new(handles.get(1).clone());
+    // Frees each handle once. Using or freeing a freed handle throws.
+    drop(handles);
+    append(boxed.target(),black_box(56));
+    test_eq!(length(boxed.target()),6);
+}
@@ -0,0 +1,29 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message,unboxed_closures,tuple_trait)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code,improper_ctypes_definitions,improper_ctypes)]
+#![no_std]
+include!("../common.rs");
+#[allow(dead_code)]
+#[derive(Clone,Copy)]
+struct RustcCLRInteropManagedClass<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str>{
+    prevent_construction:usize,
+}
+#[allow(dead_code)]
+#[inline(never)]
+extern "rust-call" fn rustc_clr_interop_managed_call_<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str,const IS_VALUETYPE:bool,const METHOD:&'static str,const IS_STATIC:bool,Ret,Args:core::marker::Tuple>(args:Args)->Ret{
+    core::intrinsics::abort();
+}
+#[allow(dead_code)]
+#[inline(never)]
+extern "rust-call" fn rustc_clr_interop_managed_ctor_<const ASSEMBLY:&'static str,const CLASS_PATH:&'static str,const IS_VALUETYPE:bool,Args:core::marker::Tuple>(args:Args)->RustcCLRInteropManagedClass<ASSEMBLY,CLASS_PATH>{
+    core::intrinsics::abort();
+}
+fn main(){
+    rustc_clr_interop_managed_call_::<"System.Console","System.Console",false,"WriteLine",true,(),()>(());
+    let max = rustc_clr_interop_managed_call_::<"System.Runtime","System.Math",false,"Max",true,i32,(i32,i32)>((black_box(4),black_box(7)));
+    test_eq!(max,7);
+    let clamped = rustc_clr_interop_managed_call_::<"System.Runtime","System.Math",false,"Clamp",true,i32,(i32,i32,i32)>((black_box(12),black_box(0),black_box(10)));
+    test_eq!(clamped,10);
+    // A constructor taking more arguments than the fixed-arity intrinsics support.
+    let date = rustc_clr_interop_managed_ctor_::<"System.Runtime","System.DateTime",true,(i32,i32,i32,i32,i32,i32)>((2024,2,29,12,30,15));
+    black_box(date);
+}
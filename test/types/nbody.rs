@@ -1,4 +1,4 @@
-#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,panic_info_message)]
 #![allow(internal_features,incomplete_features,unused_variables,dead_code,improper_ctypes_definitions)]
 #![no_std]
 include!("../common.rs");